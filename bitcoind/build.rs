@@ -8,12 +8,12 @@ mod download {
 #[cfg(feature = "download")]
 mod download {
     use std::fs::File;
-    use std::io::{self, BufRead, BufReader, Cursor, Read};
+    use std::io::{self, BufRead, BufReader};
     use std::path::{Path, PathBuf};
     use std::str::FromStr;
 
     use anyhow::Context;
-    use bitcoin_hashes::{sha256, Hash};
+    use bitcoin_hashes::{sha256, Hash, HashEngine};
     use flate2::read::GzDecoder;
     use tar::Archive;
 
@@ -107,7 +107,8 @@ mod download {
 
             println!("filename:{} version:{} hash:{}", download_filename, VERSION, expected_hash);
 
-            let (file_or_url, tarball_bytes) = match std::env::var("BITCOIND_TARBALL_FILE") {
+            // Whether the archive was downloaded, and should be removed once unpacked.
+            let (archive_path, downloaded) = match std::env::var("BITCOIND_TARBALL_FILE") {
                 Err(_) => {
                     let download_endpoint = std::env::var("BITCOIND_DOWNLOAD_ENDPOINT")
                         .unwrap_or("https://bitcoincore.org/bin".to_owned());
@@ -116,12 +117,16 @@ mod download {
                         "{}/bitcoin-core-{}/{}",
                         download_endpoint, VERSION, download_filename
                     );
-                    let resp = bitreq::get(&url)
+                    // Streams to disk, resuming if the connection drops, and checks the hash
+                    // before the archive appears under its name. Goes through the proxy set in
+                    // the usual environment variables, e.g. `HTTPS_PROXY`, if any.
+                    let archive = bitcoin_exe_home.join(&download_filename);
+                    let request = bitreq::get(&url).with_proxy_config(bitreq::Proxy::from_env());
+                    bitreq::Download::new(request, &archive)
+                        .with_sha256(expected_hash.to_byte_array())
                         .send()
-                        .with_context(|| format!("cannot reach url {}", url))?;
-                    assert_eq!(resp.status_code, 200, "url {} didn't return 200", url);
-
-                    (url, resp.as_bytes().to_vec())
+                        .with_context(|| format!("cannot download url {}", url))?;
+                    (archive, true)
                 }
                 Ok(path) => {
                     let f = File::open(&path).with_context(|| {
@@ -131,21 +136,33 @@ mod download {
                         )
                     })?;
                     let mut reader = BufReader::new(f);
-                    let mut buffer = Vec::new();
-                    reader.read_to_end(&mut buffer)?;
-                    (path, buffer)
+                    let mut engine = sha256::Hash::engine();
+                    loop {
+                        let buffer = reader.fill_buf()?;
+                        if buffer.is_empty() {
+                            break;
+                        }
+                        engine.input(buffer);
+                        let length = buffer.len();
+                        reader.consume(length);
+                    }
+
+                    let tarball_hash = sha256::Hash::from_engine(engine);
+                    assert_eq!(
+                        expected_hash, tarball_hash,
+                        "expected hash of {} is not matching",
+                        path
+                    );
+                    (PathBuf::from(path), false)
                 }
             };
 
-            let tarball_hash = sha256::Hash::hash(&tarball_bytes);
-            assert_eq!(
-                expected_hash, tarball_hash,
-                "expected hash of {} is not matching",
-                file_or_url
-            );
-
+            // Unpacks straight from the file, as the archives are too large to hold in memory.
+            let archive_file = File::open(&archive_path)
+                .with_context(|| format!("cannot open {:?}", archive_path))?;
+            let reader = BufReader::new(archive_file);
             if download_filename.ends_with(".tar.gz") {
-                let d = GzDecoder::new(&tarball_bytes[..]);
+                let d = GzDecoder::new(reader);
 
                 let targets: &[&Path] = &[
                     Path::new("bin/bitcoind"),
@@ -161,8 +178,7 @@ mod download {
                     }
                 }
             } else if download_filename.ends_with(".zip") {
-                let cursor = Cursor::new(tarball_bytes);
-                let mut archive = zip::ZipArchive::new(cursor).unwrap();
+                let mut archive = zip::ZipArchive::new(reader).unwrap();
                 for i in 0..zip::ZipArchive::len(&archive) {
                     let mut file = archive.by_index(i).unwrap();
                     let outpath = match file.enclosed_name() {
@@ -182,6 +198,10 @@ mod download {
                     }
                 }
             }
+            if downloaded {
+                std::fs::remove_file(&archive_path)
+                    .with_context(|| format!("cannot remove {:?}", archive_path))?;
+            }
 
             // Code signing for arm64 macOS:
             #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
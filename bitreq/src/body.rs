@@ -0,0 +1,218 @@
+//! Request bodies which are streamed from a reader while the request is being sent.
+
+use core::fmt;
+#[cfg(feature = "async")]
+use core::pin::Pin;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::Error;
+
+/// The body of a [`Request`](crate::Request).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Body {
+    Bytes(Vec<u8>),
+    Stream(BodyStream),
+}
+
+/// The size of the chunks a body of unknown length is sent in.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+enum BodyReader {
+    Sync(Box<dyn Read + Send>),
+    #[cfg(feature = "async")]
+    Async(Pin<Box<dyn AsyncRead + Send>>),
+}
+
+/// A request body read from a [`Read`] or [`AsyncRead`] while it is sent.
+///
+/// The reader can only be consumed once, by the first attempt to send the
+/// request, so copies of a request share it.
+#[derive(Clone)]
+pub(crate) struct BodyStream {
+    reader: Arc<Mutex<Option<BodyReader>>>,
+    len: Option<u64>,
+}
+
+impl BodyStream {
+    pub(crate) fn new<R: Read + Send + 'static>(reader: R, len: Option<u64>) -> BodyStream {
+        let reader = BodyReader::Sync(Box::new(reader));
+        BodyStream { reader: Arc::new(Mutex::new(Some(reader))), len }
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn new_async<R: AsyncRead + Send + 'static>(
+        reader: R,
+        len: Option<u64>,
+    ) -> BodyStream {
+        let reader = BodyReader::Async(Box::pin(reader));
+        BodyStream { reader: Arc::new(Mutex::new(Some(reader))), len }
+    }
+
+    /// The length of the body, if known. Bodies of unknown length are sent
+    /// with `Transfer-Encoding: chunked`.
+    pub(crate) fn len(&self) -> Option<u64> { self.len }
+
+    fn take_reader(&self) -> Result<BodyReader, Error> {
+        self.reader.lock().unwrap().take().ok_or(Error::BodyStreamConsumed)
+    }
+
+    /// Writes the body to `stream`, reading it from a blocking reader.
+    pub(crate) fn write_to<W: Write>(&self, stream: &mut W) -> Result<(), Error> {
+        #[cfg_attr(not(feature = "async"), allow(clippy::infallible_destructuring_match))]
+        let mut reader = match self.take_reader()? {
+            BodyReader::Sync(reader) => reader,
+            #[cfg(feature = "async")]
+            BodyReader::Async(_) =>
+                return Err(Error::IoError(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "request bodies read from an AsyncRead can only be sent asynchronously",
+                ))),
+        };
+        match self.len {
+            Some(len) => {
+                let written = io::copy(&mut (&mut reader).take(len), stream)?;
+                check_len(written, len)?;
+            }
+            None => {
+                let mut chunk = vec![0; CHUNK_SIZE];
+                loop {
+                    let read = match reader.read(&mut chunk) {
+                        Ok(read) => read,
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => return Err(Error::IoError(e)),
+                    };
+                    stream.write_all(&chunk_frame(&chunk[..read]))?;
+                    if read == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+        stream.flush()?;
+        Ok(())
+    }
+
+    /// Writes the body to `stream`, reading it from an async reader, or from a blocking reader if
+    /// that's what the body was created with.
+    #[cfg(feature = "async")]
+    pub(crate) async fn write_to_async<W: AsyncWrite + Unpin>(
+        &self,
+        stream: &mut W,
+    ) -> Result<(), Error> {
+        let mut reader: Pin<Box<dyn AsyncRead + Send>> = match self.take_reader()? {
+            BodyReader::Async(reader) => reader,
+            BodyReader::Sync(reader) => Box::pin(BlockingReader(reader)),
+        };
+        match self.len {
+            Some(len) => {
+                let written = tokio::io::copy(&mut (&mut reader).take(len), stream).await?;
+                check_len(written, len)?;
+            }
+            None => {
+                let mut chunk = vec![0; CHUNK_SIZE];
+                loop {
+                    let read = reader.read(&mut chunk).await?;
+                    stream.write_all(&chunk_frame(&chunk[..read])).await?;
+                    if read == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+        stream.flush().await?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BodyStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BodyStream").field("len", &self.len).finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for BodyStream {
+    fn eq(&self, other: &Self) -> bool { Arc::ptr_eq(&self.reader, &other.reader) }
+}
+
+impl Eq for BodyStream {}
+
+fn check_len(written: u64, len: u64) -> Result<(), Error> {
+    if written < len {
+        return Err(Error::IoError(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "the request body ended before its given length",
+        )));
+    }
+    Ok(())
+}
+
+/// Frames `data` as a chunk of a `Transfer-Encoding: chunked` body, or as the final chunk if
+/// `data` is empty.
+fn chunk_frame(data: &[u8]) -> Vec<u8> {
+    let mut frame = format!("{:x}\r\n", data.len()).into_bytes();
+    frame.reserve(data.len() + 4);
+    frame.extend_from_slice(data);
+    frame.extend_from_slice(b"\r\n");
+    frame
+}
+
+/// Reads a blocking reader from an async context, blocking the executor's thread while reading.
+#[cfg(feature = "async")]
+struct BlockingReader(Box<dyn Read + Send>);
+
+#[cfg(feature = "async")]
+impl AsyncRead for BlockingReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut core::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> core::task::Poll<io::Result<()>> {
+        let read = self.0.read(buf.initialize_unfilled())?;
+        buf.advance(read);
+        core::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_frames() {
+        assert_eq!(chunk_frame(b"hello"), b"5\r\nhello\r\n");
+        assert_eq!(chunk_frame(&[b'a'; 26]), [&b"1a\r\n"[..], &[b'a'; 26], b"\r\n"].concat());
+        assert_eq!(chunk_frame(b""), b"0\r\n\r\n");
+    }
+
+    #[test]
+    fn writes_chunked_body() {
+        let body = vec![7; CHUNK_SIZE + 10];
+        let stream = BodyStream::new(io::Cursor::new(body.clone()), None);
+        let mut written = Vec::new();
+        stream.write_to(&mut written).unwrap();
+
+        let expected =
+            [chunk_frame(&body[..CHUNK_SIZE]), chunk_frame(&body[CHUNK_SIZE..]), chunk_frame(b"")]
+                .concat();
+        assert_eq!(written, expected);
+        assert!(matches!(stream.write_to(&mut written), Err(Error::BodyStreamConsumed)));
+    }
+
+    #[test]
+    fn writes_body_of_known_length() {
+        let stream = BodyStream::new(&b"hello world"[..], Some(5));
+        let mut written = Vec::new();
+        stream.write_to(&mut written).unwrap();
+        assert_eq!(written, b"hello");
+
+        let stream = BodyStream::new(&b"hello"[..], Some(10));
+        match stream.write_to(&mut Vec::new()) {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,346 @@
+//! Caching responses to `GET` requests sent by a [`Client`](crate::Client), see
+//! [`CacheStorage`].
+
+use core::fmt;
+use core::time::Duration;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::request::Method;
+use crate::{CookieJar, Error, Request, Response, Url};
+
+/// Stores the responses cached by a [`Client`](crate::Client), added with
+/// [`Client::with_cache`](crate::Client::with_cache). [`MemoryCache`] is
+/// provided.
+///
+/// Responses to `GET` requests are cached by URL, including the parameters
+/// added with [`Request::with_param`], unless the request or the response has
+/// a `Cache-Control: no-store` header, or the response can't be reused as it
+/// has neither a `max-age` nor an `ETag` or `Last-Modified` header. Requests
+/// with an `Authorization` or `Cookie` header, or which are sent cookies from
+/// a [`CookieJar`], bypass the cache, as their responses may be specific to
+/// the user. So are responses with a `Cache-Control: private` header, which
+/// aren't stored, as well as responses with a `Vary` header naming anything
+/// other than `Accept-Encoding`, which may not apply to other requests.
+///
+/// A cached response is returned without contacting the server for as long
+/// as it's fresh according to the `max-age` of its `Cache-Control` header,
+/// unless it (or the request) has a `no-cache` directive. Otherwise, it's
+/// revalidated with an `If-None-Match` or `If-Modified-Since` header, and
+/// returned if the server answers `304 Not Modified`. Requests which set one
+/// of these headers themselves bypass the cache.
+pub trait CacheStorage: Send + Sync {
+    /// Returns the response cached for `url`, if any.
+    fn get(&self, url: &str) -> Option<CachedResponse>;
+
+    /// Caches `response` for `url`, replacing any response cached for it
+    /// before.
+    fn put(&self, url: &str, response: CachedResponse);
+
+    /// Drops the response cached for `url`, if any.
+    fn remove(&self, url: &str);
+}
+
+impl<S: CacheStorage + ?Sized> CacheStorage for Arc<S> {
+    fn get(&self, url: &str) -> Option<CachedResponse> { (**self).get(url) }
+
+    fn put(&self, url: &str, response: CachedResponse) { (**self).put(url, response) }
+
+    fn remove(&self, url: &str) { (**self).remove(url) }
+}
+
+/// A response stored in a [`CacheStorage`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CachedResponse {
+    /// The response, with the headers of the last `304 Not Modified`
+    /// response revalidating it, if any.
+    pub response: Response,
+    /// When the response was received or last revalidated, which its
+    /// freshness is counted from.
+    pub stored_at: SystemTime,
+}
+
+impl CachedResponse {
+    /// Creates a cached `response`, received or last revalidated at
+    /// `stored_at`.
+    pub fn new(response: Response, stored_at: SystemTime) -> CachedResponse {
+        CachedResponse { response, stored_at }
+    }
+
+    fn is_fresh(&self) -> bool {
+        let directives = CacheControl::parse(self.response.headers.get("cache-control"));
+        let age = self.stored_at.elapsed().unwrap_or_default();
+        !directives.no_cache && directives.max_age.is_some_and(|max_age| age < max_age)
+    }
+}
+
+/// Keeps up to a given number of responses in memory, dropping the least
+/// recently used one when full.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use bitreq::{Client, MemoryCache};
+///
+/// let client = Client::new(1).with_cache(MemoryCache::new(100));
+/// // Only transfers the body again if it changed since the last request.
+/// let response = client.send(bitreq::get("http://example.com/fees"))?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct MemoryCache {
+    capacity: usize,
+    entries: Mutex<LruMap>,
+}
+
+#[derive(Debug, Default)]
+struct LruMap {
+    responses: HashMap<String, CachedResponse>,
+    /// The URLs of the responses, the least recently used first.
+    lru_order: VecDeque<String>,
+}
+
+impl LruMap {
+    fn touch(&mut self, url: &str) {
+        if let Some(i) = self.lru_order.iter().position(|u| u == url) {
+            let url = self.lru_order.remove(i).expect("just found");
+            self.lru_order.push_back(url);
+        }
+    }
+}
+
+impl MemoryCache {
+    /// Creates a cache holding up to `capacity` responses.
+    pub fn new(capacity: usize) -> MemoryCache {
+        MemoryCache { capacity, entries: Mutex::new(LruMap::default()) }
+    }
+}
+
+impl CacheStorage for MemoryCache {
+    fn get(&self, url: &str) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        let response = entries.responses.get(url)?.clone();
+        entries.touch(url);
+        Some(response)
+    }
+
+    fn put(&self, url: &str, response: CachedResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.responses.insert(url.to_string(), response).is_some() {
+            entries.touch(url);
+            return;
+        }
+        entries.lru_order.push_back(url.to_string());
+        if entries.responses.len() > self.capacity {
+            if let Some(oldest) = entries.lru_order.pop_front() {
+                entries.responses.remove(&oldest);
+            }
+        }
+    }
+
+    fn remove(&self, url: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.responses.remove(url).is_some() {
+            entries.lru_order.retain(|u| u != url);
+        }
+    }
+}
+
+/// The directives of a `Cache-Control` header relevant to us.
+#[derive(Default)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    max_age: Option<Duration>,
+}
+
+impl CacheControl {
+    fn parse<S: AsRef<str>>(header: Option<S>) -> CacheControl {
+        let mut directives = CacheControl::default();
+        for directive in header.as_ref().map_or("", |h| h.as_ref()).split(',') {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            if name.eq_ignore_ascii_case("no-store") {
+                directives.no_store = true;
+            } else if name.eq_ignore_ascii_case("no-cache") {
+                directives.no_cache = true;
+            } else if name.eq_ignore_ascii_case("private") {
+                directives.private = true;
+            } else if name.eq_ignore_ascii_case("max-age") {
+                let seconds = value.and_then(|v| v.parse().ok());
+                directives.max_age = seconds.map(Duration::from_secs);
+            }
+        }
+        directives
+    }
+}
+
+/// The [`CacheStorage`] of a [`Client`](crate::Client).
+#[derive(Clone)]
+pub(crate) struct HttpCache(pub(crate) Arc<dyn CacheStorage>);
+
+impl fmt::Debug for HttpCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpCache").finish_non_exhaustive()
+    }
+}
+
+/// What to do with a request according to the cache, see [`HttpCache::lookup`].
+pub(crate) enum Lookup {
+    /// The request can be answered from the cache.
+    Fresh(Response),
+    /// The request has to be sent, and its response passed to [`HttpCache::update`] along with
+    /// the URL it's cached for and the stale response, if any.
+    Send(Request, String, Option<CachedResponse>),
+    /// The request bypasses the cache.
+    Bypass(Request),
+}
+
+impl HttpCache {
+    /// Looks up the response cached for `request`, adding revalidation headers to the request
+    /// if there is a stale one.
+    pub(crate) fn lookup(&self, mut request: Request, cookie_jar: Option<&CookieJar>) -> Lookup {
+        let request_directives = CacheControl::parse(request.header("Cache-Control"));
+        if request.method() != &Method::Get
+            || request_directives.no_store
+            || request.header("If-None-Match").is_some()
+            || request.header("If-Modified-Since").is_some()
+            || request.header("Authorization").is_some()
+            || request.header("Cookie").is_some()
+        {
+            return Lookup::Bypass(request);
+        }
+        let url = match cache_key(&request) {
+            Some(url) => url,
+            None => return Lookup::Bypass(request),
+        };
+        let cookie_jar = request.cookie_jar.as_ref().or(cookie_jar);
+        if cookie_jar.is_some_and(|jar| jar.cookie_header(&url).is_some()) {
+            return Lookup::Bypass(request);
+        }
+        let url = url.as_str().to_string();
+        let cached = match self.0.get(&url) {
+            Some(cached) if cached.is_fresh() && !request_directives.no_cache => {
+                #[cfg(feature = "log")]
+                log::trace!("Answering request to {} from the cache.", url);
+                return Lookup::Fresh(cached.response);
+            }
+            cached => cached,
+        };
+        if let Some(cached) = &cached {
+            if let Some(etag) = cached.response.headers.get("etag") {
+                request = request.with_header("If-None-Match", etag);
+            }
+            if let Some(last_modified) = cached.response.headers.get("last-modified") {
+                request = request.with_header("If-Modified-Since", last_modified);
+            }
+        }
+        Lookup::Send(request, url, cached)
+    }
+
+    /// Caches the response to a request returned by [`HttpCache::lookup`] if it can be reused,
+    /// turning a `304 Not Modified` response into the cached response it revalidated.
+    pub(crate) fn update(
+        &self,
+        url: String,
+        stale: Option<CachedResponse>,
+        result: Result<Response, Error>,
+    ) -> Result<Response, Error> {
+        let response = result?;
+        let directives = CacheControl::parse(response.headers.get("cache-control"));
+        if directives.no_store || directives.private || varies(&response) {
+            self.0.remove(&url);
+            return Ok(response);
+        }
+        let response = match stale {
+            Some(stale) if response.status_code == 304 => {
+                let mut cached = stale.response;
+                for (name, value) in response.headers {
+                    if name != "content-length" && name != "transfer-encoding" {
+                        cached.headers.insert(name, value);
+                    }
+                }
+                cached
+            }
+            _ => response,
+        };
+        let reusable = CacheControl::parse(response.headers.get("cache-control")).max_age.is_some()
+            || response.headers.contains_key("etag")
+            || response.headers.contains_key("last-modified");
+        if response.status_code == 200 && reusable {
+            self.0.put(&url, CachedResponse::new(response.clone(), SystemTime::now()));
+        }
+        Ok(response)
+    }
+}
+
+/// Returns the URL `request` is sent to, with its parameters, which its response is cached for.
+/// Requests with invalid URLs fail without being answered from the cache.
+fn cache_key(request: &Request) -> Option<Url> {
+    let mut url = Url::parse(request.url()).ok()?;
+    url.append_query_params(request.params.iter().map(|(a, b)| (a.as_str(), b.as_str())));
+    Some(url)
+}
+
+/// Returns `true` if `response` has a `Vary` header naming request headers other than
+/// `Accept-Encoding`, which is the same for all requests sent by a client.
+fn varies(response: &Response) -> bool {
+    response.headers.get("vary").is_some_and(|vary| {
+        vary.split(',')
+            .map(str::trim)
+            .any(|name| !name.is_empty() && !name.eq_ignore_ascii_case("accept-encoding"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(cache_control: &str) -> Response {
+        let headers = [("cache-control".to_string(), cache_control.to_string())];
+        Response::from_parts(200, headers.into_iter().collect(), Vec::new())
+    }
+
+    #[test]
+    fn parses_cache_control() {
+        let directives = CacheControl::parse(Some("public, Max-Age=\"60\", no-cache"));
+        assert_eq!(directives.max_age, Some(Duration::from_secs(60)));
+        assert!(directives.no_cache && !directives.no_store);
+        assert!(CacheControl::parse(Some("no-store")).no_store);
+        assert!(CacheControl::parse(Some("private, max-age=60")).private);
+        assert_eq!(CacheControl::parse(None::<&str>).max_age, None);
+    }
+
+    #[test]
+    fn checks_freshness() {
+        let now = SystemTime::now();
+        assert!(CachedResponse::new(response("max-age=60"), now).is_fresh());
+        let old = now - Duration::from_secs(61);
+        assert!(!CachedResponse::new(response("max-age=60"), old).is_fresh());
+        assert!(!CachedResponse::new(response("max-age=60, no-cache"), now).is_fresh());
+        assert!(!CachedResponse::new(response(""), now).is_fresh());
+    }
+
+    #[test]
+    fn memory_cache_evicts_least_recently_used() {
+        let cache = MemoryCache::new(2);
+        let cached = CachedResponse::new(response("max-age=60"), SystemTime::now());
+        cache.put("a", cached.clone());
+        cache.put("b", cached.clone());
+        assert!(cache.get("a").is_some());
+        cache.put("c", cached.clone());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some() && cache.get("c").is_some());
+        cache.remove("a");
+        assert!(cache.get("a").is_none());
+    }
+}
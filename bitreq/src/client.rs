@@ -2,25 +2,65 @@
 //!
 //! The `Client` caches connections to avoid repeated TCP handshakes and TLS negotiations.
 //!
-//! Due to std limitations, `Client` currently only supports async requests.
+//! Blocking requests check a connection out of the pool for the duration of the request and
+//! return it once the response has been fully read, while async requests share connections (and
+//! may pipeline requests over them).
 
-#![cfg(feature = "async")]
-
-use std::collections::{hash_map, HashMap, VecDeque};
+use core::fmt;
+use core::time::Duration;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
+use crate::cache::{CacheStorage, HttpCache, Lookup};
+#[cfg(feature = "async")]
 use crate::connection::AsyncConnection;
+use crate::connection::{enforce_timeout, get_redirect, Connection, NextHop};
+use crate::cookie::CookieJar;
+use crate::middleware::Middleware;
+#[cfg(feature = "proxy")]
+use crate::proxy::ProxyConfig;
 use crate::request::{OwnedConnectionParams as ConnectionKey, ParsedRequest};
+use crate::resolve::{Resolver, SharedResolver};
+use crate::retry::send_with_retry;
+#[cfg(feature = "async")]
+use crate::retry::send_with_retry_async;
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use crate::tls::TlsConfig;
 use crate::{Error, Request, Response};
 
 /// A client that caches connections for reuse.
 ///
 /// The client maintains a pool of up to `capacity` connections, evicting
-/// the least recently used connection when the cache is full.
+/// the least recently used connection when the cache is full. Blocking and
+/// async requests use separate pools, each of which holds up to `capacity`
+/// connections.
+///
+/// By default, the pool keeps one connection per host, for as long as the
+/// server keeps it alive. See
+/// [`with_max_connections_per_host`](Client::with_max_connections_per_host),
+/// [`with_idle_timeout`](Client::with_idle_timeout) and
+/// [`with_max_connection_lifetime`](Client::with_max_connection_lifetime)
+/// to change this. Before a connection is reused, it's checked for having
+/// been closed by the server, in which case it's replaced by a new one.
 ///
 /// # Example
 ///
 /// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use bitreq::{Client, RequestExt};
+///
+/// let client = Client::new(10); // Cache up to 10 connections
+/// let response = bitreq::get("http://example.com").send_with_client(&client)?;
+/// // This request reuses the connection opened by the first one, if the server kept it alive.
+/// let response = bitreq::get("http://example.com").send_with_client(&client)?;
+/// # Ok(()) }
+/// ```
+///
+/// With the `async` feature, the same client can be used for async requests:
+///
+/// ```no_run
+/// # #[cfg(feature = "async")]
 /// # async fn request() {
 /// use bitreq::{Client, RequestExt};
 ///
@@ -32,13 +72,157 @@ use crate::{Error, Request, Response};
 /// ```
 #[derive(Clone)]
 pub struct Client {
-    r#async: Arc<Mutex<ClientImpl<AsyncConnection>>>,
+    sync: Arc<Mutex<ClientImpl<Connection>>>,
+    #[cfg(feature = "async")]
+    r#async: Arc<Mutex<ClientImpl<Arc<AsyncConnection>>>>,
+    pool_config: PoolConfig,
+    #[cfg(feature = "proxy")]
+    proxy_config: Option<Arc<ProxyConfig>>,
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    tls_config: Option<Arc<TlsConfig>>,
+    cookie_jar: Option<CookieJar>,
+    resolver: Option<SharedResolver>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    cache: Option<HttpCache>,
 }
 
-struct ClientImpl<T> {
-    connections: HashMap<ConnectionKey, Arc<T>>,
-    lru_order: VecDeque<ConnectionKey>,
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("pool_config", &self.pool_config)
+            .field("cookie_jar", &self.cookie_jar)
+            .field("middlewares", &self.middlewares.len())
+            .field("cache", &self.cache.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Statistics about the connections pooled by a [`Client`], returned by
+/// [`Client::pool_stats`]. Both the blocking and the async pool are counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PoolStats {
+    /// The number of requests sent over a connection taken from the pool.
+    pub hits: u64,
+    /// The number of requests for which a new connection had to be opened.
+    pub misses: u64,
+    /// The number of connections dropped from the pool, as the pool was
+    /// full, they were idle or open for too long, or the server closed them.
+    pub evictions: u64,
+    /// The number of connections in the pool.
+    pub connections: usize,
+}
+
+/// The limits of the connection pools of a [`Client`].
+#[derive(Clone, Copy, Debug)]
+struct PoolConfig {
     capacity: usize,
+    max_per_host: usize,
+    idle_timeout: Option<Duration>,
+    max_lifetime: Option<Duration>,
+}
+
+/// A connection in a pool.
+struct Pooled<T> {
+    connection: T,
+    created_at: Instant,
+    /// When the connection was last taken from or returned to the pool.
+    last_used: Instant,
+}
+
+impl<T> Pooled<T> {
+    fn is_expired(&self, config: &PoolConfig, now: Instant) -> bool {
+        let older_than = |since: Instant, limit: Option<Duration>| {
+            limit.is_some_and(|limit| now.saturating_duration_since(since) >= limit)
+        };
+        older_than(self.last_used, config.idle_timeout)
+            || older_than(self.created_at, config.max_lifetime)
+    }
+}
+
+struct ClientImpl<T> {
+    /// The pooled connections to each host, the most recently returned last.
+    connections: HashMap<ConnectionKey, Vec<Pooled<T>>>,
+    stats: PoolStats,
+}
+
+impl<T> ClientImpl<T> {
+    fn new() -> Self { ClientImpl { connections: HashMap::new(), stats: PoolStats::default() } }
+
+    /// Drops the connections which were idle or open for too long.
+    fn evict_expired(&mut self, config: &PoolConfig) {
+        let now = Instant::now();
+        let stats = &mut self.stats;
+        self.connections.retain(|_, connections| {
+            connections.retain(|pooled| {
+                let expired = pooled.is_expired(config, now);
+                stats.evictions += u64::from(expired);
+                !expired
+            });
+            !connections.is_empty()
+        });
+    }
+
+    /// Removes the most recently used connection to `key` from the pool, if any.
+    fn take(&mut self, key: &ConnectionKey) -> Option<Pooled<T>> {
+        let connections = self.connections.get_mut(key)?;
+        let pooled = connections.pop();
+        if connections.is_empty() {
+            self.connections.remove(key);
+        }
+        pooled
+    }
+
+    /// Removes the connections to `key` for which `is_match` returns `true` from the pool.
+    #[cfg(feature = "async")]
+    fn remove(&mut self, key: &ConnectionKey, is_match: impl Fn(&T) -> bool) {
+        if let Some(connections) = self.connections.get_mut(key) {
+            connections.retain(|pooled| !is_match(&pooled.connection));
+            if connections.is_empty() {
+                self.connections.remove(key);
+            }
+        }
+    }
+
+    /// Adds `connection` to the pool unless it already holds the maximum number of connections
+    /// to `key`, evicting the least recently used connection if the pool is over capacity.
+    fn insert(
+        &mut self,
+        key: ConnectionKey,
+        connection: T,
+        created_at: Instant,
+        config: &PoolConfig,
+    ) {
+        let connections = self.connections.entry(key).or_default();
+        if connections.len() >= config.max_per_host {
+            self.stats.evictions += 1;
+            return;
+        }
+        connections.push(Pooled { connection, created_at, last_used: Instant::now() });
+        if self.len() > config.capacity {
+            let oldest = self
+                .connections
+                .iter()
+                .flat_map(|(key, connections)| {
+                    connections
+                        .iter()
+                        .enumerate()
+                        .map(move |(i, pooled)| (key, i, pooled.last_used))
+                })
+                .min_by_key(|(_, _, last_used)| *last_used)
+                .map(|(key, i, _)| (key.clone(), i));
+            if let Some((key, i)) = oldest {
+                let connections = self.connections.get_mut(&key).expect("just found");
+                connections.remove(i);
+                if connections.is_empty() {
+                    self.connections.remove(&key);
+                }
+                self.stats.evictions += 1;
+            }
+        }
+    }
+
+    fn len(&self) -> usize { self.connections.values().map(Vec::len).sum() }
 }
 
 impl Client {
@@ -50,57 +234,399 @@ impl Client {
     ///   reached, the least recently used connection is evicted.
     pub fn new(capacity: usize) -> Self {
         Client {
-            r#async: Arc::new(Mutex::new(ClientImpl {
-                connections: HashMap::new(),
-                lru_order: VecDeque::new(),
+            sync: Arc::new(Mutex::new(ClientImpl::new())),
+            #[cfg(feature = "async")]
+            r#async: Arc::new(Mutex::new(ClientImpl::new())),
+            pool_config: PoolConfig {
                 capacity,
-            })),
+                max_per_host: 1,
+                idle_timeout: None,
+                max_lifetime: None,
+            },
+            #[cfg(feature = "proxy")]
+            proxy_config: None,
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            tls_config: None,
+            cookie_jar: None,
+            resolver: None,
+            middlewares: Vec::new(),
+            cache: None,
+        }
+    }
+
+    /// Sets the maximum number of connections to the same host kept in each
+    /// pool. Defaults to 1.
+    ///
+    /// Blocking requests sent concurrently from several threads each need a
+    /// connection of their own, so this should be raised to the number of
+    /// such threads for all their connections to be reused. Async requests
+    /// open a new connection rather than waiting for a busy one while there
+    /// are fewer than this many to the host.
+    pub fn with_max_connections_per_host(mut self, max: usize) -> Self {
+        self.pool_config.max_per_host = max;
+        self
+    }
+
+    /// Drops connections which haven't been used for `timeout` from the
+    /// pools, rather than waiting for the server to close them. By default,
+    /// idle connections are kept.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_config.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Stops reusing connections once they've been open for `lifetime`, for
+    /// example to pick up DNS changes. By default, connections are reused
+    /// for as long as the server keeps them alive.
+    pub fn with_max_connection_lifetime(mut self, lifetime: Duration) -> Self {
+        self.pool_config.max_lifetime = Some(lifetime);
+        self
+    }
+
+    /// Returns statistics about the connections pooled by this client and
+    /// its clones.
+    pub fn pool_stats(&self) -> PoolStats {
+        #[allow(unused_mut)]
+        let mut stats = {
+            let pool = self.sync.lock().unwrap();
+            PoolStats { connections: pool.len(), ..pool.stats }
+        };
+        #[cfg(feature = "async")]
+        {
+            let pool = self.r#async.lock().unwrap();
+            stats.hits += pool.stats.hits;
+            stats.misses += pool.stats.misses;
+            stats.evictions += pool.stats.evictions;
+            stats.connections += pool.len();
         }
+        stats
+    }
+
+    /// Sets the proxy configuration used to pick a proxy for each request
+    /// (and each redirect) which doesn't have one set with
+    /// [`Request::with_proxy`](crate::Request::with_proxy) or
+    /// [`Request::with_proxy_config`](crate::Request::with_proxy_config).
+    ///
+    /// By default, requests are sent directly. To use the proxy environment
+    /// variables, pass [`Proxy::from_env`](crate::Proxy::from_env).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), bitreq::Error> {
+    /// use bitreq::{Client, Proxy, ProxyConfig};
+    ///
+    /// let config = ProxyConfig::new()
+    ///     .with_https_proxy(Proxy::new_http("proxy.internal:3128")?)
+    ///     .with_no_proxy("localhost");
+    /// let client = Client::new(10).with_proxy_config(config);
+    /// let response = client.send(bitreq::get("https://example.com"))?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "proxy")]
+    pub fn with_proxy_config(mut self, proxy_config: ProxyConfig) -> Self {
+        self.proxy_config = Some(Arc::new(proxy_config));
+        self
+    }
+
+    /// Sets the TLS settings used for HTTPS requests which don't have any
+    /// set with [`Request::with_tls_config`](crate::Request::with_tls_config).
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub fn with_tls_config(mut self, tls_config: TlsConfig) -> Self {
+        self.tls_config = Some(Arc::new(tls_config));
+        self
+    }
+
+    /// Sets the cookie jar used by requests which don't have one set with
+    /// [`Request::with_cookie_jar`](crate::Request::with_cookie_jar), so that
+    /// cookies set in responses are sent with later requests.
+    pub fn with_cookie_jar(mut self, cookie_jar: CookieJar) -> Self {
+        self.cookie_jar = Some(cookie_jar);
+        self
+    }
+
+    /// Sets the resolver used by requests which don't have one set with
+    /// [`Request::with_resolver`](crate::Request::with_resolver). See
+    /// [`Resolver`] for details.
+    pub fn with_resolver<R: Resolver + 'static>(mut self, resolver: R) -> Self {
+        self.resolver = Some(SharedResolver(Arc::new(resolver)));
+        self
+    }
+
+    /// Adds `middleware` to the hooks run around every request sent by this
+    /// client, after the middlewares added before. See [`Middleware`] for
+    /// details.
+    pub fn with_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Caches the responses to `GET` requests sent by this client and its
+    /// clones in `storage`, revalidating them once they're stale. See
+    /// [`CacheStorage`] for details.
+    ///
+    /// The cache sits between the middlewares and the network, so it sees
+    /// the requests as changed by the middlewares.
+    pub fn with_cache<S: CacheStorage + 'static>(mut self, storage: S) -> Self {
+        self.cache = Some(HttpCache(Arc::new(storage)));
+        self
+    }
+
+    /// Runs the [`Middleware::on_request`] hooks, in order.
+    fn on_request(&self, mut request: Request) -> Result<Request, Error> {
+        for middleware in &self.middlewares {
+            request = middleware.on_request(request)?;
+        }
+        Ok(request)
+    }
+
+    /// Runs the [`Middleware::on_response`] hooks, in reverse order.
+    fn on_response(
+        &self,
+        request: &Request,
+        mut result: Result<Response, Error>,
+    ) -> Result<Response, Error> {
+        for middleware in self.middlewares.iter().rev() {
+            result = middleware.on_response(request, result);
+        }
+        result
+    }
+
+    #[allow(unused_mut)]
+    fn parse_request(&self, mut request: Request) -> Result<ParsedRequest, Error> {
+        if request.cookie_jar.is_none() {
+            request.cookie_jar = self.cookie_jar.clone();
+        }
+        if request.resolver.is_none() {
+            request.resolver = self.resolver.clone();
+        }
+        #[cfg(any(feature = "rustls", feature = "native-tls"))]
+        if request.tls_config.is_none() {
+            request.tls_config = self.tls_config.clone();
+        }
+        #[cfg(feature = "proxy")]
+        if request.proxy_config.is_none() {
+            request.proxy_config = self.proxy_config.clone();
+        }
+        ParsedRequest::new(request)
+    }
+
+    /// Sends a request using a cached connection if available.
+    ///
+    /// The connection is returned to the cache once the response has been read, unless the server
+    /// asked for it to be closed.
+    ///
+    /// # Errors
+    ///
+    /// See [`Request::send`].
+    pub fn send(&self, request: Request) -> Result<Response, Error> {
+        if self.middlewares.is_empty() {
+            return self.send_unhooked(request);
+        }
+        let request = self.on_request(request)?;
+        let result = self.send_unhooked(request.clone());
+        self.on_response(&request, result)
+    }
+
+    /// Sends a request without running the middlewares, answering it from the cache if possible.
+    fn send_unhooked(&self, request: Request) -> Result<Response, Error> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.send_uncached(request),
+        };
+        match cache.lookup(request, self.cookie_jar.as_ref()) {
+            Lookup::Fresh(response) => Ok(response),
+            Lookup::Send(request, url, stale) =>
+                cache.update(url, stale, self.send_uncached(request)),
+            Lookup::Bypass(request) => self.send_uncached(request),
+        }
+    }
+
+    fn send_uncached(&self, request: Request) -> Result<Response, Error> {
+        let parsed_request = self.parse_request(request)?;
+        send_with_retry(parsed_request, |parsed_request| {
+            let client = self.clone();
+            enforce_timeout(parsed_request.timeout_at, move || client.send_parsed(parsed_request))
+        })
+    }
+
+    fn send_parsed(&self, mut request: ParsedRequest) -> Result<Response, Error> {
+        loop {
+            let mut response = self.send_once(&request)?;
+            request.store_cookies(&response.set_cookies);
+            let url = response.headers.get("location");
+            match get_redirect(request, response.status_code, url) {
+                NextHop::Redirect(next_request) => request = next_request?.0,
+                NextHop::Destination(request) => {
+                    response.redirects = request.redirect_history();
+                    let dst_url = request.url;
+                    dst_url.write_base_url_to(&mut response.url).unwrap();
+                    dst_url.write_resource_to(&mut response.url).unwrap();
+                    return Ok(response);
+                }
+            }
+        }
+    }
+
+    /// Sends a single request, without following redirects, returning the connection to the
+    /// cache afterwards if it can be reused.
+    fn send_once(&self, request: &ParsedRequest) -> Result<Response, Error> {
+        let key: ConnectionKey = request.connection_params().into();
+
+        // A streamed body can't be sent again if the cached connection turns out to be closed,
+        // so such requests always use a fresh connection.
+        if !request.config.has_body_stream() {
+            while let Some((connection, created_at)) = self.checkout(&key) {
+                if let Some((response, connection)) = connection.send_keep_alive(request)? {
+                    if let Some(connection) = connection {
+                        self.check_in(key, connection, created_at);
+                    }
+                    return Ok(response);
+                }
+                // The server closed the idle connection without answering our idempotent
+                // request, so retry on another one.
+                #[cfg(feature = "log")]
+                log::trace!("Cached connection to {:?} was closed, reconnecting.", key);
+                self.sync.lock().unwrap().stats.evictions += 1;
+            }
+        }
+
+        self.sync.lock().unwrap().stats.misses += 1;
+        let connection = Connection::new(request.connection_params(), request.timeouts())?;
+        let created_at = Instant::now();
+        let (response, connection) = connection
+            .send_keep_alive(request)?
+            .expect("fresh connections are never reported as closed");
+        if let Some(connection) = connection {
+            self.check_in(key, connection, created_at);
+        }
+        Ok(response)
+    }
+
+    /// Takes a connection to `key` which can still be used out of the pool, along with the time
+    /// it was opened at, dropping any which can't.
+    fn checkout(&self, key: &ConnectionKey) -> Option<(Connection, Instant)> {
+        let mut pool = self.sync.lock().unwrap();
+        pool.evict_expired(&self.pool_config);
+        while let Some(mut pooled) = pool.take(key) {
+            if pooled.connection.is_stale() {
+                pool.stats.evictions += 1;
+                continue;
+            }
+            pool.stats.hits += 1;
+            return Some((pooled.connection, pooled.created_at));
+        }
+        None
+    }
+
+    /// Returns `connection` to the pool after a request was sent over it.
+    fn check_in(&self, key: ConnectionKey, connection: Connection, created_at: Instant) {
+        let mut pool = self.sync.lock().unwrap();
+        pool.insert(key, connection, created_at, &self.pool_config);
     }
 
     /// Sends a request asynchronously using a cached connection if available.
+    #[cfg(feature = "async")]
     pub async fn send_async(&self, request: Request) -> Result<Response, Error> {
-        let parsed_request = ParsedRequest::new(request)?;
+        if self.middlewares.is_empty() {
+            return self.send_unhooked_async(request).await;
+        }
+        let request = self.on_request(request)?;
+        let result = self.send_unhooked_async(request.clone()).await;
+        self.on_response(&request, result)
+    }
+
+    /// Sends a request asynchronously without running the middlewares, answering it from the
+    /// cache if possible.
+    #[cfg(feature = "async")]
+    async fn send_unhooked_async(&self, request: Request) -> Result<Response, Error> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.send_uncached_async(request).await,
+        };
+        match cache.lookup(request, self.cookie_jar.as_ref()) {
+            Lookup::Fresh(response) => Ok(response),
+            Lookup::Send(request, url, stale) =>
+                cache.update(url, stale, self.send_uncached_async(request).await),
+            Lookup::Bypass(request) => self.send_uncached_async(request).await,
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn send_uncached_async(&self, request: Request) -> Result<Response, Error> {
+        let parsed_request = self.parse_request(request)?;
+        send_with_retry_async(parsed_request, |parsed_request| {
+            self.send_parsed_async(parsed_request)
+        })
+        .await
+    }
+
+    #[cfg(feature = "async")]
+    async fn send_parsed_async(&self, parsed_request: ParsedRequest) -> Result<Response, Error> {
         let key = parsed_request.connection_params();
         let owned_key = key.into();
 
-        // Try to get cached connection
-        let conn_opt = {
-            let state = self.r#async.lock().unwrap();
-
-            if let Some(conn) = state.connections.get(&owned_key) {
-                Some(Arc::clone(conn))
-            } else {
-                None
-            }
-        };
-        let conn = if let Some(conn) = conn_opt {
+        let conn = if let Some(conn) = self.checkout_async(&owned_key).await {
             conn
         } else {
-            let connection = AsyncConnection::new(key, parsed_request.timeout_at).await?;
+            self.r#async.lock().unwrap().stats.misses += 1;
+            let connection = AsyncConnection::new(key, parsed_request.timeouts()).await?;
             let connection = Arc::new(connection);
 
             let mut state = self.r#async.lock().unwrap();
-            if let hash_map::Entry::Vacant(entry) = state.connections.entry(owned_key) {
-                entry.insert(Arc::clone(&connection));
-                state.lru_order.push_back(key.into());
-                if state.connections.len() > state.capacity {
-                    if let Some(oldest_key) = state.lru_order.pop_front() {
-                        state.connections.remove(&oldest_key);
-                    }
-                }
-            }
+            state.insert(owned_key, Arc::clone(&connection), Instant::now(), &self.pool_config);
             connection
         };
 
         // Send the request
         conn.send(parsed_request).await
     }
+
+    /// Picks a pooled connection to `key` to send an async request over, dropping any which
+    /// can't be used anymore.
+    ///
+    /// Prefers connections without requests in flight, and only shares a busy one once the pool
+    /// holds the maximum number of connections to the host.
+    #[cfg(feature = "async")]
+    async fn checkout_async(&self, key: &ConnectionKey) -> Option<Arc<AsyncConnection>> {
+        loop {
+            let connection = {
+                let mut pool = self.r#async.lock().unwrap();
+                pool.evict_expired(&self.pool_config);
+                let connections = pool.connections.get_mut(key)?;
+                let at_limit = connections.len() >= self.pool_config.max_per_host;
+                let pooled = match connections.iter_mut().rev().find(|p| p.connection.is_idle()) {
+                    Some(pooled) => pooled,
+                    None if at_limit => connections.last_mut().expect("not empty"),
+                    None => return None,
+                };
+                pooled.last_used = Instant::now();
+                Arc::clone(&pooled.connection)
+            };
+
+            if connection.is_stale().await {
+                let mut pool = self.r#async.lock().unwrap();
+                pool.remove(key, |pooled| Arc::ptr_eq(pooled, &connection));
+                pool.stats.evictions += 1;
+                continue;
+            }
+            self.r#async.lock().unwrap().stats.hits += 1;
+            return Some(connection);
+        }
+    }
 }
 
 /// Extension trait for `Request` to use with `Client`.
 pub trait RequestExt {
+    /// Sends this request using the provided client's connection pool.
+    ///
+    /// # Errors
+    ///
+    /// See [`Request::send`].
+    fn send_with_client(self, client: &Client) -> Result<Response, Error>;
+
     /// Sends this request asynchronously using the provided client's connection pool.
+    #[cfg(feature = "async")]
     fn send_async_with_client(
         self,
         client: &Client,
@@ -108,6 +634,9 @@ pub trait RequestExt {
 }
 
 impl RequestExt for Request {
+    fn send_with_client(self, client: &Client) -> Result<Response, Error> { client.send(self) }
+
+    #[cfg(feature = "async")]
     fn send_async_with_client(
         self,
         client: &Client,
@@ -0,0 +1,269 @@
+//! Decoding of compressed response bodies, see the `compression` feature.
+
+use alloc::collections::BTreeMap;
+use std::io::Write;
+
+use flate2::write::{DeflateDecoder, GzDecoder, ZlibDecoder};
+
+use crate::Error;
+
+/// The value of the `Accept-Encoding` header sent with every request, unless it's been set
+/// explicitly.
+pub(crate) const ACCEPT_ENCODING: &str = "gzip, deflate";
+
+/// How many raw body bytes we collect before passing them to the decompressor.
+const INPUT_BATCH_LENGTH: usize = 4 * 1024;
+
+enum Inner {
+    Gzip(GzDecoder<Vec<u8>>),
+    /// `Content-Encoding: deflate` is meant to be a zlib stream, but some servers send raw deflate
+    /// data instead. We tell them apart once we've seen the first two bytes.
+    UndetectedDeflate,
+    Zlib(ZlibDecoder<Vec<u8>>),
+    Deflate(DeflateDecoder<Vec<u8>>),
+}
+
+/// Decompresses a response body as it's being read, enforcing the body size limit on the
+/// decompressed data.
+///
+/// Raw body bytes are [`push`](Decoder::push)ed in as they're read, and decoded bytes can be
+/// [`pop`](Decoder::pop)ped out as soon as the decompressor makes them available.
+pub(crate) struct Decoder {
+    inner: Inner,
+    /// Raw bytes which haven't been passed to the decompressor yet.
+    input: Vec<u8>,
+    /// Decoded bytes, of which the first `output_pos` have been popped already.
+    output: Vec<u8>,
+    output_pos: usize,
+    total_in: usize,
+    total_out: usize,
+    max_body_size: Option<usize>,
+    finished: bool,
+}
+
+impl Decoder {
+    /// Returns a decoder for the body described by `headers` if it has a `Content-Encoding` we
+    /// can decode.
+    ///
+    /// As the headers will describe the decoded body once it's been read, the `Content-Encoding`
+    /// and `Content-Length` headers are removed. The latter is set to [`Decoder::total_out`] by
+    /// the readers once the body is finished.
+    pub(crate) fn from_headers(
+        headers: &mut BTreeMap<String, String>,
+        max_body_size: Option<usize>,
+    ) -> Option<Decoder> {
+        let inner = match headers.get("content-encoding")?.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Inner::Gzip(GzDecoder::new(Vec::new())),
+            "deflate" => Inner::UndetectedDeflate,
+            _ => return None,
+        };
+        headers.remove("content-encoding");
+        headers.remove("content-length");
+
+        Some(Decoder {
+            inner,
+            input: Vec::new(),
+            output: Vec::new(),
+            output_pos: 0,
+            total_in: 0,
+            total_out: 0,
+            max_body_size,
+            finished: false,
+        })
+    }
+
+    /// Adds a byte of the raw body. `expected_length` is the number of raw bytes expected to
+    /// follow, including this one, as returned by the [`ResponseLazy`](crate::ResponseLazy)
+    /// iterator.
+    pub(crate) fn push(&mut self, byte: u8, expected_length: usize) -> Result<(), Error> {
+        self.input.push(byte);
+        if expected_length <= 1 || self.input.len() >= INPUT_BATCH_LENGTH {
+            self.decode_input()?;
+        }
+        Ok(())
+    }
+
+    /// Decodes any remaining input, to be called once the raw body has been read completely.
+    pub(crate) fn finish(&mut self) -> Result<(), Error> {
+        if let Inner::UndetectedDeflate = self.inner {
+            if !self.input.is_empty() {
+                // The body was too short to tell, but a single byte can't be a zlib stream.
+                self.inner = Inner::Deflate(DeflateDecoder::new(Vec::new()));
+            }
+        }
+        self.decode_input()?;
+        self.finished = true;
+        match &mut self.inner {
+            // An empty body isn't a valid stream in any encoding, but there's nothing to decode.
+            _ if self.total_in == 0 => return Ok(()),
+            Inner::Gzip(decoder) => decoder.try_finish(),
+            Inner::Zlib(decoder) => decoder.try_finish(),
+            Inner::Deflate(decoder) => decoder.try_finish(),
+            Inner::UndetectedDeflate => unreachable!("only possible for empty bodies"),
+        }
+        .map_err(Error::DecompressionError)?;
+        self.take_output()
+    }
+
+    /// Returns `true` once [`Decoder::finish`] has been called.
+    pub(crate) fn is_finished(&self) -> bool { self.finished }
+
+    /// The total number of decoded bytes.
+    pub(crate) fn total_out(&self) -> usize { self.total_out }
+
+    /// Returns the next decoded byte, if any is available yet, along with the number of decoded
+    /// bytes available including this one.
+    pub(crate) fn pop(&mut self) -> Option<(u8, usize)> {
+        let byte = *self.output.get(self.output_pos)?;
+        let available = self.output.len() - self.output_pos;
+        self.output_pos += 1;
+        Some((byte, available))
+    }
+
+    /// Returns all decoded bytes which haven't been popped.
+    #[cfg(any(feature = "async", test))]
+    pub(crate) fn into_output(mut self) -> Vec<u8> {
+        self.output.drain(..self.output_pos);
+        self.output
+    }
+
+    fn decode_input(&mut self) -> Result<(), Error> {
+        if let Inner::UndetectedDeflate = self.inner {
+            if self.input.len() < 2 {
+                return Ok(());
+            }
+            // A zlib header is a multiple of 31 and declares the deflate compression method,
+            // neither of which a raw deflate stream is likely to do by accident. See RFC 1950.
+            let header = u16::from_be_bytes([self.input[0], self.input[1]]);
+            self.inner = if self.input[0] & 0x0f == 8 && header % 31 == 0 {
+                Inner::Zlib(ZlibDecoder::new(Vec::new()))
+            } else {
+                Inner::Deflate(DeflateDecoder::new(Vec::new()))
+            };
+        }
+
+        let input = core::mem::take(&mut self.input);
+        self.total_in += input.len();
+        let mut remaining = &input[..];
+        while !remaining.is_empty() {
+            // Each write decodes at most one internal buffer's worth of data, so checking the
+            // body size after every write bounds how much memory a decompression bomb can make us
+            // allocate.
+            let written = match &mut self.inner {
+                Inner::Gzip(decoder) => decoder.write(remaining),
+                Inner::Zlib(decoder) => decoder.write(remaining),
+                Inner::Deflate(decoder) => decoder.write(remaining),
+                Inner::UndetectedDeflate => unreachable!("we picked a decoder above"),
+            }
+            .map_err(Error::DecompressionError)?;
+            self.take_output()?;
+            if written == 0 {
+                // The compressed stream has ended, ignore any trailing garbage.
+                break;
+            }
+            remaining = &remaining[written..];
+        }
+        Ok(())
+    }
+
+    fn take_output(&mut self) -> Result<(), Error> {
+        let decoded = match &mut self.inner {
+            Inner::Gzip(decoder) => decoder.get_mut(),
+            Inner::Zlib(decoder) => decoder.get_mut(),
+            Inner::Deflate(decoder) => decoder.get_mut(),
+            Inner::UndetectedDeflate => return Ok(()),
+        };
+        self.total_out = self.total_out.saturating_add(decoded.len());
+        if self.max_body_size.is_some_and(|max| self.total_out > max) {
+            return Err(Error::BodyOverflow);
+        }
+        self.output.drain(..self.output_pos);
+        self.output_pos = 0;
+        self.output.append(decoded);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+
+    use super::*;
+
+    const BODY: &[u8] = b"Hello, world! Hello, world! Hello, world! Hello, world!";
+
+    fn decode(encoding: &str, data: &[u8], max_body_size: Option<usize>) -> Result<Vec<u8>, Error> {
+        let mut headers = BTreeMap::new();
+        headers.insert("content-encoding".to_string(), encoding.to_string());
+        headers.insert("content-length".to_string(), data.len().to_string());
+        let mut decoder = Decoder::from_headers(&mut headers, max_body_size).unwrap();
+        assert!(headers.is_empty());
+
+        let mut decoded = Vec::new();
+        for (i, byte) in data.iter().enumerate() {
+            decoder.push(*byte, data.len() - i)?;
+            while let Some((byte, _)) = decoder.pop() {
+                decoded.push(byte);
+            }
+        }
+        decoder.finish()?;
+        assert_eq!(decoder.total_out(), decoded.len() + decoder.output.len());
+        decoded.extend(decoder.into_output());
+        Ok(decoded)
+    }
+
+    #[test]
+    fn decodes_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(BODY).unwrap();
+        assert_eq!(decode("gzip", &encoder.finish().unwrap(), None).unwrap(), BODY);
+    }
+
+    #[test]
+    fn decodes_zlib_and_raw_deflate() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(BODY).unwrap();
+        assert_eq!(decode("deflate", &encoder.finish().unwrap(), None).unwrap(), BODY);
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(BODY).unwrap();
+        assert_eq!(decode("Deflate", &encoder.finish().unwrap(), None).unwrap(), BODY);
+    }
+
+    #[test]
+    fn ignores_unknown_encodings() {
+        let mut headers = BTreeMap::new();
+        headers.insert("content-encoding".to_string(), "br".to_string());
+        assert!(Decoder::from_headers(&mut headers, None).is_none());
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn empty_body() {
+        assert!(decode("gzip", &[], None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_truncated_and_corrupt_data() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(BODY).unwrap();
+        let data = encoder.finish().unwrap();
+        let res = decode("gzip", &data[..data.len() - 4], None);
+        assert!(matches!(res, Err(Error::DecompressionError(_))));
+
+        let res = decode("gzip", b"definitely not gzip", None);
+        assert!(matches!(res, Err(Error::DecompressionError(_))));
+    }
+
+    #[test]
+    fn limits_decoded_size() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&vec![0; 1024 * 1024]).unwrap();
+        let data = encoder.finish().unwrap();
+        assert!(data.len() < 16 * 1024);
+        let res = decode("gzip", &data, Some(64 * 1024));
+        assert!(matches!(res, Err(Error::BodyOverflow)));
+        assert_eq!(decode("gzip", &data, Some(1024 * 1024)).unwrap().len(), 1024 * 1024);
+    }
+}
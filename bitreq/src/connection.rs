@@ -2,7 +2,11 @@ use core::time::Duration;
 #[cfg(feature = "async")]
 use std::future::Future;
 use std::io::{self, Read, Write};
-use std::net::{TcpStream, ToSocketAddrs};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::Path;
 #[cfg(feature = "async")]
 use std::pin::Pin;
 #[cfg(feature = "async")]
@@ -13,53 +17,236 @@ use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use std::time::Instant;
 
-#[cfg(all(feature = "async", feature = "proxy"))]
-use tokio::io::AsyncReadExt;
 #[cfg(feature = "async")]
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, ReadHalf, WriteHalf};
+#[cfg(all(feature = "async", feature = "proxy"))]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 #[cfg(feature = "async")]
 use tokio::net::TcpStream as AsyncTcpStream;
+#[cfg(all(feature = "async", unix))]
+use tokio::net::UnixStream as AsyncUnixStream;
 #[cfg(feature = "async")]
 use tokio::sync::Mutex as AsyncMutex;
 
+use crate::error::ReadIdleTimeoutReached;
+#[cfg(feature = "proxy")]
+use crate::proxy::{ProxyKind, Socks5ReplyRemainder, Socks5Target};
 use crate::request::{ConnectionParams, OwnedConnectionParams, ParsedRequest};
+use crate::resolve::{self, SharedResolver};
+#[cfg(all(feature = "websocket", feature = "async"))]
+use crate::response::read_upgrade_head_async;
+#[cfg(feature = "websocket")]
+use crate::response::{read_upgrade_head, UpgradeResponse};
 #[cfg(feature = "async")]
-use crate::Response;
-use crate::{Error, Method, ResponseLazy};
+use crate::ResponseStream;
+use crate::{Error, Method, Response, ResponseLazy};
+
+/// Performs the SOCKS5 handshake over `$tcp`, asking the proxy to connect to `$target`, see
+/// [`Proxy`](crate::Proxy).
+#[cfg(feature = "proxy")]
+macro_rules! socks5_handshake {
+    ($tcp: ident, $proxy: ident, $target: ident, $port: expr $(, $await: tt)?) => {
+        $tcp.write_all(&$proxy.socks5_greeting())$(.$await)??;
+        let mut reply = [0; 2];
+        $tcp.read_exact(&mut reply)$(.$await)??;
+        if $proxy.socks5_verify_method(reply)? {
+            $tcp.write_all(&$proxy.socks5_auth_request()?)$(.$await)??;
+            $tcp.read_exact(&mut reply)$(.$await)??;
+            crate::Proxy::socks5_verify_auth(reply)?;
+        }
+
+        $tcp.write_all(&crate::Proxy::socks5_connect_request(&$target, $port)?)$(.$await)??;
+        let mut reply = [0; 4];
+        $tcp.read_exact(&mut reply)$(.$await)??;
+        // The rest of the reply is the address the proxy bound to, which we don't need.
+        let remaining = match crate::Proxy::socks5_verify_connect(reply)? {
+            Socks5ReplyRemainder::Bytes(len) => len,
+            Socks5ReplyRemainder::Domain => {
+                let mut len = [0; 1];
+                $tcp.read_exact(&mut len)$(.$await)??;
+                usize::from(len[0]) + 2
+            }
+        };
+        $tcp.read_exact(&mut vec![0; remaining])$(.$await)??;
+    };
+}
 
 #[cfg(feature = "async")]
 const BACKING_READ_BUFFER_LENGTH: usize = 16 * 1024;
 
 type UnsecuredStream = TcpStream;
 
-#[cfg(feature = "rustls")]
+mod happy_eyeballs;
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
 mod rustls_stream;
-#[cfg(feature = "rustls")]
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
 type SecuredStream = rustls_stream::SecuredStream;
 
 pub(crate) enum HttpStream {
-    Unsecured(UnsecuredStream, Option<Instant>),
-    #[cfg(feature = "rustls")]
-    Secured(Box<SecuredStream>, Option<Instant>),
+    Unsecured(UnsecuredStream, Timeouts),
+    /// A Unix domain socket, with the byte read while waiting for data, if any, as sockets of
+    /// this kind can't be peeked at.
+    #[cfg(unix)]
+    Unix(UnixStream, Timeouts, Option<u8>),
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    Secured(Box<SecuredStream>, Timeouts),
     #[cfg(feature = "async")]
     Buffer(std::io::Cursor<Vec<u8>>),
 }
 
 impl HttpStream {
-    fn create_unsecured(reader: UnsecuredStream, timeout_at: Option<Instant>) -> HttpStream {
-        HttpStream::Unsecured(reader, timeout_at)
+    fn create_unsecured(reader: UnsecuredStream, timeouts: Timeouts) -> HttpStream {
+        HttpStream::Unsecured(reader, timeouts)
     }
 
     #[cfg(feature = "async")]
     pub(crate) fn create_buffer(buffer: Vec<u8>) -> HttpStream {
         HttpStream::Buffer(std::io::Cursor::new(buffer))
     }
+
+    pub(crate) fn set_timeouts(&mut self, new_timeouts: Timeouts) {
+        match self {
+            HttpStream::Unsecured(_, timeouts) => *timeouts = new_timeouts,
+            #[cfg(unix)]
+            HttpStream::Unix(_, timeouts, _) => *timeouts = new_timeouts,
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            HttpStream::Secured(_, timeouts) => *timeouts = new_timeouts,
+            #[cfg(feature = "async")]
+            HttpStream::Buffer(_) => {}
+        }
+    }
+
+    /// Checks, without blocking, whether the server closed the underlying socket of an idle
+    /// connection. Data the server sent unprompted also makes plaintext streams unusable, as it
+    /// would be read as the start of the next response.
+    fn is_closed(&mut self) -> bool {
+        fn peek_closed(tcp: &TcpStream, data_means_closed: bool) -> bool {
+            if tcp.set_nonblocking(true).is_err() {
+                return true;
+            }
+            let closed = match tcp.peek(&mut [0; 1]) {
+                Ok(0) => true,
+                Ok(_) => data_means_closed,
+                Err(e) => e.kind() != io::ErrorKind::WouldBlock,
+            };
+            tcp.set_nonblocking(false).is_err() || closed
+        }
+        match self {
+            HttpStream::Unsecured(inner, _) => peek_closed(inner, true),
+            #[cfg(unix)]
+            HttpStream::Unix(inner, _, peeked) => {
+                if peeked.is_some() || inner.set_nonblocking(true).is_err() {
+                    return true;
+                }
+                let closed = match inner.read(&mut [0; 1]) {
+                    Err(e) => e.kind() != io::ErrorKind::WouldBlock,
+                    Ok(_) => true,
+                };
+                inner.set_nonblocking(false).is_err() || closed
+            }
+            // TLS servers may send records such as session tickets at any time, which we can't
+            // tell apart from a response without reading them.
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            HttpStream::Secured(inner, _) => peek_closed(inner.get_ref(), false),
+            #[cfg(feature = "async")]
+            HttpStream::Buffer(_) => false,
+        }
+    }
+
+    /// Blocks until the server sends something over the underlying socket, returning `false` if
+    /// it closed the connection instead.
+    fn wait_for_data(&mut self) -> io::Result<bool> {
+        let (tcp, timeouts) = match self {
+            HttpStream::Unsecured(inner, timeouts) => (&*inner, *timeouts),
+            #[cfg(unix)]
+            HttpStream::Unix(inner, timeouts, peeked) => {
+                if peeked.is_some() {
+                    return Ok(true);
+                }
+                let (timeout, idle) = timeouts.read_timeout()?;
+                let _ = inner.set_read_timeout(timeout);
+                let mut byte = [0; 1];
+                return match inner.read(&mut byte) {
+                    Ok(0) => Ok(false),
+                    Ok(_) => {
+                        *peeked = Some(byte[0]);
+                        Ok(true)
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => Err(read_timeout_err(idle)),
+                    Err(e) => Err(e),
+                };
+            }
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            HttpStream::Secured(inner, timeouts) => (inner.get_ref(), *timeouts),
+            #[cfg(feature = "async")]
+            HttpStream::Buffer(_) => return Ok(true),
+        };
+        let (timeout, idle) = timeouts.read_timeout()?;
+        let _ = tcp.set_read_timeout(timeout);
+        match tcp.peek(&mut [0; 1]) {
+            Ok(n) => Ok(n > 0),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Err(read_timeout_err(idle)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The timeouts of a request, which are enforced on connecting and on reading and writing.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Timeouts {
+    /// When the whole request times out, see [`Request::with_timeout`](crate::Request::with_timeout).
+    pub(crate) timeout_at: Option<Instant>,
+    /// See [`Request::with_connect_timeout`](crate::Request::with_connect_timeout).
+    pub(crate) connect: Option<Duration>,
+    /// See [`Request::with_read_idle_timeout`](crate::Request::with_read_idle_timeout).
+    pub(crate) read_idle: Option<Duration>,
 }
 
-fn timeout_err() -> io::Error {
+impl Timeouts {
+    /// Returns when a connection started now has to be established by, and whether that's
+    /// determined by the connect timeout rather than the request's timeout.
+    fn connect_deadline(&self) -> (Option<Instant>, bool) {
+        match self.connect.and_then(|timeout| Instant::now().checked_add(timeout)) {
+            Some(connect_at) if self.timeout_at.map_or(true, |at| connect_at < at) =>
+                (Some(connect_at), true),
+            _ => (self.timeout_at, false),
+        }
+    }
+
+    /// Returns the timeout for the next read from a socket, and whether that's the read idle
+    /// timeout rather than the time left until the request times out.
+    fn read_timeout(&self) -> io::Result<(Option<Duration>, bool)> {
+        let remaining = timeout_at_to_duration(self.timeout_at)?;
+        Ok(match (remaining, self.read_idle) {
+            (Some(remaining), Some(idle)) if remaining <= idle => (Some(remaining), false),
+            (_, Some(idle)) => (Some(idle), true),
+            (remaining, None) => (remaining, false),
+        })
+    }
+}
+
+pub(crate) fn timeout_err() -> io::Error {
     io::Error::new(io::ErrorKind::TimedOut, "the timeout of the request was reached")
 }
 
+/// Returns the error for a read which timed out, after [`Timeouts::read_timeout`] said whether
+/// the read idle timeout was used.
+fn read_timeout_err(idle: bool) -> io::Error {
+    if idle {
+        ReadIdleTimeoutReached::io_error()
+    } else {
+        timeout_err()
+    }
+}
+
+#[cfg(unix)]
+fn unix_https_err() -> Error {
+    Error::IoError(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "HTTPS isn't supported over Unix domain sockets",
+    ))
+}
+
 fn timeout_at_to_duration(timeout_at: Option<Instant>) -> Result<Option<Duration>, io::Error> {
     if let Some(timeout_at) = timeout_at {
         if let Some(duration) = timeout_at.checked_duration_since(Instant::now()) {
@@ -74,28 +261,42 @@ fn timeout_at_to_duration(timeout_at: Option<Instant>) -> Result<Option<Duration
 
 impl Read for HttpStream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let timeout = |tcp: &TcpStream, timeout_at: Option<Instant>| -> io::Result<()> {
-            let _ = tcp.set_read_timeout(timeout_at_to_duration(timeout_at)?);
-            Ok(())
+        let timeout = |tcp: &TcpStream, timeouts: &Timeouts| -> io::Result<bool> {
+            let (timeout, idle) = timeouts.read_timeout()?;
+            let _ = tcp.set_read_timeout(timeout);
+            Ok(idle)
         };
 
-        let result = match self {
-            HttpStream::Unsecured(inner, timeout_at) => {
-                timeout(inner, *timeout_at)?;
-                inner.read(buf)
+        let (result, idle) = match self {
+            HttpStream::Unsecured(inner, timeouts) => {
+                let idle = timeout(inner, timeouts)?;
+                (inner.read(buf), idle)
             }
-            #[cfg(feature = "rustls")]
-            HttpStream::Secured(inner, timeout_at) => {
-                timeout(inner.get_ref(), *timeout_at)?;
-                inner.read(buf)
+            #[cfg(unix)]
+            HttpStream::Unix(inner, timeouts, peeked) => match peeked.take() {
+                Some(byte) if !buf.is_empty() => {
+                    buf[0] = byte;
+                    (Ok(1), false)
+                }
+                byte => {
+                    *peeked = byte;
+                    let (timeout, idle) = timeouts.read_timeout()?;
+                    let _ = inner.set_read_timeout(timeout);
+                    (inner.read(buf), idle)
+                }
+            },
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            HttpStream::Secured(inner, timeouts) => {
+                let idle = timeout(inner.get_ref(), timeouts)?;
+                (inner.read(buf), idle)
             }
             #[cfg(feature = "async")]
-            HttpStream::Buffer(cursor) => std::io::Read::read(cursor, buf),
+            HttpStream::Buffer(cursor) => (std::io::Read::read(cursor, buf), false),
         };
         match result {
             Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
                 // We're a blocking socket, so EWOULDBLOCK indicates a timeout
-                Err(timeout_err())
+                Err(read_timeout_err(idle))
             }
             r => r,
         }
@@ -110,13 +311,18 @@ fn set_socket_write_timeout(tcp: &TcpStream, timeout_at: Option<Instant>) -> io:
 impl Write for HttpStream {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         let result = match self {
-            HttpStream::Unsecured(inner, timeout_at) => {
-                set_socket_write_timeout(inner, *timeout_at)?;
+            HttpStream::Unsecured(inner, timeouts) => {
+                set_socket_write_timeout(inner, timeouts.timeout_at)?;
                 inner.write(buf)
             }
-            #[cfg(feature = "rustls")]
-            HttpStream::Secured(inner, timeout_at) => {
-                set_socket_write_timeout(inner.get_ref(), *timeout_at)?;
+            #[cfg(unix)]
+            HttpStream::Unix(inner, timeouts, _) => {
+                inner.set_write_timeout(timeout_at_to_duration(timeouts.timeout_at)?)?;
+                inner.write(buf)
+            }
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            HttpStream::Secured(inner, timeouts) => {
+                set_socket_write_timeout(inner.get_ref(), timeouts.timeout_at)?;
                 inner.write(buf)
             }
             #[cfg(feature = "async")]
@@ -136,13 +342,18 @@ impl Write for HttpStream {
 
     fn flush(&mut self) -> io::Result<()> {
         let result = match self {
-            HttpStream::Unsecured(inner, timeout_at) => {
-                set_socket_write_timeout(inner, *timeout_at)?;
+            HttpStream::Unsecured(inner, timeouts) => {
+                set_socket_write_timeout(inner, timeouts.timeout_at)?;
+                inner.flush()
+            }
+            #[cfg(unix)]
+            HttpStream::Unix(inner, timeouts, _) => {
+                inner.set_write_timeout(timeout_at_to_duration(timeouts.timeout_at)?)?;
                 inner.flush()
             }
-            #[cfg(feature = "rustls")]
-            HttpStream::Secured(inner, timeout_at) => {
-                set_socket_write_timeout(inner.get_ref(), *timeout_at)?;
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            HttpStream::Secured(inner, timeouts) => {
+                set_socket_write_timeout(inner.get_ref(), timeouts.timeout_at)?;
                 inner.flush()
             }
             #[cfg(feature = "async")]
@@ -161,13 +372,15 @@ impl Write for HttpStream {
     }
 }
 
-#[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
+#[cfg(any(feature = "tokio-rustls", feature = "tokio-native-tls"))]
 type AsyncSecuredStream = rustls_stream::AsyncSecuredStream;
 
 #[cfg(feature = "async")]
 pub(crate) enum AsyncHttpStream {
     Unsecured(AsyncTcpStream),
-    #[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
+    #[cfg(unix)]
+    Unix(AsyncUnixStream),
+    #[cfg(any(feature = "tokio-rustls", feature = "tokio-native-tls"))]
     Secured(Box<AsyncSecuredStream>),
 }
 
@@ -180,7 +393,9 @@ impl AsyncRead for AsyncHttpStream {
     ) -> Poll<io::Result<()>> {
         match &mut *self {
             AsyncHttpStream::Unsecured(inner) => Pin::new(inner).poll_read(cx, buf),
-            #[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
+            #[cfg(unix)]
+            AsyncHttpStream::Unix(inner) => Pin::new(inner).poll_read(cx, buf),
+            #[cfg(any(feature = "tokio-rustls", feature = "tokio-native-tls"))]
             AsyncHttpStream::Secured(inner) => Pin::new(inner).poll_read(cx, buf),
         }
     }
@@ -195,7 +410,9 @@ impl AsyncWrite for AsyncHttpStream {
     ) -> Poll<io::Result<usize>> {
         match &mut *self {
             AsyncHttpStream::Unsecured(inner) => Pin::new(inner).poll_write(cx, buf),
-            #[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
+            #[cfg(unix)]
+            AsyncHttpStream::Unix(inner) => Pin::new(inner).poll_write(cx, buf),
+            #[cfg(any(feature = "tokio-rustls", feature = "tokio-native-tls"))]
             AsyncHttpStream::Secured(inner) => Pin::new(inner).poll_write(cx, buf),
         }
     }
@@ -203,7 +420,9 @@ impl AsyncWrite for AsyncHttpStream {
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         match &mut *self {
             AsyncHttpStream::Unsecured(inner) => Pin::new(inner).poll_flush(cx),
-            #[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
+            #[cfg(unix)]
+            AsyncHttpStream::Unix(inner) => Pin::new(inner).poll_flush(cx),
+            #[cfg(any(feature = "tokio-rustls", feature = "tokio-native-tls"))]
             AsyncHttpStream::Secured(inner) => Pin::new(inner).poll_flush(cx),
         }
     }
@@ -211,12 +430,94 @@ impl AsyncWrite for AsyncHttpStream {
     fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         match &mut *self {
             AsyncHttpStream::Unsecured(inner) => Pin::new(inner).poll_shutdown(cx),
-            #[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
+            #[cfg(unix)]
+            AsyncHttpStream::Unix(inner) => Pin::new(inner).poll_shutdown(cx),
+            #[cfg(any(feature = "tokio-rustls", feature = "tokio-native-tls"))]
             AsyncHttpStream::Secured(inner) => Pin::new(inner).poll_shutdown(cx),
         }
     }
 }
 
+/// Wraps an async reader, failing reads with [`Error::ReadIdleTimeout`] once the reader has
+/// been waiting for data for longer than the read idle timeout.
+#[cfg(feature = "async")]
+pub(crate) struct IdleTimeoutReader<R> {
+    inner: R,
+    idle_timeout: Option<Duration>,
+    /// Fires when the current wait for data times out. Only (re)armed once a read has to wait,
+    /// so that reads served from a buffer stay cheap.
+    sleep: Option<Pin<Box<tokio::time::Sleep>>>,
+    /// Whether a read completed since `sleep` was armed.
+    progressed: bool,
+}
+
+#[cfg(feature = "async")]
+impl<R> IdleTimeoutReader<R> {
+    pub(crate) fn new(inner: R, idle_timeout: Option<Duration>) -> Self {
+        IdleTimeoutReader { inner, idle_timeout, sleep: None, progressed: true }
+    }
+}
+
+/// Passes on the result of a read from an [`IdleTimeoutReader`]'s inner reader, or the read
+/// idle timeout error if the read has to wait and the timeout is reached.
+#[cfg(feature = "async")]
+fn poll_idle_timeout<T>(
+    cx: &mut Context<'_>,
+    idle_timeout: Option<Duration>,
+    sleep: &mut Option<Pin<Box<tokio::time::Sleep>>>,
+    progressed: &mut bool,
+    poll: Poll<io::Result<T>>,
+) -> Poll<io::Result<T>> {
+    let idle_timeout = match (poll, idle_timeout) {
+        (Poll::Ready(result), _) => {
+            *progressed = true;
+            return Poll::Ready(result);
+        }
+        (Poll::Pending, None) => return Poll::Pending,
+        (Poll::Pending, Some(idle_timeout)) => idle_timeout,
+    };
+    let deadline = tokio::time::Instant::now() + idle_timeout;
+    let sleep = sleep.get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+    if *progressed {
+        sleep.as_mut().reset(deadline);
+        *progressed = false;
+    }
+    match sleep.as_mut().poll(cx) {
+        Poll::Ready(()) => Poll::Ready(Err(ReadIdleTimeoutReached::io_error())),
+        Poll::Pending => Poll::Pending,
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncRead for IdleTimeoutReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        poll_idle_timeout(cx, this.idle_timeout, &mut this.sleep, &mut this.progressed, poll)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncBufRead + Unpin> AsyncBufRead for IdleTimeoutReader<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_fill_buf(cx);
+        poll_idle_timeout(cx, this.idle_timeout, &mut this.sleep, &mut this.progressed, poll)
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) { Pin::new(&mut self.inner).consume(amt) }
+}
+
+/// The read and write halves of a connection switched to another protocol, see
+/// [`AsyncConnection::upgrade`].
+#[cfg(all(feature = "websocket", feature = "async"))]
+pub(crate) type UpgradedStream =
+    (tokio::io::BufReader<ReadHalf<AsyncHttpStream>>, WriteHalf<AsyncHttpStream>);
+
 /// The starting value of [`AsyncConnectionState::permits`].
 #[cfg(feature = "async")]
 const MAX_SEND_PERMITS: isize = isize::MAX;
@@ -274,30 +575,9 @@ impl AsyncConnection {
     /// Creates a new `AsyncConnection`.
     pub(crate) async fn new(
         params: ConnectionParams<'_>,
-        timeout_at: Option<Instant>,
+        timeouts: Timeouts,
     ) -> Result<AsyncConnection, Error> {
-        let future = async move {
-            let socket = Self::connect(params).await?;
-
-            if params.https {
-                #[cfg(not(any(
-                    feature = "async-https-rustls",
-                    feature = "async-https-rustls-probe"
-                )))]
-                return Err(Error::HttpsFeatureNotEnabled);
-                #[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
-                rustls_stream::wrap_async_stream(socket, params.host).await
-            } else {
-                Ok(AsyncHttpStream::Unsecured(socket))
-            }
-        };
-        let stream = if let Some(timeout_at) = timeout_at {
-            tokio::time::timeout_at(timeout_at.into(), future)
-                .await
-                .unwrap_or(Err(Error::IoError(timeout_err())))?
-        } else {
-            future.await?
-        };
+        let stream = Self::open_stream(params, timeouts).await?;
         let (read, write) = tokio::io::split(stream);
 
         Ok(AsyncConnection(Mutex::new(Arc::new(AsyncConnectionState {
@@ -314,47 +594,97 @@ impl AsyncConnection {
         }))))
     }
 
-    async fn tcp_connect(host: &str, port: u16) -> Result<AsyncTcpStream, Error> {
-        #[cfg(feature = "log")]
-        log::trace!("Looking up host {host}");
-
-        let addrs = tokio::net::lookup_host((host, port)).await.map_err(Error::IoError)?;
-        let addrs: Vec<_> = addrs.collect();
-        let addrs_count = addrs.len();
+    /// Returns `true` if no requests are in flight over this connection, so that a new one
+    /// wouldn't have to wait for them.
+    pub(crate) fn is_idle(&self) -> bool {
+        let conn = self.0.lock().unwrap();
+        conn.permits.load(Ordering::Relaxed) > 0
+            && conn.readable_request_id.load(Ordering::Acquire)
+                == conn.next_request_id.load(Ordering::Acquire)
+    }
 
-        if addrs.is_empty() {
-            return Err(Error::AddressNotFound);
+    /// Returns `true` if this connection can't be used for further requests, as the server
+    /// either said it won't accept any more or, while no requests are in flight, closed the
+    /// connection or sent data we didn't ask for.
+    pub(crate) async fn is_stale(&self) -> bool {
+        let conn = Arc::clone(&*self.0.lock().unwrap());
+        if conn.permits.load(Ordering::Relaxed) <= 0
+            || Instant::now() > *conn.socket_new_requests_timeout.lock().unwrap()
+        {
+            return true;
         }
+        let Ok(mut read) = conn.read.try_lock() else { return false };
+        if conn.readable_request_id.load(Ordering::Acquire)
+            != conn.next_request_id.load(Ordering::Acquire)
+        {
+            return false;
+        }
+        core::future::poll_fn(|cx| Poll::Ready(Pin::new(&mut *read).poll_fill_buf(cx).is_ready()))
+            .await
+    }
 
-        // Try all resolved addresses. Return the first one to which we could connect. If all
-        // failed return the last error encountered.
-        for (i, addr) in addrs.iter().enumerate() {
-            #[cfg(feature = "log")]
-            log::trace!("Attempting to connect to {addr} for {host}");
-
-            match AsyncTcpStream::connect(addr).await {
-                Ok(s) => {
-                    #[cfg(feature = "log")]
-                    log::trace!("Connected to {addr} for {host}");
-                    return Ok(s);
+    /// Connects to the server, setting up TLS if needed.
+    async fn open_stream(
+        params: ConnectionParams<'_>,
+        timeouts: Timeouts,
+    ) -> Result<AsyncHttpStream, Error> {
+        let future = async move {
+            #[cfg(unix)]
+            if let Some(path) = params.unix_socket {
+                if params.https {
+                    return Err(unix_https_err());
                 }
-                Err(e) =>
-                    if i == addrs_count - 1 {
-                        return Err(Error::IoError(e));
-                    },
+                return Ok(AsyncHttpStream::Unix(AsyncUnixStream::connect(path).await?));
             }
+
+            let socket = Self::connect(params, timeouts.connect).await?;
+
+            if params.https {
+                #[cfg(not(any(feature = "tokio-rustls", feature = "tokio-native-tls")))]
+                return Err(Error::HttpsFeatureNotEnabled);
+                #[cfg(any(feature = "tokio-rustls", feature = "tokio-native-tls"))]
+                rustls_stream::wrap_async_stream(socket, params.host, params.tls.map(|tls| &**tls))
+                    .await
+            } else {
+                Ok(AsyncHttpStream::Unsecured(socket))
+            }
+        };
+        if let Some(timeout_at) = timeouts.timeout_at {
+            tokio::time::timeout_at(timeout_at.into(), future)
+                .await
+                .unwrap_or(Err(Error::IoError(timeout_err())))
+        } else {
+            future.await
         }
+    }
 
-        Err(Error::AddressNotFound)
+    async fn tcp_connect(
+        resolver: Option<&SharedResolver>,
+        host: &str,
+        port: u16,
+        connect_timeout: Option<Duration>,
+    ) -> Result<AsyncTcpStream, Error> {
+        let addrs = resolve::resolve_async(resolver, host, port).await?;
+        let connect = happy_eyeballs::connect_async(addrs);
+        match connect_timeout {
+            Some(timeout) =>
+                tokio::time::timeout(timeout, connect).await.unwrap_or(Err(Error::ConnectTimeout)),
+            None => connect.await,
+        }
     }
 
     /// Asynchronously connect to the server.
-    async fn connect(params: ConnectionParams<'_>) -> Result<AsyncTcpStream, Error> {
+    async fn connect(
+        params: ConnectionParams<'_>,
+        connect_timeout: Option<Duration>,
+    ) -> Result<AsyncTcpStream, Error> {
         #[cfg(feature = "proxy")]
         match &params.proxy {
-            Some(proxy) => {
+            Some(proxy) if matches!(proxy.kind, ProxyKind::Basic) => {
                 // do proxy things
-                let mut tcp = Self::tcp_connect(&proxy.server, proxy.port).await?;
+                let mut tcp =
+                    Self::tcp_connect(params.resolver, &proxy.server, proxy.port, connect_timeout)
+                        .await?;
 
                 let proxy_request = proxy.connect(params.host, params.port);
                 tcp.write_all(proxy_request.as_bytes()).await?;
@@ -385,11 +715,27 @@ impl AsyncConnection {
 
                 Ok(tcp)
             }
-            None => Self::tcp_connect(params.host, params.port).await,
+            Some(proxy) => {
+                let mut tcp =
+                    Self::tcp_connect(params.resolver, &proxy.server, proxy.port, connect_timeout)
+                        .await?;
+                let target = match Socks5Target::new(params.host, proxy.remote_dns()) {
+                    Ok(target) => target,
+                    Err(host) => {
+                        let addrs =
+                            resolve::resolve_async(params.resolver, host, params.port).await?;
+                        Socks5Target::Ip(addrs[0].ip())
+                    }
+                };
+                socks5_handshake!(tcp, proxy, target, params.port, await);
+                Ok(tcp)
+            }
+            None =>
+                Self::tcp_connect(params.resolver, params.host, params.port, connect_timeout).await,
         }
 
         #[cfg(not(feature = "proxy"))]
-        Self::tcp_connect(params.host, params.port).await
+        Self::tcp_connect(params.resolver, params.host, params.port, connect_timeout).await
     }
 
     async fn timeout<O, F: Future<Output = O>>(timeout: Option<Instant>, f: F) -> Result<O, Error> {
@@ -400,6 +746,36 @@ impl AsyncConnection {
         }
     }
 
+    /// Sends an `Upgrade` request over a connection of its own and reads the head of the
+    /// response, handing back the stream positioned where the data of the protocol the connection
+    /// switched to starts. Redirects aren't followed.
+    #[cfg(feature = "websocket")]
+    pub(crate) async fn upgrade(
+        request: &ParsedRequest,
+    ) -> Result<(UpgradeResponse, UpgradedStream), Error> {
+        let upgrade = async {
+            let mut stream =
+                Self::open_stream(request.connection_params(), request.timeouts()).await?;
+            #[cfg(feature = "log")]
+            log::trace!(
+                "Writing upgrade request on connection to {:?}.",
+                request.connection_params()
+            );
+            request.write_to_async(&mut stream).await?;
+
+            let (read, write) = tokio::io::split(stream);
+            let mut read = tokio::io::BufReader::with_capacity(BACKING_READ_BUFFER_LENGTH, read);
+            let response = read_upgrade_head_async(
+                &mut IdleTimeoutReader::new(&mut read, request.config.read_idle_timeout),
+                request.config.max_headers_size,
+                request.config.max_status_line_len,
+            )
+            .await?;
+            Ok((response, (read, write)))
+        };
+        Self::timeout(request.timeout_at, upgrade).await?
+    }
+
     /// Sends the [`Request`](struct.Request.html) asynchronously using HTTP.
     pub(crate) fn send<'a>(
         &'a self,
@@ -465,7 +841,7 @@ impl AsyncConnection {
                 };
                 (_internal) => {
                     let new_connection =
-                        AsyncConnection::new(request.connection_params(), request.timeout_at)
+                        AsyncConnection::new(request.connection_params(), request.timeouts())
                             .await?;
                     *self.0.lock().unwrap() = Arc::clone(&*new_connection.0.lock().unwrap());
                     core::mem::drop(read);
@@ -503,18 +879,14 @@ impl AsyncConnection {
                 );
                 this_request.id = Some(request_id);
                 let write_res =
-                    Self::timeout(request.timeout_at, write.write_all(&request.as_bytes())).await;
+                    Self::timeout(request.timeout_at, request.write_to_async(&mut *write)).await;
                 match write_res {
-                    Err(e) => {
+                    Err(e) | Ok(Err(e)) => {
                         // If we failed to write the request, mark the socket as dead for future
                         // requests.
                         conn.permits.store(0, Ordering::Release);
                         return Err(e);
                     }
-                    Ok(Err(ioe)) => {
-                        conn.permits.store(0, Ordering::Release);
-                        return Err(Error::IoError(ioe));
-                    }
                     Ok(Ok(())) => {}
                 }
             }
@@ -567,7 +939,7 @@ impl AsyncConnection {
                 );
 
                 let response = Response::create_async(
-                    &mut *read,
+                    IdleTimeoutReader::new(&mut *read, request.config.read_idle_timeout),
                     request.config.method == Method::Head,
                     request.config.max_headers_size,
                     request.config.max_status_line_len,
@@ -653,12 +1025,70 @@ impl AsyncConnection {
             async_handle_redirects(self, request, response).await
         })
     }
+
+    /// Sends the [`Request`](struct.Request.html) over a connection of its own, returning the
+    /// response as soon as its head has been read, leaving the body to be streamed from the
+    /// connection.
+    pub(crate) async fn send_streaming(
+        mut request: ParsedRequest,
+    ) -> Result<ResponseStream, Error> {
+        loop {
+            let mut stream =
+                Self::open_stream(request.connection_params(), request.timeouts()).await?;
+
+            #[cfg(feature = "log")]
+            log::trace!(
+                "Writing streamed HTTP request on connection to {:?}.",
+                request.connection_params(),
+            );
+            Self::timeout(request.timeout_at, request.write_to_async(&mut stream)).await??;
+
+            let stream = tokio::io::BufReader::with_capacity(BACKING_READ_BUFFER_LENGTH, stream);
+            let stream = IdleTimeoutReader::new(stream, request.config.read_idle_timeout);
+            let mut response = Self::timeout(
+                request.timeout_at,
+                ResponseStream::from_stream(
+                    stream,
+                    request.config.method == Method::Head,
+                    request.config.max_headers_size,
+                    request.config.max_status_line_len,
+                    request.config.max_body_size,
+                    request.timeout_at,
+                ),
+            )
+            .await??;
+
+            request.store_cookies(&response.set_cookies);
+            let url = response.headers.get("location");
+            match async_get_redirect(request, response.status_code, url) {
+                NextHopAsync::Redirect(redirect) => request = redirect?.0,
+                NextHopAsync::Destination(request) => {
+                    response.redirects = request.redirect_history();
+                    let dst_url = request.url;
+                    dst_url.write_base_url_to(&mut response.url).unwrap();
+                    dst_url.write_resource_to(&mut response.url).unwrap();
+                    return Ok(response);
+                }
+            }
+        }
+    }
 }
 
 /// A connection to the server for sending
 /// [`Request`](struct.Request.html)s.
 pub struct Connection {
     stream: HttpStream,
+    /// Whether we already read a response from this connection, i.e. it was kept alive for
+    /// further requests.
+    reused: bool,
+    /// The number of further requests we may send over this connection, capped by the `max`
+    /// parameter of the server's `Keep-Alive` header.
+    remaining_requests: usize,
+    /// The time at which we should stop sending new requests over this socket and should instead
+    /// connect again.
+    /// Defaults to 60 seconds after open to align with nginx's default timeout of 75 seconds, but
+    /// can be overridden by the `Keep-Alive` header.
+    socket_new_requests_timeout: Instant,
 }
 
 impl Connection {
@@ -666,70 +1096,91 @@ impl Connection {
     /// for specifics about *what* is being sent.
     pub(crate) fn new(
         params: ConnectionParams<'_>,
-        timeout_at: Option<Instant>,
+        timeouts: Timeouts,
     ) -> Result<Connection, Error> {
-        let socket = Self::connect(params, timeout_at)?;
+        #[cfg(unix)]
+        if let Some(path) = params.unix_socket {
+            return Self::new_unix(path, params.https, timeouts);
+        }
+
+        let socket = Self::connect(params, timeouts)?;
 
         let stream = if params.https {
-            #[cfg(not(feature = "rustls"))]
+            #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
             return Err(Error::HttpsFeatureNotEnabled);
-            #[cfg(feature = "rustls")]
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
             {
-                let tls = rustls_stream::wrap_stream(socket, params.host)?;
-                HttpStream::Secured(Box::new(tls), timeout_at)
+                let tls =
+                    rustls_stream::wrap_stream(socket, params.host, params.tls.map(|tls| &**tls))?;
+                HttpStream::Secured(Box::new(tls), timeouts)
             }
         } else {
-            HttpStream::create_unsecured(socket, timeout_at)
+            HttpStream::create_unsecured(socket, timeouts)
         };
 
-        Ok(Connection { stream })
+        Ok(Connection {
+            stream,
+            reused: false,
+            remaining_requests: usize::MAX,
+            socket_new_requests_timeout: Instant::now() + Duration::from_secs(60),
+        })
     }
 
-    fn tcp_connect(host: &str, port: u16, timeout_at: Option<Instant>) -> Result<TcpStream, Error> {
+    /// Creates a new `Connection` over the Unix domain socket at `path`.
+    #[cfg(unix)]
+    fn new_unix(path: &Path, https: bool, timeouts: Timeouts) -> Result<Connection, Error> {
+        if https {
+            return Err(unix_https_err());
+        }
         #[cfg(feature = "log")]
-        log::trace!("Looking up host {host}");
-
-        let addrs = (host, port).to_socket_addrs().map_err(Error::IoError)?;
-        let addrs_count = addrs.len();
+        log::trace!("Connecting to Unix socket {}", path.display());
+        // Connecting to a Unix socket doesn't block, so just check the timeout hasn't passed yet.
+        timeout_at_to_duration(timeouts.timeout_at)?;
+        let socket = UnixStream::connect(path)?;
+
+        Ok(Connection {
+            stream: HttpStream::Unix(socket, timeouts, None),
+            reused: false,
+            remaining_requests: usize::MAX,
+            socket_new_requests_timeout: Instant::now() + Duration::from_secs(60),
+        })
+    }
 
-        // Try all resolved addresses. Return the first one to which we could connect. If all
-        // failed return the last error encountered.
-        for (i, addr) in addrs.enumerate() {
-            #[cfg(feature = "log")]
-            log::trace!("Attempting to connect to {addr} for {host}");
+    /// Returns `true` if the server is still willing to accept new requests over this
+    /// connection.
+    pub(crate) fn can_send_requests(&self) -> bool {
+        self.remaining_requests > 0 && Instant::now() < self.socket_new_requests_timeout
+    }
 
-            let stream = if let Some(timeout) = timeout_at_to_duration(timeout_at)? {
-                TcpStream::connect_timeout(&addr, timeout)
-            } else {
-                TcpStream::connect(addr)
-            };
+    /// Returns `true` if this idle connection can't be used for further requests, as the server
+    /// either said it won't accept any more or closed the connection.
+    pub(crate) fn is_stale(&mut self) -> bool {
+        !self.can_send_requests() || self.stream.is_closed()
+    }
 
-            match stream {
-                Ok(s) => {
-                    #[cfg(feature = "log")]
-                    log::trace!("Connected to {addr} for {host}");
-                    return Ok(s);
-                }
-                Err(e) =>
-                    if i == addrs_count - 1 {
-                        return Err(Error::IoError(e));
-                    },
-            }
+    fn tcp_connect(
+        resolver: Option<&SharedResolver>,
+        host: &str,
+        port: u16,
+        timeouts: Timeouts,
+    ) -> Result<TcpStream, Error> {
+        let addrs = resolve::resolve(resolver, host, port)?;
+        let (connect_at, is_connect_timeout) = timeouts.connect_deadline();
+        match happy_eyeballs::connect(addrs, connect_at) {
+            Err(Error::IoError(e)) if is_connect_timeout && e.kind() == io::ErrorKind::TimedOut =>
+                Err(Error::ConnectTimeout),
+            result => result,
         }
-
-        Err(Error::AddressNotFound)
     }
 
     /// Connect to the server.
-    fn connect(
-        params: ConnectionParams<'_>,
-        timeout_at: Option<Instant>,
-    ) -> Result<TcpStream, Error> {
+    fn connect(params: ConnectionParams<'_>, timeouts: Timeouts) -> Result<TcpStream, Error> {
         #[cfg(feature = "proxy")]
         match &params.proxy {
-            Some(proxy) => {
+            Some(proxy) if matches!(proxy.kind, ProxyKind::Basic) => {
                 // do proxy things
-                let mut tcp = Self::tcp_connect(&proxy.server, proxy.port, timeout_at)?;
+                let mut tcp =
+                    Self::tcp_connect(params.resolver, &proxy.server, proxy.port, timeouts)?;
 
                 write!(tcp, "{}", proxy.connect(params.host, params.port))?;
                 tcp.flush()?;
@@ -759,11 +1210,24 @@ impl Connection {
 
                 Ok(tcp)
             }
-            None => Self::tcp_connect(params.host, params.port, timeout_at),
+            Some(proxy) => {
+                let mut tcp =
+                    Self::tcp_connect(params.resolver, &proxy.server, proxy.port, timeouts)?;
+                let target = match Socks5Target::new(params.host, proxy.remote_dns()) {
+                    Ok(target) => target,
+                    Err(host) => {
+                        let addrs = resolve::resolve(params.resolver, host, params.port)?;
+                        Socks5Target::Ip(addrs[0].ip())
+                    }
+                };
+                socks5_handshake!(tcp, proxy, target, params.port);
+                Ok(tcp)
+            }
+            None => Self::tcp_connect(params.resolver, params.host, params.port, timeouts),
         }
 
         #[cfg(not(feature = "proxy"))]
-        Self::tcp_connect(params.host, params.port, timeout_at)
+        Self::tcp_connect(params.resolver, params.host, params.port, timeouts)
     }
 
     /// Sends the [`Request`](struct.Request.html), consumes this
@@ -773,13 +1237,14 @@ impl Connection {
             // Send request
             #[cfg(feature = "log")]
             log::trace!("Writing HTTP request.");
-            self.stream.write_all(&request.as_bytes())?;
+            request.write_to(&mut self.stream)?;
 
             // Receive response
             #[cfg(feature = "log")]
             log::trace!("Reading HTTP response.");
             let response = ResponseLazy::from_stream(
                 self.stream,
+                request.config.method == Method::Head,
                 request.config.max_headers_size,
                 request.config.max_status_line_len,
                 request.config.max_body_size,
@@ -787,20 +1252,149 @@ impl Connection {
             handle_redirects(request, response)
         })
     }
+
+    /// Sends an `Upgrade` request and reads the head of the response, handing back the stream
+    /// positioned where the data of the protocol the connection switched to starts. Redirects
+    /// aren't followed.
+    #[cfg(feature = "websocket")]
+    pub(crate) fn upgrade(
+        mut self,
+        request: &ParsedRequest,
+    ) -> Result<(UpgradeResponse, io::BufReader<HttpStream>), Error> {
+        #[cfg(feature = "log")]
+        log::trace!("Writing upgrade request on connection to {:?}.", request.connection_params());
+        request.write_to(&mut self.stream)?;
+        read_upgrade_head(
+            self.stream,
+            request.config.max_headers_size,
+            request.config.max_status_line_len,
+        )
+    }
+
+    /// Sends the [`Request`](struct.Request.html) and fully reads the [`Response`], without
+    /// following redirects or enforcing the request's timeout on DNS lookups.
+    ///
+    /// Unlike [`Connection::send`], the connection is handed back alongside the response if the
+    /// server agreed to keep it alive, so that it can be used for further requests.
+    ///
+    /// Returns `Ok(None)` if this connection was reused, the request's method is idempotent and
+    /// the server closed the connection before sending any part of a response, in which case the
+    /// request can be retried over a new connection.
+    pub(crate) fn send_keep_alive(
+        mut self,
+        request: &ParsedRequest,
+    ) -> Result<Option<(Response, Option<Connection>)>, Error> {
+        debug_assert!(self.can_send_requests());
+        self.remaining_requests -= 1;
+        self.stream.set_timeouts(request.timeouts());
+
+        #[cfg(feature = "log")]
+        log::trace!("Writing HTTP request on connection to {:?}.", request.connection_params());
+        let written = request.write_to(&mut self.stream);
+        let readable = written.and_then(|()| self.stream.wait_for_data().map_err(Error::from));
+        // The server may have acted on the request before closing the connection, so only send
+        // it again if doing so is harmless.
+        let replayable = self.reused && request.config.method.is_idempotent();
+        match readable {
+            Ok(true) => {}
+            Ok(false) if replayable => return Ok(None),
+            // A stale socket may also have been reset by the server.
+            Err(Error::IoError(e)) if replayable && e.kind() != io::ErrorKind::TimedOut =>
+                return Ok(None),
+            Ok(false) if self.reused =>
+                return Err(Error::IoError(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the server closed the connection without responding",
+                ))),
+            Ok(false) => {}
+            Err(e) => return Err(e),
+        }
+
+        #[cfg(feature = "log")]
+        log::trace!("Reading HTTP response.");
+        let is_head = request.config.method == Method::Head;
+        let response = ResponseLazy::from_stream(
+            self.stream,
+            is_head,
+            request.config.max_headers_size,
+            request.config.max_status_line_len,
+            request.config.max_body_size,
+        )?;
+        let (response, stream) =
+            Response::create_reusable(response, is_head, request.config.max_body_size)?;
+
+        let keep_alive = match keep_alive_params(&response) {
+            // Any bytes the server sent beyond the end of the response would be misinterpreted as
+            // the start of the next one, so only reuse the stream if none are buffered.
+            Some(params) if stream.as_ref().is_some_and(|s| s.buffer().is_empty()) => params,
+            _ => return Ok(Some((response, None))),
+        };
+        let mut connection = Connection {
+            stream: stream.expect("checked above").into_inner(),
+            reused: true,
+            remaining_requests: self.remaining_requests,
+            socket_new_requests_timeout: self.socket_new_requests_timeout,
+        };
+        if let Some(timeout_secs) = keep_alive.timeout_secs {
+            connection.socket_new_requests_timeout = Instant::now()
+                .checked_add(Duration::from_secs(timeout_secs.saturating_sub(1)))
+                .unwrap_or(Instant::now());
+        }
+        if let Some(max) = keep_alive.max {
+            connection.remaining_requests = connection.remaining_requests.min(max);
+        }
+        let connection = Some(connection).filter(Connection::can_send_requests);
+        Ok(Some((response, connection)))
+    }
+}
+
+/// The parameters of a response's `Keep-Alive` header.
+struct KeepAliveParams {
+    timeout_secs: Option<u64>,
+    max: Option<usize>,
+}
+
+/// Returns the server's `Keep-Alive` parameters if it allows sending further requests over the
+/// connection the response was read from.
+///
+/// This follows the same rules as [`AsyncConnection`]: the server has to explicitly send a
+/// `Connection: keep-alive` header, and any `Keep-Alive` parameter we don't understand makes us
+/// stop using the connection.
+fn keep_alive_params(response: &Response) -> Option<KeepAliveParams> {
+    let keep_alive = response.headers.get("connection")?;
+    if !keep_alive.eq_ignore_ascii_case("keep-alive") {
+        return None;
+    }
+
+    let mut params = KeepAliveParams { timeout_secs: None, max: None };
+    if let Some(header) = response.headers.get("keep-alive") {
+        for param in header.split(',') {
+            let (k, v) = param.trim().split_once('=')?;
+            let v = v.parse::<usize>().ok()?;
+            match k.trim() {
+                "timeout" => params.timeout_secs = Some(v as u64),
+                "max" => params.max = Some(v),
+                _ => return None,
+            }
+        }
+    }
+    Some(params)
 }
 
 fn handle_redirects(
     request: ParsedRequest,
     mut response: ResponseLazy,
 ) -> Result<ResponseLazy, Error> {
+    request.store_cookies(&response.set_cookies);
     let status_code = response.status_code;
     let url = response.headers.get("location");
     match get_redirect(request, status_code, url) {
         NextHop::Redirect(request) => {
             let (request, _) = request?;
-            Connection::new(request.connection_params(), request.timeout_at)?.send(request)
+            Connection::new(request.connection_params(), request.timeouts())?.send(request)
         }
         NextHop::Destination(request) => {
+            response.redirects = request.redirect_history();
             let dst_url = request.url;
             dst_url.write_base_url_to(&mut response.url).unwrap();
             dst_url.write_resource_to(&mut response.url).unwrap();
@@ -815,6 +1409,7 @@ async fn async_handle_redirects(
     request: ParsedRequest,
     mut response: Response,
 ) -> Result<Response, Error> {
+    request.store_cookies(&response.set_cookies);
     let status_code = response.status_code;
     let url = response.headers.get("location");
     match async_get_redirect(request, status_code, url) {
@@ -824,12 +1419,13 @@ async fn async_handle_redirects(
             let new_connection;
             if needs_new_connection {
                 new_connection =
-                    AsyncConnection::new(request.connection_params(), request.timeout_at).await?;
+                    AsyncConnection::new(request.connection_params(), request.timeouts()).await?;
                 connection = &new_connection;
             }
             connection.send(request).await
         }
         NextHopAsync::Destination(request) => {
+            response.redirects = request.redirect_history();
             let dst_url = request.url;
             dst_url.write_base_url_to(&mut response.url).unwrap();
             dst_url.write_resource_to(&mut response.url).unwrap();
@@ -840,34 +1436,44 @@ async fn async_handle_redirects(
 
 macro_rules! redirect_utils {
     ($get_redirect: ident, $NextHop: ident, $Response: ident) => {
-        enum $NextHop {
+        pub(crate) enum $NextHop {
             Redirect(Result<(ParsedRequest, bool), Error>),
             Destination(ParsedRequest),
         }
 
-        fn $get_redirect(
+        pub(crate) fn $get_redirect(
             mut request: ParsedRequest,
             status_code: i32,
             url: Option<&String>,
         ) -> $NextHop {
             match status_code {
-                301 | 302 | 303 | 307 => {
+                301 | 302 | 303 | 307 | 308 => {
                     let url = match url {
                         Some(url) => url,
                         None => return $NextHop::Redirect(Err(Error::RedirectLocationMissing)),
                     };
+                    let new_url = match request.redirect_url(url.as_str()) {
+                        Ok(new_url) => new_url,
+                        Err(err) => return $NextHop::Redirect(Err(err)),
+                    };
+                    if !request.allows_redirect(status_code, &new_url) {
+                        #[cfg(feature = "log")]
+                        log::debug!("Not following redirect ({}) to: {}", status_code, url);
+                        return $NextHop::Destination(request);
+                    }
                     #[cfg(feature = "log")]
                     log::debug!("Redirecting ({}) to: {}", status_code, url);
 
                     // TODO: Do this check without allocating a whole new params object
                     let previous_params: OwnedConnectionParams = request.connection_params().into();
 
-                    match request.redirect_to(url.as_str()) {
+                    match request.redirect_to(new_url) {
                         Ok(()) => {
                             if status_code == 303 {
                                 match request.config.method {
                                     Method::Post | Method::Put | Method::Delete => {
                                         request.config.method = Method::Get;
+                                        request.config.drop_body_stream();
                                     }
                                     _ => {}
                                 }
@@ -893,9 +1499,9 @@ redirect_utils!(async_get_redirect, NextHopAsync, Response);
 /// parking the current one with a timeout.
 ///
 /// While bitreq does use timeouts (somewhat) properly, some
-/// interfaces such as [ToSocketAddrs] don't allow for specifying the
+/// interfaces such as [ToSocketAddrs](std::net::ToSocketAddrs) don't allow for specifying the
 /// timeout. Hence this.
-fn enforce_timeout<F, R>(timeout_at: Option<Instant>, f: F) -> Result<R, Error>
+pub(crate) fn enforce_timeout<F, R>(timeout_at: Option<Instant>, f: F) -> Result<R, Error>
 where
     F: 'static + Send + FnOnce() -> Result<R, Error>,
     R: 'static + Send,
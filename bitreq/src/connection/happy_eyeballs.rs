@@ -0,0 +1,181 @@
+//! Dialing several addresses of a host in parallel, as described by
+//! [RFC 8305](https://datatracker.ietf.org/doc/html/rfc8305).
+
+use core::time::Duration;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Instant;
+use std::{io, thread};
+
+use super::timeout_at_to_duration;
+#[cfg(feature = "async")]
+use super::AsyncTcpStream;
+use crate::Error;
+
+/// How long to wait for a connection attempt before starting the next one in parallel, as
+/// recommended by RFC 8305 section 5.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Orders `addrs` so that IPv6 and IPv4 addresses alternate, starting with the family of the
+/// first address and otherwise keeping the resolver's order (RFC 8305 section 4).
+pub(super) fn interleave(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let prefer_v6 = addrs.first().is_some_and(SocketAddr::is_ipv6);
+    let (preferred, other): (Vec<_>, Vec<_>) =
+        addrs.into_iter().partition(|addr| addr.is_ipv6() == prefer_v6);
+    let mut interleaved = Vec::with_capacity(preferred.len() + other.len());
+    let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return interleaved,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+fn connect_one(addr: SocketAddr, timeout_at: Option<Instant>) -> io::Result<TcpStream> {
+    #[cfg(feature = "log")]
+    log::trace!("Attempting to connect to {addr}");
+
+    if let Some(timeout) = timeout_at_to_duration(timeout_at)? {
+        TcpStream::connect_timeout(&addr, timeout)
+    } else {
+        TcpStream::connect(addr)
+    }
+}
+
+/// Connects to the first of `addrs` which accepts the connection, starting a new attempt
+/// whenever one fails or [`CONNECTION_ATTEMPT_DELAY`] passes without any attempt finishing.
+///
+/// Attempts which are still pending once a connection is established are left to finish on
+/// their own threads, and the connections they establish are dropped.
+pub(super) fn connect(
+    addrs: Vec<SocketAddr>,
+    timeout_at: Option<Instant>,
+) -> Result<TcpStream, Error> {
+    let mut addrs = interleave(addrs).into_iter().peekable();
+    if addrs.len() == 1 {
+        return Ok(connect_one(addrs.next().expect("one address"), timeout_at)?);
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let mut pending = 0;
+    let mut last_err = None;
+    loop {
+        if let Some(addr) = addrs.next() {
+            let sender = sender.clone();
+            thread::spawn(move || {
+                let result = connect_one(addr, timeout_at).map(|stream| (addr, stream));
+                let _ = sender.send(result);
+            });
+            pending += 1;
+        }
+        if pending == 0 {
+            return Err(last_err.map_or(Error::AddressNotFound, Error::IoError));
+        }
+
+        let result = if addrs.peek().is_some() {
+            let delay = match timeout_at_to_duration(timeout_at)? {
+                Some(remaining) => remaining.min(CONNECTION_ATTEMPT_DELAY),
+                None => CONNECTION_ATTEMPT_DELAY,
+            };
+            match receiver.recv_timeout(delay) {
+                Ok(result) => result,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => unreachable!("we hold a sender"),
+            }
+        } else {
+            receiver.recv().expect("we hold a sender")
+        };
+        pending -= 1;
+        match result {
+            Ok((_addr, stream)) => {
+                #[cfg(feature = "log")]
+                log::trace!("Connected to {_addr}");
+                return Ok(stream);
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+}
+
+/// Like [`connect`], for async connections.
+///
+/// Attempts which are still pending once a connection is established are cancelled.
+#[cfg(feature = "async")]
+pub(super) async fn connect_async(addrs: Vec<SocketAddr>) -> Result<AsyncTcpStream, Error> {
+    let mut addrs = interleave(addrs).into_iter().peekable();
+    let mut attempts = tokio::task::JoinSet::new();
+    let mut last_err = None;
+    loop {
+        if let Some(addr) = addrs.next() {
+            #[cfg(feature = "log")]
+            log::trace!("Attempting to connect to {addr}");
+            attempts.spawn(async move { (addr, AsyncTcpStream::connect(addr).await) });
+        }
+
+        let result = if addrs.peek().is_some() {
+            match tokio::time::timeout(CONNECTION_ATTEMPT_DELAY, attempts.join_next()).await {
+                Ok(result) => result,
+                Err(_) => continue,
+            }
+        } else {
+            attempts.join_next().await
+        };
+        match result {
+            None => return Err(last_err.map_or(Error::AddressNotFound, Error::IoError)),
+            Some(Ok((_addr, Ok(stream)))) => {
+                #[cfg(feature = "log")]
+                log::trace!("Connected to {_addr}");
+                return Ok(stream);
+            }
+            Some(Ok((_, Err(e)))) => last_err = Some(e),
+            Some(Err(e)) => last_err = Some(io::Error::new(io::ErrorKind::Other, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr, TcpListener};
+
+    use super::*;
+
+    fn v4(n: u8) -> SocketAddr { (Ipv4Addr::new(10, 0, 0, n), 80).into() }
+    fn v6(n: u16) -> SocketAddr { (Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, n), 80).into() }
+
+    #[test]
+    fn interleaves_families() {
+        assert_eq!(
+            interleave(vec![v6(1), v6(2), v6(3), v4(1), v4(2)]),
+            [v6(1), v4(1), v6(2), v4(2), v6(3)]
+        );
+        assert_eq!(interleave(vec![v4(1), v4(2), v6(1)]), [v4(1), v6(1), v4(2)]);
+        assert_eq!(interleave(vec![v4(1), v4(2)]), [v4(1), v4(2)]);
+        assert!(interleave(vec![]).is_empty());
+    }
+
+    #[test]
+    fn connects_past_dead_addresses() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let live = listener.local_addr().unwrap();
+        // Nothing listens on the port of a closed listener, so connecting to it is refused.
+        let dead = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let stream = connect(vec![dead, dead, live], None).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), live);
+
+        match connect(vec![dead, dead], None) {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::ConnectionRefused),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(matches!(connect(vec![], None), Err(Error::AddressNotFound)));
+    }
+
+    #[test]
+    fn gives_up_at_timeout() {
+        let timeout_at = Instant::now();
+        match connect(vec![v4(1), v4(2)], Some(timeout_at)) {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::TimedOut),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+}
@@ -7,24 +7,36 @@ use std::io;
 use std::net::TcpStream;
 use std::sync::OnceLock;
 
+#[cfg(any(
+    all(feature = "native-tls", not(feature = "rustls")),
+    all(feature = "tokio-native-tls", not(feature = "tokio-rustls"))
+))]
+use native_tls::{Certificate, Identity, TlsConnector};
 #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
-use native_tls::{HandshakeError, TlsConnector, TlsStream};
+use native_tls::{HandshakeError, TlsStream};
 #[cfg(feature = "rustls")]
-use rustls::pki_types::ServerName;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
 #[cfg(feature = "rustls")]
-use rustls::{self, ClientConfig, ClientConnection, RootCertStore, StreamOwned};
-#[cfg(all(feature = "native-tls", not(feature = "rustls"), feature = "tokio-native-tls"))]
+use rustls::client::WebPkiServerVerifier;
+#[cfg(feature = "rustls")]
+use rustls::pki_types::pem::PemObject;
+#[cfg(feature = "rustls")]
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+#[cfg(feature = "rustls")]
+use rustls::{
+    self, CertificateError, ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore,
+    SignatureScheme, StreamOwned,
+};
+#[cfg(all(feature = "tokio-native-tls", not(feature = "tokio-rustls")))]
 use tokio_native_tls::TlsConnector as AsyncTlsConnector;
-#[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
+#[cfg(feature = "tokio-rustls")]
 use tokio_rustls::{client::TlsStream, TlsConnector};
 #[cfg(feature = "webpki-roots")]
 use webpki_roots::TLS_SERVER_ROOTS;
 
-#[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
-use super::{AsyncHttpStream, AsyncTcpStream};
-#[cfg(all(feature = "native-tls", not(feature = "rustls"), feature = "tokio-native-tls"))]
+#[cfg(any(feature = "tokio-rustls", feature = "tokio-native-tls"))]
 use super::{AsyncHttpStream, AsyncTcpStream};
-use crate::Error;
+use crate::{Error, TlsConfig};
 
 #[cfg(feature = "rustls")]
 pub type SecuredStream = StreamOwned<ClientConnection, TcpStream>;
@@ -33,30 +45,142 @@ pub type SecuredStream = StreamOwned<ClientConnection, TcpStream>;
 static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
 
 #[cfg(feature = "rustls")]
-fn build_client_config() -> Arc<ClientConfig> {
+fn pem_err(err: rustls::pki_types::pem::Error) -> Error {
+    Error::IoError(io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(feature = "rustls")]
+fn build_client_config(tls: Option<&TlsConfig>) -> Result<Arc<ClientConfig>, Error> {
     let mut root_certificates = RootCertStore::empty();
 
-    #[cfg(feature = "https-rustls-probe")]
-    for cert in rustls_native_certs::load_native_certs().certs {
-        let _ = root_certificates.add(cert);
+    if tls.map_or(true, |tls| tls.built_in_roots) {
+        #[cfg(feature = "https-rustls-probe")]
+        for cert in rustls_native_certs::load_native_certs().certs {
+            let _ = root_certificates.add(cert);
+        }
+
+        #[cfg(feature = "webpki-roots")]
+        root_certificates.extend(TLS_SERVER_ROOTS.iter().cloned());
+    }
+
+    let tls = match tls {
+        Some(tls) => tls,
+        None => {
+            let config = ClientConfig::builder()
+                .with_root_certificates(root_certificates)
+                .with_no_client_auth();
+            return Ok(Arc::new(config));
+        }
+    };
+
+    for pem in &tls.root_certificates_pem {
+        for cert in CertificateDer::pem_slice_iter(pem) {
+            root_certificates.add(cert.map_err(pem_err)?).map_err(Error::RustlsCreateConnection)?;
+        }
+    }
+
+    let builder = if tls.pinned_spki_sha256.is_empty() {
+        ClientConfig::builder().with_root_certificates(root_certificates)
+    } else {
+        let verifier = WebPkiServerVerifier::builder(Arc::new(root_certificates))
+            .build()
+            .map_err(|err| Error::IoError(io::Error::new(io::ErrorKind::InvalidInput, err)))?;
+        let verifier = PinningVerifier { verifier, tls: tls.clone() };
+        ClientConfig::builder().dangerous().with_custom_certificate_verifier(Arc::new(verifier))
+    };
+
+    let config = match &tls.client_identity_pem {
+        Some((cert_chain_pem, private_key_pem)) => {
+            let cert_chain = CertificateDer::pem_slice_iter(cert_chain_pem)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(pem_err)?;
+            let private_key = PrivateKeyDer::from_pem_slice(private_key_pem).map_err(pem_err)?;
+            builder
+                .with_client_auth_cert(cert_chain, private_key)
+                .map_err(Error::RustlsCreateConnection)?
+        }
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Arc::new(config))
+}
+
+/// Returns the shared default configuration, or the one of `tls`, building it on first use.
+#[cfg(feature = "rustls")]
+fn client_config(tls: Option<&TlsConfig>) -> Result<Arc<ClientConfig>, Error> {
+    match tls {
+        None => Ok(CONFIG.get_or_init(|| build_client_config(None).unwrap()).clone()),
+        Some(tls) => tls.rustls_config.get_or_try_init(|| build_client_config(Some(tls))),
+    }
+}
+
+/// Verifies server certificates as usual, then checks them against the
+/// [pinned public keys](TlsConfig::with_pinned_spki_sha256).
+#[cfg(feature = "rustls")]
+#[derive(Debug)]
+struct PinningVerifier {
+    verifier: Arc<WebPkiServerVerifier>,
+    tls: TlsConfig,
+}
+
+#[cfg(feature = "rustls")]
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.verifier.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        if self.tls.matches_pins(end_entity) {
+            Ok(verified)
+        } else {
+            Err(rustls::Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure))
+        }
     }
 
-    #[cfg(feature = "webpki-roots")]
-    root_certificates.extend(TLS_SERVER_ROOTS.iter().cloned());
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verifier.verify_tls12_signature(message, cert, dss)
+    }
 
-    let config =
-        ClientConfig::builder().with_root_certificates(root_certificates).with_no_client_auth();
-    Arc::new(config)
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.verifier.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.verifier.supported_verify_schemes()
+    }
 }
 
 #[cfg(feature = "rustls")]
-pub(super) fn wrap_stream(tcp: TcpStream, host: &str) -> Result<SecuredStream, Error> {
+pub(super) fn wrap_stream(
+    tcp: TcpStream,
+    host: &str,
+    tls: Option<&TlsConfig>,
+) -> Result<SecuredStream, Error> {
     #[cfg(feature = "log")]
     log::trace!("Setting up TLS parameters for {host}.");
     let dns_name = ServerName::try_from(host)
         .map(|name| name.to_owned())
         .map_err(|err| Error::IoError(io::Error::new(io::ErrorKind::Other, err)))?;
-    let sess = ClientConnection::new(CONFIG.get_or_init(build_client_config).clone(), dns_name)
+    let sess = ClientConnection::new(client_config(tls)?, dns_name)
         .map_err(Error::RustlsCreateConnection)?;
 
     #[cfg(feature = "log")]
@@ -66,13 +190,14 @@ pub(super) fn wrap_stream(tcp: TcpStream, host: &str) -> Result<SecuredStream, E
 
 // Async rustls TLS implementation
 
-#[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
+#[cfg(feature = "tokio-rustls")]
 pub type AsyncSecuredStream = TlsStream<tokio::net::TcpStream>;
 
-#[cfg(any(feature = "async-https-rustls", feature = "async-https-rustls-probe"))]
+#[cfg(feature = "tokio-rustls")]
 pub(super) async fn wrap_async_stream(
     tcp: AsyncTcpStream,
     host: &str,
+    tls: Option<&TlsConfig>,
 ) -> Result<AsyncHttpStream, Error> {
     #[cfg(feature = "log")]
     log::trace!("Setting up TLS parameters for {host}.");
@@ -80,7 +205,7 @@ pub(super) async fn wrap_async_stream(
         .map(|name| name.to_owned())
         .map_err(|err| Error::IoError(io::Error::new(io::ErrorKind::Other, err)))?;
 
-    let connector = TlsConnector::from(CONFIG.get_or_init(build_client_config).clone());
+    let connector = TlsConnector::from(client_config(tls)?);
 
     #[cfg(feature = "log")]
     log::trace!("Establishing TLS session to {host}.");
@@ -93,13 +218,16 @@ pub(super) async fn wrap_async_stream(
 #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
 pub type SecuredStream = TlsStream<TcpStream>;
 
-#[cfg(all(feature = "native-tls", not(feature = "rustls")))]
-static CONNECTOR: OnceLock<Result<TlsConnector, Error>> = OnceLock::new();
+#[cfg(any(
+    all(feature = "native-tls", not(feature = "rustls")),
+    all(feature = "tokio-native-tls", not(feature = "tokio-rustls"))
+))]
+static CONNECTOR: OnceLock<TlsConnector> = OnceLock::new();
 
 #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
 fn native_tls_err<S>(e: HandshakeError<S>) -> Error {
     match e {
-        HandshakeError::Failure(e) => Error::NativeTlsError(e),
+        HandshakeError::Failure(e) => Error::NativeTlsCreateConnection(e),
         HandshakeError::WouldBlock(_) => {
             debug_assert!(false, "We shouldn't hit a blocking error");
             Error::Other("Got a WouldBlock error from native-tls")
@@ -107,45 +235,110 @@ fn native_tls_err<S>(e: HandshakeError<S>) -> Error {
     }
 }
 
-#[cfg(all(feature = "native-tls", not(feature = "rustls")))]
-fn build_tls_connector() -> Result<TlsConnector, Error> {
-    TlsConnector::builder().build().map_err(Error::NativeTlsError)
+#[cfg(any(
+    all(feature = "native-tls", not(feature = "rustls")),
+    all(feature = "tokio-native-tls", not(feature = "tokio-rustls"))
+))]
+fn build_tls_connector(tls: Option<&TlsConfig>) -> Result<TlsConnector, native_tls::Error> {
+    let mut builder = TlsConnector::builder();
+    if let Some(tls) = tls {
+        for pem in &tls.root_certificates_pem {
+            for cert in crate::tls::pem_certificates(pem) {
+                builder.add_root_certificate(Certificate::from_pem(cert)?);
+            }
+        }
+        builder.disable_built_in_roots(!tls.built_in_roots);
+        if let Some((cert_chain_pem, private_key_pem)) = &tls.client_identity_pem {
+            builder.identity(Identity::from_pkcs8(cert_chain_pem, private_key_pem)?);
+        }
+    }
+    builder.build()
+}
+
+/// Returns the shared default connector, or the one of `tls`, building it on first use.
+#[cfg(any(
+    all(feature = "native-tls", not(feature = "rustls")),
+    all(feature = "tokio-native-tls", not(feature = "tokio-rustls"))
+))]
+fn tls_connector(tls: Option<&TlsConfig>) -> Result<TlsConnector, Error> {
+    if let Some(tls) = tls {
+        return tls.native_tls_connector.get_or_try_init(|| {
+            build_tls_connector(Some(tls)).map_err(Error::NativeTlsCreateConnection)
+        });
+    }
+    // TODO: Once we can `get_or_try_init`, use that instead
+    // https://github.com/rust-lang/rust/issues/109737
+    if let Some(connector) = CONNECTOR.get() {
+        return Ok(connector.clone());
+    }
+    let connector = build_tls_connector(None).map_err(Error::NativeTlsCreateConnection)?;
+    Ok(CONNECTOR.get_or_init(|| connector).clone())
+}
+
+/// Checks the server's certificate against the pinned public keys in `tls`, if any. Unlike
+/// rustls, native-tls doesn't let us do this during the handshake.
+#[cfg(any(
+    all(feature = "native-tls", not(feature = "rustls")),
+    all(feature = "tokio-native-tls", not(feature = "tokio-rustls"))
+))]
+fn verify_pins<S: io::Read + io::Write>(
+    tls: Option<&TlsConfig>,
+    stream: &native_tls::TlsStream<S>,
+) -> Result<(), Error> {
+    let tls = match tls {
+        Some(tls) if !tls.pinned_spki_sha256.is_empty() => tls,
+        _ => return Ok(()),
+    };
+    let cert = stream.peer_certificate().map_err(Error::NativeTlsCreateConnection)?;
+    let cert =
+        cert.map(|cert| cert.to_der()).transpose().map_err(Error::NativeTlsCreateConnection)?;
+    match cert {
+        Some(cert) if tls.matches_pins(&cert) => Ok(()),
+        _ => Err(Error::IoError(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the server certificate doesn't match any pinned public key",
+        ))),
+    }
 }
 
 #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
-pub(super) fn wrap_stream(tcp: TcpStream, host: &str) -> Result<SecuredStream, Error> {
+pub(super) fn wrap_stream(
+    tcp: TcpStream,
+    host: &str,
+    tls: Option<&TlsConfig>,
+) -> Result<SecuredStream, Error> {
     #[cfg(feature = "log")]
     log::trace!("Setting up TLS parameters for {host}.");
 
-    // TODO: Once we can `get_or_try_init`, so that instead
-    // https://github.com/rust-lang/rust/issues/109737
-    let connector = CONNECTOR.get_or_init(build_tls_connector)?;
+    let connector = tls_connector(tls)?;
 
     #[cfg(feature = "log")]
     log::trace!("Establishing TLS session to {host}.");
 
-    connector.connect(host, tcp).map_err(native_tls_err)
+    let stream = connector.connect(host, tcp).map_err(native_tls_err)?;
+    verify_pins(tls, &stream)?;
+    Ok(stream)
 }
 
-#[cfg(all(feature = "native-tls", not(feature = "rustls"), feature = "tokio-native-tls"))]
+#[cfg(all(feature = "tokio-native-tls", not(feature = "tokio-rustls")))]
 pub type AsyncSecuredStream = tokio_native_tls::TlsStream<tokio::net::TcpStream>;
 
-#[cfg(all(feature = "native-tls", not(feature = "rustls"), feature = "tokio-native-tls"))]
+#[cfg(all(feature = "tokio-native-tls", not(feature = "tokio-rustls")))]
 pub(super) async fn wrap_async_stream(
     tcp: AsyncTcpStream,
     host: &str,
+    tls: Option<&TlsConfig>,
 ) -> Result<AsyncHttpStream, Error> {
     #[cfg(feature = "log")]
     log::trace!("Setting up TLS parameters for {host}.");
 
-    // TODO: Once we can `get_or_try_init`, so that instead
-    // https://github.com/rust-lang/rust/issues/109737
-    let connector = AsyncTlsConnector::from(CONNECTOR.get_or_init(build_tls_connector)?.clone());
+    let connector = AsyncTlsConnector::from(tls_connector(tls)?);
 
     #[cfg(feature = "log")]
     log::trace!("Establishing TLS session to {host}.");
 
-    let tls = connector.connect(host, tcp).await.map_err(native_tls_err)?;
+    let stream = connector.connect(host, tcp).await.map_err(Error::NativeTlsCreateConnection)?;
+    verify_pins(tls, stream.get_ref())?;
 
-    Ok(AsyncHttpStream::Secured(Box::new(tls)))
+    Ok(AsyncHttpStream::Secured(Box::new(stream)))
 }
@@ -0,0 +1,447 @@
+//! Storing cookies set by servers and sending them back, see [`CookieJar`].
+
+use core::cmp::Reverse;
+use core::time::Duration;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::Url;
+
+/// Stores the cookies set by servers with `Set-Cookie` headers, and sends
+/// them back with the requests they apply to, following
+/// [RFC 6265](https://datatracker.ietf.org/doc/html/rfc6265).
+///
+/// Cookies are matched against the domain and path of each request,
+/// including every redirect followed, and dropped once they expire.
+/// `Secure` cookies are only sent over (and only accepted from) HTTPS, and
+/// `HttpOnly` cookies are sent like any other, as every request is made over
+/// HTTP. Cookies whose `Domain` is a public suffix (such as `com`) aren't
+/// rejected, as there's no public suffix list to check them against.
+///
+/// Cloning a jar doesn't copy the cookies: the clones share them. Attach a
+/// jar to a request with
+/// [`Request::with_cookie_jar`](crate::Request::with_cookie_jar), or to all
+/// requests sent by a [`Client`](crate::Client) with
+/// [`Client::with_cookie_jar`](crate::Client::with_cookie_jar).
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use bitreq::CookieJar;
+///
+/// let jar = CookieJar::new();
+/// bitreq::post("http://example.com/login")
+///     .with_form(&[("user", "satoshi"), ("password", "hunter2")])
+///     .with_cookie_jar(jar.clone())
+///     .send()?;
+/// // The session cookie set by the login is sent along.
+/// let response = bitreq::get("http://example.com/dashboard").with_cookie_jar(jar).send()?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CookieJar(Arc<Mutex<Vec<Cookie>>>);
+
+/// A cookie stored in a [`CookieJar`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    /// The domain, lowercase and without a leading dot.
+    domain: String,
+    /// Whether the cookie is only sent to `domain` itself rather than to its subdomains too,
+    /// because it was set without a `Domain` attribute.
+    host_only: bool,
+    path: String,
+    expires: Option<SystemTime>,
+    secure: bool,
+    http_only: bool,
+}
+
+impl CookieJar {
+    /// Creates an empty cookie jar.
+    pub fn new() -> CookieJar { CookieJar::default() }
+
+    /// Stores the cookie set by the `Set-Cookie` header value `set_cookie`,
+    /// as if it was received in a response from `url`.
+    ///
+    /// Invalid cookies, and cookies for a domain `url` can't set cookies
+    /// for, are ignored. A cookie replaces any with the same name, domain and
+    /// path, and a cookie which has already expired just removes it.
+    pub fn set_cookie(&self, url: &Url, set_cookie: &str) {
+        let now = SystemTime::now();
+        let Some(cookie) = Cookie::parse(set_cookie, url, now) else { return };
+        let mut cookies = self.lock(now);
+        let existing = cookies.iter().position(|c| {
+            c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path
+        });
+        match existing {
+            _ if cookie.is_expired(now) =>
+                if let Some(i) = existing {
+                    cookies.remove(i);
+                },
+            Some(i) => cookies[i] = cookie,
+            None => cookies.push(cookie),
+        }
+    }
+
+    /// Returns the value of the `Cookie` header to send with a request to
+    /// `url`, or `None` if no cookies apply to it.
+    ///
+    /// Cookies with longer paths are listed first.
+    pub fn cookie_header(&self, url: &Url) -> Option<String> {
+        let host = url.base_url().to_ascii_lowercase();
+        let cookies = self.lock(SystemTime::now());
+        let mut matching = cookies
+            .iter()
+            .filter(|c| {
+                let domain_matches =
+                    if c.host_only { host == c.domain } else { domain_matches(&host, &c.domain) };
+                domain_matches && path_matches(url.path(), &c.path) && (!c.secure || url.is_https())
+            })
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            return None;
+        }
+        matching.sort_by_key(|c| Reverse(c.path.len()));
+        let pairs = matching.iter().map(|c| format!("{}={}", c.name, c.value));
+        Some(pairs.collect::<Vec<_>>().join("; "))
+    }
+
+    /// Returns the cookies in the jar which haven't expired.
+    pub fn cookies(&self) -> Vec<Cookie> { self.lock(SystemTime::now()).clone() }
+
+    /// Removes all cookies from the jar.
+    pub fn clear(&self) { self.0.lock().unwrap().clear(); }
+
+    /// Locks the cookies, dropping the ones which expired before `now`.
+    fn lock(&self, now: SystemTime) -> MutexGuard<'_, Vec<Cookie>> {
+        let mut cookies = self.0.lock().unwrap();
+        cookies.retain(|c| !c.is_expired(now));
+        cookies
+    }
+}
+
+impl PartialEq for CookieJar {
+    fn eq(&self, other: &Self) -> bool { Arc::ptr_eq(&self.0, &other.0) }
+}
+
+impl Eq for CookieJar {}
+
+impl Cookie {
+    /// Parses a `Set-Cookie` header value received from `url`, see RFC 6265 section 5.2.
+    fn parse(set_cookie: &str, url: &Url, now: SystemTime) -> Option<Cookie> {
+        let mut attributes = set_cookie.split(';');
+        let (name, value) = attributes.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let host = url.base_url().to_ascii_lowercase();
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url.path()).to_string(),
+            expires: None,
+            secure: false,
+            http_only: false,
+        };
+        let mut domain = None;
+        let mut max_age = None;
+        for attribute in attributes {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "expires" =>
+                    if let Some(expires) = parse_cookie_date(value) {
+                        cookie.expires = Some(expires);
+                    },
+                "max-age" =>
+                    if let Ok(seconds) = value.parse::<i64>() {
+                        // A Max-Age of zero or less expires the cookie right away.
+                        max_age = Some(match u64::try_from(seconds) {
+                            Ok(seconds) if seconds > 0 =>
+                                now.checked_add(Duration::from_secs(seconds)),
+                            _ => Some(UNIX_EPOCH),
+                        });
+                    },
+                "domain" if !value.is_empty() => {
+                    let value = value.strip_prefix('.').unwrap_or(value);
+                    domain = Some(value.to_ascii_lowercase());
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                _ => {}
+            }
+        }
+
+        // Max-Age takes precedence over Expires.
+        if let Some(max_age) = max_age {
+            cookie.expires = max_age;
+        }
+        if let Some(domain) = domain {
+            if !domain_matches(&host, &domain) {
+                return None;
+            }
+            cookie.domain = domain;
+            cookie.host_only = false;
+        }
+        if cookie.secure && !url.is_https() {
+            return None;
+        }
+        Some(cookie)
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool { self.expires.is_some_and(|at| at <= now) }
+
+    /// The name of the cookie.
+    pub fn name(&self) -> &str { &self.name }
+
+    /// The value of the cookie.
+    pub fn value(&self) -> &str { &self.value }
+
+    /// The domain the cookie is sent to, lowercase and without a leading
+    /// dot. The cookie is sent to subdomains too unless
+    /// [`is_host_only`](Cookie::is_host_only).
+    pub fn domain(&self) -> &str { &self.domain }
+
+    /// Returns `true` if the cookie was set without a `Domain` attribute,
+    /// and so is only sent to the exact host which set it.
+    pub fn is_host_only(&self) -> bool { self.host_only }
+
+    /// The path the cookie is sent to, along with the paths below it.
+    pub fn path(&self) -> &str { &self.path }
+
+    /// When the cookie expires, or `None` for a session cookie which lasts
+    /// as long as the jar.
+    pub fn expires(&self) -> Option<SystemTime> { self.expires }
+
+    /// Returns `true` if the cookie is only sent over HTTPS.
+    pub fn is_secure(&self) -> bool { self.secure }
+
+    /// Returns `true` if the cookie was set with the `HttpOnly` attribute,
+    /// i.e. it shouldn't be exposed to anything but HTTP requests.
+    pub fn is_http_only(&self) -> bool { self.http_only }
+}
+
+/// Returns `true` if cookies for `domain` apply to `host`, see RFC 6265 section 5.1.3.
+fn domain_matches(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    let is_ip = host.starts_with('[') || host.parse::<IpAddr>().is_ok();
+    !is_ip && host.strip_suffix(domain).is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Returns the path a cookie without a `Path` attribute applies to, see RFC 6265 section 5.1.4.
+fn default_path(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(i) => &path[..i],
+    }
+}
+
+/// Returns `true` if cookies for `cookie_path` apply to `path`, see RFC 6265 section 5.1.4.
+fn path_matches(path: &str, cookie_path: &str) -> bool {
+    path.strip_prefix(cookie_path)
+        .is_some_and(|rest| rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'))
+}
+
+/// Parses the date of an `Expires` attribute, leniently accepting the many formats in use, see
+/// RFC 6265 section 5.1.1.
+fn parse_cookie_date(date: &str) -> Option<SystemTime> {
+    let is_delimiter =
+        |c: char| c == '\t' || matches!(c, ' '..='/' | ';'..='@' | '['..='`' | '{'..='~');
+    let (mut time, mut day, mut month, mut year) = (None, None, None, None);
+    for token in date.split(is_delimiter).filter(|token| !token.is_empty()) {
+        if time.is_none() {
+            if let Some(parsed) = parse_time(token) {
+                time = Some(parsed);
+                continue;
+            }
+        }
+        if day.is_none() {
+            if let Some(parsed) = parse_digits(token, 1, 2) {
+                day = Some(parsed);
+                continue;
+            }
+        }
+        if month.is_none() {
+            let prefix = token.get(..3).unwrap_or("").to_ascii_lowercase();
+            const MONTHS: [&str; 12] = [
+                "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+            ];
+            if let Some(i) = MONTHS.iter().position(|month| *month == prefix) {
+                month = Some(i as u64 + 1);
+                continue;
+            }
+        }
+        if year.is_none() {
+            if let Some(parsed) = parse_digits(token, 2, 4) {
+                year = Some(parsed);
+            }
+        }
+    }
+
+    let ((hours, minutes, seconds), day, month, mut year) = (time?, day?, month?, year?);
+    if year < 70 {
+        year += 2000;
+    } else if year < 100 {
+        year += 1900;
+    }
+    if day == 0 || day > 31 || year < 1601 || hours > 23 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+
+    let days = days_from_civil(year as i64, month, day);
+    let seconds = days * 86400 + (hours * 3600 + minutes * 60 + seconds) as i64;
+    match u64::try_from(seconds) {
+        Ok(seconds) => UNIX_EPOCH.checked_add(Duration::from_secs(seconds)),
+        // Dates before 1970 are in the past all the same.
+        Err(_) => Some(UNIX_EPOCH),
+    }
+}
+
+/// Parses a `hh:mm:ss` time, where each field has one or two digits.
+fn parse_time(token: &str) -> Option<(u64, u64, u64)> {
+    let mut fields = token.splitn(3, ':');
+    let hours = fields.next().and_then(|field| parse_digits(field, 1, 2))?;
+    let minutes = fields.next().and_then(|field| parse_digits(field, 1, 2))?;
+    let seconds = fields.next().and_then(|field| parse_digits(field, 1, 2))?;
+    Some((hours, minutes, seconds))
+}
+
+/// Parses the `min` to `max` digits `token` starts with, which may be followed by anything but
+/// another digit.
+fn parse_digits(token: &str, min: usize, max: usize) -> Option<u64> {
+    let digits = token.bytes().take_while(u8::is_ascii_digit).count();
+    if digits < min || digits > max {
+        return None;
+    }
+    token[..digits].parse().ok()
+}
+
+/// Returns the number of days since 1970-01-01 of the given date in the proleptic Gregorian
+/// calendar, see <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+fn days_from_civil(year: i64, month: u64, day: u64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year as i64;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url { Url::parse(url).unwrap() }
+
+    fn at(seconds: u64) -> Option<SystemTime> { Some(UNIX_EPOCH + Duration::from_secs(seconds)) }
+
+    #[test]
+    fn parses_dates() {
+        for date in [
+            "Wed, 21 Oct 2015 07:28:00 GMT",
+            "Wednesday, 21-Oct-15 07:28:00 GMT",
+            "Wed Oct 21 07:28:00 2015",
+            "21 oct 2015 7:28:0",
+        ] {
+            assert_eq!(parse_cookie_date(date), at(1445412480), "{}", date);
+        }
+        assert_eq!(parse_cookie_date("Thu, 01 Jan 1970 00:00:00 GMT"), at(0));
+        assert_eq!(parse_cookie_date("Tue, 29 Feb 2028 12:00:00 GMT"), at(1835438400));
+        assert_eq!(parse_cookie_date("Mon, 01 Jan 1900 00:00:00 GMT"), Some(UNIX_EPOCH));
+        assert_eq!(parse_cookie_date("Wed, 21 Oct 2015 25:28:00 GMT"), None);
+        assert_eq!(parse_cookie_date("Wed, 21 2015 07:28:00 GMT"), None);
+        assert_eq!(parse_cookie_date("soon"), None);
+    }
+
+    #[test]
+    fn parses_set_cookie() {
+        let now = UNIX_EPOCH + Duration::from_secs(1000);
+        let cookie = Cookie::parse(
+            " sid = abc ; Path=/app; Domain=.Example.com; Max-Age=60; \
+            Expires=Wed, 21 Oct 2015 07:28:00 GMT; Secure; HttpOnly",
+            &url("https://www.example.com/login"),
+            now,
+        )
+        .unwrap();
+        assert_eq!(cookie.name(), "sid");
+        assert_eq!(cookie.value(), "abc");
+        assert_eq!(cookie.domain(), "example.com");
+        assert!(!cookie.is_host_only());
+        assert_eq!(cookie.path(), "/app");
+        assert_eq!(cookie.expires(), at(1060));
+        assert!(cookie.is_secure() && cookie.is_http_only());
+
+        let cookie = Cookie::parse("a=b", &url("http://example.com/x/y"), now).unwrap();
+        assert_eq!(cookie.domain(), "example.com");
+        assert!(cookie.is_host_only());
+        assert_eq!(cookie.path(), "/x");
+        assert_eq!(cookie.expires(), None);
+
+        let cookie = Cookie::parse("a=b; Max-Age=0", &url("http://example.com"), now).unwrap();
+        assert!(cookie.is_expired(now));
+
+        let example = url("http://example.com");
+        assert_eq!(Cookie::parse("novalue", &example, now), None);
+        assert_eq!(Cookie::parse("=b", &example, now), None);
+        assert_eq!(Cookie::parse("a=b; Domain=other.com", &example, now), None);
+        assert_eq!(Cookie::parse("a=b; Domain=www.example.com", &example, now), None);
+        assert_eq!(Cookie::parse("a=b; Secure", &example, now), None);
+    }
+
+    #[test]
+    fn matches_domains_and_paths() {
+        assert!(domain_matches("example.com", "example.com"));
+        assert!(domain_matches("www.example.com", "example.com"));
+        assert!(!domain_matches("wwwexample.com", "example.com"));
+        assert!(!domain_matches("example.com", "www.example.com"));
+        assert!(!domain_matches("1.2.3.4", "2.3.4"));
+
+        assert!(path_matches("/", "/"));
+        assert!(path_matches("/app", "/app"));
+        assert!(path_matches("/app/x", "/app"));
+        assert!(path_matches("/app/x", "/app/"));
+        assert!(!path_matches("/apple", "/app"));
+        assert!(!path_matches("/", "/app"));
+    }
+
+    #[test]
+    fn sends_matching_cookies() {
+        let jar = CookieJar::new();
+        jar.set_cookie(&url("http://www.example.com/a/b"), "host=1");
+        jar.set_cookie(&url("http://www.example.com/"), "domain=2; Domain=example.com");
+        jar.set_cookie(&url("https://www.example.com/"), "secure=3; Secure; Path=/a");
+        jar.set_cookie(&url("http://www.example.com/"), "expired=4; Max-Age=-1");
+
+        assert_eq!(
+            jar.cookie_header(&url("https://www.example.com/a/c")).unwrap(),
+            "host=1; secure=3; domain=2"
+        );
+        assert_eq!(
+            jar.cookie_header(&url("http://www.example.com/a/c")).unwrap(),
+            "host=1; domain=2"
+        );
+        assert_eq!(jar.cookie_header(&url("http://api.example.com/a")).unwrap(), "domain=2");
+        assert_eq!(jar.cookie_header(&url("http://other.com/")), None);
+
+        // Cookies are replaced by name, domain and path, and removed once expired.
+        jar.set_cookie(&url("http://www.example.com/a/b"), "host=5");
+        jar.set_cookie(&url("http://www.example.com/"), "domain=6; Domain=example.com; Max-Age=0");
+        assert_eq!(jar.cookie_header(&url("http://www.example.com/a/")).unwrap(), "host=5");
+        assert_eq!(jar.cookies().len(), 2);
+
+        let shared = jar.clone();
+        shared.clear();
+        assert!(jar.cookies().is_empty());
+        assert_ne!(jar, CookieJar::new());
+    }
+}
@@ -0,0 +1,331 @@
+//! Downloading response bodies to files, resuming interrupted downloads, see [`Download`].
+
+use core::fmt;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use bitcoin_hashes::{sha256, Hash, HashEngine};
+
+use crate::connection::timeout_err;
+use crate::{Error, Request, RetryPolicy};
+
+/// A callback reporting the progress of a download, see [`Download::with_progress`].
+pub type ProgressFn = Box<dyn FnMut(u64, Option<u64>) + Send>;
+
+/// The size of the writes to the file, and so how often progress is
+/// reported.
+const WRITE_BUFFER_LENGTH: usize = 64 * 1024;
+
+/// Downloads the body of a response to a file, resuming the download if the
+/// connection drops.
+///
+/// The body is streamed to a temporary file next to the destination, named
+/// like it with a `.part` suffix, which is renamed to the destination once
+/// the download is complete and its digest, if one was given with
+/// [`with_sha256`](Download::with_sha256), matches. If the download fails,
+/// the temporary file is removed.
+///
+/// When reading the body fails with a transient error (see
+/// [`RetryPolicy::is_transient_error`]), or the connection closes before the
+/// whole body was received, the request is sent again with a `Range` header
+/// asking for the rest of the body, and an `If-Range` header so that the
+/// server sends the whole body again if it changed in the meantime. Servers
+/// which don't support ranges, or don't identify the body with an `ETag` or
+/// `Last-Modified` header, send the whole body again. Failing to connect is
+/// only retried according to the request's [`RetryPolicy`], if any.
+///
+/// The timeout of the request, if any, applies to the whole download, so the
+/// download isn't resumed once it's reached.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use bitreq::Download;
+///
+/// # let expected_sha256 = [0; 32];
+/// let request = bitreq::get("https://bitcoincore.org/bin/bitcoin-core-29.0/SHA256SUMS");
+/// let length = Download::new(request, "SHA256SUMS")
+///     .with_sha256(expected_sha256)
+///     .with_progress(|done, total| match total {
+///         Some(total) => println!("{}/{} bytes", done, total),
+///         None => println!("{} bytes", done),
+///     })
+///     .send()?;
+/// # Ok(()) }
+/// ```
+pub struct Download {
+    request: Request,
+    path: PathBuf,
+    progress: Option<ProgressFn>,
+    sha256: Option<[u8; 32]>,
+    max_resumes: u32,
+}
+
+impl fmt::Debug for Download {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Download")
+            .field("request", &self.request)
+            .field("path", &self.path)
+            .field("progress", &self.progress.is_some())
+            .field("sha256", &self.sha256)
+            .field("max_resumes", &self.max_resumes)
+            .finish()
+    }
+}
+
+/// The state of a download across resumptions.
+struct Partial {
+    file: File,
+    engine: sha256::HashEngine,
+    /// The number of bytes written to the file.
+    length: u64,
+    /// The length of the whole body, if the server told us.
+    total: Option<u64>,
+    /// The `ETag` or `Last-Modified` header of the body, to resume the download with.
+    validator: Option<String>,
+}
+
+impl Partial {
+    /// Drops what was downloaded so far, as the server is sending the whole body.
+    fn restart(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        self.engine = sha256::HashEngine::default();
+        self.length = 0;
+        Ok(())
+    }
+}
+
+impl Download {
+    /// Creates a download of the body of the response to `request` to the
+    /// file at `path`, which is replaced if it exists.
+    pub fn new<P: Into<PathBuf>>(request: Request, path: P) -> Download {
+        Download { request, path: path.into(), progress: None, sha256: None, max_resumes: 5 }
+    }
+
+    /// Sets a callback called as the body is written to the file, with the
+    /// number of bytes written so far and the length of the whole body, if
+    /// the server told us.
+    pub fn with_progress<F: FnMut(u64, Option<u64>) + Send + 'static>(
+        mut self,
+        progress: F,
+    ) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Sets the SHA256 digest the body must have, failing the download with
+    /// [`Error::DigestMismatch`] otherwise.
+    pub fn with_sha256(mut self, digest: [u8; 32]) -> Self {
+        self.sha256 = Some(digest);
+        self
+    }
+
+    /// Sets how many times an interrupted download is resumed before giving
+    /// up. Defaults to 5.
+    pub fn with_max_resumes(mut self, max_resumes: u32) -> Self {
+        self.max_resumes = max_resumes;
+        self
+    }
+
+    /// Downloads the body to the file, returning its length.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the last attempt if the download couldn't be
+    /// completed, [`Error::UnexpectedStatusCode`] if the server didn't
+    /// answer with `200 OK` (or `206 Partial Content` when resuming), and
+    /// [`Error::DigestMismatch`] if the body doesn't have the expected
+    /// digest.
+    pub fn send(mut self) -> Result<u64, Error> {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".part");
+        let part_path = self.path.with_file_name(name);
+
+        let result = self.download(&part_path);
+        if result.is_ok() {
+            fs::rename(&part_path, &self.path)?;
+        } else {
+            let _ = fs::remove_file(&part_path);
+        }
+        result
+    }
+
+    fn download(&mut self, part_path: &Path) -> Result<u64, Error> {
+        let mut partial = Partial {
+            file: File::create(part_path)?,
+            engine: sha256::HashEngine::default(),
+            length: 0,
+            total: None,
+            validator: None,
+        };
+        let timeout_at = self.request.effective_timeout().map(|t| Instant::now() + t);
+        let timed_out = || timeout_at.is_some_and(|at| Instant::now() >= at);
+        let mut resumes = 0;
+        loop {
+            match self.attempt(&mut partial, timeout_at) {
+                Ok(()) => break,
+                Err(e)
+                    if resumes < self.max_resumes
+                        && RetryPolicy::is_transient_error(&e)
+                        && !timed_out() =>
+                {
+                    #[cfg(feature = "log")]
+                    log::debug!("Download interrupted after {} bytes: {}", partial.length, e);
+                    resumes += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        partial.file.sync_all()?;
+
+        if let Some(expected) = self.sha256 {
+            if sha256::Hash::from_engine(partial.engine).to_byte_array() != expected {
+                return Err(Error::DigestMismatch);
+            }
+        }
+        Ok(partial.length)
+    }
+
+    /// Sends the request, resuming the download if some of the body was already downloaded, and
+    /// writes the body to the file.
+    fn attempt(&mut self, partial: &mut Partial, timeout_at: Option<Instant>) -> Result<(), Error> {
+        let mut request = self.request.clone();
+        if let Some(timeout_at) = timeout_at {
+            let remaining = timeout_at.checked_duration_since(Instant::now());
+            request = request.with_timeout(remaining.ok_or_else(timeout_err)?);
+        }
+        // Ranges apply to the encoded body, so make sure it isn't decoded.
+        if request.header("Accept-Encoding").is_none() {
+            request = request.with_header("Accept-Encoding", "identity");
+        }
+        let resuming = match &partial.validator {
+            Some(validator) if partial.length > 0 => {
+                let range = format!("bytes={}-", partial.length);
+                request = request.with_header("Range", range).with_header("If-Range", validator);
+                true
+            }
+            _ => false,
+        };
+
+        let mut response = request.send_lazy()?;
+        match response.status_code {
+            206 if resuming && range_start(&response.headers) == Some(partial.length) => {
+                #[cfg(feature = "log")]
+                log::trace!("Resuming download at byte {}", partial.length);
+            }
+            200 => {
+                partial.restart()?;
+                partial.total = response.headers.get("content-length").and_then(|l| l.parse().ok());
+                partial.validator = validator(&response.headers);
+            }
+            status => return Err(Error::UnexpectedStatusCode(status)),
+        }
+
+        let mut buffer = Vec::with_capacity(WRITE_BUFFER_LENGTH);
+        let mut result = Ok(());
+        for byte in &mut response {
+            match byte {
+                Ok((byte, _)) => buffer.push(byte),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+            if buffer.len() == WRITE_BUFFER_LENGTH {
+                self.write(partial, &buffer)?;
+                buffer.clear();
+            }
+        }
+        // Keep what we received before an error, to resume after it.
+        self.write(partial, &buffer)?;
+        result?;
+
+        match partial.total {
+            Some(total) if partial.length < total =>
+                Err(Error::IoError(io::ErrorKind::UnexpectedEof.into())),
+            _ => Ok(()),
+        }
+    }
+
+    fn write(&mut self, partial: &mut Partial, bytes: &[u8]) -> Result<(), Error> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        partial.file.write_all(bytes)?;
+        partial.engine.input(bytes);
+        partial.length += bytes.len() as u64;
+        if let Some(progress) = &mut self.progress {
+            progress(partial.length, partial.total);
+        }
+        Ok(())
+    }
+}
+
+/// Downloads the body of the response to `request` to the file at `path`,
+/// checking that it has the SHA256 digest `sha256` if given, and calling
+/// `progress` as it's written if given. Returns the length of the body.
+///
+/// This is a shorthand for [`Download`], which has the details.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// # let expected_sha256 = [0; 32];
+/// let request = bitreq::get("https://bitcoincore.org/bin/bitcoin-core-29.0/SHA256SUMS");
+/// let progress: bitreq::ProgressFn = Box::new(|done, _| println!("{} bytes", done));
+/// bitreq::download_to_file(request, "SHA256SUMS", Some(expected_sha256), Some(progress))?;
+/// # Ok(()) }
+/// ```
+pub fn download_to_file<P: Into<PathBuf>>(
+    request: Request,
+    path: P,
+    sha256: Option<[u8; 32]>,
+    progress: Option<ProgressFn>,
+) -> Result<u64, Error> {
+    let mut download = Download::new(request, path);
+    download.sha256 = sha256;
+    download.progress = progress;
+    download.send()
+}
+
+/// Returns the strong `ETag` of a response, or else its `Last-Modified` date, which identify the
+/// body in an `If-Range` header.
+fn validator(headers: &BTreeMap<String, String>) -> Option<String> {
+    match headers.get("etag") {
+        Some(etag) if !etag.starts_with("W/") => Some(etag.clone()),
+        _ => headers.get("last-modified").cloned(),
+    }
+}
+
+/// Returns the position of the first byte of a `206 Partial Content` response, from its
+/// `Content-Range` header.
+fn range_start(headers: &BTreeMap<String, String>) -> Option<u64> {
+    let range = headers.get("content-range")?.trim().strip_prefix("bytes ")?;
+    range.split('-').next()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_range_and_validator() {
+        let headers = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        assert_eq!(range_start(&headers(&[("content-range", "bytes 100-199/200")])), Some(100));
+        assert_eq!(range_start(&headers(&[("content-range", "bytes */200")])), None);
+        assert_eq!(range_start(&headers(&[])), None);
+
+        let modified = ("last-modified", "Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(validator(&headers(&[("etag", "\"abc\""), modified])).unwrap(), "\"abc\"");
+        assert_eq!(validator(&headers(&[("etag", "W/\"abc\""), modified])).unwrap(), modified.1);
+        assert_eq!(validator(&headers(&[("etag", "W/\"abc\"")])), None);
+    }
+}
@@ -28,6 +28,15 @@ pub enum Error {
     /// Ran into an IO problem while loading the response.
     #[cfg(feature = "std")]
     IoError(io::Error),
+    /// Couldn't connect to the server, or to the proxy, within
+    /// [Request::with_connect_timeout](crate::request::Request::with_connect_timeout).
+    #[cfg(feature = "std")]
+    ConnectTimeout,
+    /// The server sent nothing for longer than
+    /// [Request::with_read_idle_timeout](crate::request::Request::with_read_idle_timeout)
+    /// while we were waiting for the response.
+    #[cfg(feature = "std")]
+    ReadIdleTimeout,
     /// Couldn't parse the incoming chunk's length while receiving a
     /// response with the header `Transfer-Encoding: chunked`.
     MalformedChunkLength,
@@ -75,9 +84,50 @@ pub enum Error {
     /// The provided credentials were rejected by the proxy server.
     #[cfg(feature = "proxy")]
     InvalidProxyCreds,
+    /// The SOCKS5 proxy could not connect to the server, the contained
+    /// value is the reply code sent by the proxy (see
+    /// [RFC 1928](https://datatracker.ietf.org/doc/html/rfc1928#section-6)).
+    #[cfg(feature = "proxy")]
+    Socks5ConnectFailed(u8),
     /// The response body size surpasses
     /// [Request::with_max_body_size](crate::request::Request::with_max_body_size).
     BodyOverflow,
+    /// The request body was read from a reader which was already consumed
+    /// by an earlier attempt to send the request, so it couldn't be sent
+    /// again (for example when following a redirect).
+    #[cfg(feature = "std")]
+    BodyStreamConsumed,
+    /// The response body could not be decompressed according to its
+    /// `Content-Encoding` header.
+    #[cfg(feature = "compression")]
+    DecompressionError(io::Error),
+    /// The server answered an [`EventSource`](crate::EventSource) with a
+    /// status code other than 200, or without a `text/event-stream` body.
+    /// The contained value is the status code.
+    #[cfg(feature = "std")]
+    NotAnEventStream(i32),
+    /// The server answered a [`Download`](crate::Download) with a status
+    /// code other than 200, or 206 when resuming it. The contained value is
+    /// the status code.
+    #[cfg(feature = "download")]
+    UnexpectedStatusCode(i32),
+    /// The SHA256 digest of a [`Download`](crate::Download) didn't match
+    /// the expected one.
+    #[cfg(feature = "download")]
+    DigestMismatch,
+    /// The server didn't switch to the WebSocket protocol when opening a
+    /// [`WebSocket`](crate::WebSocket). The contained value is the status
+    /// code of its response.
+    #[cfg(feature = "websocket")]
+    WebSocketHandshakeFailed(i32),
+    /// The server broke the WebSocket protocol. The contained value
+    /// describes how.
+    #[cfg(feature = "websocket")]
+    WebSocketProtocol(&'static str),
+    /// The [`WebSocket`](crate::WebSocket) connection is closed, or being
+    /// closed.
+    #[cfg(feature = "websocket")]
+    WebSocketClosed,
     // TODO: Uncomment these two for 3.0
     // /// The URL does not start with http:// or https://.
     // InvalidProtocol,
@@ -100,6 +150,10 @@ impl fmt::Display for Error {
             SerdeJsonError(err) => write!(f, "{}", err),
             #[cfg(feature = "std")]
             IoError(err) => write!(f, "{}", err),
+            #[cfg(feature = "std")]
+            ConnectTimeout => write!(f, "the connect timeout was reached before connecting to the server"),
+            #[cfg(feature = "std")]
+            ReadIdleTimeout => write!(f, "the read idle timeout was reached while waiting for the server"),
             InvalidUrl(err) => write!(f, "failed to parse given URL: {}", err),
             InvalidUtf8InBody(err) => write!(f, "{}", err),
             #[cfg(feature = "rustls")]
@@ -125,7 +179,25 @@ impl fmt::Display for Error {
             ProxyConnect => write!(f, "could not connect to the proxy server"),
             #[cfg(feature = "proxy")]
             InvalidProxyCreds => write!(f, "the provided proxy credentials are invalid"),
+            #[cfg(feature = "proxy")]
+            Socks5ConnectFailed(code) => write!(f, "the socks5 proxy failed to connect to the server (reply code {})", code),
             BodyOverflow => write!(f, "the response body size surpassed max_body_size"),
+            #[cfg(feature = "std")]
+            BodyStreamConsumed => write!(f, "the request body stream was already consumed and can't be sent again"),
+            #[cfg(feature = "compression")]
+            DecompressionError(err) => write!(f, "failed to decompress the response body: {}", err),
+            #[cfg(feature = "std")]
+            NotAnEventStream(status) => write!(f, "the response (status code {}) is not an event stream", status),
+            #[cfg(feature = "download")]
+            UnexpectedStatusCode(status) => write!(f, "the server answered the download with status code {}", status),
+            #[cfg(feature = "download")]
+            DigestMismatch => write!(f, "the sha256 digest of the download doesn't match the expected one"),
+            #[cfg(feature = "websocket")]
+            WebSocketHandshakeFailed(status) => write!(f, "the server refused to open a websocket (status code {})", status),
+            #[cfg(feature = "websocket")]
+            WebSocketProtocol(problem) => write!(f, "websocket protocol error: {}", problem),
+            #[cfg(feature = "websocket")]
+            WebSocketClosed => write!(f, "the websocket connection is closed"),
             // TODO: Uncomment these two for 3.0
             // InvalidProtocol => write!(f, "the url does not start with http:// or https://"),
             // InvalidProtocolInRedirect => write!(f, "got redirected to an absolute url which does not start with http:// or https://"),
@@ -147,6 +219,10 @@ impl error::Error for Error {
             InvalidUtf8InBody(err) => Some(err),
             #[cfg(feature = "rustls")]
             RustlsCreateConnection(err) => Some(err),
+            #[cfg(feature = "native-tls")]
+            NativeTlsCreateConnection(err) => Some(err),
+            #[cfg(feature = "compression")]
+            DecompressionError(err) => Some(err),
             _ => None,
         }
     }
@@ -154,9 +230,33 @@ impl error::Error for Error {
 
 #[cfg(feature = "std")]
 impl From<io::Error> for Error {
-    fn from(other: io::Error) -> Error { Error::IoError(other) }
+    fn from(other: io::Error) -> Error {
+        if other.get_ref().is_some_and(|err| err.is::<ReadIdleTimeoutReached>()) {
+            return Error::ReadIdleTimeout;
+        }
+        Error::IoError(other)
+    }
+}
+
+/// The payload of the [`io::Error`] returned by reads from a connection once its read idle
+/// timeout is reached, which turns into [`Error::ReadIdleTimeout`] when converted.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub(crate) struct ReadIdleTimeoutReached;
+
+#[cfg(feature = "std")]
+impl ReadIdleTimeoutReached {
+    pub(crate) fn io_error() -> io::Error { io::Error::new(io::ErrorKind::TimedOut, Self) }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for ReadIdleTimeoutReached {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", Error::ReadIdleTimeout) }
 }
 
+#[cfg(feature = "std")]
+impl error::Error for ReadIdleTimeoutReached {}
+
 impl From<UrlParseError> for Error {
     fn from(other: UrlParseError) -> Error { Error::InvalidUrl(other) }
 }
@@ -56,10 +56,13 @@
 //! This feature enables asynchronous HTTP requests using tokio. It provides
 //! [`send_async()`](struct.Request.html#method.send_async) and
 //! [`send_lazy_async()`](struct.Request.html#method.send_lazy_async) methods
-//! that return futures for non-blocking operation.
+//! that return futures for non-blocking operation, and
+//! [`send_streaming_async()`](struct.Request.html#method.send_streaming_async),
+//! which returns a [`ResponseStream`](struct.ResponseStream.html) to read
+//! large bodies as they arrive without blocking.
 //!
 //! It also enables [`Client`](struct.Client.html) to reuse TCP connections
-//! across requests.
+//! across async requests, and to pipeline requests over them.
 //!
 //! ## `async-https` or `async-https-rustls`
 //!
@@ -83,7 +86,33 @@
 //!
 //! ## `proxy`
 //!
-//! This feature enables HTTP proxy support.
+//! This feature enables HTTP (`CONNECT`) and SOCKS5 proxy support.
+//!
+//! ## `compression`
+//!
+//! This feature sends an `Accept-Encoding: gzip, deflate` header with
+//! every request (unless one was set explicitly) and transparently
+//! decompresses response bodies with a `Content-Encoding` of `gzip` or
+//! `deflate`, using the [`flate2`](https://crates.io/crates/flate2)
+//! crate. The `Content-Encoding` header is removed from decompressed
+//! responses, and their `Content-Length` header is replaced by the
+//! decompressed length once the body has been read, as for chunked
+//! bodies. [`with_max_body_size`](struct.Request.html#method.with_max_body_size)
+//! limits the decompressed size of the body.
+//!
+//! ## `download`
+//!
+//! This feature adds [`Download`](struct.Download.html) and its shorthand
+//! [`download_to_file`](fn.download_to_file.html), which stream a
+//! response body to a file, resuming the download if the connection drops
+//! and checking the SHA256 digest of the file, using the
+//! [`bitcoin_hashes`](https://crates.io/crates/bitcoin_hashes) crate.
+//!
+//! ## `websocket`
+//!
+//! This feature adds [`WebSocket`](struct.WebSocket.html), a WebSocket
+//! client opened with an `Upgrade: websocket` request, and
+//! `AsyncWebSocket` with the `async` feature as well.
 //!
 //! # Examples
 //!
@@ -129,7 +158,12 @@
 //! ## Body (sending)
 //!
 //! To include a body, add `with_body("<body contents>")` before
-//! `send()`.
+//! `send()`. Large bodies can instead be streamed from a reader with
+//! `with_body_reader` (or `with_body_async_reader` for async requests),
+//! without loading them into memory first. Forms are sent with
+//! `with_form(&[("key", "value")])`, or with `with_multipart` for
+//! `multipart/form-data` bodies with file uploads (see
+//! [`Multipart`](struct.Multipart.html)).
 //!
 //! ```
 //! # #[cfg(feature = "std")]
@@ -197,14 +231,89 @@
 //! # fn main() -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
 //! ```
 //!
+//! ## Retries
+//!
+//! Requests which fail with a transient error, like a refused or reset
+//! connection, or with a status code such as `503 Service Unavailable`
+//! can be sent again with exponential backoff by adding
+//! `with_retry(RetryPolicy::new())` before `send()`. See
+//! [`RetryPolicy`] for what is retried and how often.
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use std::time::Duration;
+//!
+//! use bitreq::RetryPolicy;
+//!
+//! let response = bitreq::get("http://example.com")
+//!     .with_timeout(Duration::from_secs(30))
+//!     .with_retry(RetryPolicy::new().with_max_attempts(5))
+//!     .send()?;
+//! # Ok(()) }
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+//! ```
+//!
+//! ## Redirects
+//!
+//! `301`, `302`, `303`, `307` and `308` redirects are followed by
+//! default, and the URLs a response was redirected from are listed in its
+//! `redirects` field. Which redirects are followed can be changed with
+//! `with_redirect_policy`, see [`RedirectPolicy`](struct.RedirectPolicy.html).
+//! The `Authorization`, `Proxy-Authorization` and `Cookie` headers are never
+//! sent to another origin than the request's.
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use bitreq::RedirectPolicy;
+//!
+//! let response = bitreq::get("http://example.com")
+//!     .with_redirect_policy(RedirectPolicy::none())
+//!     .send()?;
+//! if let Some(location) = response.headers.get("location") {
+//!     println!("redirected to {}", location);
+//! }
+//! # Ok(()) }
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+//! ```
+//!
+//! ## Cookies
+//!
+//! Cookies aren't stored by default. To keep the cookies set by the server,
+//! for example to stay logged in, add `with_cookie_jar(jar)` with a shared
+//! [`CookieJar`](struct.CookieJar.html), or set one for all requests sent
+//! by a `Client` with `Client::with_cookie_jar`.
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use bitreq::{Client, CookieJar};
+//!
+//! let client = Client::new(10).with_cookie_jar(CookieJar::new());
+//! client.send(bitreq::post("http://example.com/login").with_body("secret"))?;
+//! let response = client.send(bitreq::get("http://example.com/account"))?;
+//! # Ok(()) }
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+//! ```
+//!
 //! ## Proxy
 //!
 //! To use a proxy server, simply create a `Proxy` instance and use
 //! `.with_proxy()` on your request.
 //!
 //! Supported proxy formats are `host:port` and
-//! `user:password@proxy:host`. Only HTTP CONNECT proxies are
-//! supported at this time.
+//! `user:password@proxy:host`. HTTP CONNECT proxies are created with
+//! `Proxy::new_http`, and SOCKS5 proxies (such as Tor's, to reach onion
+//! services) with `Proxy::new_socks5`.
+//!
+//! A `ProxyConfig`, set with `.with_proxy_config()` on a request or a
+//! [`Client`], picks the proxy for each URL instead. Proxy environment
+//! variables such as `https_proxy` and `no_proxy` are only used when asked
+//! for, with `Proxy::from_env`.
 //!
 //! ```no_run
 //! # #[cfg(feature = "std")]
@@ -216,6 +325,124 @@
 //!         .with_proxy(proxy)
 //!         .send()?;
 //!     println!("{}", response.as_str()?);
+//!
+//!     let tor = bitreq::Proxy::new_socks5("socks5h://127.0.0.1:9050")?;
+//!     let response = bitreq::get("http://exampleonionaddress.onion")
+//!         .with_proxy(tor)
+//!         .send()?;
+//!     println!("{}", response.as_str()?);
+//! }
+//! # Ok(()) }
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+//! ```
+//!
+//! ## Custom TLS settings
+//!
+//! With any of the HTTPS features enabled, a `TlsConfig` set with
+//! `.with_tls_config()` on a request (or `Client::with_tls_config` on a
+//! [`Client`]) adds trusted root certificates, disables the built-in
+//! ones, presents a client certificate to servers requiring mutual TLS,
+//! or pins the server's public key.
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! #[cfg(any(feature = "https-rustls", feature = "https-native-tls"))]
+//! {
+//!     let tls = bitreq::TlsConfig::new()
+//!         .with_root_certificates_pem(std::fs::read("ca.pem")?)
+//!         .with_built_in_roots(false)
+//!         .with_client_identity_pem(std::fs::read("client.pem")?, std::fs::read("client.key")?);
+//!     let response = bitreq::get("https://node.internal:8332").with_tls_config(tls).send()?;
+//!     println!("{}", response.as_str()?);
+//! }
+//! # Ok(()) }
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+//! ```
+//!
+//! ## Connection reuse
+//!
+//! Each call to `send()` opens a new connection to the server. To keep
+//! connections alive and reuse them for later requests to the same
+//! server, create a [`Client`](struct.Client.html) and send requests
+//! through it.
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use bitreq::RequestExt;
+//!
+//! let client = bitreq::Client::new(10);
+//! for _ in 0..3 {
+//!     let response = bitreq::get("http://example.com").send_with_client(&client)?;
+//!     println!("{}", response.as_str()?);
+//! }
+//! # Ok(()) }
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+//! ```
+//!
+//! ## Middleware
+//!
+//! A `Client` can run hooks around every request it sends, to change the
+//! request and observe or replace the response, see
+//! [`Middleware`](trait.Middleware.html). [`DefaultHeaders`](struct.DefaultHeaders.html)
+//! and [`BaseUrl`](struct.BaseUrl.html) are provided, as well as `Logger`
+//! with the `log` feature.
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use bitreq::{BaseUrl, Client, DefaultHeaders};
+//!
+//! let client = Client::new(10)
+//!     .with_middleware(BaseUrl::new("http://example.com/api"))
+//!     .with_middleware(DefaultHeaders::new().with_header("Authorization", "Bearer secret"));
+//! let response = client.send(bitreq::get("/status"))?;
+//! # Ok(()) }
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+//! ```
+//!
+//! ## Caching
+//!
+//! A `Client` can cache the responses to `GET` requests, answering requests
+//! from the cache while the response is fresh according to its
+//! `Cache-Control: max-age`, and otherwise revalidating it with an
+//! `If-None-Match` or `If-Modified-Since` header. Responses are stored in a
+//! [`CacheStorage`](trait.CacheStorage.html), like the provided
+//! [`MemoryCache`](struct.MemoryCache.html).
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use bitreq::{Client, MemoryCache};
+//!
+//! let client = Client::new(10).with_cache(MemoryCache::new(100));
+//! let response = client.send(bitreq::get("http://example.com/fees"))?;
+//! // Either not sent at all, or answered with `304 Not Modified` if unchanged.
+//! let cached = client.send(bitreq::get("http://example.com/fees"))?;
+//! assert_eq!(response.as_bytes(), cached.as_bytes());
+//! # Ok(()) }
+//! # #[cfg(not(feature = "std"))]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
+//! ```
+//!
+//! ## Server-Sent Events
+//!
+//! An [`EventSource`](struct.EventSource.html) reads the events of a
+//! Server-Sent Events stream, reconnecting and resuming from the last event
+//! when the stream ends. `AsyncEventSource` does the same asynchronously.
+//!
+//! ```no_run
+//! # #[cfg(feature = "std")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let request = bitreq::get("http://localhost:3000/events")
+//!     .with_read_idle_timeout(std::time::Duration::from_secs(60));
+//! for event in bitreq::EventSource::new(request) {
+//!     println!("{}", event?.data);
 //! }
 //! # Ok(()) }
 //! # #[cfg(not(feature = "std"))]
@@ -245,6 +472,17 @@
 //!   ```
 //! If the timeout is set with `with_timeout`, the environment
 //! variable will be ignored.
+//!
+//! This timeout covers the whole request, from looking up the host to
+//! reading the last byte of the response. Two narrower timeouts can be set
+//! alongside it:
+//!
+//! - [`with_connect_timeout`](struct.Request.html#method.with_connect_timeout)
+//!   limits how long establishing each connection may take, failing with
+//!   [`Error::ConnectTimeout`].
+//! - [`with_read_idle_timeout`](struct.Request.html#method.with_read_idle_timeout)
+//!   limits how long the server may go without sending anything while the
+//!   response is read, failing with [`Error::ReadIdleTimeout`].
 
 #![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 #![deny(missing_docs)]
@@ -254,26 +492,82 @@
 
 extern crate alloc;
 
+#[cfg(feature = "std")]
+mod body;
+#[cfg(feature = "std")]
+mod cache;
 #[cfg(feature = "std")]
 mod client;
+#[cfg(feature = "compression")]
+mod compression;
 #[cfg(feature = "std")]
 mod connection;
+#[cfg(feature = "std")]
+mod cookie;
+#[cfg(feature = "download")]
+mod download;
 mod error;
+#[cfg(feature = "std")]
+mod middleware;
+#[cfg(feature = "std")]
+mod multipart;
 #[cfg(feature = "proxy")]
 mod proxy;
 #[cfg(feature = "std")]
+mod redirect;
+#[cfg(feature = "std")]
 mod request;
 #[cfg(feature = "std")]
+mod resolve;
+#[cfg(feature = "std")]
 mod response;
+#[cfg(feature = "std")]
+mod retry;
+#[cfg(feature = "std")]
+mod sse;
+#[cfg(all(feature = "std", any(feature = "rustls", feature = "native-tls")))]
+mod tls;
 mod url;
+#[cfg(feature = "websocket")]
+mod websocket;
 
-#[cfg(feature = "async")]
-pub use client::{Client, RequestExt};
+#[cfg(feature = "std")]
+pub use cache::{CacheStorage, CachedResponse, MemoryCache};
+#[cfg(feature = "std")]
+pub use client::{Client, PoolStats, RequestExt};
+#[cfg(feature = "std")]
+pub use cookie::{Cookie, CookieJar};
+#[cfg(feature = "download")]
+pub use download::{download_to_file, Download, ProgressFn};
 pub use error::*;
+#[cfg(all(feature = "std", feature = "log"))]
+pub use middleware::Logger;
+#[cfg(feature = "std")]
+pub use middleware::{BaseUrl, DefaultHeaders, Middleware};
+#[cfg(feature = "std")]
+pub use multipart::{Multipart, Part};
 #[cfg(feature = "proxy")]
 pub use proxy::*;
 #[cfg(feature = "std")]
+pub use redirect::{RedirectAttempt, RedirectPolicy};
+#[cfg(feature = "std")]
 pub use request::*;
 #[cfg(feature = "std")]
+pub use resolve::{Resolver, StaticResolver, SystemResolver};
+#[cfg(feature = "async")]
+pub use response::ResponseStream;
+#[cfg(feature = "std")]
 pub use response::{Response, ResponseLazy};
+#[cfg(feature = "std")]
+pub use retry::RetryPolicy;
+#[cfg(feature = "async")]
+pub use sse::AsyncEventSource;
+#[cfg(feature = "std")]
+pub use sse::{Event, EventSource};
+#[cfg(all(feature = "std", any(feature = "rustls", feature = "native-tls")))]
+pub use tls::TlsConfig;
 pub use url::{ParseError as UrlParseError, Url};
+#[cfg(all(feature = "websocket", feature = "async"))]
+pub use websocket::AsyncWebSocket;
+#[cfg(feature = "websocket")]
+pub use websocket::{CloseFrame, Message, WebSocket};
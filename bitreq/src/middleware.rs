@@ -0,0 +1,235 @@
+//! Hooks run around the requests sent by a [`Client`](crate::Client), see [`Middleware`].
+
+use alloc::collections::BTreeMap;
+
+use crate::{Error, Request, Response};
+
+/// A hook run around every request sent by a [`Client`](crate::Client),
+/// added with [`Client::with_middleware`](crate::Client::with_middleware).
+///
+/// Middlewares can change requests before they're sent, for example to add
+/// authentication or sign them, and observe or replace the outcome, for
+/// example to record metrics or turn some responses into errors.
+///
+/// The [`on_request`](Middleware::on_request) hooks run in the order the
+/// middlewares were added, and the [`on_response`](Middleware::on_response)
+/// hooks in the reverse order, so the first middleware added sees the
+/// request first and the outcome last. The hooks run once per call to
+/// [`Client::send`](crate::Client::send) or
+/// [`Client::send_async`](crate::Client::send_async): retries and redirects
+/// happen in between them.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// use bitreq::{Client, Error, Middleware, Request, Response};
+///
+/// /// Counts the requests which failed or got an error status.
+/// #[derive(Default)]
+/// struct CountFailures(AtomicUsize);
+///
+/// impl Middleware for CountFailures {
+///     fn on_response(
+///         &self,
+///         _request: &Request,
+///         result: Result<Response, Error>,
+///     ) -> Result<Response, Error> {
+///         if result.as_ref().map_or(true, |response| response.status_code >= 400) {
+///             self.0.fetch_add(1, Ordering::Relaxed);
+///         }
+///         result
+///     }
+/// }
+///
+/// let client = Client::new(1).with_middleware(CountFailures::default());
+/// let response = client.send(bitreq::get("http://example.com"))?;
+/// # Ok(()) }
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Called with each request before it's sent, returning the request to
+    /// send instead. Returning an error aborts the request: the error is
+    /// returned as is, without calling any `on_response` hooks.
+    ///
+    /// Does nothing by default.
+    fn on_request(&self, request: Request) -> Result<Request, Error> { Ok(request) }
+
+    /// Called with the outcome of sending `request`, as returned by the
+    /// `on_request` hooks, returning the outcome to pass on instead.
+    ///
+    /// Does nothing by default.
+    fn on_response(
+        &self,
+        request: &Request,
+        result: Result<Response, Error>,
+    ) -> Result<Response, Error> {
+        let _ = request;
+        result
+    }
+}
+
+/// Adds headers to every request which doesn't already have a header of the
+/// same name (compared case-insensitively).
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use bitreq::{Client, DefaultHeaders};
+///
+/// let headers = DefaultHeaders::new().with_header("User-Agent", "my-wallet/1.0");
+/// let client = Client::new(1).with_middleware(headers);
+/// let response = client.send(bitreq::get("http://example.com"))?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DefaultHeaders {
+    headers: BTreeMap<String, String>,
+}
+
+impl DefaultHeaders {
+    /// Creates a middleware which doesn't add any headers yet.
+    pub fn new() -> DefaultHeaders { DefaultHeaders::default() }
+
+    /// Adds the header `key` with `value` to requests which don't set it.
+    pub fn with_header<T: Into<String>, U: Into<String>>(mut self, key: T, value: U) -> Self {
+        self.headers.insert(key.into(), value.into());
+        self
+    }
+}
+
+impl Middleware for DefaultHeaders {
+    fn on_request(&self, mut request: Request) -> Result<Request, Error> {
+        for (key, value) in &self.headers {
+            if request.header(key).is_none() {
+                request = request.with_header(key, value);
+            }
+        }
+        Ok(request)
+    }
+}
+
+/// Resolves the URLs of requests which don't start with `http://` or
+/// `https://` against a base URL, so that requests can be created with just
+/// the path.
+///
+/// The URL of a request is appended to the base URL, with a single `/` in
+/// between.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use bitreq::{BaseUrl, Client};
+///
+/// let client = Client::new(1).with_middleware(BaseUrl::new("http://127.0.0.1:3000/api"));
+/// // Sent to http://127.0.0.1:3000/api/blocks/tip/height
+/// let response = client.send(bitreq::get("/blocks/tip/height"))?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BaseUrl {
+    base: String,
+}
+
+impl BaseUrl {
+    /// Creates a middleware resolving relative URLs against `base`.
+    pub fn new<T: Into<String>>(base: T) -> BaseUrl { BaseUrl { base: base.into() } }
+}
+
+impl Middleware for BaseUrl {
+    fn on_request(&self, request: Request) -> Result<Request, Error> {
+        let url = request.url();
+        let is_absolute = ["http://", "https://"].iter().any(|scheme| {
+            url.get(..scheme.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+        });
+        if is_absolute {
+            return Ok(request);
+        }
+        let url = format!("{}/{}", self.base.trim_end_matches('/'), url.trim_start_matches('/'));
+        Ok(request.with_url(url))
+    }
+}
+
+/// Logs every request sent and its outcome through the [`log`] crate.
+///
+/// Requests are logged when they're sent, and again with the status code of
+/// the response, or the error, once they're done. Uses the `bitreq` target
+/// and logs at the `Debug` level by default.
+#[cfg(feature = "log")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Logger {
+    level: log::Level,
+}
+
+#[cfg(feature = "log")]
+impl Logger {
+    /// Creates a middleware logging at the `Debug` level.
+    pub fn new() -> Logger { Logger { level: log::Level::Debug } }
+
+    /// Sets the level requests are logged at.
+    pub fn with_level(mut self, level: log::Level) -> Self {
+        self.level = level;
+        self
+    }
+}
+
+#[cfg(feature = "log")]
+impl Default for Logger {
+    fn default() -> Self { Logger::new() }
+}
+
+#[cfg(feature = "log")]
+impl Middleware for Logger {
+    fn on_request(&self, request: Request) -> Result<Request, Error> {
+        log::log!(target: "bitreq", self.level, "Sending {} {}", request.method(), request.url());
+        Ok(request)
+    }
+
+    fn on_response(
+        &self,
+        request: &Request,
+        result: Result<Response, Error>,
+    ) -> Result<Response, Error> {
+        let (method, url) = (request.method(), request.url());
+        match &result {
+            Ok(response) => log::log!(
+                target: "bitreq",
+                self.level,
+                "{method} {url} returned {} {}",
+                response.status_code,
+                response.reason_phrase
+            ),
+            Err(err) => log::log!(target: "bitreq", self.level, "{method} {url} failed: {err}"),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_headers_dont_override() {
+        let headers = DefaultHeaders::new().with_header("User-Agent", "a").with_header("X-Id", "1");
+        let request = crate::get("http://example.com").with_header("user-agent", "b");
+        let request = headers.on_request(request).unwrap();
+        assert_eq!(request.header("User-Agent"), Some("b"));
+        assert_eq!(request.header("x-id"), Some("1"));
+    }
+
+    #[test]
+    fn base_url_resolves_relative_urls() {
+        let resolve = |base: &str, url: &str| {
+            let request = BaseUrl::new(base).on_request(crate::get(url)).unwrap();
+            request.url().to_string()
+        };
+        assert_eq!(resolve("http://node:8332", "/wallet/a"), "http://node:8332/wallet/a");
+        assert_eq!(resolve("http://node:8332/", "wallet/a"), "http://node:8332/wallet/a");
+        assert_eq!(resolve("http://node:8332/api/", "/v1"), "http://node:8332/api/v1");
+        assert_eq!(resolve("http://node:8332", "HTTPS://other/x"), "HTTPS://other/x");
+    }
+}
@@ -0,0 +1,212 @@
+//! Building `multipart/form-data` request bodies, see [`Multipart`].
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::Path;
+
+use crate::url::percent_encoded_triplet;
+
+/// A `multipart/form-data` request body, as sent by HTML forms with file
+/// uploads.
+///
+/// Set as the body of a request with
+/// [`Request::with_multipart`](crate::Request::with_multipart).
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use bitreq::{Multipart, Part};
+///
+/// let form = Multipart::new()
+///     .with_text("label", "mempool snapshot")
+///     .with_part(
+///         "snapshot",
+///         Part::bytes(vec![0xde, 0xad, 0xbe, 0xef])
+///             .with_file_name("mempool.dat")
+///             .with_content_type("application/octet-stream"),
+///     );
+/// let response = bitreq::post("http://example.com/upload").with_multipart(form).send()?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Multipart {
+    boundary: String,
+    parts: Vec<(String, Part)>,
+}
+
+/// A part of a [`Multipart`] body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Part {
+    data: Vec<u8>,
+    file_name: Option<String>,
+    content_type: Option<String>,
+}
+
+impl Default for Multipart {
+    fn default() -> Self { Multipart::new() }
+}
+
+impl Multipart {
+    /// Creates an empty body with a random boundary.
+    pub fn new() -> Multipart {
+        let mut boundary = String::from("bitreq-boundary-");
+        for _ in 0..2 {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_usize(boundary.len());
+            boundary.push_str(&format!("{:016x}", hasher.finish()));
+        }
+        Multipart { boundary, parts: Vec::new() }
+    }
+
+    /// Adds a text field called `name`.
+    pub fn with_text<N: Into<String>, V: Into<String>>(self, name: N, value: V) -> Multipart {
+        self.with_part(name, Part::text(value))
+    }
+
+    /// Adds a part called `name`.
+    pub fn with_part<N: Into<String>>(mut self, name: N, part: Part) -> Multipart {
+        self.parts.push((name.into(), part));
+        self
+    }
+
+    /// The boundary separating the parts, as given in the `Content-Type`
+    /// header.
+    pub fn boundary(&self) -> &str { &self.boundary }
+
+    /// The value of the `Content-Type` header to send the body with.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// Encodes the body.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, part) in &self.parts {
+            body.extend_from_slice(format!("--{}\r\n", self.boundary).as_bytes());
+            let mut disposition =
+                format!("Content-Disposition: form-data; name=\"{}\"", quote(name));
+            if let Some(file_name) = &part.file_name {
+                disposition.push_str(&format!("; filename=\"{}\"", quote(file_name)));
+            }
+            body.extend_from_slice(disposition.as_bytes());
+            body.extend_from_slice(b"\r\n");
+            if let Some(content_type) = &part.content_type {
+                body.extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+            }
+            body.extend_from_slice(b"\r\n");
+            body.extend_from_slice(&part.data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        body
+    }
+}
+
+impl Part {
+    /// Creates a text part.
+    pub fn text<V: Into<String>>(value: V) -> Part {
+        Part { data: value.into().into_bytes(), file_name: None, content_type: None }
+    }
+
+    /// Creates a part containing `data`, sent as `application/octet-stream`
+    /// unless another content type is set.
+    pub fn bytes<D: Into<Vec<u8>>>(data: D) -> Part {
+        Part {
+            data: data.into(),
+            file_name: None,
+            content_type: Some("application/octet-stream".to_string()),
+        }
+    }
+
+    /// Creates a part containing the file at `path`, named after the file
+    /// and sent as `application/octet-stream` unless another content type is
+    /// set.
+    ///
+    /// The file is read into memory right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the file can't be read.
+    pub fn file<P: AsRef<Path>>(path: P) -> io::Result<Part> {
+        let path = path.as_ref();
+        let part = Part::bytes(std::fs::read(path)?);
+        Ok(match path.file_name() {
+            Some(file_name) => part.with_file_name(file_name.to_string_lossy()),
+            None => part,
+        })
+    }
+
+    /// Sets the file name sent with the part.
+    pub fn with_file_name<T: Into<String>>(mut self, file_name: T) -> Part {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    /// Sets the `Content-Type` of the part.
+    ///
+    /// Line breaks are removed, so that the value can't add headers to the
+    /// part or end it early.
+    pub fn with_content_type<T: Into<String>>(mut self, content_type: T) -> Part {
+        let mut content_type = content_type.into();
+        content_type.retain(|c| c != '\r' && c != '\n');
+        self.content_type = Some(content_type);
+        self
+    }
+}
+
+/// Escapes a field or file name for a quoted `Content-Disposition` parameter,
+/// percent-encoding quotes and line breaks like browsers do.
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' | '\r' | '\n' => quoted.extend(percent_encoded_triplet(c as u8)),
+            c => quoted.push(c),
+        }
+    }
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_parts() {
+        let mut form = Multipart::new()
+            .with_text("a", "1")
+            .with_part("b\"", Part::bytes(&b"\x00\x01"[..]).with_file_name("x\r\n.bin"))
+            .with_part("c", Part::text("{}").with_content_type("application/json"));
+        form.boundary = "XYZ".to_string();
+
+        let expected = "--XYZ\r\n\
+            Content-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n\
+            --XYZ\r\n\
+            Content-Disposition: form-data; name=\"b%22\"; filename=\"x%0D%0A.bin\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\n\x00\x01\r\n\
+            --XYZ\r\n\
+            Content-Disposition: form-data; name=\"c\"\r\n\
+            Content-Type: application/json\r\n\r\n{}\r\n\
+            --XYZ--\r\n";
+        assert_eq!(form.to_bytes(), expected.as_bytes());
+        assert_eq!(form.content_type(), "multipart/form-data; boundary=XYZ");
+
+        let empty = Multipart::new();
+        assert_eq!(empty.to_bytes(), format!("--{}--\r\n", empty.boundary).as_bytes());
+    }
+
+    #[test]
+    fn strips_line_breaks_from_content_types() {
+        let part = Part::text("x").with_content_type("text/plain\r\nX-Injected: 1\r\n\r\nbody");
+        assert_eq!(part.content_type.as_deref(), Some("text/plainX-Injected: 1body"));
+    }
+
+    #[test]
+    fn boundaries_are_random() {
+        let boundary = Multipart::new().boundary;
+        assert_eq!(boundary.len(), "bitreq-boundary-".len() + 32);
+        assert_ne!(boundary, Multipart::new().boundary);
+    }
+}
@@ -0,0 +1,225 @@
+//! Deciding which redirects are followed, see [`RedirectPolicy`].
+
+use core::fmt;
+use std::sync::Arc;
+
+use crate::Url;
+
+type RedirectFilter = Arc<dyn Fn(&RedirectAttempt) -> bool + Send + Sync>;
+
+/// The headers which are removed from a request when it is redirected to
+/// another origin, as they could leak credentials to it.
+const SENSITIVE_HEADERS: [&str; 3] = ["authorization", "proxy-authorization", "cookie"];
+
+/// Decides which redirects a request follows.
+///
+/// By default, all `301`, `302`, `303`, `307` and `308` redirects are
+/// followed, up to the request's [maximum number of
+/// redirects](crate::Request::with_max_redirects). A redirect which isn't
+/// followed isn't an error: the redirect response itself is returned, with
+/// its `Location` header.
+///
+/// Whatever the policy, the `Authorization`, `Proxy-Authorization` and
+/// `Cookie` headers set on a request are only sent to its original origin
+/// (scheme, host and port): they are removed when the request is redirected
+/// to another one. Cookies from a [`CookieJar`](crate::CookieJar) are still
+/// sent to the hosts they apply to.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use bitreq::RedirectPolicy;
+///
+/// // Follow redirects within the same origin, but never to plain HTTP.
+/// let policy = RedirectPolicy::new()
+///     .with_same_origin_only(true)
+///     .with_filter(|attempt| attempt.next().scheme() == "https");
+/// let response = bitreq::get("https://example.com").with_redirect_policy(policy).send()?;
+/// for url in &response.redirects {
+///     println!("redirected from {}", url);
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct RedirectPolicy {
+    follow: bool,
+    same_origin_only: bool,
+    filter: Option<RedirectFilter>,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self { RedirectPolicy { follow: true, same_origin_only: false, filter: None } }
+}
+
+impl fmt::Debug for RedirectPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RedirectPolicy")
+            .field("follow", &self.follow)
+            .field("same_origin_only", &self.same_origin_only)
+            .field("custom_filter", &self.filter.is_some())
+            .finish()
+    }
+}
+
+impl PartialEq for RedirectPolicy {
+    fn eq(&self, other: &Self) -> bool {
+        let same_filter = match (&self.filter, &other.filter) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        same_filter
+            && self.follow == other.follow
+            && self.same_origin_only == other.same_origin_only
+    }
+}
+
+impl Eq for RedirectPolicy {}
+
+impl RedirectPolicy {
+    /// Creates the default policy, which follows all redirects.
+    pub fn new() -> RedirectPolicy { RedirectPolicy::default() }
+
+    /// Creates a policy which doesn't follow any redirects.
+    pub fn none() -> RedirectPolicy {
+        RedirectPolicy { follow: false, ..RedirectPolicy::default() }
+    }
+
+    /// Sets whether only redirects to the origin (scheme, host and port) of
+    /// the current URL are followed. Defaults to `false`.
+    pub fn with_same_origin_only(mut self, same_origin_only: bool) -> RedirectPolicy {
+        self.same_origin_only = same_origin_only;
+        self
+    }
+
+    /// Sets a callback which is asked whether each redirect is followed,
+    /// after the other checks of the policy passed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use bitreq::RedirectPolicy;
+    ///
+    /// // Don't follow redirects to a login page.
+    /// let policy = RedirectPolicy::new().with_filter(|attempt| attempt.next().path() != "/login");
+    /// ```
+    pub fn with_filter<F>(mut self, filter: F) -> RedirectPolicy
+    where
+        F: Fn(&RedirectAttempt) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Returns whether the redirect described by `attempt` is followed.
+    pub(crate) fn allows(&self, attempt: &RedirectAttempt) -> bool {
+        if !self.follow || (self.same_origin_only && !attempt.is_same_origin()) {
+            return false;
+        }
+        self.filter.as_ref().map_or(true, |filter| filter(attempt))
+    }
+}
+
+/// A redirect which is about to be followed, as given to the filter of a
+/// [`RedirectPolicy`].
+#[derive(Debug)]
+pub struct RedirectAttempt<'a> {
+    pub(crate) status_code: i32,
+    pub(crate) previous: &'a Url,
+    pub(crate) next: &'a Url,
+    pub(crate) history: &'a [Url],
+}
+
+impl<'a> RedirectAttempt<'a> {
+    /// The status code of the redirect response.
+    pub fn status_code(&self) -> i32 { self.status_code }
+
+    /// The URL which answered with the redirect.
+    pub fn previous(&self) -> &'a Url { self.previous }
+
+    /// The URL the request would be redirected to.
+    pub fn next(&self) -> &'a Url { self.next }
+
+    /// The URLs which were redirected from before `previous`, starting with
+    /// the URL of the request.
+    pub fn history(&self) -> &'a [Url] { self.history }
+
+    /// Whether the redirect stays within the origin (scheme, host and port)
+    /// of the previous URL.
+    pub fn is_same_origin(&self) -> bool { is_same_origin(self.previous, self.next) }
+}
+
+/// Returns whether `a` and `b` have the same scheme, host and port.
+pub(crate) fn is_same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme().eq_ignore_ascii_case(b.scheme())
+        && a.base_url().eq_ignore_ascii_case(b.base_url())
+        && a.port() == b.port()
+}
+
+/// Returns whether `header` is removed from requests redirected to another
+/// origin.
+pub(crate) fn is_sensitive_header(header: &str) -> bool {
+    SENSITIVE_HEADERS.iter().any(|sensitive| header.eq_ignore_ascii_case(sensitive))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allows(policy: &RedirectPolicy, previous: &str, next: &str) -> bool {
+        let previous = Url::parse(previous).unwrap();
+        let next = Url::parse(next).unwrap();
+        policy.allows(&RedirectAttempt {
+            status_code: 302,
+            previous: &previous,
+            next: &next,
+            history: &[],
+        })
+    }
+
+    #[test]
+    fn compares_origins() {
+        let same =
+            |a: &str, b: &str| is_same_origin(&Url::parse(a).unwrap(), &Url::parse(b).unwrap());
+        assert!(same("http://example.com/a", "http://EXAMPLE.com:80/b?c"));
+        assert!(same("https://example.com", "https://example.com:443/"));
+        assert!(!same("http://example.com", "https://example.com"));
+        assert!(!same("http://example.com", "http://example.com:8080"));
+        assert!(!same("http://example.com", "http://api.example.com"));
+    }
+
+    #[test]
+    fn applies_policy() {
+        let url = "http://example.com/a";
+        let other_origin = "http://example.org/b";
+        assert!(allows(&RedirectPolicy::new(), url, other_origin));
+        assert!(!allows(&RedirectPolicy::none(), url, "http://example.com/b"));
+
+        let same_origin = RedirectPolicy::new().with_same_origin_only(true);
+        assert!(allows(&same_origin, url, "http://example.com/b"));
+        assert!(!allows(&same_origin, url, other_origin));
+
+        let filtered = RedirectPolicy::new().with_filter(|attempt| attempt.next().path() != "/b");
+        assert!(allows(&filtered, url, "http://example.com/c"));
+        assert!(!allows(&filtered, url, other_origin));
+        assert!(!allows(
+            &filtered.clone().with_same_origin_only(true),
+            url,
+            "http://example.org/c"
+        ));
+
+        assert_eq!(filtered.clone(), filtered);
+        assert_ne!(filtered, RedirectPolicy::new().with_filter(|_| true));
+        assert_ne!(RedirectPolicy::new(), RedirectPolicy::none());
+    }
+
+    #[test]
+    fn sensitive_headers() {
+        assert!(is_sensitive_header("Authorization"));
+        assert!(is_sensitive_header("cookie"));
+        assert!(is_sensitive_header("PROXY-AUTHORIZATION"));
+        assert!(!is_sensitive_header("Cookie2x"));
+        assert!(!is_sensitive_header("Accept"));
+    }
+}
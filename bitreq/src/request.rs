@@ -5,17 +5,38 @@ use core::fmt::Write;
 use core::time::Duration;
 #[cfg(feature = "std")]
 use std::env;
+#[cfg(all(feature = "std", unix))]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "std")]
+use std::sync::Arc;
 #[cfg(feature = "std")]
 use std::time::Instant;
 
+#[cfg(feature = "std")]
+use crate::body::{Body, BodyStream};
 #[cfg(feature = "async")]
 use crate::connection::AsyncConnection;
 #[cfg(feature = "std")]
-use crate::connection::Connection;
+use crate::connection::{Connection, Timeouts};
+#[cfg(feature = "std")]
+use crate::cookie::CookieJar;
+#[cfg(feature = "std")]
+use crate::multipart::Multipart;
 #[cfg(feature = "proxy")]
-use crate::proxy::Proxy;
+use crate::proxy::{Proxy, ProxyConfig};
+#[cfg(feature = "std")]
+use crate::redirect::{self, RedirectAttempt, RedirectPolicy};
+#[cfg(feature = "std")]
+use crate::resolve::{Resolver, SharedResolver};
+#[cfg(feature = "std")]
+use crate::retry::{self, RetryPolicy};
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use crate::tls::TlsConfig;
+use crate::url::percent_encode_string;
 #[cfg(feature = "std")]
 use crate::url::Url;
+#[cfg(feature = "async")]
+use crate::ResponseStream;
 #[cfg(feature = "std")]
 use crate::{Error, Response, ResponseLazy};
 
@@ -48,6 +69,22 @@ pub enum Method {
     Custom(String),
 }
 
+impl Method {
+    /// Returns `true` if sending a request with this method several times has the same effect
+    /// as sending it once, so that it can safely be sent again after a failure.
+    pub(crate) fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            Method::Get
+                | Method::Head
+                | Method::Put
+                | Method::Delete
+                | Method::Options
+                | Method::Trace
+        )
+    }
+}
+
 impl fmt::Display for Method {
     /// Formats the Method to the form in the HTTP request,
     /// ie. Method::Get -> "GET", Method::Post -> "POST", etc.
@@ -86,17 +123,33 @@ impl fmt::Display for Method {
 pub struct Request {
     pub(crate) method: Method,
     url: URL,
-    params: Vec<(String, String)>,
+    pub(crate) params: Vec<(String, String)>,
     headers: BTreeMap<String, String>,
-    body: Option<Vec<u8>>,
+    body: Option<Body>,
     timeout: Option<Duration>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) read_idle_timeout: Option<Duration>,
     pub(crate) pipelining: bool,
     pub(crate) max_headers_size: Option<usize>,
     pub(crate) max_status_line_len: Option<usize>,
     pub(crate) max_body_size: Option<usize>,
     max_redirects: usize,
+    #[cfg(feature = "std")]
+    redirect_policy: RedirectPolicy,
     #[cfg(feature = "proxy")]
     pub(crate) proxy: Option<Proxy>,
+    #[cfg(feature = "proxy")]
+    pub(crate) proxy_config: Option<Arc<ProxyConfig>>,
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub(crate) tls_config: Option<Arc<TlsConfig>>,
+    #[cfg(feature = "std")]
+    pub(crate) retry: Option<RetryPolicy>,
+    #[cfg(feature = "std")]
+    pub(crate) cookie_jar: Option<CookieJar>,
+    #[cfg(all(feature = "std", unix))]
+    pub(crate) unix_socket: Option<PathBuf>,
+    #[cfg(feature = "std")]
+    pub(crate) resolver: Option<SharedResolver>,
 }
 
 impl Request {
@@ -115,6 +168,8 @@ impl Request {
             headers: BTreeMap::new(),
             body: None,
             timeout: None,
+            connect_timeout: None,
+            read_idle_timeout: None,
             pipelining: false,
             // Default matches chrome as of 2022-11:
             // https://groups.google.com/a/chromium.org/g/chromium-os-discuss/c/in-f59OKYAE/m/uVanwcXkAgAJ
@@ -125,11 +180,64 @@ impl Request {
             // Picked somewhat randomly
             max_body_size: Some(1024 * 1024 * 1024),
             max_redirects: 100,
+            #[cfg(feature = "std")]
+            redirect_policy: RedirectPolicy::default(),
             #[cfg(feature = "proxy")]
             proxy: None,
+            #[cfg(feature = "proxy")]
+            proxy_config: None,
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            tls_config: None,
+            #[cfg(feature = "std")]
+            retry: None,
+            #[cfg(feature = "std")]
+            cookie_jar: None,
+            #[cfg(all(feature = "std", unix))]
+            unix_socket: None,
+            #[cfg(feature = "std")]
+            resolver: None,
         }
     }
 
+    /// Returns the method of the request.
+    pub fn method(&self) -> &Method { &self.method }
+
+    /// Returns the URL of the request, as given when it was created,
+    /// without the parameters added with
+    /// [`with_param`](struct.Request.html#method.with_param).
+    pub fn url(&self) -> &str { &self.url }
+
+    /// Returns the value of the header `key`, compared
+    /// case-insensitively, if it was set.
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the body of the request, unless it has none or it's read
+    /// from a reader.
+    pub fn body(&self) -> Option<&[u8]> {
+        match &self.body {
+            Some(Body::Bytes(bytes)) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Returns the timeout set with [`with_timeout`](Request::with_timeout), or else the one
+    /// set in the `BITREQ_TIMEOUT` environment variable, in seconds.
+    #[cfg(feature = "std")]
+    pub(crate) fn effective_timeout(&self) -> Option<Duration> {
+        self.timeout.or_else(|| match env::var("BITREQ_TIMEOUT") {
+            Ok(t) => t.parse::<u64>().ok().map(Duration::from_secs),
+            Err(_) => None,
+        })
+    }
+
+    /// Replaces the URL of the request.
+    pub fn with_url<T: Into<URL>>(mut self, url: T) -> Request {
+        self.url = url.into();
+        self
+    }
+
     /// Add headers to the request this is called on. Use this
     /// function to add headers to your requests.
     pub fn with_headers<T, K, V>(mut self, headers: T) -> Request
@@ -154,10 +262,78 @@ impl Request {
     pub fn with_body<T: Into<Vec<u8>>>(mut self, body: T) -> Request {
         let body = body.into();
         let body_length = body.len();
-        self.body = Some(body);
+        self.body = Some(Body::Bytes(body));
         self.with_header("Content-Length", format!("{}", body_length))
     }
 
+    /// Sets the request body to the contents of `reader`, which is read
+    /// while the request is being sent rather than loaded into memory
+    /// first.
+    ///
+    /// If `len` is given, exactly that many bytes are read and sent with a
+    /// `Content-Length` header. Otherwise, `reader` is read until its end
+    /// and sent with `Transfer-Encoding: chunked`, which some servers don't
+    /// support.
+    ///
+    /// The body can only be sent once: following a redirect which requires
+    /// sending it again fails with
+    /// [`BodyStreamConsumed`](enum.Error.html#variant.BodyStreamConsumed),
+    /// and a [`RetryPolicy`] doesn't apply to the request. When sent with
+    /// `send_async`, reading blocks the async runtime's thread, see
+    /// `with_body_async_reader` to avoid that.
+    #[cfg(feature = "std")]
+    pub fn with_body_reader<R>(self, reader: R, len: Option<u64>) -> Request
+    where
+        R: std::io::Read + Send + 'static,
+    {
+        self.with_body_stream(BodyStream::new(reader, len))
+    }
+
+    /// Sets the request body to the contents of the async `reader`, which
+    /// is read while the request is being sent. Such requests can only be
+    /// sent with `send_async`.
+    ///
+    /// See [`with_body_reader`](Request::with_body_reader) for details.
+    #[cfg(feature = "async")]
+    pub fn with_body_async_reader<R>(self, reader: R, len: Option<u64>) -> Request
+    where
+        R: tokio::io::AsyncRead + Send + 'static,
+    {
+        self.with_body_stream(BodyStream::new_async(reader, len))
+    }
+
+    #[cfg(feature = "std")]
+    fn with_body_stream(mut self, body: BodyStream) -> Request {
+        self.remove_body_headers();
+        let header = match body.len() {
+            Some(len) => ("Content-Length", len.to_string()),
+            None => ("Transfer-Encoding", "chunked".to_string()),
+        };
+        self.body = Some(Body::Stream(body));
+        self.with_header(header.0, header.1)
+    }
+
+    fn remove_body_headers(&mut self) {
+        self.headers.retain(|key, _| {
+            !key.eq_ignore_ascii_case("content-length")
+                && !key.eq_ignore_ascii_case("transfer-encoding")
+        });
+    }
+
+    /// Drops the body if it's streamed from a reader, which is done when a
+    /// `303 See Other` redirect turns the request into a `GET`.
+    #[cfg(feature = "std")]
+    pub(crate) fn drop_body_stream(&mut self) {
+        if let Some(Body::Stream(_)) = self.body {
+            self.body = None;
+            self.remove_body_headers();
+        }
+    }
+
+    /// Returns `true` if the request body can't be sent again once sent.
+    #[cfg(feature = "std")]
+    pub(crate) fn has_body_stream(&self) -> bool { matches!(self.body, Some(Body::Stream(_))) }
+
     /// Adds given key and value as query parameter to request url
     /// (resource).
     ///
@@ -167,6 +343,29 @@ impl Request {
         self
     }
 
+    /// Sets the body to the given fields, encoded as
+    /// `application/x-www-form-urlencoded` like a submitted HTML form.
+    ///
+    /// The keys and values are percent-encoded.
+    pub fn with_form<K: AsRef<str>, V: AsRef<str>>(self, fields: &[(K, V)]) -> Request {
+        let mut body = String::new();
+        for (key, value) in fields {
+            if !body.is_empty() {
+                body.push('&');
+            }
+            body.push_str(&percent_encode_string(key.as_ref()));
+            body.push('=');
+            body.push_str(&percent_encode_string(value.as_ref()));
+        }
+        self.with_header("Content-Type", "application/x-www-form-urlencoded").with_body(body)
+    }
+
+    /// Sets the body to the given `multipart/form-data` form, as sent by
+    /// HTML forms with file uploads.
+    pub fn with_multipart(self, form: Multipart) -> Request {
+        self.with_header("Content-Type", form.content_type()).with_body(form.to_bytes())
+    }
+
     /// Converts given argument to JSON and sets it as body.
     ///
     /// # Errors
@@ -191,6 +390,34 @@ impl Request {
         self
     }
 
+    /// Sets how long establishing a connection to the server, or to
+    /// the proxy, may take. This covers the TCP handshakes with all
+    /// the addresses the host resolves to, but not looking the host
+    /// up, nor any proxy or TLS handshake.
+    ///
+    /// If it's reached, sending fails with
+    /// [`Error::ConnectTimeout`](enum.Error.html#variant.ConnectTimeout).
+    /// The [request timeout](#method.with_timeout) still applies to the
+    /// whole request.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Request {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long the server may go without sending anything while
+    /// we're waiting for or reading its response, for example to
+    /// detect a stalled connection without limiting how long a large
+    /// download may take in total.
+    ///
+    /// If it's reached, reading fails with
+    /// [`Error::ReadIdleTimeout`](enum.Error.html#variant.ReadIdleTimeout).
+    /// The [request timeout](#method.with_timeout) still applies to the
+    /// whole request.
+    pub fn with_read_idle_timeout(mut self, timeout: Duration) -> Request {
+        self.read_idle_timeout = Some(timeout);
+        self
+    }
+
     /// Sets the max redirects we follow until giving up. 100 by
     /// default.
     ///
@@ -203,6 +430,14 @@ impl Request {
         self
     }
 
+    /// Sets which redirects are followed. See [`RedirectPolicy`] for
+    /// details.
+    #[cfg(feature = "std")]
+    pub fn with_redirect_policy(mut self, redirect_policy: RedirectPolicy) -> Request {
+        self.redirect_policy = redirect_policy;
+        self
+    }
+
     /// Sets the maximum size of all the headers this request will
     /// accept.
     ///
@@ -268,6 +503,76 @@ impl Request {
         self
     }
 
+    /// Sets the proxy configuration used to pick a proxy for the request and
+    /// each of its redirects, unless one was set with
+    /// [`with_proxy`](Request::with_proxy).
+    ///
+    /// Without either, the request is sent directly. To use the proxy
+    /// environment variables, pass [`Proxy::from_env`].
+    #[cfg(feature = "proxy")]
+    pub fn with_proxy_config(mut self, proxy_config: ProxyConfig) -> Request {
+        self.proxy_config = Some(Arc::new(proxy_config));
+        self
+    }
+
+    /// Sets the TLS settings used for HTTPS connections, such as extra
+    /// trusted root certificates or a client certificate. See
+    /// [`TlsConfig`] for details.
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub fn with_tls_config(mut self, tls_config: TlsConfig) -> Request {
+        self.tls_config = Some(Arc::new(tls_config));
+        self
+    }
+
+    /// Sends the cookies in `cookie_jar` which apply to the request, and
+    /// stores the cookies set by the responses, including the ones to
+    /// redirects. See [`CookieJar`] for details.
+    #[cfg(feature = "std")]
+    pub fn with_cookie_jar(mut self, cookie_jar: CookieJar) -> Request {
+        self.cookie_jar = Some(cookie_jar);
+        self
+    }
+
+    /// Sends the request over the Unix domain socket at `path` instead of
+    /// connecting to the host of the URL, which is still sent in the `Host`
+    /// header. Redirects are followed over the same socket.
+    ///
+    /// Proxies are never used for such requests, and `https` URLs return an
+    /// error.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), bitreq::Error> {
+    /// let response = bitreq::get("http://localhost/v1.43/containers/json")
+    ///     .with_unix_socket("/var/run/docker.sock")
+    ///     .send()?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(all(feature = "std", unix))]
+    pub fn with_unix_socket<P: Into<PathBuf>>(mut self, path: P) -> Request {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
+    /// Resolves the host names of the request, its redirects and its proxy
+    /// with `resolver` instead of the system's resolver. See [`Resolver`]
+    /// for details.
+    #[cfg(feature = "std")]
+    pub fn with_resolver<R: Resolver + 'static>(mut self, resolver: R) -> Request {
+        self.resolver = Some(SharedResolver(Arc::new(resolver)));
+        self
+    }
+
+    /// Retries the request according to `retry` if it fails with a
+    /// transient error or a retryable status code. See [`RetryPolicy`] for
+    /// details.
+    #[cfg(feature = "std")]
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Request {
+        self.retry = Some(retry);
+        self
+    }
+
     /// Enables HTTP request pipelining for this request.
     ///
     /// Note that because pipelined requests may be replayed in case of failure, you should only
@@ -295,12 +600,14 @@ impl Request {
     #[cfg(feature = "std")]
     pub fn send(self) -> Result<Response, Error> {
         let parsed_request = ParsedRequest::new(self)?;
-        let is_head = parsed_request.config.method == Method::Head;
-        let max_body_size = parsed_request.config.max_body_size;
-        let connection =
-            Connection::new(parsed_request.connection_params(), parsed_request.timeout_at)?;
-        let response = connection.send(parsed_request)?;
-        Response::create(response, is_head, max_body_size)
+        retry::send_with_retry(parsed_request, |parsed_request| {
+            let is_head = parsed_request.config.method == Method::Head;
+            let max_body_size = parsed_request.config.max_body_size;
+            let connection =
+                Connection::new(parsed_request.connection_params(), parsed_request.timeouts())?;
+            let response = connection.send(parsed_request)?;
+            Response::create(response, is_head, max_body_size)
+        })
     }
 
     /// Sends this request to the host, loaded lazily.
@@ -311,8 +618,10 @@ impl Request {
     #[cfg(feature = "std")]
     pub fn send_lazy(self) -> Result<ResponseLazy, Error> {
         let parsed_request = ParsedRequest::new(self)?;
-        Connection::new(parsed_request.connection_params(), parsed_request.timeout_at)?
-            .send(parsed_request)
+        retry::send_with_retry(parsed_request, |parsed_request| {
+            Connection::new(parsed_request.connection_params(), parsed_request.timeouts())?
+                .send(parsed_request)
+        })
     }
 
     /// Sends this request to the host asynchronously.
@@ -327,19 +636,20 @@ impl Request {
     #[cfg(feature = "async")]
     pub async fn send_async(self) -> Result<Response, Error> {
         let parsed_request = ParsedRequest::new(self)?;
-        AsyncConnection::new(parsed_request.connection_params(), parsed_request.timeout_at)
-            .await?
-            .send(parsed_request)
-            .await
+        retry::send_with_retry_async(parsed_request, |parsed_request| async move {
+            AsyncConnection::new(parsed_request.connection_params(), parsed_request.timeouts())
+                .await?
+                .send(parsed_request)
+                .await
+        })
+        .await
     }
 
     /// Sends this request to the host asynchronously, "loaded lazily".
     ///
     /// Note that due to API limitations the response is not actually loaded lazily - it is loaded
-    /// immediately and then can be re-read from the response. In a future version an
-    /// `AsyncResponseLazy` will be added and lazy loading support will be added.
-    ///
-    /// Until then, you should use [`Self::send_async`].
+    /// immediately and then can be re-read from the response. Use [`Self::send_streaming_async`]
+    /// to read the body as it arrives instead.
     ///
     /// # Errors
     ///
@@ -349,9 +659,27 @@ impl Request {
         let response = self.send_async().await?;
         Ok(ResponseLazy::dummy_from_response(response))
     }
+
+    /// Sends this request to the host asynchronously, returning the
+    /// response as soon as its headers have been received, with the body
+    /// left to be streamed from the connection.
+    ///
+    /// See [`ResponseStream`] for how the body is read. The request is
+    /// always sent over a new connection, which isn't reused afterwards.
+    ///
+    /// # Errors
+    ///
+    /// See [`send_async`](struct.Request.html#method.send_async). Errors
+    /// while reading the body are returned when reading it.
+    #[cfg(feature = "async")]
+    pub async fn send_streaming_async(self) -> Result<ResponseStream, Error> {
+        let parsed_request = ParsedRequest::new(self)?;
+        retry::send_with_retry_async(parsed_request, AsyncConnection::send_streaming).await
+    }
 }
 
 #[cfg(feature = "std")]
+#[derive(Clone)]
 pub(crate) struct ParsedRequest {
     pub(crate) url: Url,
     pub(crate) redirects: Vec<Url>,
@@ -361,56 +689,26 @@ pub(crate) struct ParsedRequest {
 
 #[cfg(feature = "std")]
 impl ParsedRequest {
-    #[allow(unused_mut)]
-    pub(crate) fn new(mut config: Request) -> Result<ParsedRequest, Error> {
+    pub(crate) fn new(config: Request) -> Result<ParsedRequest, Error> {
         let mut url = Url::parse(&config.url)?;
         let params = config.params.iter().map(|(a, b)| (a.as_str(), b.as_str()));
         url.append_query_params(params);
 
-        #[cfg(all(feature = "proxy", feature = "std"))]
-        // Set default proxy from environment variables
-        //
-        // Curl documentation: https://everything.curl.dev/usingcurl/proxies/env
-        //
-        // Accepted variables are `http_proxy`, `https_proxy`, `HTTPS_PROXY`, `ALL_PROXY`
-        //
-        // Note: https://everything.curl.dev/usingcurl/proxies/env#http_proxy-in-lower-case-only
-        if config.proxy.is_none() {
-            // Set HTTP proxies if request's protocol is HTTPS and they're given
-            if url.is_https() {
-                if let Ok(proxy) =
-                    std::env::var("https_proxy").map_err(|_| std::env::var("HTTPS_PROXY"))
-                {
-                    if let Ok(proxy) = Proxy::new_http(proxy) {
-                        config.proxy = Some(proxy);
-                    }
-                }
-            }
-            // Set HTTP proxies if request's protocol is HTTP and they're given
-            else if let Ok(proxy) = std::env::var("http_proxy") {
-                if let Ok(proxy) = Proxy::new_http(proxy) {
-                    config.proxy = Some(proxy);
-                }
-            }
-            // Set any given proxies if neither of HTTP/HTTPS were given
-            else if let Ok(proxy) =
-                std::env::var("all_proxy").map_err(|_| std::env::var("ALL_PROXY"))
-            {
-                if let Ok(proxy) = Proxy::new_http(proxy) {
-                    config.proxy = Some(proxy);
-                }
-            }
-        }
-
-        let timeout = config.timeout.or_else(|| match env::var("BITREQ_TIMEOUT") {
-            Ok(t) => t.parse::<u64>().ok().map(Duration::from_secs),
-            Err(_) => None,
-        });
-        let timeout_at = timeout.map(|t| Instant::now() + t);
+        let timeout_at = config.effective_timeout().map(|t| Instant::now() + t);
 
         Ok(ParsedRequest { url, redirects: Vec::new(), config, timeout_at })
     }
 
+    /// Stores the cookies set by a response to this request in the
+    /// request's cookie jar, if any.
+    pub(crate) fn store_cookies(&self, set_cookies: &[String]) {
+        if let Some(jar) = &self.config.cookie_jar {
+            for set_cookie in set_cookies {
+                jar.set_cookie(&self.url, set_cookie);
+            }
+        }
+    }
+
     fn get_http_head(&self) -> String {
         let mut http = String::with_capacity(32);
 
@@ -437,9 +735,26 @@ impl ParsedRequest {
         }
         http += "\r\n";
 
-        // Add other headers
+        // Add other headers, adding the cookies from the cookie jar to any set explicitly
+        let jar_cookies =
+            self.config.cookie_jar.as_ref().and_then(|jar| jar.cookie_header(&self.url));
+        let is_cookie = |key: &String| key.eq_ignore_ascii_case("cookie");
         for (k, v) in &self.config.headers {
-            write!(http, "{}: {}\r\n", k, v).unwrap();
+            match &jar_cookies {
+                Some(cookies) if is_cookie(k) => write!(http, "{}: {}; {}\r\n", k, v, cookies),
+                _ => write!(http, "{}: {}\r\n", k, v),
+            }
+            .unwrap();
+        }
+        if let Some(cookies) = jar_cookies {
+            if !self.config.headers.keys().any(is_cookie) {
+                write!(http, "Cookie: {}\r\n", cookies).unwrap();
+            }
+        }
+
+        #[cfg(feature = "compression")]
+        if !self.config.headers.keys().any(|k| k.eq_ignore_ascii_case("accept-encoding")) {
+            write!(http, "Accept-Encoding: {}\r\n", crate::compression::ACCEPT_ENCODING).unwrap();
         }
 
         if self.config.method == Method::Post
@@ -471,18 +786,47 @@ impl ParsedRequest {
     /// the server.
     pub(crate) fn as_bytes(&self) -> Vec<u8> {
         let mut head = self.get_http_head().into_bytes();
-        if let Some(body) = &self.config.body {
+        if let Some(Body::Bytes(body)) = &self.config.body {
             head.extend(body);
         }
         head
     }
 
-    /// Returns the redirected version of this Request, unless an
-    /// infinite redirection loop was detected, or the redirection
-    /// limit was reached.
-    pub(crate) fn redirect_to(&mut self, url: &str) -> Result<(), Error> {
-        if url.contains("://") {
-            let mut new_url = Url::parse(url).map_err(|_| {
+    /// Writes the HTTP request to `stream`, reading a streamed body as it
+    /// goes.
+    pub(crate) fn write_to<W: std::io::Write>(&self, stream: &mut W) -> Result<(), Error> {
+        match &self.config.body {
+            Some(Body::Stream(body)) => {
+                stream.write_all(self.get_http_head().as_bytes())?;
+                body.write_to(stream)
+            }
+            _ => Ok(stream.write_all(&self.as_bytes())?),
+        }
+    }
+
+    /// Writes the HTTP request to `stream`, reading a streamed body as it
+    /// goes.
+    #[cfg(feature = "async")]
+    pub(crate) async fn write_to_async<W>(&self, stream: &mut W) -> Result<(), Error>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        use tokio::io::AsyncWriteExt;
+
+        match &self.config.body {
+            Some(Body::Stream(body)) => {
+                stream.write_all(self.get_http_head().as_bytes()).await?;
+                body.write_to_async(stream).await
+            }
+            _ => Ok(stream.write_all(&self.as_bytes()).await?),
+        }
+    }
+
+    /// Resolves the `Location` of a redirect response to this request,
+    /// keeping the fragment of the current URL if the new one has none.
+    pub(crate) fn redirect_url(&self, url: &str) -> Result<Url, Error> {
+        let mut new_url = if url.contains("://") {
+            Url::parse(url).map_err(|_| {
                 // TODO: Uncomment this for 3.0
                 // Error::InvalidProtocolInRedirect
                 #[cfg(feature = "std")]
@@ -496,24 +840,44 @@ impl ParsedRequest {
                 {
                     Error::Other("invalid protocol in redirect")
                 }
-            })?;
-            // Preserve fragment from original URL if new URL doesn't have one (RFC 7231 section 7.1.2)
-            new_url.preserve_fragment_from(&self.url);
-            std::mem::swap(&mut new_url, &mut self.url);
-            self.redirects.push(new_url);
+            })?
         } else {
             // The url does not have the protocol part, assuming it's
             // a relative resource.
             let mut absolute_url = String::new();
             self.url.write_base_url_to(&mut absolute_url).unwrap();
             absolute_url.push_str(url);
-            let mut new_url = Url::parse(&absolute_url)?;
+            Url::parse(&absolute_url)?
+        };
+
+        // Preserve fragment from original URL if new URL doesn't have one (RFC 7231 section 7.1.2)
+        new_url.preserve_fragment_from(&self.url);
+        Ok(new_url)
+    }
 
-            // Preserve fragment from original URL if new URL doesn't have one (RFC 7231 section 7.1.2)
-            new_url.preserve_fragment_from(&self.url);
-            std::mem::swap(&mut new_url, &mut self.url);
-            self.redirects.push(new_url);
+    /// Returns whether the request's redirect policy allows following a
+    /// redirect with `status_code` to `new_url`.
+    pub(crate) fn allows_redirect(&self, status_code: i32, new_url: &Url) -> bool {
+        self.config.redirect_policy.allows(&RedirectAttempt {
+            status_code,
+            previous: &self.url,
+            next: new_url,
+            history: &self.redirects,
+        })
+    }
+
+    /// Redirects this Request to `new_url`, unless an infinite
+    /// redirection loop was detected, or the redirection limit was
+    /// reached.
+    ///
+    /// Headers carrying credentials are removed if `new_url` has another
+    /// origin.
+    pub(crate) fn redirect_to(&mut self, mut new_url: Url) -> Result<(), Error> {
+        if !redirect::is_same_origin(&self.url, &new_url) {
+            self.config.headers.retain(|key, _| !redirect::is_sensitive_header(key));
         }
+        std::mem::swap(&mut new_url, &mut self.url);
+        self.redirects.push(new_url);
 
         if self.redirects.len() > self.config.max_redirects {
             Err(Error::TooManyRedirections)
@@ -524,9 +888,30 @@ impl ParsedRequest {
         }
     }
 
+    /// The URLs this request was redirected from, in the format of
+    /// [`Response::url`].
+    pub(crate) fn redirect_history(&self) -> Vec<String> {
+        let mut history = Vec::with_capacity(self.redirects.len());
+        for url in &self.redirects {
+            let mut url_str = String::new();
+            url.write_base_url_to(&mut url_str).unwrap();
+            url.write_resource_to(&mut url_str).unwrap();
+            history.push(url_str);
+        }
+        history
+    }
+
     pub(crate) fn connection_params(&self) -> ConnectionParams<'_> {
         ConnectionParams::from_request(self)
     }
+
+    pub(crate) fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            timeout_at: self.timeout_at,
+            connect: self.config.connect_timeout,
+            read_idle: self.config.read_idle_timeout,
+        }
+    }
 }
 
 /// A key which determines whether an existing connection can be reused
@@ -538,6 +923,11 @@ pub(crate) struct ConnectionParams<'a> {
     pub(crate) port: u16,
     #[cfg(feature = "proxy")]
     pub(crate) proxy: Option<&'a Proxy>,
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub(crate) tls: Option<&'a Arc<TlsConfig>>,
+    #[cfg(unix)]
+    pub(crate) unix_socket: Option<&'a Path>,
+    pub(crate) resolver: Option<&'a SharedResolver>,
 }
 
 #[cfg(feature = "std")]
@@ -548,7 +938,16 @@ impl<'a> ConnectionParams<'a> {
             host: request.url.base_url(),
             port: request.url.port(),
             #[cfg(feature = "proxy")]
-            proxy: request.config.proxy.as_ref(),
+            proxy: request
+                .config
+                .proxy
+                .as_ref()
+                .or_else(|| request.config.proxy_config.as_ref()?.proxy_for(&request.url)),
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            tls: request.config.tls_config.as_ref(),
+            #[cfg(unix)]
+            unix_socket: request.config.unix_socket.as_deref(),
+            resolver: request.config.resolver.as_ref(),
         }
     }
 }
@@ -562,6 +961,11 @@ pub(crate) struct OwnedConnectionParams {
     pub(crate) port: u16,
     #[cfg(feature = "proxy")]
     pub(crate) proxy: Option<Proxy>,
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub(crate) tls: Option<Arc<TlsConfig>>,
+    #[cfg(unix)]
+    pub(crate) unix_socket: Option<PathBuf>,
+    pub(crate) resolver: Option<SharedResolver>,
 }
 
 #[cfg(feature = "std")]
@@ -570,6 +974,17 @@ impl PartialEq<ConnectionParams<'_>> for OwnedConnectionParams {
         if self.https != other.https || self.host != other.host || self.port != other.port {
             return false;
         }
+        #[cfg(any(feature = "rustls", feature = "native-tls"))]
+        if self.tls.as_ref() != other.tls {
+            return false;
+        }
+        #[cfg(unix)]
+        if self.unix_socket.as_deref() != other.unix_socket {
+            return false;
+        }
+        if self.resolver.as_ref() != other.resolver {
+            return false;
+        }
         #[cfg(feature = "proxy")]
         {
             self.proxy.as_ref() == other.proxy
@@ -590,6 +1005,11 @@ impl From<ConnectionParams<'_>> for OwnedConnectionParams {
             port: other.port,
             #[cfg(feature = "proxy")]
             proxy: other.proxy.cloned(),
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            tls: other.tls.cloned(),
+            #[cfg(unix)]
+            unix_socket: other.unix_socket.map(Path::to_path_buf),
+            resolver: other.resolver.cloned(),
         }
     }
 }
@@ -0,0 +1,209 @@
+//! Resolving host names to the addresses connected to, see [`Resolver`].
+
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+
+use crate::Error;
+
+/// Resolves the host names of requests, and of their proxies, to the
+/// addresses connected to.
+///
+/// Set on a request with [`Request::with_resolver`](crate::Request::with_resolver)
+/// or on all requests sent by a client with
+/// [`Client::with_resolver`](crate::Client::with_resolver). Without one, the
+/// system's resolver is used, like [`SystemResolver`] does.
+///
+/// When a host resolves to several addresses, they are dialed "Happy
+/// Eyeballs" style ([RFC 8305](https://datatracker.ietf.org/doc/html/rfc8305)):
+/// IPv6 and IPv4 addresses are tried alternately, starting a new attempt every
+/// 250 milliseconds while the previous ones are still pending, and the first
+/// connection established is used.
+///
+/// Async requests call the resolver on a thread for blocking work, so it may
+/// block.
+pub trait Resolver: Send + Sync {
+    /// Returns the addresses to connect to for `host` and `port`, in order of
+    /// preference.
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>>;
+}
+
+impl<R: Resolver + ?Sized> Resolver for Arc<R> {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        (**self).resolve(host, port)
+    }
+}
+
+/// Resolves host names with the system's resolver.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok((host, port).to_socket_addrs()?.collect())
+    }
+}
+
+/// Resolves some host names to fixed addresses, like curl's `--resolve`
+/// option, and all others with the system's resolver.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use std::net::Ipv4Addr;
+///
+/// use bitreq::StaticResolver;
+///
+/// let resolver = StaticResolver::new().with_host("node.internal", [Ipv4Addr::LOCALHOST.into()]);
+/// let response = bitreq::get("http://node.internal:8332").with_resolver(resolver).send()?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StaticResolver {
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+impl StaticResolver {
+    /// Creates a resolver which doesn't pin any host names yet.
+    pub fn new() -> StaticResolver { StaticResolver::default() }
+
+    /// Resolves `host` to `addrs`, on any port, replacing the addresses
+    /// given for it before. Host names are matched case-insensitively.
+    pub fn with_host<H, I>(mut self, host: H, addrs: I) -> StaticResolver
+    where
+        H: Into<String>,
+        I: IntoIterator<Item = IpAddr>,
+    {
+        let host = host.into().to_ascii_lowercase();
+        self.hosts.insert(host, addrs.into_iter().collect());
+        self
+    }
+}
+
+impl Resolver for StaticResolver {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        match self.hosts.get(&host.to_ascii_lowercase()) {
+            Some(addrs) => Ok(addrs.iter().map(|ip| SocketAddr::new(*ip, port)).collect()),
+            None => SystemResolver.resolve(host, port),
+        }
+    }
+}
+
+/// A [`Resolver`] set on a request, compared by identity so that requests can
+/// be compared and connections are only shared by requests using the same
+/// resolver.
+#[derive(Clone)]
+pub(crate) struct SharedResolver(pub(crate) Arc<dyn Resolver>);
+
+impl fmt::Debug for SharedResolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedResolver").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for SharedResolver {
+    fn eq(&self, other: &Self) -> bool { Arc::ptr_eq(&self.0, &other.0) }
+}
+
+impl Eq for SharedResolver {}
+
+impl Hash for SharedResolver {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Arc::as_ptr(&self.0) as *const () as usize).hash(state)
+    }
+}
+
+/// Resolves `host` with `resolver`, or with the system's resolver if none is
+/// set.
+pub(crate) fn resolve(
+    resolver: Option<&SharedResolver>,
+    host: &str,
+    port: u16,
+) -> Result<Vec<SocketAddr>, Error> {
+    #[cfg(feature = "log")]
+    log::trace!("Looking up host {host}");
+
+    let addrs = match resolver {
+        Some(resolver) => resolver.0.resolve(host, port)?,
+        None => SystemResolver.resolve(host, port)?,
+    };
+    if addrs.is_empty() {
+        return Err(Error::AddressNotFound);
+    }
+    Ok(addrs)
+}
+
+/// Like [`resolve`], without blocking the async runtime.
+#[cfg(feature = "async")]
+pub(crate) async fn resolve_async(
+    resolver: Option<&SharedResolver>,
+    host: &str,
+    port: u16,
+) -> Result<Vec<SocketAddr>, Error> {
+    #[cfg(feature = "log")]
+    log::trace!("Looking up host {host}");
+
+    let addrs: Vec<_> = match resolver {
+        Some(resolver) => {
+            let (resolver, host) = (Arc::clone(&resolver.0), host.to_owned());
+            tokio::task::spawn_blocking(move || resolver.resolve(&host, port))
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))??
+        }
+        None => tokio::net::lookup_host((host, port)).await?.collect(),
+    };
+    if addrs.is_empty() {
+        return Err(Error::AddressNotFound);
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+    use std::net::Ipv6Addr;
+
+    use super::*;
+
+    #[test]
+    fn static_resolver_pins_hosts() {
+        let ip = IpAddr::from([10, 0, 0, 1]);
+        let resolver = StaticResolver::new()
+            .with_host("Node.Internal", [ip, Ipv6Addr::LOCALHOST.into()])
+            .with_host("other.internal", []);
+        assert_eq!(
+            resolver.resolve("node.INTERNAL", 8332).unwrap(),
+            [SocketAddr::new(ip, 8332), SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 8332)]
+        );
+        assert!(resolver.resolve("other.internal", 80).unwrap().is_empty());
+        assert_eq!(
+            resolver.resolve("127.0.0.1", 80).unwrap(),
+            [SocketAddr::from(([127, 0, 0, 1], 80))]
+        );
+
+        let shared = SharedResolver(Arc::new(resolver));
+        assert!(matches!(
+            resolve(Some(&shared), "other.internal", 80),
+            Err(Error::AddressNotFound)
+        ));
+        assert_eq!(resolve(Some(&shared), "node.internal", 1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn shared_resolvers_compare_by_identity() {
+        let hash = |resolver: &SharedResolver| {
+            let mut hasher = DefaultHasher::new();
+            resolver.hash(&mut hasher);
+            hasher.finish()
+        };
+        let a = SharedResolver(Arc::new(SystemResolver));
+        let b = SharedResolver(Arc::new(SystemResolver));
+        assert_eq!(a, a.clone());
+        assert_eq!(hash(&a), hash(&a.clone()));
+        assert_ne!(a, b);
+    }
+}
@@ -3,13 +3,21 @@ use core::str;
 #[cfg(feature = "async")]
 use std::future::Future;
 #[cfg(feature = "std")]
-use std::io::{self, BufReader, Bytes, Read};
+use std::io::{self, BufRead, BufReader, Read};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{ready, Context, Poll};
 
 #[cfg(feature = "async")]
-use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, ReadBuf};
 
+#[cfg(feature = "compression")]
+use crate::compression::Decoder;
 #[cfg(feature = "std")]
 use crate::connection::HttpStream;
+#[cfg(feature = "std")]
+use crate::error::ReadIdleTimeoutReached;
 use crate::Error;
 
 #[cfg(feature = "std")]
@@ -46,19 +54,55 @@ pub struct Response {
     /// <http://example.com?foo=bar> would be corrected to
     /// <http://example.com/?foo=bar>).
     pub url: String,
+    /// The URLs the request was redirected from before reaching `url`,
+    /// starting with the URL of the request. Empty if no redirects were
+    /// followed.
+    pub redirects: Vec<String>,
 
     body: Vec<u8>,
+    /// The values of all `Set-Cookie` headers, which are otherwise collapsed into the last one in
+    /// `headers`.
+    pub(crate) set_cookies: Vec<String>,
 }
 
 impl Response {
+    #[cfg(test)]
+    pub(crate) fn from_parts(
+        status_code: i32,
+        headers: BTreeMap<String, String>,
+        body: Vec<u8>,
+    ) -> Response {
+        Response {
+            status_code,
+            reason_phrase: String::new(),
+            headers,
+            url: String::new(),
+            redirects: Vec::new(),
+            body,
+            set_cookies: Vec::new(),
+        }
+    }
+
     #[cfg(feature = "std")]
     pub(crate) fn create(
-        mut parent: ResponseLazy,
+        parent: ResponseLazy,
         is_head: bool,
         max_body_size: Option<usize>,
     ) -> Result<Response, Error> {
+        Response::create_reusable(parent, is_head, max_body_size).map(|(response, _)| response)
+    }
+
+    /// Like [`Response::create`], but also hands back the underlying stream if the response was
+    /// read up to its end, i.e. if the stream is positioned where the next response would start.
+    #[cfg(feature = "std")]
+    pub(crate) fn create_reusable(
+        mut parent: ResponseLazy,
+        is_head: bool,
+        max_body_size: Option<usize>,
+    ) -> Result<(Response, Option<BufReader<HttpStream>>), Error> {
+        let has_body = !is_head && parent.status_code != 204 && parent.status_code != 304;
         let mut body = Vec::new();
-        if !is_head && parent.status_code != 204 && parent.status_code != 304 {
+        if has_body {
             for byte in &mut parent {
                 let (byte, length) = byte?;
                 if max_body_size.is_some_and(|max| body.len().saturating_add(length) > max) {
@@ -69,9 +113,28 @@ impl Response {
             }
         }
 
-        let ResponseLazy { status_code, reason_phrase, headers, url, .. } = parent;
+        let ResponseLazy {
+            status_code,
+            reason_phrase,
+            headers,
+            url,
+            redirects,
+            stream,
+            state,
+            set_cookies,
+            ..
+        } = parent;
+        let stream = match state {
+            _ if !has_body => Some(stream.into_inner()),
+            HttpStreamState::ContentLength(0) | HttpStreamState::Chunked(false, 0, _) =>
+                Some(stream.into_inner()),
+            _ => None,
+        };
 
-        Ok(Response { status_code, reason_phrase, headers, url, body })
+        Ok((
+            Response { status_code, reason_phrase, headers, url, redirects, body, set_cookies },
+            stream,
+        ))
     }
 
     #[cfg(feature = "async")]
@@ -96,34 +159,39 @@ impl Response {
             mut headers,
             state,
             max_trailing_headers_size,
+            set_cookies,
         } = read_metadata_async(&mut stream, max_headers_size, max_status_line_len).await?;
 
+        let has_body = !is_head && status_code != 204 && status_code != 304;
+        #[cfg(feature = "compression")]
+        let mut decoder =
+            if has_body { Decoder::from_headers(&mut headers, max_body_size) } else { None };
+
         let mut body = Vec::new();
-        if !is_head && status_code != 204 && status_code != 304 {
+        let mut push_byte = |(byte, length): (u8, usize)| -> Result<(), Error> {
+            #[cfg(feature = "compression")]
+            if let Some(decoder) = &mut decoder {
+                return decoder.push(byte, length);
+            }
+            if max_body_size.is_some_and(|max| body.len().saturating_add(length) > max) {
+                return Err(Error::BodyOverflow);
+            }
+            body.reserve(length);
+            body.push(byte);
+            Ok(())
+        };
+        if has_body {
             match state {
                 EndOnClose => {
                     while let Some(byte_result) = read_until_closed_async(&mut stream).await {
-                        let (byte, length) = byte_result?;
-                        if max_body_size.is_some_and(|max| body.len().saturating_add(length) > max)
-                        {
-                            return Err(Error::BodyOverflow);
-                        }
-                        body.reserve(length);
-                        body.push(byte);
+                        push_byte(byte_result?)?;
                     }
                 }
                 ContentLength(mut length) => {
                     while let Some(byte_result) =
                         read_with_content_length_async(&mut stream, &mut length).await
                     {
-                        let (byte, expected_length) = byte_result?;
-                        if max_body_size
-                            .is_some_and(|max| body.len().saturating_add(expected_length) > max)
-                        {
-                            return Err(Error::BodyOverflow);
-                        }
-                        body.reserve(expected_length);
-                        body.push(byte);
+                        push_byte(byte_result?)?;
                     }
                 }
                 Chunked(mut expecting_chunks, mut chunk_length, mut content_length) =>
@@ -137,18 +205,27 @@ impl Response {
                     )
                     .await
                     {
-                        let (byte, length) = byte_result?;
-                        if max_body_size.is_some_and(|max| body.len().saturating_add(length) > max)
-                        {
-                            return Err(Error::BodyOverflow);
-                        }
-                        body.reserve(length);
-                        body.push(byte);
+                        push_byte(byte_result?)?;
                     },
             }
         }
 
-        Ok(Response { status_code, reason_phrase, headers, url: String::new(), body })
+        #[cfg(feature = "compression")]
+        if let Some(mut decoder) = decoder {
+            decoder.finish()?;
+            headers.insert("content-length".to_string(), decoder.total_out().to_string());
+            body = decoder.into_output();
+        }
+
+        Ok(Response {
+            status_code,
+            reason_phrase,
+            headers,
+            url: String::new(),
+            redirects: Vec::new(),
+            body,
+            set_cookies,
+        })
     }
 
     /// Returns the body as an `&str`.
@@ -305,44 +382,98 @@ pub struct ResponseLazy {
     /// <http://example.com?foo=bar> would be corrected to
     /// <http://example.com/?foo=bar>).
     pub url: String,
+    /// The URLs the request was redirected from before reaching `url`,
+    /// starting with the URL of the request. Empty if no redirects were
+    /// followed.
+    pub redirects: Vec<String>,
 
     stream: HttpStreamBytes,
     state: HttpStreamState,
     max_trailing_headers_size: Option<usize>,
     max_body_size: Option<usize>,
     bytes_read: usize,
+    #[cfg(feature = "compression")]
+    decoder: Option<Decoder>,
+    pub(crate) set_cookies: Vec<String>,
 }
 
+/// An iterator over the bytes of a buffered [`HttpStream`].
+///
+/// This works like [`io::Bytes`], but allows getting the reader back once we're done with it, so
+/// that the connection can be reused.
 #[cfg(feature = "std")]
-type HttpStreamBytes = Bytes<BufReader<HttpStream>>;
+pub(crate) struct HttpStreamBytes(BufReader<HttpStream>);
+
+#[cfg(feature = "std")]
+impl HttpStreamBytes {
+    fn into_inner(self) -> BufReader<HttpStream> { self.0 }
+}
+
+#[cfg(feature = "std")]
+impl Iterator for HttpStreamBytes {
+    type Item = io::Result<u8>;
+
+    fn next(&mut self) -> Option<io::Result<u8>> {
+        loop {
+            return match self.0.fill_buf() {
+                Ok([]) => None,
+                Ok(buf) => {
+                    let byte = buf[0];
+                    self.0.consume(1);
+                    Some(Ok(byte))
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => Some(Err(e)),
+            };
+        }
+    }
+}
 
 #[cfg(feature = "std")]
 impl ResponseLazy {
     pub(crate) fn from_stream(
         stream: HttpStream,
+        is_head: bool,
         max_headers_size: Option<usize>,
         max_status_line_len: Option<usize>,
         max_body_size: Option<usize>,
     ) -> Result<ResponseLazy, Error> {
-        let mut stream = BufReader::with_capacity(BACKING_READ_BUFFER_LENGTH, stream).bytes();
+        let mut stream =
+            HttpStreamBytes(BufReader::with_capacity(BACKING_READ_BUFFER_LENGTH, stream));
+        #[allow(unused_mut)]
         let ResponseMetadata {
             status_code,
             reason_phrase,
-            headers,
-            state,
+            mut headers,
+            mut state,
             max_trailing_headers_size,
+            set_cookies,
         } = read_metadata(&mut stream, max_headers_size, max_status_line_len)?;
 
+        // Responses without a body may still describe one in their headers, which are kept as
+        // sent.
+        let has_body = !is_head && status_code != 204 && status_code != 304;
+        if !has_body {
+            state = HttpStreamState::ContentLength(0);
+        }
+        #[cfg(feature = "compression")]
+        let decoder =
+            if has_body { Decoder::from_headers(&mut headers, max_body_size) } else { None };
+
         Ok(ResponseLazy {
             status_code,
             reason_phrase,
             headers,
             url: String::new(),
+            redirects: Vec::new(),
             stream,
             state,
             max_trailing_headers_size,
             max_body_size,
             bytes_read: 0,
+            #[cfg(feature = "compression")]
+            decoder,
+            set_cookies,
         })
     }
 
@@ -354,12 +485,17 @@ impl ResponseLazy {
             reason_phrase: response.reason_phrase,
             headers: response.headers,
             url: response.url,
-            stream: BufReader::with_capacity(1, http_stream).bytes(),
+            redirects: response.redirects,
+            stream: HttpStreamBytes(BufReader::with_capacity(1, http_stream)),
             state: HttpStreamState::EndOnClose,
             max_trailing_headers_size: None,
             // Body was already fully loaded and size-checked by send_async
             max_body_size: None,
             bytes_read: 0,
+            // ...and decompressed, if needed
+            #[cfg(feature = "compression")]
+            decoder: None,
+            set_cookies: response.set_cookies,
         }
     }
 }
@@ -369,8 +505,31 @@ impl Iterator for ResponseLazy {
     type Item = Result<(u8, usize), Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        #[cfg(feature = "compression")]
+        if self.decoder.is_some() {
+            return self.next_decoded();
+        }
+
+        let result = self.next_raw();
+
+        // Check body size limit before returning the byte
+        if let Some(Ok((_, expected_length))) = &result {
+            if self.max_body_size.is_some_and(|max| self.bytes_read + expected_length > max) {
+                return Some(Err(Error::BodyOverflow));
+            }
+            self.bytes_read += 1;
+        }
+
+        result
+    }
+}
+
+#[cfg(feature = "std")]
+impl ResponseLazy {
+    /// Reads the next byte of the body as it was sent by the server.
+    fn next_raw(&mut self) -> Option<<Self as Iterator>::Item> {
         use HttpStreamState::*;
-        let result = match self.state {
+        match self.state {
             EndOnClose => read_until_closed(&mut self.stream),
             ContentLength(ref mut length) => read_with_content_length(&mut self.stream, length),
             Chunked(ref mut expecting_chunks, ref mut length, ref mut content_length) =>
@@ -382,17 +541,36 @@ impl Iterator for ResponseLazy {
                     content_length,
                     self.max_trailing_headers_size,
                 ),
-        };
+        }
+    }
 
-        // Check body size limit before returning the byte
-        if let Some(Ok((_, expected_length))) = &result {
-            if self.max_body_size.is_some_and(|max| self.bytes_read + expected_length > max) {
-                return Some(Err(Error::BodyOverflow));
+    /// Reads the next byte of the decompressed body, feeding the decoder with raw bytes as
+    /// needed. The decoder enforces the body size limit.
+    #[cfg(feature = "compression")]
+    fn next_decoded(&mut self) -> Option<<Self as Iterator>::Item> {
+        loop {
+            let decoder = self.decoder.as_mut()?;
+            if let Some(byte) = decoder.pop() {
+                return Some(Ok(byte));
+            }
+            if decoder.is_finished() {
+                return None;
+            }
+            let res = match self.next_raw() {
+                Some(Ok((byte, length))) => self.decoder.as_mut()?.push(byte, length),
+                Some(Err(err)) => Err(err),
+                None => {
+                    let decoder = self.decoder.as_mut()?;
+                    let res = decoder.finish();
+                    let length = decoder.total_out().to_string();
+                    self.headers.insert("content-length".to_string(), length);
+                    res
+                }
+            };
+            if let Err(err) = res {
+                return Some(Err(err));
             }
-            self.bytes_read += 1;
         }
-
-        result
     }
 }
 
@@ -405,6 +583,7 @@ impl Read for ResponseLazy {
             // so it is ignored.
             let (byte, _) = res.map_err(|e| match e {
                 Error::IoError(e) => e,
+                Error::ReadIdleTimeout => ReadIdleTimeoutReached::io_error(),
                 _ => io::Error::new(io::ErrorKind::Other, e),
             })?;
 
@@ -422,6 +601,393 @@ impl Read for ResponseLazy {
     }
 }
 
+/// An HTTP response whose body is streamed from the connection as it is read.
+///
+/// Returned by [`Request::send_streaming_async`](struct.Request.html#method.send_streaming_async).
+///
+/// Unlike [`ResponseLazy`], reading the body doesn't block: it can be read through the
+/// [`AsyncRead`] implementation, or chunk by chunk with [`ResponseStream::chunk`]. Chunked
+/// transfer encoding and compression are decoded, and
+/// [`max_body_size`](struct.Request.html#method.with_max_body_size) is enforced as the body is
+/// read. Errors other than I/O errors are returned by [`AsyncRead`] wrapped in an [`io::Error`]
+/// of kind [`Other`](io::ErrorKind::Other).
+///
+/// The response is read over a connection of its own, which is closed when the response is
+/// dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn run() -> Result<(), bitreq::Error> {
+/// let mut response = bitreq::get("http://example.com").send_streaming_async().await?;
+/// let mut length = 0;
+/// while let Some(chunk) = response.chunk().await? {
+///     length += chunk.len();
+/// }
+/// println!("{} bytes", length);
+/// # Ok(()) }
+/// ```
+#[cfg(feature = "async")]
+pub struct ResponseStream {
+    /// The status code of the response, eg. 404.
+    pub status_code: i32,
+    /// The reason phrase of the response, eg. "Not Found".
+    pub reason_phrase: String,
+    /// The headers of the response. The header field names (the
+    /// keys) are all lowercase.
+    ///
+    /// Trailers sent after a chunked body are added once the body has been read.
+    pub headers: BTreeMap<String, String>,
+    /// The URL of the resource returned in this response. May differ from the
+    /// request URL if it was redirected or typo corrections were applied (e.g.
+    /// <http://example.com?foo=bar> would be corrected to
+    /// <http://example.com/?foo=bar>).
+    pub url: String,
+    /// The URLs the request was redirected from before reaching `url`,
+    /// starting with the URL of the request. Empty if no redirects were
+    /// followed.
+    pub redirects: Vec<String>,
+
+    stream: Box<dyn AsyncBufRead + Send + Unpin>,
+    state: BodyStreamState,
+    /// The part of a line that has been read so far, while in a state reading lines.
+    line: Vec<u8>,
+    /// The total length of the chunks read so far, to set `Content-Length` once the body has been
+    /// read.
+    chunked_length: usize,
+    max_trailing_headers_size: Option<usize>,
+    max_body_size: Option<usize>,
+    bytes_read: usize,
+    timeout: Option<Pin<Box<tokio::time::Sleep>>>,
+    #[cfg(feature = "compression")]
+    decoder: Option<Decoder>,
+    pub(crate) set_cookies: Vec<String>,
+}
+
+/// Where a [`ResponseStream`] is in reading the body.
+#[cfg(feature = "async")]
+enum BodyStreamState {
+    /// Reading until the server closes the connection.
+    EndOnClose,
+    /// Reading the given number of remaining bytes.
+    ContentLength(usize),
+    /// Reading the line with the length of the next chunk.
+    ChunkLength,
+    /// Reading the data of a chunk, with the given number of bytes left.
+    ChunkData(usize),
+    /// Reading the `\r\n` after the data of a chunk.
+    ChunkEnd,
+    /// Reading the trailers after the last chunk.
+    Trailers,
+    /// The body has been read completely.
+    Done,
+}
+
+#[cfg(feature = "async")]
+impl ResponseStream {
+    pub(crate) async fn from_stream<R: AsyncBufRead + Send + Unpin + 'static>(
+        mut stream: R,
+        is_head: bool,
+        max_headers_size: Option<usize>,
+        max_status_line_len: Option<usize>,
+        max_body_size: Option<usize>,
+        timeout_at: Option<std::time::Instant>,
+    ) -> Result<ResponseStream, Error> {
+        #[allow(unused_mut)]
+        let ResponseMetadata {
+            status_code,
+            reason_phrase,
+            mut headers,
+            state,
+            max_trailing_headers_size,
+            set_cookies,
+        } = read_metadata_async(&mut stream, max_headers_size, max_status_line_len).await?;
+
+        let has_body = !is_head && status_code != 204 && status_code != 304;
+        #[cfg(feature = "compression")]
+        let decoder =
+            if has_body { Decoder::from_headers(&mut headers, max_body_size) } else { None };
+
+        let state = match state {
+            _ if !has_body => BodyStreamState::Done,
+            HttpStreamState::EndOnClose => BodyStreamState::EndOnClose,
+            HttpStreamState::ContentLength(length) => BodyStreamState::ContentLength(length),
+            HttpStreamState::Chunked(..) => BodyStreamState::ChunkLength,
+        };
+
+        Ok(ResponseStream {
+            status_code,
+            reason_phrase,
+            headers,
+            url: String::new(),
+            redirects: Vec::new(),
+            stream: Box::new(stream),
+            state,
+            line: Vec::new(),
+            chunked_length: 0,
+            max_trailing_headers_size,
+            max_body_size,
+            bytes_read: 0,
+            timeout: timeout_at.map(|at| Box::pin(tokio::time::sleep_until(at.into()))),
+            #[cfg(feature = "compression")]
+            decoder,
+            set_cookies,
+        })
+    }
+
+    /// Reads the next chunk of the body, returning `None` once the body has been read completely.
+    ///
+    /// The chunks are at most 16 KiB long, and don't necessarily match the chunks the server sent
+    /// the body in.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if we run into an error while reading or decoding the body, or if the body
+    /// is larger than [`max_body_size`](struct.Request.html#method.with_max_body_size).
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let mut chunk = vec![0; BACKING_READ_BUFFER_LENGTH];
+        let length = core::future::poll_fn(|cx| self.poll_body(cx, &mut chunk)).await?;
+        if length == 0 {
+            return Ok(None);
+        }
+        chunk.truncate(length);
+        Ok(Some(chunk))
+    }
+
+    /// Reads body bytes into `dst`, which must not be empty, returning 0 at the end of the body.
+    fn poll_body(&mut self, cx: &mut Context<'_>, dst: &mut [u8]) -> Poll<Result<usize, Error>> {
+        if let Some(timeout) = &mut self.timeout {
+            if timeout.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Err(Error::IoError(crate::connection::timeout_err())));
+            }
+        }
+
+        #[cfg(feature = "compression")]
+        if self.decoder.is_some() {
+            return self.poll_decoded(cx, dst);
+        }
+
+        let length = ready!(self.poll_raw(cx, dst))?;
+        self.bytes_read += length;
+        let expected = match self.state {
+            BodyStreamState::ContentLength(left) | BodyStreamState::ChunkData(left) => left,
+            _ => 0,
+        };
+        if self.max_body_size.is_some_and(|max| self.bytes_read.saturating_add(expected) > max) {
+            return Poll::Ready(Err(Error::BodyOverflow));
+        }
+        Poll::Ready(Ok(length))
+    }
+
+    /// Reads decompressed body bytes into `dst`, feeding the decoder with raw bytes as needed.
+    /// The decoder enforces the body size limit.
+    #[cfg(feature = "compression")]
+    fn poll_decoded(&mut self, cx: &mut Context<'_>, dst: &mut [u8]) -> Poll<Result<usize, Error>> {
+        let mut raw = [0; 4 * 1024];
+        loop {
+            let Some(decoder) = self.decoder.as_mut() else { return Poll::Ready(Ok(0)) };
+            let mut length = 0;
+            while length < dst.len() {
+                match decoder.pop() {
+                    Some((byte, _)) => dst[length] = byte,
+                    None => break,
+                }
+                length += 1;
+            }
+            if length > 0 || decoder.is_finished() {
+                return Poll::Ready(Ok(length));
+            }
+
+            let read = ready!(self.poll_raw(cx, &mut raw))?;
+            let Some(decoder) = self.decoder.as_mut() else { return Poll::Ready(Ok(0)) };
+            if read == 0 {
+                decoder.finish()?;
+                let length = decoder.total_out().to_string();
+                self.headers.insert("content-length".to_string(), length);
+            } else {
+                for (i, byte) in raw[..read].iter().enumerate() {
+                    decoder.push(*byte, read - i)?;
+                }
+            }
+        }
+    }
+
+    /// Reads the body as it was sent by the server into `dst`, returning 0 at the end of the
+    /// body.
+    fn poll_raw(&mut self, cx: &mut Context<'_>, dst: &mut [u8]) -> Poll<Result<usize, Error>> {
+        use BodyStreamState::*;
+        loop {
+            match self.state {
+                Done => return Poll::Ready(Ok(0)),
+                EndOnClose => {
+                    let length = ready!(self.poll_copy(cx, dst, usize::MAX))?;
+                    if length == 0 {
+                        self.state = Done;
+                    }
+                    return Poll::Ready(Ok(length));
+                }
+                ContentLength(0) => self.state = Done,
+                ContentLength(left) => {
+                    let length = ready!(self.poll_copy(cx, dst, left))?;
+                    if length == 0 {
+                        return Poll::Ready(Err(Error::IoError(
+                            io::ErrorKind::UnexpectedEof.into(),
+                        )));
+                    }
+                    self.state = ContentLength(left - length);
+                    return Poll::Ready(Ok(length));
+                }
+                ChunkLength => {
+                    // Max length of the chunk length line is 1KB, see `read_chunked`.
+                    let line = ready!(self.poll_line(cx, Some(1024), Error::MalformedChunkLength))?;
+                    let length = line.split(';').next().unwrap_or("").trim();
+                    let length = if length.is_empty() {
+                        0
+                    } else {
+                        usize::from_str_radix(length, 16)
+                            .map_err(|_| Error::MalformedChunkLength)?
+                    };
+                    if length == 0 {
+                        self.state = Trailers;
+                    } else {
+                        self.chunked_length = self.chunked_length.saturating_add(length);
+                        self.state = ChunkData(length);
+                    }
+                }
+                ChunkData(left) => {
+                    let length = ready!(self.poll_copy(cx, dst, left))?;
+                    if length == 0 {
+                        return Poll::Ready(Err(Error::IoError(
+                            io::ErrorKind::UnexpectedEof.into(),
+                        )));
+                    }
+                    self.state = if length == left { ChunkEnd } else { ChunkData(left - length) };
+                    return Poll::Ready(Ok(length));
+                }
+                ChunkEnd => {
+                    ready!(self.poll_line(cx, Some(2), Error::MalformedChunkEnd))?;
+                    self.state = ChunkLength;
+                }
+                Trailers => {
+                    let max_len = self.max_trailing_headers_size;
+                    let line = ready!(self.poll_line(cx, max_len, Error::HeadersOverflow))?;
+                    if let Some(max_headers_size) = &mut self.max_trailing_headers_size {
+                        *max_headers_size = max_headers_size.saturating_sub(line.len() + 2);
+                    }
+                    if let Some((header, value)) = parse_header(line) {
+                        self.headers.insert(header, value);
+                    } else {
+                        self.headers
+                            .insert("content-length".to_string(), self.chunked_length.to_string());
+                        self.headers.remove("transfer-encoding");
+                        self.state = Done;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copies at most `max` bytes from the connection into `dst`.
+    fn poll_copy(
+        &mut self,
+        cx: &mut Context<'_>,
+        dst: &mut [u8],
+        max: usize,
+    ) -> Poll<Result<usize, Error>> {
+        let buf = ready!(Pin::new(&mut self.stream).poll_fill_buf(cx))?;
+        let length = buf.len().min(dst.len()).min(max);
+        dst[..length].copy_from_slice(&buf[..length]);
+        Pin::new(&mut self.stream).consume(length);
+        Poll::Ready(Ok(length))
+    }
+
+    /// Reads a line, without the line break, keeping what has been read so far in `self.line`
+    /// while waiting for more data.
+    fn poll_line(
+        &mut self,
+        cx: &mut Context<'_>,
+        max_len: Option<usize>,
+        overflow_error: Error,
+    ) -> Poll<Result<String, Error>> {
+        loop {
+            let buf = ready!(Pin::new(&mut self.stream).poll_fill_buf(cx))?;
+            if buf.is_empty() {
+                return Poll::Ready(Err(Error::IoError(io::ErrorKind::UnexpectedEof.into())));
+            }
+            let (length, found_end) = match buf.iter().position(|byte| *byte == b'\n') {
+                Some(i) => (i, true),
+                None => (buf.len(), false),
+            };
+            self.line.extend_from_slice(&buf[..length]);
+            Pin::new(&mut self.stream).consume(length + usize::from(found_end));
+            if max_len.is_some_and(|max_len| self.line.len() >= max_len) {
+                return Poll::Ready(Err(overflow_error));
+            }
+            if found_end {
+                let mut line = core::mem::take(&mut self.line);
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                let line = String::from_utf8(line).map_err(|_| Error::InvalidUtf8InResponse)?;
+                return Poll::Ready(Ok(line));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncRead for ResponseStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        let length =
+            ready!(self.poll_body(cx, buf.initialize_unfilled())).map_err(|e| match e {
+                Error::IoError(e) => e,
+                Error::ReadIdleTimeout => ReadIdleTimeoutReached::io_error(),
+                _ => io::Error::new(io::ErrorKind::Other, e),
+            })?;
+        buf.advance(length);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The status code and headers of the response to an `Upgrade` request.
+#[cfg(feature = "websocket")]
+pub(crate) struct UpgradeResponse {
+    pub(crate) status_code: i32,
+    pub(crate) headers: BTreeMap<String, String>,
+}
+
+/// Reads the status line and headers of the response to an `Upgrade` request, handing back the
+/// stream positioned where the data of the protocol the connection switched to starts.
+#[cfg(feature = "websocket")]
+pub(crate) fn read_upgrade_head(
+    stream: HttpStream,
+    max_headers_size: Option<usize>,
+    max_status_line_len: Option<usize>,
+) -> Result<(UpgradeResponse, BufReader<HttpStream>), Error> {
+    let mut stream = HttpStreamBytes(BufReader::with_capacity(BACKING_READ_BUFFER_LENGTH, stream));
+    let metadata = read_metadata(&mut stream, max_headers_size, max_status_line_len)?;
+    let response = UpgradeResponse { status_code: metadata.status_code, headers: metadata.headers };
+    Ok((response, stream.into_inner()))
+}
+
+/// Like [`read_upgrade_head`], for an async stream.
+#[cfg(all(feature = "websocket", feature = "async"))]
+pub(crate) async fn read_upgrade_head_async<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    max_headers_size: Option<usize>,
+    max_status_line_len: Option<usize>,
+) -> Result<UpgradeResponse, Error> {
+    let metadata = read_metadata_async(stream, max_headers_size, max_status_line_len).await?;
+    Ok(UpgradeResponse { status_code: metadata.status_code, headers: metadata.headers })
+}
+
 #[cfg(feature = "std")]
 enum HttpStreamState {
     // No Content-Length, and Transfer-Encoding != chunked, so we just
@@ -449,6 +1015,7 @@ struct ResponseMetadata {
     headers: BTreeMap<String, String>,
     state: HttpStreamState,
     max_trailing_headers_size: Option<usize>,
+    set_cookies: Vec<String>,
 }
 
 macro_rules! maybe_await {
@@ -479,7 +1046,7 @@ macro_rules! define_read_methods {
             if let Some(byte) = maybe_await!(bytes.next(), $($await)?) {
                 match byte {
                     Ok(byte) => Some(Ok((byte, 1))),
-                    Err(err) => Some(Err(Error::IoError(err))),
+                    Err(err) => Some(Err(Error::from(err))),
                 }
             } else {
                 None
@@ -497,7 +1064,7 @@ macro_rules! define_read_methods {
                     match byte {
                         // Cap Content-Length to 16KiB, to avoid out-of-memory issues.
                         Ok(byte) => return Some(Ok((byte, (*content_length).min(MAX_CONTENT_LENGTH) + 1))),
-                        Err(err) => return Some(Err(Error::IoError(err))),
+                        Err(err) => return Some(Err(Error::from(err))),
                     }
                 }
             }
@@ -597,7 +1164,7 @@ macro_rules! define_read_methods {
 
                             return Some(Ok((byte, (*chunk_length).min(MAX_CONTENT_LENGTH) + 1)));
                         }
-                        Err(err) => return Some(Err(Error::IoError(err))),
+                        Err(err) => return Some(Err(Error::from(err))),
                     }
                 }
             }
@@ -615,6 +1182,7 @@ macro_rules! define_read_methods {
             let (status_code, reason_phrase) = parse_status_line(&line);
 
             let mut headers = BTreeMap::new();
+            let mut set_cookies = Vec::new();
             loop {
                 let line = maybe_await!($read_line(stream, max_headers_size, Error::HeadersOverflow), $($await)?)?;
                 if line.is_empty() {
@@ -625,6 +1193,9 @@ macro_rules! define_read_methods {
                     *max_headers_size -= line.len() + 2;
                 }
                 if let Some(header) = parse_header(line) {
+                    if header.0 == "set-cookie" {
+                        set_cookies.push(header.1.clone());
+                    }
                     headers.insert(header.0, header.1);
                 }
             }
@@ -662,6 +1233,7 @@ macro_rules! define_read_methods {
                 headers,
                 state,
                 max_trailing_headers_size: max_headers_size,
+                set_cookies,
             })
         }
 
@@ -689,7 +1261,7 @@ macro_rules! define_read_methods {
                             bytes.push(byte);
                         }
                     }
-                    Err(err) => return Err(Error::IoError(err)),
+                    Err(err) => return Err(Error::from(err)),
                 }
             }
             String::from_utf8(bytes).map_err(|_error| Error::InvalidUtf8InResponse)
@@ -755,3 +1327,48 @@ fn parse_header(mut line: String) -> Option<(String, String)> {
     }
     None
 }
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+    use super::*;
+
+    async fn stream(response: &'static [u8], max_body_size: Option<usize>) -> ResponseStream {
+        // A tiny buffer makes lines and chunks span several reads.
+        let stream = tokio::io::BufReader::with_capacity(3, response);
+        ResponseStream::from_stream(stream, false, None, None, max_body_size, None).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn streams_chunked_body_with_trailers() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nX-Trailer: yes\r\n\r\n";
+        let mut response = stream(response, None).await;
+        let mut body = String::new();
+        response.read_to_string(&mut body).await.unwrap();
+        assert_eq!(body, "hello, world");
+        assert_eq!(response.headers["x-trailer"], "yes");
+        assert_eq!(response.headers["content-length"], "12");
+        assert!(!response.headers.contains_key("transfer-encoding"));
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_or_truncated_bodies() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+        let res = stream(response, None).await.chunk().await;
+        assert!(matches!(res, Err(Error::MalformedChunkLength)));
+
+        let response =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nabc\r\n0\r\n\r\n";
+        let res = stream(response, None).await.read_to_end(&mut Vec::new()).await;
+        assert!(res.unwrap_err().to_string().contains("chunk did not end"));
+
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello";
+        let res = stream(response, None).await.read_to_end(&mut Vec::new()).await;
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        // The limit is checked against the announced length before reading the body.
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello";
+        let res = stream(response, Some(9)).await.chunk().await;
+        assert!(matches!(res, Err(Error::BodyOverflow)));
+    }
+}
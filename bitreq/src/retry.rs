@@ -0,0 +1,402 @@
+//! Retrying requests which failed transiently, see [`RetryPolicy`].
+
+use core::fmt;
+#[cfg(feature = "async")]
+use core::future::Future;
+use core::time::Duration;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::request::{Method, ParsedRequest};
+#[cfg(feature = "async")]
+use crate::ResponseStream;
+use crate::{Error, Response, ResponseLazy};
+
+type ErrorFilter = Arc<dyn Fn(&Error) -> bool + Send + Sync>;
+
+/// A response whose status code can be checked against a [`RetryPolicy`].
+pub(crate) trait RetryableResponse {
+    fn status_code(&self) -> i32;
+}
+
+impl RetryableResponse for Response {
+    fn status_code(&self) -> i32 { self.status_code }
+}
+
+impl RetryableResponse for ResponseLazy {
+    fn status_code(&self) -> i32 { self.status_code }
+}
+
+#[cfg(feature = "async")]
+impl RetryableResponse for ResponseStream {
+    fn status_code(&self) -> i32 { self.status_code }
+}
+
+/// Decides whether, and after how long, a failed request is sent again.
+///
+/// A request is retried if it failed with an error which looks transient (a
+/// refused, reset or timed out connection, by default), or if the server
+/// answered with one of the [retryable status
+/// codes](RetryPolicy::with_retry_status_codes) (`429`, `502`, `503` and
+/// `504` by default). The delay between attempts doubles each time, starting
+/// from 100 milliseconds and up to 10 seconds by default, and is randomly
+/// shortened by up to half to avoid many clients retrying at once.
+///
+/// Requests whose method isn't idempotent (such as `POST`) are only retried
+/// if [`with_retry_non_idempotent`](RetryPolicy::with_retry_non_idempotent) is
+/// set, as the server may have acted on the first attempt. Retries never go
+/// past the request's [timeout](crate::Request::with_timeout): if the next
+/// attempt couldn't start before it, the last error or response is returned.
+/// Requests with a body streamed from a reader (see
+/// [`Request::with_body_reader`](crate::Request::with_body_reader)) are never
+/// retried, as the body can only be read once.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use std::time::Duration;
+///
+/// use bitreq::RetryPolicy;
+///
+/// let retry = RetryPolicy::new()
+///     .with_max_attempts(5)
+///     .with_backoff(Duration::from_millis(200), Duration::from_secs(2));
+/// let response = bitreq::get("http://example.com")
+///     .with_timeout(Duration::from_secs(30))
+///     .with_retry(retry)
+///     .send()?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    status_codes: Vec<i32>,
+    error_filter: Option<ErrorFilter>,
+    retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            jitter: true,
+            status_codes: vec![429, 502, 503, 504],
+            error_filter: None,
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("jitter", &self.jitter)
+            .field("status_codes", &self.status_codes)
+            .field("custom_error_filter", &self.error_filter.is_some())
+            .field("retry_non_idempotent", &self.retry_non_idempotent)
+            .finish()
+    }
+}
+
+impl PartialEq for RetryPolicy {
+    fn eq(&self, other: &Self) -> bool {
+        let same_filter = match (&self.error_filter, &other.error_filter) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        same_filter
+            && self.max_attempts == other.max_attempts
+            && self.initial_backoff == other.initial_backoff
+            && self.max_backoff == other.max_backoff
+            && self.jitter == other.jitter
+            && self.status_codes == other.status_codes
+            && self.retry_non_idempotent == other.retry_non_idempotent
+    }
+}
+
+impl Eq for RetryPolicy {}
+
+impl RetryPolicy {
+    /// Creates the default policy: up to 3 attempts, with exponential backoff
+    /// starting at 100 milliseconds.
+    pub fn new() -> RetryPolicy { RetryPolicy::default() }
+
+    /// Sets the maximum number of times the request is sent, including the
+    /// first attempt. `1` disables retrying.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> RetryPolicy {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sets the delay before the first retry, which doubles for each further
+    /// retry up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> RetryPolicy {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Sets whether the delays are randomly shortened by up to half. Defaults
+    /// to `true`.
+    pub fn with_jitter(mut self, jitter: bool) -> RetryPolicy {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the status codes of responses which are retried, replacing the
+    /// default ones. When no attempts are left, the last response is
+    /// returned as is.
+    pub fn with_retry_status_codes<I: IntoIterator<Item = i32>>(mut self, codes: I) -> RetryPolicy {
+        self.status_codes = codes.into_iter().collect();
+        self
+    }
+
+    /// Sets which errors are retried, replacing the default check for
+    /// connection failures, resets and timeouts.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use bitreq::{Error, RetryPolicy};
+    ///
+    /// // Also retry if the host name couldn't be resolved.
+    /// let retry = RetryPolicy::new().with_retry_on_error(|err| {
+    ///     matches!(err, Error::AddressNotFound) || RetryPolicy::is_transient_error(err)
+    /// });
+    /// ```
+    pub fn with_retry_on_error<F>(mut self, filter: F) -> RetryPolicy
+    where
+        F: Fn(&Error) -> bool + Send + Sync + 'static,
+    {
+        self.error_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Sets whether requests with methods which aren't idempotent, such as
+    /// `POST`, `PATCH` and `CONNECT`, are retried. Defaults to `false`.
+    pub fn with_retry_non_idempotent(mut self, retry_non_idempotent: bool) -> RetryPolicy {
+        self.retry_non_idempotent = retry_non_idempotent;
+        self
+    }
+
+    /// Returns whether `err` is retried by default: I/O errors caused by a
+    /// refused, reset, aborted or timed out connection, or by the server
+    /// closing the connection early, and reaching the connect or read idle
+    /// timeout.
+    pub fn is_transient_error(err: &Error) -> bool {
+        match err {
+            Error::ConnectTimeout | Error::ReadIdleTimeout => true,
+            Error::IoError(err) => matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::NotConnected
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::Interrupted
+                    | io::ErrorKind::UnexpectedEof
+            ),
+            _ => false,
+        }
+    }
+
+    fn retries_method(&self, method: &Method) -> bool {
+        self.retry_non_idempotent || method.is_idempotent()
+    }
+
+    fn retries_error(&self, err: &Error) -> bool {
+        match &self.error_filter {
+            Some(filter) => filter(err),
+            None => RetryPolicy::is_transient_error(err),
+        }
+    }
+
+    /// Returns the delay to wait before retrying after the given number of failed `attempts`, or
+    /// `None` if the outcome of the last attempt should be returned.
+    fn next_delay<R: RetryableResponse>(
+        &self,
+        request: &ParsedRequest,
+        attempts: u32,
+        result: &Result<R, Error>,
+    ) -> Option<Duration> {
+        if attempts >= self.max_attempts || !self.retries_method(&request.config.method) {
+            return None;
+        }
+        let retry = match result {
+            Ok(response) => self.status_codes.contains(&response.status_code()),
+            Err(err) => self.retries_error(err),
+        };
+        if !retry {
+            return None;
+        }
+
+        let exponent = attempts.saturating_sub(1).min(31);
+        let mut delay = self.initial_backoff.saturating_mul(1 << exponent).min(self.max_backoff);
+        if self.jitter {
+            delay -= delay.mul_f64(random_fraction() / 2.0);
+        }
+        match request.timeout_at {
+            Some(timeout_at) if Instant::now() + delay >= timeout_at => None,
+            _ => Some(delay),
+        }
+    }
+}
+
+/// Returns a random number in `[0, 1)`, good enough to spread out retries.
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(Instant::now().elapsed().as_nanos());
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Sends `request` with `send`, retrying according to its [`RetryPolicy`], if any.
+pub(crate) fn send_with_retry<R, F>(request: ParsedRequest, mut send: F) -> Result<R, Error>
+where
+    R: RetryableResponse,
+    F: FnMut(ParsedRequest) -> Result<R, Error>,
+{
+    let policy = match request.config.retry.clone() {
+        Some(policy) if !request.config.has_body_stream() => policy,
+        _ => return send(request),
+    };
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let result = send(request.clone());
+        match policy.next_delay(&request, attempts, &result) {
+            Some(delay) => {
+                #[cfg(feature = "log")]
+                log::debug!("Retrying request to {} in {:?}.", request.url.base_url(), delay);
+                std::thread::sleep(delay);
+            }
+            None => return result,
+        }
+    }
+}
+
+/// Sends `request` with `send`, retrying according to its [`RetryPolicy`], if any.
+#[cfg(feature = "async")]
+pub(crate) async fn send_with_retry_async<R, F, Fut>(
+    request: ParsedRequest,
+    mut send: F,
+) -> Result<R, Error>
+where
+    R: RetryableResponse,
+    F: FnMut(ParsedRequest) -> Fut,
+    Fut: Future<Output = Result<R, Error>>,
+{
+    let policy = match request.config.retry.clone() {
+        Some(policy) if !request.config.has_body_stream() => policy,
+        _ => return send(request).await,
+    };
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let result = send(request.clone()).await;
+        match policy.next_delay(&request, attempts, &result) {
+            Some(delay) => {
+                #[cfg(feature = "log")]
+                log::debug!("Retrying request to {} in {:?}.", request.url.base_url(), delay);
+                tokio::time::sleep(delay).await;
+            }
+            None => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Request;
+
+    fn parsed(request: Request) -> ParsedRequest { ParsedRequest::new(request).unwrap() }
+
+    impl RetryableResponse for i32 {
+        fn status_code(&self) -> i32 { *self }
+    }
+
+    fn reset() -> Result<i32, Error> {
+        Err(Error::IoError(io::Error::new(io::ErrorKind::ConnectionReset, "reset")))
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let policy = RetryPolicy::new()
+            .with_max_attempts(10)
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(350))
+            .with_jitter(false);
+        let request = parsed(crate::get("http://localhost"));
+        let delays: Vec<_> =
+            (1..10).map(|n| policy.next_delay(&request, n, &reset()).unwrap()).collect();
+        assert_eq!(delays[0], Duration::from_millis(100));
+        assert_eq!(delays[1], Duration::from_millis(200));
+        assert_eq!(delays[2], Duration::from_millis(350));
+        assert_eq!(delays[8], Duration::from_millis(350));
+        assert_eq!(policy.next_delay(&request, 10, &reset()), None);
+    }
+
+    #[test]
+    fn jitter_shortens_delay_by_up_to_half() {
+        let policy =
+            RetryPolicy::new().with_backoff(Duration::from_secs(1), Duration::from_secs(1));
+        let request = parsed(crate::get("http://localhost"));
+        for _ in 0..100 {
+            let delay = policy.next_delay(&request, 1, &reset()).unwrap();
+            assert!(delay > Duration::from_millis(500) && delay <= Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn retries_statuses_and_errors() {
+        let policy = RetryPolicy::new();
+        let request = parsed(crate::get("http://localhost"));
+        assert!(policy.next_delay(&request, 1, &Ok(503)).is_some());
+        assert!(policy.next_delay(&request, 1, &Ok(500)).is_none());
+        assert!(policy.next_delay(&request, 1, &Ok(200)).is_none());
+        assert!(policy.next_delay(&request, 1, &Err::<i32, _>(Error::BodyOverflow)).is_none());
+
+        let policy = policy
+            .with_retry_status_codes([500])
+            .with_retry_on_error(|err| matches!(err, Error::BodyOverflow));
+        assert!(policy.next_delay(&request, 1, &Ok(500)).is_some());
+        assert!(policy.next_delay(&request, 1, &Ok(503)).is_none());
+        assert!(policy.next_delay(&request, 1, &Err::<i32, _>(Error::BodyOverflow)).is_some());
+        assert!(policy.next_delay(&request, 1, &reset()).is_none());
+    }
+
+    #[test]
+    fn retries_non_idempotent_methods_only_if_enabled() {
+        let policy = RetryPolicy::new();
+        let post = parsed(crate::post("http://localhost"));
+        assert!(policy.next_delay(&post, 1, &reset()).is_none());
+        let put = parsed(crate::put("http://localhost"));
+        assert!(policy.next_delay(&put, 1, &reset()).is_some());
+
+        let policy = policy.with_retry_non_idempotent(true);
+        assert!(policy.next_delay(&post, 1, &reset()).is_some());
+    }
+
+    #[test]
+    fn stops_retrying_at_timeout() {
+        let policy =
+            RetryPolicy::new().with_backoff(Duration::from_secs(5), Duration::from_secs(5));
+        let request = parsed(crate::get("http://localhost").with_timeout(Duration::from_secs(1)));
+        assert!(policy.next_delay(&request, 1, &reset()).is_none());
+        let request = parsed(crate::get("http://localhost").with_timeout(Duration::from_secs(60)));
+        assert!(policy.next_delay(&request, 1, &reset()).is_some());
+    }
+}
@@ -0,0 +1,463 @@
+//! Reading [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html)
+//! streams, see [`EventSource`].
+
+use core::time::Duration;
+use core::{fmt, mem};
+use std::collections::BTreeMap;
+
+#[cfg(feature = "async")]
+use crate::ResponseStream;
+use crate::{Error, Request, ResponseLazy};
+
+/// How long to wait before reconnecting, unless set with
+/// [`EventSource::with_retry_delay`] or by the server.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(3);
+
+/// An event received from a Server-Sent Events stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Event {
+    /// The type of the event, set by its `event` field, or `message` if it
+    /// has none.
+    pub event: String,
+    /// The `data` fields of the event, joined by newlines.
+    pub data: String,
+    /// The last event ID set by the server, by this event or an earlier
+    /// one, if any.
+    pub id: Option<String>,
+}
+
+/// Parses the event stream format, byte by byte.
+#[derive(Debug)]
+struct Parser {
+    line: Vec<u8>,
+    /// Whether the last line ended with a `\r`, in which case a `\n` right after it belongs to
+    /// the same line break.
+    after_cr: bool,
+    /// Whether a line was read from the current stream yet, as the first one may start with a
+    /// byte order mark.
+    in_stream: bool,
+    event: String,
+    /// The `data` fields of the pending event, each followed by a newline.
+    data: String,
+    /// The last event ID, which outlives the stream it was set by.
+    last_event_id: Option<String>,
+    /// The reconnection delay, which the server may change.
+    retry_delay: Duration,
+}
+
+impl Parser {
+    fn new() -> Parser {
+        Parser {
+            line: Vec::new(),
+            after_cr: false,
+            in_stream: false,
+            event: String::new(),
+            data: String::new(),
+            last_event_id: None,
+            retry_delay: DEFAULT_RETRY_DELAY,
+        }
+    }
+
+    /// Gets ready to parse a new stream, dropping any partially received event.
+    fn reset(&mut self) {
+        self.line.clear();
+        self.after_cr = false;
+        self.in_stream = false;
+        self.event.clear();
+        self.data.clear();
+    }
+
+    /// Parses the next byte of the stream, returning the event it completes, if any.
+    fn push(&mut self, byte: u8) -> Option<Event> {
+        let after_cr = mem::replace(&mut self.after_cr, byte == b'\r');
+        match byte {
+            b'\n' if after_cr => None,
+            b'\r' | b'\n' => {
+                let line = mem::take(&mut self.line);
+                let event = self.process_line(&line);
+                // Keep the line's allocation around for the next one.
+                self.line = line;
+                self.line.clear();
+                event
+            }
+            _ => {
+                self.line.push(byte);
+                None
+            }
+        }
+    }
+
+    fn process_line(&mut self, line: &[u8]) -> Option<Event> {
+        let line = String::from_utf8_lossy(line);
+        let mut line = &*line;
+        if !mem::replace(&mut self.in_stream, true) {
+            line = line.strip_prefix('\u{feff}').unwrap_or(line);
+        }
+
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            // A comment, which servers send to keep the connection alive.
+            "" => {}
+            "event" => self.event = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') =>
+                self.last_event_id = Some(value.to_string()).filter(|id| !id.is_empty()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) =>
+                if let Ok(millis) = value.parse() {
+                    self.retry_delay = Duration::from_millis(millis);
+                },
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let event = mem::take(&mut self.event);
+        let mut data = mem::take(&mut self.data);
+        if data.is_empty() {
+            return None;
+        }
+        data.pop();
+        let event = if event.is_empty() { "message".to_string() } else { event };
+        Some(Event { event, data, id: self.last_event_id.clone() })
+    }
+}
+
+/// The state shared by [`EventSource`] and [`AsyncEventSource`].
+#[derive(Debug)]
+struct Source {
+    request: Request,
+    parser: Parser,
+    /// Whether we connected before, so that connecting again has to wait for the retry delay.
+    connected: bool,
+    /// Whether the server told us to stop, or isn't serving an event stream at all.
+    closed: bool,
+}
+
+impl Source {
+    fn new(request: Request) -> Source {
+        Source { request, parser: Parser::new(), connected: false, closed: false }
+    }
+
+    /// Returns how long to wait before connecting, and the request to connect with.
+    fn connect(&mut self) -> (Option<Duration>, Request) {
+        let delay = Some(self.parser.retry_delay).filter(|_| self.connected);
+        self.connected = true;
+
+        let mut request = self.request.clone();
+        if request.header("Accept").is_none() {
+            request = request.with_header("Accept", "text/event-stream");
+        }
+        if request.header("Cache-Control").is_none() {
+            request = request.with_header("Cache-Control", "no-cache");
+        }
+        if let Some(id) = &self.parser.last_event_id {
+            request = request.with_header("Last-Event-ID", id);
+        }
+        (delay, request)
+    }
+
+    /// Checks the head of a response to a connection attempt, returning `Ok(false)` if the server
+    /// told us to stop reconnecting.
+    fn accept(
+        &mut self,
+        status_code: i32,
+        headers: &BTreeMap<String, String>,
+    ) -> Result<bool, Error> {
+        if status_code == 204 {
+            self.closed = true;
+            return Ok(false);
+        }
+        let is_event_stream = headers.get("content-type").is_some_and(|content_type| {
+            let mime = content_type.split(';').next().unwrap_or_default();
+            mime.trim().eq_ignore_ascii_case("text/event-stream")
+        });
+        if status_code != 200 || !is_event_stream {
+            self.closed = true;
+            return Err(Error::NotAnEventStream(status_code));
+        }
+        self.parser.reset();
+        Ok(true)
+    }
+}
+
+/// Reads the events of a [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html)
+/// stream, as an iterator.
+///
+/// The request is sent when the first event is asked for, with an
+/// `Accept: text/event-stream` header unless it sets one already. When the
+/// stream ends, or reading it fails, the request is sent again after the
+/// retry delay, with a `Last-Event-ID` header so that the server can resume
+/// after the last event received. The server can change the retry delay
+/// with a `retry` field.
+///
+/// Errors are returned by the iterator as they happen, and the next call to
+/// [`next`](Iterator::next) tries to reconnect, so stop iterating to give
+/// up. The iterator ends if the server answers with `204 No Content`, and
+/// after returning [`Error::NotAnEventStream`] if it answers with another
+/// status code than 200 or a body which isn't an event stream.
+///
+/// As the stream may stay open for as long as the server likes, use
+/// [`Request::with_read_idle_timeout`] rather than
+/// [`Request::with_timeout`] to detect dead connections.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use bitreq::EventSource;
+///
+/// for event in EventSource::new(bitreq::get("http://localhost:3000/events")) {
+///     let event = event?;
+///     println!("{}: {}", event.event, event.data);
+/// }
+/// # Ok(()) }
+/// ```
+pub struct EventSource {
+    source: Source,
+    response: Option<ResponseLazy>,
+}
+
+impl EventSource {
+    /// Creates an event source reading the events sent in response to
+    /// `request`.
+    pub fn new(request: Request) -> EventSource {
+        EventSource { source: Source::new(request), response: None }
+    }
+
+    /// Sets how long to wait before reconnecting, until the server sets
+    /// it. Defaults to 3 seconds.
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.source.parser.retry_delay = delay;
+        self
+    }
+
+    /// Sets the ID of the last event received, for example in an earlier
+    /// run, to resume the stream after it.
+    pub fn with_last_event_id<T: Into<String>>(mut self, id: T) -> Self {
+        self.source.parser.last_event_id = Some(id.into());
+        self
+    }
+
+    /// Returns the ID of the last event received, which is sent to the
+    /// server when reconnecting.
+    pub fn last_event_id(&self) -> Option<&str> { self.source.parser.last_event_id.as_deref() }
+
+    /// Returns how long to wait before reconnecting.
+    pub fn retry_delay(&self) -> Duration { self.source.parser.retry_delay }
+
+    fn connect(&mut self) -> Result<Option<ResponseLazy>, Error> {
+        let (delay, request) = self.source.connect();
+        if let Some(delay) = delay {
+            std::thread::sleep(delay);
+        }
+        let response = request.send_lazy()?;
+        let accepted = self.source.accept(response.status_code, &response.headers)?;
+        Ok(Some(response).filter(|_| accepted))
+    }
+}
+
+impl fmt::Debug for EventSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventSource").field("source", &self.source).finish_non_exhaustive()
+    }
+}
+
+impl Iterator for EventSource {
+    type Item = Result<Event, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.source.closed {
+                return None;
+            }
+            let response = match &mut self.response {
+                Some(response) => response,
+                None => match self.connect() {
+                    Ok(Some(response)) => self.response.insert(response),
+                    Ok(None) => return None,
+                    Err(e) => return Some(Err(e)),
+                },
+            };
+            match response.next() {
+                Some(Ok((byte, _))) =>
+                    if let Some(event) = self.source.parser.push(byte) {
+                        return Some(Ok(event));
+                    },
+                Some(Err(e)) => {
+                    self.response = None;
+                    return Some(Err(e));
+                }
+                None => self.response = None,
+            }
+        }
+    }
+}
+
+/// Reads the events of a [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html)
+/// stream asynchronously.
+///
+/// Like [`EventSource`], with events read by
+/// [`next_event`](AsyncEventSource::next_event) rather than an iterator.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn run() -> Result<(), bitreq::Error> {
+/// use bitreq::AsyncEventSource;
+///
+/// let mut events = AsyncEventSource::new(bitreq::get("http://localhost:3000/events"));
+/// while let Some(event) = events.next_event().await {
+///     let event = event?;
+///     println!("{}: {}", event.event, event.data);
+/// }
+/// # Ok(()) }
+/// ```
+#[cfg(feature = "async")]
+pub struct AsyncEventSource {
+    source: Source,
+    response: Option<ResponseStream>,
+    /// The last chunk of the body read, and how much of it was parsed.
+    chunk: Vec<u8>,
+    parsed: usize,
+}
+
+#[cfg(feature = "async")]
+impl AsyncEventSource {
+    /// Creates an event source reading the events sent in response to
+    /// `request`.
+    pub fn new(request: Request) -> AsyncEventSource {
+        AsyncEventSource {
+            source: Source::new(request),
+            response: None,
+            chunk: Vec::new(),
+            parsed: 0,
+        }
+    }
+
+    /// Sets how long to wait before reconnecting, until the server sets
+    /// it. Defaults to 3 seconds.
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.source.parser.retry_delay = delay;
+        self
+    }
+
+    /// Sets the ID of the last event received, for example in an earlier
+    /// run, to resume the stream after it.
+    pub fn with_last_event_id<T: Into<String>>(mut self, id: T) -> Self {
+        self.source.parser.last_event_id = Some(id.into());
+        self
+    }
+
+    /// Returns the ID of the last event received, which is sent to the
+    /// server when reconnecting.
+    pub fn last_event_id(&self) -> Option<&str> { self.source.parser.last_event_id.as_deref() }
+
+    /// Returns how long to wait before reconnecting.
+    pub fn retry_delay(&self) -> Duration { self.source.parser.retry_delay }
+
+    /// Waits for the next event, returning `None` once the server told us
+    /// to stop reconnecting.
+    ///
+    /// Errors are returned as they happen, and the next call tries to
+    /// reconnect, like [`EventSource`] does.
+    pub async fn next_event(&mut self) -> Option<Result<Event, Error>> {
+        loop {
+            if self.source.closed {
+                return None;
+            }
+            while self.parsed < self.chunk.len() {
+                let byte = self.chunk[self.parsed];
+                self.parsed += 1;
+                if let Some(event) = self.source.parser.push(byte) {
+                    return Some(Ok(event));
+                }
+            }
+            let response = match &mut self.response {
+                Some(response) => response,
+                None => match self.connect().await {
+                    Ok(Some(response)) => self.response.insert(response),
+                    Ok(None) => return None,
+                    Err(e) => return Some(Err(e)),
+                },
+            };
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    self.chunk = chunk;
+                    self.parsed = 0;
+                }
+                Ok(None) => self.response = None,
+                Err(e) => {
+                    self.response = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+
+    async fn connect(&mut self) -> Result<Option<ResponseStream>, Error> {
+        let (delay, request) = self.source.connect();
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
+        let response = request.send_streaming_async().await?;
+        let accepted = self.source.accept(response.status_code, &response.headers)?;
+        Ok(Some(response).filter(|_| accepted))
+    }
+}
+
+#[cfg(feature = "async")]
+impl fmt::Debug for AsyncEventSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsyncEventSource").field("source", &self.source).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(stream: &str) -> (Vec<Event>, Parser) {
+        let mut parser = Parser::new();
+        let events = stream.bytes().filter_map(|byte| parser.push(byte)).collect();
+        (events, parser)
+    }
+
+    fn event(event: &str, data: &str, id: Option<&str>) -> Event {
+        Event { event: event.to_string(), data: data.to_string(), id: id.map(str::to_string) }
+    }
+
+    #[test]
+    fn parses_events() {
+        let (events, parser) = parse(
+            "\u{feff}: keep-alive\n\ndata: a\ndata:b\n\nevent: block\r\nid: 7\r\ndata: {}\r\n\r\n\
+             id\rdata\r\rretry: 250\nretry: soon\n\ndata: partial",
+        );
+        assert_eq!(
+            events,
+            [
+                event("message", "a\nb", None),
+                event("block", "{}", Some("7")),
+                event("message", "", None),
+            ]
+        );
+        assert_eq!(parser.retry_delay, Duration::from_millis(250));
+    }
+
+    #[test]
+    fn skips_events_without_data() {
+        let (events, parser) = parse("event: ping\nid: 3\n\ndata: x\n\n");
+        assert_eq!(events, [event("message", "x", Some("3"))]);
+        assert_eq!(parser.last_event_id.as_deref(), Some("3"));
+    }
+}
@@ -516,7 +516,7 @@ impl core::fmt::Display for Url {
 }
 
 /// Returns the `%HH` triplet representing `byte` for percent encoding.
-fn percent_encoded_triplet(byte: u8) -> [char; 3] {
+pub(crate) fn percent_encoded_triplet(byte: u8) -> [char; 3] {
     const HEX: &[u8; 16] = b"0123456789ABCDEF";
     ['%', HEX[(byte >> 4) as usize] as char, HEX[(byte & 0x0F) as usize] as char]
 }
@@ -542,7 +542,7 @@ fn percent_encode_char(c: char, result: &mut String) {
 }
 
 /// Percent-encodes the entire input string and returns the encoded version.
-fn percent_encode_string(input: &str) -> String {
+pub(crate) fn percent_encode_string(input: &str) -> String {
     let mut encoded = String::with_capacity(input.len());
     for ch in input.chars() {
         percent_encode_char(ch, &mut encoded);
@@ -0,0 +1,619 @@
+//! A WebSocket (RFC 6455) client, see [`WebSocket`].
+
+use core::fmt;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufReader, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::engine::Engine;
+use bitcoin_hashes::{sha1, Hash};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+
+#[cfg(feature = "async")]
+use crate::connection::{AsyncConnection, AsyncHttpStream, IdleTimeoutReader};
+use crate::connection::{Connection, HttpStream, Timeouts};
+use crate::request::ParsedRequest;
+use crate::response::UpgradeResponse;
+use crate::{Error, Request};
+
+/// The GUID the server appends to our key to compute its `Sec-WebSocket-Accept` header.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// The status code of a close frame sent when closing normally.
+const CLOSE_NORMAL: u16 = 1000;
+
+/// A message sent or received over a [`WebSocket`] or an [`AsyncWebSocket`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// A text message.
+    Text(String),
+    /// A binary message.
+    Binary(Vec<u8>),
+    /// A ping, which is answered with a pong automatically when received.
+    Ping(Vec<u8>),
+    /// A pong, the answer to a ping.
+    Pong(Vec<u8>),
+    /// A request to close the connection, with the status code and reason
+    /// for closing it, if any. Receiving one answers it automatically.
+    Close(Option<CloseFrame>),
+}
+
+/// The status code and reason of a [`Message::Close`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CloseFrame {
+    /// The status code, eg. 1000 for a normal closure.
+    pub code: u16,
+    /// Why the connection is being closed.
+    pub reason: String,
+}
+
+impl Message {
+    fn opcode_and_payload(&self) -> (u8, Vec<u8>) {
+        match self {
+            Message::Text(text) => (OPCODE_TEXT, text.as_bytes().to_vec()),
+            Message::Binary(bytes) => (OPCODE_BINARY, bytes.clone()),
+            Message::Ping(bytes) => (OPCODE_PING, bytes.clone()),
+            Message::Pong(bytes) => (OPCODE_PONG, bytes.clone()),
+            Message::Close(None) => (OPCODE_CLOSE, Vec::new()),
+            Message::Close(Some(frame)) => {
+                let mut payload = frame.code.to_be_bytes().to_vec();
+                payload.extend_from_slice(frame.reason.as_bytes());
+                (OPCODE_CLOSE, payload)
+            }
+        }
+    }
+}
+
+/// Returns `N` bytes which can't be predicted by the server, for our key and the masking keys of
+/// our frames. These don't need to be cryptographically secure, as masking only prevents the
+/// payloads from being read as requests by proxies along the way.
+fn random_bytes<const N: usize>() -> [u8; N] {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut bytes = [0; N];
+    for chunk in bytes.chunks_mut(8) {
+        // Each `RandomState` is seeded differently.
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        hasher.write_u128(now.as_nanos());
+        chunk.copy_from_slice(&hasher.finish().to_le_bytes()[..chunk.len()]);
+    }
+    bytes
+}
+
+/// Adds the headers of the opening handshake to `request`, returning it along with the
+/// `Sec-WebSocket-Accept` header the server has to answer with.
+fn handshake_request(request: Request) -> Result<(ParsedRequest, String), Error> {
+    let key = STANDARD.encode(random_bytes::<16>());
+    let accept = accept_key(&key);
+    let request = request
+        .with_header("Upgrade", "websocket")
+        .with_header("Connection", "Upgrade")
+        .with_header("Sec-WebSocket-Key", key)
+        .with_header("Sec-WebSocket-Version", "13");
+    Ok((ParsedRequest::new(request)?, accept))
+}
+
+/// Returns the `Sec-WebSocket-Accept` header answering a `Sec-WebSocket-Key` header.
+fn accept_key(key: &str) -> String {
+    let digest = sha1::Hash::hash(format!("{}{}", key, ACCEPT_GUID).as_bytes());
+    STANDARD.encode(digest.to_byte_array())
+}
+
+/// Checks that the server agreed to switch to the WebSocket protocol.
+fn check_handshake(response: &UpgradeResponse, accept: &str) -> Result<(), Error> {
+    let has_token = |name: &str, token: &str| {
+        response
+            .headers
+            .get(name)
+            .is_some_and(|value| value.split(',').any(|v| v.trim().eq_ignore_ascii_case(token)))
+    };
+    if response.status_code != 101
+        || !has_token("upgrade", "websocket")
+        || !has_token("connection", "upgrade")
+    {
+        return Err(Error::WebSocketHandshakeFailed(response.status_code));
+    }
+    if response.headers.get("sec-websocket-accept").map(|value| value.trim()) != Some(accept) {
+        return Err(Error::WebSocketProtocol("invalid Sec-WebSocket-Accept header"));
+    }
+    Ok(())
+}
+
+/// Encodes a message in a single masked frame.
+fn encode_frame(message: &Message) -> Vec<u8> {
+    let (opcode, mut payload) = message.opcode_and_payload();
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len @ 0..=125 => frame.push(0x80 | len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    let mask = random_bytes::<4>();
+    frame.extend_from_slice(&mask);
+    apply_mask(&mut payload, mask);
+    frame.extend_from_slice(&payload);
+    frame
+}
+
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+/// The first two bytes of a frame.
+struct FrameHeader {
+    fin: bool,
+    opcode: u8,
+    /// The 7 bit payload length, 126 and 127 meaning that it follows in the next 2 or 8 bytes.
+    length: u8,
+}
+
+impl FrameHeader {
+    fn parse(bytes: [u8; 2]) -> Result<FrameHeader, Error> {
+        let header = FrameHeader {
+            fin: bytes[0] & 0x80 != 0,
+            opcode: bytes[0] & 0x0F,
+            length: bytes[1] & 0x7F,
+        };
+        if bytes[0] & 0x70 != 0 {
+            return Err(Error::WebSocketProtocol("reserved bits set without an extension"));
+        }
+        if bytes[1] & 0x80 != 0 {
+            return Err(Error::WebSocketProtocol("masked frame from the server"));
+        }
+        if header.opcode >= OPCODE_CLOSE && (!header.fin || header.length > 125) {
+            return Err(Error::WebSocketProtocol("fragmented or oversized control frame"));
+        }
+        Ok(header)
+    }
+}
+
+/// Reads a frame from `$reader`, returning its header and payload.
+macro_rules! read_frame {
+    ($reader: expr, $session: expr $(, $await: tt)?) => {{
+        let mut bytes = [0; 2];
+        $reader.read_exact(&mut bytes)$(.$await)??;
+        let header = FrameHeader::parse(bytes)?;
+        let length = match header.length {
+            126 => {
+                let mut length = [0; 2];
+                $reader.read_exact(&mut length)$(.$await)??;
+                u64::from(u16::from_be_bytes(length))
+            }
+            127 => {
+                let mut length = [0; 8];
+                $reader.read_exact(&mut length)$(.$await)??;
+                u64::from_be_bytes(length)
+            }
+            length => u64::from(length),
+        };
+        $session.check_length(&header, length)?;
+        // Grow the payload as it arrives, rather than allocating as much as the peer announced
+        // up front.
+        let mut payload = Vec::new();
+        (&mut $reader).take(length).read_to_end(&mut payload)$(.$await)??;
+        if (payload.len() as u64) < length {
+            return Err(Error::IoError(io::ErrorKind::UnexpectedEof.into()));
+        }
+        (header, payload)
+    }};
+}
+
+/// The state of a connection shared by [`WebSocket`] and [`AsyncWebSocket`]: the message being
+/// reassembled from fragments and the closing handshake.
+#[derive(Debug)]
+struct Session {
+    /// The opcode and payload received so far of a fragmented message.
+    fragments: Option<(u8, Vec<u8>)>,
+    max_message_size: Option<usize>,
+    close_sent: bool,
+    close_received: bool,
+    /// A frame to send in answer to the last frame received, i.e. a pong or a close frame.
+    answer: Option<Vec<u8>>,
+}
+
+impl Session {
+    fn new(max_message_size: Option<usize>) -> Session {
+        Session {
+            fragments: None,
+            max_message_size,
+            close_sent: false,
+            close_received: false,
+            answer: None,
+        }
+    }
+
+    /// Checks that a frame with a payload of `length` bytes won't make the message exceed the
+    /// maximum size, before reading the payload.
+    fn check_length(&self, header: &FrameHeader, length: u64) -> Result<(), Error> {
+        let received = match &self.fragments {
+            Some((_, payload)) if header.opcode == OPCODE_CONTINUATION => payload.len() as u64,
+            _ => 0,
+        };
+        match self.max_message_size {
+            Some(max) if received.saturating_add(length) > max as u64 => Err(Error::BodyOverflow),
+            _ if length > usize::MAX as u64 => Err(Error::BodyOverflow),
+            _ => Ok(()),
+        }
+    }
+
+    /// Encodes a message to send, failing if the connection is being closed.
+    fn outgoing(&mut self, message: &Message) -> Result<Vec<u8>, Error> {
+        if self.close_sent {
+            return Err(Error::WebSocketClosed);
+        }
+        self.close_sent = matches!(message, Message::Close(_));
+        Ok(encode_frame(message))
+    }
+
+    /// Processes a received frame, returning the message it completes, if any, and setting the
+    /// frame to answer it with, if any.
+    fn incoming(
+        &mut self,
+        header: FrameHeader,
+        payload: Vec<u8>,
+    ) -> Result<Option<Message>, Error> {
+        let (opcode, payload) = match header.opcode {
+            OPCODE_PING => {
+                if !self.close_sent {
+                    self.answer = Some(encode_frame(&Message::Pong(payload.clone())));
+                }
+                return Ok(Some(Message::Ping(payload)));
+            }
+            OPCODE_PONG => return Ok(Some(Message::Pong(payload))),
+            OPCODE_CLOSE => {
+                self.close_received = true;
+                let frame = parse_close(payload)?;
+                // Echo the status code, as the closing handshake asks for.
+                let code = frame.as_ref().map(|frame| frame.code);
+                let answer =
+                    Message::Close(code.map(|code| CloseFrame { code, reason: String::new() }));
+                self.answer = self.outgoing(&answer).ok();
+                return Ok(Some(Message::Close(frame)));
+            }
+            OPCODE_TEXT | OPCODE_BINARY if self.fragments.is_some() =>
+                return Err(Error::WebSocketProtocol("new message before the end of the last one")),
+            OPCODE_TEXT | OPCODE_BINARY if !header.fin => {
+                self.fragments = Some((header.opcode, payload));
+                return Ok(None);
+            }
+            OPCODE_TEXT | OPCODE_BINARY => (header.opcode, payload),
+            OPCODE_CONTINUATION => {
+                let (opcode, mut received) = self
+                    .fragments
+                    .take()
+                    .ok_or(Error::WebSocketProtocol("continuation frame without a message"))?;
+                received.extend_from_slice(&payload);
+                if !header.fin {
+                    self.fragments = Some((opcode, received));
+                    return Ok(None);
+                }
+                (opcode, received)
+            }
+            _ => return Err(Error::WebSocketProtocol("unknown opcode")),
+        };
+        let message = if opcode == OPCODE_TEXT {
+            Message::Text(String::from_utf8(payload).map_err(|_| Error::InvalidUtf8InResponse)?)
+        } else {
+            Message::Binary(payload)
+        };
+        Ok(Some(message))
+    }
+}
+
+fn parse_close(payload: Vec<u8>) -> Result<Option<CloseFrame>, Error> {
+    match payload.len() {
+        0 => Ok(None),
+        1 => Err(Error::WebSocketProtocol("close frame with a truncated status code")),
+        _ => {
+            let code = u16::from_be_bytes([payload[0], payload[1]]);
+            let reason = String::from_utf8(payload[2..].to_vec())
+                .map_err(|_| Error::InvalidUtf8InResponse)?;
+            Ok(Some(CloseFrame { code, reason }))
+        }
+    }
+}
+
+/// A WebSocket connection, opened with an `Upgrade: websocket` request.
+///
+/// Messages are sent in a single frame, and fragmented messages from the
+/// server are reassembled, up to the
+/// [maximum body size](Request::with_max_body_size) of the request. Pings
+/// are answered automatically, as is the server closing the connection,
+/// after which [`read`](WebSocket::read) fails with
+/// [`Error::WebSocketClosed`].
+///
+/// The request is sent over a connection of its own, which can go through
+/// a proxy, use TLS for `wss://` (or `https://`) URLs, or a Unix domain
+/// socket. Redirects aren't followed. The timeout of the request only
+/// applies to the opening handshake, while its read idle timeout applies to
+/// every read.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> Result<(), bitreq::Error> {
+/// use bitreq::{Message, WebSocket};
+///
+/// let mut socket = WebSocket::connect(bitreq::get("wss://mempool.space/api/v1/ws"))?;
+/// socket.send(Message::Text(r#"{"action":"want","data":["blocks"]}"#.to_string()))?;
+/// while let Message::Text(text) = socket.read()? {
+///     println!("{}", text);
+/// }
+/// socket.close(None)?;
+/// # Ok(()) }
+/// ```
+pub struct WebSocket {
+    stream: BufReader<HttpStream>,
+    session: Session,
+}
+
+impl fmt::Debug for WebSocket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WebSocket").field("session", &self.session).finish_non_exhaustive()
+    }
+}
+
+impl WebSocket {
+    /// Opens a WebSocket connection by sending `request` with the headers
+    /// of the opening handshake.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WebSocketHandshakeFailed`] if the server doesn't
+    /// switch to the WebSocket protocol, and otherwise the same errors as
+    /// [`Request::send`].
+    pub fn connect(request: Request) -> Result<WebSocket, Error> {
+        let (request, accept) = handshake_request(request)?;
+        let connection = Connection::new(request.connection_params(), request.timeouts())?;
+        let (response, mut stream) = connection.upgrade(&request)?;
+        check_handshake(&response, &accept)?;
+
+        let read_idle = request.config.read_idle_timeout;
+        stream.get_mut().set_timeouts(Timeouts { read_idle, ..Timeouts::default() });
+        Ok(WebSocket { stream, session: Session::new(request.config.max_body_size) })
+    }
+
+    /// Sends `message`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WebSocketClosed`] if a [`Message::Close`] was
+    /// already sent.
+    pub fn send(&mut self, message: Message) -> Result<(), Error> {
+        let frame = self.session.outgoing(&message)?;
+        self.write(&frame)
+    }
+
+    /// Waits for the next message from the server.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WebSocketClosed`] once a [`Message::Close`] was
+    /// received, and [`Error::WebSocketProtocol`] if the server breaks the
+    /// protocol.
+    pub fn read(&mut self) -> Result<Message, Error> {
+        loop {
+            if self.session.close_received {
+                return Err(Error::WebSocketClosed);
+            }
+            let (header, payload) = read_frame!(self.stream, self.session);
+            let message = self.session.incoming(header, payload)?;
+            if let Some(answer) = self.session.answer.take() {
+                self.write(&answer)?;
+            }
+            if let Some(message) = message {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Closes the connection, sending a [`Message::Close`] with `frame`, or
+    /// a normal closure status code if `None`, and waiting for the server to
+    /// answer it. Messages received in the meantime are dropped.
+    pub fn close(mut self, frame: Option<CloseFrame>) -> Result<(), Error> {
+        if !self.session.close_sent {
+            let frame = frame.unwrap_or(CloseFrame { code: CLOSE_NORMAL, reason: String::new() });
+            self.send(Message::Close(Some(frame)))?;
+        }
+        loop {
+            match self.read() {
+                Ok(Message::Close(_)) | Err(Error::WebSocketClosed) => return Ok(()),
+                Ok(_) => {}
+                // The server may close the connection right after its close frame.
+                Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                    return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let stream = self.stream.get_mut();
+        stream.write_all(frame)?;
+        stream.flush()?;
+        Ok(())
+    }
+}
+
+/// A WebSocket connection, like [`WebSocket`], used asynchronously.
+#[cfg(feature = "async")]
+pub struct AsyncWebSocket {
+    read: tokio::io::BufReader<ReadHalf<AsyncHttpStream>>,
+    write: WriteHalf<AsyncHttpStream>,
+    read_idle_timeout: Option<core::time::Duration>,
+    session: Session,
+}
+
+#[cfg(feature = "async")]
+impl fmt::Debug for AsyncWebSocket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsyncWebSocket").field("session", &self.session).finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncWebSocket {
+    /// Opens a WebSocket connection asynchronously, see
+    /// [`WebSocket::connect`].
+    pub async fn connect(request: Request) -> Result<AsyncWebSocket, Error> {
+        let (request, accept) = handshake_request(request)?;
+        let (response, (read, write)) = AsyncConnection::upgrade(&request).await?;
+        check_handshake(&response, &accept)?;
+        Ok(AsyncWebSocket {
+            read,
+            write,
+            read_idle_timeout: request.config.read_idle_timeout,
+            session: Session::new(request.config.max_body_size),
+        })
+    }
+
+    /// Sends `message`, see [`WebSocket::send`].
+    pub async fn send(&mut self, message: Message) -> Result<(), Error> {
+        let frame = self.session.outgoing(&message)?;
+        self.write(&frame).await
+    }
+
+    /// Waits for the next message from the server, see [`WebSocket::read`].
+    pub async fn read(&mut self) -> Result<Message, Error> {
+        loop {
+            if self.session.close_received {
+                return Err(Error::WebSocketClosed);
+            }
+            let mut reader = IdleTimeoutReader::new(&mut self.read, self.read_idle_timeout);
+            let (header, payload) = read_frame!(reader, self.session, await);
+            let message = self.session.incoming(header, payload)?;
+            if let Some(answer) = self.session.answer.take() {
+                self.write(&answer).await?;
+            }
+            if let Some(message) = message {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Closes the connection, see [`WebSocket::close`].
+    pub async fn close(mut self, frame: Option<CloseFrame>) -> Result<(), Error> {
+        if !self.session.close_sent {
+            let frame = frame.unwrap_or(CloseFrame { code: CLOSE_NORMAL, reason: String::new() });
+            self.send(Message::Close(Some(frame))).await?;
+        }
+        loop {
+            match self.read().await {
+                Ok(Message::Close(_)) | Err(Error::WebSocketClosed) => return Ok(()),
+                Ok(_) => {}
+                Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                    return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        self.write.write_all(frame).await?;
+        self.write.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits an encoded frame into its header and its unmasked payload.
+    fn decode(frame: &[u8]) -> (u8, Vec<u8>) {
+        let (length, offset) = match frame[1] & 0x7F {
+            126 => (u16::from_be_bytes([frame[2], frame[3]]) as usize, 4),
+            127 => (u64::from_be_bytes(frame[2..10].try_into().unwrap()) as usize, 10),
+            length => (length as usize, 2),
+        };
+        let mask = frame[offset..offset + 4].try_into().unwrap();
+        let mut payload = frame[offset + 4..].to_vec();
+        assert_eq!(payload.len(), length);
+        apply_mask(&mut payload, mask);
+        (frame[0], payload)
+    }
+
+    fn header(byte: u8, length: u8) -> FrameHeader { FrameHeader::parse([byte, length]).unwrap() }
+
+    #[test]
+    fn computes_accept_key() {
+        // The example of RFC 6455 section 1.3.
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn encodes_masked_frames() {
+        let frame = encode_frame(&Message::Text("hello".to_string()));
+        assert_eq!(frame[1], 0x80 | 5);
+        assert_eq!(decode(&frame), (0x81, b"hello".to_vec()));
+
+        for length in [126, 0x10000] {
+            let frame = encode_frame(&Message::Binary(vec![7; length]));
+            assert_eq!(decode(&frame), (0x82, vec![7; length]));
+        }
+
+        let close = Message::Close(Some(CloseFrame { code: 1000, reason: "bye".to_string() }));
+        assert_eq!(decode(&encode_frame(&close)), (0x88, b"\x03\xe8bye".to_vec()));
+    }
+
+    #[test]
+    fn rejects_invalid_frame_headers() {
+        assert!(FrameHeader::parse([0x81, 0x85]).is_err()); // Masked
+        assert!(FrameHeader::parse([0xC1, 0x05]).is_err()); // Reserved bit
+        assert!(FrameHeader::parse([0x09, 0x05]).is_err()); // Fragmented ping
+        assert!(FrameHeader::parse([0x89, 126]).is_err()); // Oversized ping
+    }
+
+    #[test]
+    fn reassembles_fragments() {
+        let mut session = Session::new(Some(10));
+        assert!(session.incoming(header(0x01, 3), b"hel".to_vec()).unwrap().is_none());
+        // Control frames may come between fragments.
+        let ping = session.incoming(header(0x89, 1), b"p".to_vec()).unwrap();
+        assert_eq!(ping, Some(Message::Ping(b"p".to_vec())));
+        assert_eq!(decode(&session.answer.take().unwrap()), (0x8A, b"p".to_vec()));
+        assert!(session.incoming(header(0x00, 1), b"l".to_vec()).unwrap().is_none());
+        let message = session.incoming(header(0x80, 1), b"o".to_vec()).unwrap();
+        assert_eq!(message, Some(Message::Text("hello".to_string())));
+
+        assert!(session.incoming(header(0x80, 1), b"o".to_vec()).is_err());
+        session.incoming(header(0x02, 6), b"123456".to_vec()).unwrap();
+        assert!(matches!(session.check_length(&header(0x80, 5), 5), Err(Error::BodyOverflow)));
+        assert!(session.incoming(header(0x81, 1), b"x".to_vec()).is_err());
+    }
+
+    #[test]
+    fn answers_close_frames() {
+        let mut session = Session::new(None);
+        let message = session.incoming(header(0x88, 4), b"\x03\xe9go".to_vec()).unwrap();
+        let frame = CloseFrame { code: 1001, reason: "go".to_string() };
+        assert_eq!(message, Some(Message::Close(Some(frame))));
+        assert_eq!(decode(&session.answer.take().unwrap()), (0x88, b"\x03\xe9".to_vec()));
+        assert!(matches!(
+            session.outgoing(&Message::Ping(Vec::new())),
+            Err(Error::WebSocketClosed)
+        ));
+        assert!(parse_close(vec![3]).is_err());
+    }
+}
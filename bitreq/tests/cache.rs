@@ -0,0 +1,226 @@
+#![cfg(feature = "std")]
+
+extern crate bitreq;
+
+mod setup;
+
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use bitreq::{CacheStorage, Client, CookieJar, MemoryCache, Url};
+
+use self::setup::{header, read_head, spawn_raw_server};
+
+/// The path and `If-None-Match` header of each request received by the server.
+type Requests = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+/// Spawns a server answering `/etag` with a body identified by an `ETag`, or `304 Not Modified`
+/// if the request has a matching `If-None-Match` header, `/fresh` with a body fresh for a minute
+/// ending with the query of the request, `/private` with a body fresh for a minute which is
+/// specific to the user, `/vary` with a body fresh for a minute varying on the request header
+/// named by the query, and `/no-store` with a body which must not be cached.
+/// Returns the server's address and the requests it received.
+fn spawn_server() -> (SocketAddr, Requests) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let requests = Arc::clone(&received);
+    let addr = spawn_raw_server(move |_, mut sock| {
+        let head = read_head(&sock).unwrap_or_default();
+        let path = head.split(' ').nth(1).unwrap_or_default();
+        let if_none_match = header(&head, "If-None-Match");
+        let (route, query) = path.split_once('?').unwrap_or((path, ""));
+        let owned;
+        let response = match route {
+            "/etag" if if_none_match == Some("\"v1\"") =>
+                "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nX-Revalidated: yes\r\n\
+                 Connection: close\r\n\r\n",
+            "/etag" =>
+                "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 5\r\n\
+                 Connection: close\r\n\r\nhello",
+            "/fresh" => {
+                owned = format!(
+                    "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\nfresh{}",
+                    5 + query.len(),
+                    query
+                );
+                &owned
+            }
+            "/private" =>
+                "HTTP/1.1 200 OK\r\nCache-Control: private, max-age=60\r\n\
+                 Content-Length: 4\r\nConnection: close\r\n\r\nmine",
+            "/vary" => {
+                owned = format!(
+                    "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nVary: {}\r\n\
+                     Content-Length: 6\r\nConnection: close\r\n\r\nvaries",
+                    query
+                );
+                &owned
+            }
+            _ =>
+                "HTTP/1.1 200 OK\r\nCache-Control: no-store\r\nETag: \"v1\"\r\n\
+                 Content-Length: 6\r\nConnection: close\r\n\r\nsecret",
+        };
+        requests.lock().unwrap().push((path.to_string(), if_none_match.map(String::from)));
+        let _ = sock.write_all(response.as_bytes());
+    });
+    (addr, received)
+}
+
+#[test]
+fn cache_revalidates_with_etag() {
+    let (addr, requests) = spawn_server();
+    let client = Client::new(1).with_cache(MemoryCache::new(10));
+    let url = format!("http://{}/etag", addr);
+
+    let response = client.send(bitreq::get(&url)).unwrap();
+    assert_eq!(response.as_str().unwrap(), "hello");
+    assert_eq!(response.headers.get("x-revalidated"), None);
+
+    let response = client.send(bitreq::get(&url)).unwrap();
+    assert_eq!(response.status_code, 200);
+    assert_eq!(response.as_str().unwrap(), "hello");
+    assert_eq!(response.headers.get("x-revalidated").unwrap(), "yes");
+
+    // Conditional requests are left to the caller.
+    let response = client.send(bitreq::get(&url).with_header("If-None-Match", "\"v1\"")).unwrap();
+    assert_eq!(response.status_code, 304);
+
+    let expected = [None, Some("\"v1\""), Some("\"v1\"")];
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.iter().map(|(_, etag)| etag.as_deref()).collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn cache_answers_fresh_responses() {
+    let (addr, requests) = spawn_server();
+    let storage = Arc::new(MemoryCache::new(10));
+    let client = Client::new(1).with_cache(Arc::clone(&storage));
+    let url = format!("http://{}/fresh", addr);
+
+    for _ in 0..3 {
+        let response = client.send(bitreq::get(&url)).unwrap();
+        assert_eq!(response.as_str().unwrap(), "fresh");
+    }
+    assert_eq!(requests.lock().unwrap().len(), 1);
+    assert!(storage.get(&url).is_some());
+
+    // The request can ask for the response to be revalidated, or not to use the cache at all.
+    client.send(bitreq::get(&url).with_header("Cache-Control", "no-cache")).unwrap();
+    client.send(bitreq::get(&url).with_header("Cache-Control", "no-store")).unwrap();
+    client.send(bitreq::post(&url)).unwrap();
+    assert_eq!(requests.lock().unwrap().len(), 4);
+}
+
+#[test]
+fn cache_keys_include_params() {
+    let (addr, requests) = spawn_server();
+    let storage = Arc::new(MemoryCache::new(10));
+    let client = Client::new(1).with_cache(Arc::clone(&storage));
+    let url = format!("http://{}/fresh", addr);
+
+    for _ in 0..2 {
+        for height in ["1", "2"] {
+            let response = client.send(bitreq::get(&url).with_param("height", height)).unwrap();
+            assert_eq!(response.as_str().unwrap(), format!("freshheight={}", height));
+        }
+    }
+    assert_eq!(requests.lock().unwrap().len(), 2);
+    assert!(storage.get(&format!("{}?height=1", url)).is_some());
+    assert!(storage.get(&url).is_none());
+}
+
+#[test]
+fn cache_skips_authorized_requests() {
+    let (addr, requests) = spawn_server();
+    let storage = Arc::new(MemoryCache::new(10));
+    let client = Client::new(1).with_cache(Arc::clone(&storage));
+    let url = format!("http://{}/fresh", addr);
+
+    for user in ["dXNlcjE6cGFzcw==", "dXNlcjI6cGFzcw=="] {
+        let request = bitreq::get(&url).with_header("Authorization", format!("Basic {}", user));
+        assert_eq!(client.send(request).unwrap().as_str().unwrap(), "fresh");
+    }
+    assert_eq!(requests.lock().unwrap().len(), 2);
+    assert!(storage.get(&url).is_none());
+}
+
+#[test]
+fn cache_skips_requests_with_cookies() {
+    let (addr, requests) = spawn_server();
+    let storage = Arc::new(MemoryCache::new(10));
+    let jar = CookieJar::new();
+    let client = Client::new(1).with_cache(Arc::clone(&storage)).with_cookie_jar(jar.clone());
+    let url = format!("http://{}/fresh", addr);
+
+    for _ in 0..2 {
+        let request = bitreq::get(&url).with_header("Cookie", "session=abc");
+        assert_eq!(client.send(request).unwrap().as_str().unwrap(), "fresh");
+    }
+    assert_eq!(requests.lock().unwrap().len(), 2);
+
+    // Cookies from the jar of the client apply too.
+    jar.set_cookie(&Url::parse(&url).unwrap(), "session=abc");
+    for _ in 0..2 {
+        assert_eq!(client.send(bitreq::get(&url)).unwrap().as_str().unwrap(), "fresh");
+    }
+    assert_eq!(requests.lock().unwrap().len(), 4);
+    assert!(storage.get(&url).is_none());
+}
+
+#[test]
+fn cache_skips_private_and_varying_responses() {
+    let (addr, requests) = spawn_server();
+    let storage = Arc::new(MemoryCache::new(10));
+    let client = Client::new(1).with_cache(Arc::clone(&storage));
+
+    for path in ["/private", "/vary?Cookie"] {
+        let url = format!("http://{}{}", addr, path);
+        for _ in 0..2 {
+            client.send(bitreq::get(&url)).unwrap();
+        }
+        assert!(storage.get(&url).is_none());
+    }
+    assert_eq!(requests.lock().unwrap().len(), 4);
+
+    // Responses only varying on the encoding of their body are cached.
+    let url = format!("http://{}/vary?Accept-Encoding", addr);
+    for _ in 0..2 {
+        assert_eq!(client.send(bitreq::get(&url)).unwrap().as_str().unwrap(), "varies");
+    }
+    assert_eq!(requests.lock().unwrap().len(), 5);
+    assert!(storage.get(&url).is_some());
+}
+
+#[test]
+fn cache_skips_no_store_responses() {
+    let (addr, requests) = spawn_server();
+    let storage = Arc::new(MemoryCache::new(10));
+    let client = Client::new(1).with_cache(Arc::clone(&storage));
+    let url = format!("http://{}/no-store", addr);
+
+    for _ in 0..2 {
+        let response = client.send(bitreq::get(&url)).unwrap();
+        assert_eq!(response.as_str().unwrap(), "secret");
+    }
+    assert!(storage.get(&url).is_none());
+    assert_eq!(
+        *requests.lock().unwrap(),
+        [("/no-store".to_string(), None), ("/no-store".into(), None)]
+    );
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_cache_revalidates_with_etag() {
+    let (addr, requests) = spawn_server();
+    let client = Client::new(1).with_cache(MemoryCache::new(10));
+    let url = format!("http://{}/etag", addr);
+
+    for _ in 0..2 {
+        let response = client.send_async(bitreq::get(&url)).await.unwrap();
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.as_str().unwrap(), "hello");
+    }
+    assert_eq!(requests.lock().unwrap()[1].1.as_deref(), Some("\"v1\""));
+}
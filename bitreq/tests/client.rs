@@ -0,0 +1,256 @@
+#![cfg(feature = "std")]
+
+extern crate bitreq;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use bitreq::{Client, PoolStats, RequestExt};
+
+const KEEP_ALIVE: &[u8] =
+    b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: keep-alive\r\n\r\nok";
+
+/// Spawns a TCP server that replies to every request with `response`, closing each connection
+/// after `requests_per_connection` requests. Returns the bound address and a counter of accepted
+/// connections.
+fn spawn_server(
+    response: &'static [u8],
+    requests_per_connection: usize,
+) -> (SocketAddr, Arc<AtomicUsize>) {
+    spawn_slow_server(response, requests_per_connection, Duration::ZERO)
+}
+
+/// Like [`spawn_server`], waiting for `delay` before answering each request.
+fn spawn_slow_server(
+    response: &'static [u8],
+    requests_per_connection: usize,
+    delay: Duration,
+) -> (SocketAddr, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = Arc::clone(&connections);
+    thread::spawn(move || {
+        for sock in listener.incoming() {
+            let sock = match sock {
+                Ok(sock) => sock,
+                Err(_) => return,
+            };
+            accepted.fetch_add(1, Ordering::SeqCst);
+            thread::spawn(move || serve(sock, response, requests_per_connection, delay));
+        }
+    });
+    (addr, connections)
+}
+
+fn serve(mut sock: TcpStream, response: &[u8], requests_per_connection: usize, delay: Duration) {
+    let mut buf = [0u8; 4096];
+    let mut acc: Vec<u8> = Vec::new();
+    let mut served = 0;
+    while served < requests_per_connection {
+        let n = match sock.read(&mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+        acc.extend_from_slice(&buf[..n]);
+        while let Some(end) = find_double_crlf(&acc) {
+            acc.drain(..end);
+            thread::sleep(delay);
+            if sock.write_all(response).is_err() {
+                return;
+            }
+            served += 1;
+        }
+    }
+}
+
+fn find_double_crlf(buf: &[u8]) -> Option<usize> {
+    buf.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4)
+}
+
+#[test]
+fn sync_client_reuses_keep_alive_connection() {
+    let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: keep-alive\r\n\r\nok";
+    let (addr, connections) = spawn_server(response, usize::MAX);
+    let url = format!("http://{}/", addr);
+
+    let client = bitreq::Client::new(10);
+    for _ in 0..5 {
+        let response = bitreq::get(&url).send_with_client(&client).unwrap();
+        assert_eq!(response.as_str().unwrap(), "ok");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn sync_client_honours_connection_close() {
+    let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+    let (addr, connections) = spawn_server(response, usize::MAX);
+    let url = format!("http://{}/", addr);
+
+    let client = bitreq::Client::new(10);
+    for _ in 0..3 {
+        let response = client.send(bitreq::get(&url)).unwrap();
+        assert_eq!(response.as_str().unwrap(), "ok");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn sync_client_honours_keep_alive_max() {
+    let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: keep-alive\r\n\
+        Keep-Alive: timeout=30, max=2\r\n\r\nok";
+    let (addr, connections) = spawn_server(response, usize::MAX);
+    let url = format!("http://{}/", addr);
+
+    let client = bitreq::Client::new(10);
+    for _ in 0..4 {
+        let response = client.send(bitreq::get(&url)).unwrap();
+        assert_eq!(response.as_str().unwrap(), "ok");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn sync_client_retries_closed_connection() {
+    // The server promises to keep the connection alive, but closes it after every response, so
+    // each cached connection is dead by the time the next request goes out.
+    let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: keep-alive\r\n\r\nok";
+    let (addr, connections) = spawn_server(response, 1);
+    let url = format!("http://{}/", addr);
+
+    let client = bitreq::Client::new(10);
+    for _ in 0..3 {
+        let response = client.send(bitreq::put(&url).with_body("body")).unwrap();
+        assert_eq!(response.as_str().unwrap(), "ok");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn sync_client_does_not_replay_non_idempotent_requests() {
+    // The server answers the first request on each connection, then reads the second one and
+    // closes the connection without answering it.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let received = Arc::clone(&requests);
+    thread::spawn(move || {
+        for mut sock in listener.incoming().flatten() {
+            let mut buf = [0u8; 4096];
+            let mut acc: Vec<u8> = Vec::new();
+            let mut served = 0;
+            while served < 2 {
+                match sock.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => acc.extend_from_slice(&buf[..n]),
+                }
+                while let Some(end) = find_double_crlf(&acc) {
+                    acc.drain(..end);
+                    received.fetch_add(1, Ordering::SeqCst);
+                    served += 1;
+                    if served == 1 {
+                        sock.write_all(KEEP_ALIVE).unwrap();
+                    }
+                }
+            }
+        }
+    });
+    let url = format!("http://{}/", addr);
+
+    let client = Client::new(10);
+    client.send(bitreq::get(&url)).unwrap();
+    assert!(client.send(bitreq::post(&url)).is_err());
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+}
+
+fn stats(client: &Client) -> (u64, u64, u64, usize) {
+    let PoolStats { hits, misses, evictions, connections, .. } = client.pool_stats();
+    (hits, misses, evictions, connections)
+}
+
+#[test]
+fn sync_client_replaces_dead_connections_on_checkout() {
+    let (addr, connections) = spawn_server(KEEP_ALIVE, 1);
+    let url = format!("http://{}/", addr);
+
+    let client = Client::new(10);
+    client.send(bitreq::get(&url)).unwrap();
+    // Give the server's close time to arrive, so the connection is found dead on checkout.
+    thread::sleep(Duration::from_millis(100));
+    client.send(bitreq::get(&url)).unwrap();
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+    assert_eq!(stats(&client), (0, 2, 1, 1));
+}
+
+#[test]
+fn sync_client_evicts_idle_and_old_connections() {
+    let (addr, connections) = spawn_server(KEEP_ALIVE, usize::MAX);
+    let url = format!("http://{}/", addr);
+
+    let client = Client::new(10).with_idle_timeout(Duration::from_millis(100));
+    client.send(bitreq::get(&url)).unwrap();
+    client.send(bitreq::get(&url)).unwrap();
+    thread::sleep(Duration::from_millis(200));
+    client.send(bitreq::get(&url)).unwrap();
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+    assert_eq!(stats(&client), (1, 2, 1, 1));
+
+    let client = Client::new(10).with_max_connection_lifetime(Duration::from_millis(300));
+    let start = connections.load(Ordering::SeqCst);
+    for _ in 0..5 {
+        client.send(bitreq::get(&url)).unwrap();
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(connections.load(Ordering::SeqCst) - start, 2);
+    assert_eq!(client.pool_stats().evictions, 1);
+}
+
+#[test]
+fn sync_client_limits_connections_per_host() {
+    let (addr, connections) = spawn_slow_server(KEEP_ALIVE, usize::MAX, Duration::from_millis(200));
+    let url = format!("http://{}/", addr);
+
+    let send_concurrently = |client: &Client| {
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| client.send(bitreq::get(&url)).unwrap());
+            }
+        })
+    };
+
+    // Only two of the three connections opened are kept, so one more is needed next time.
+    let client = Client::new(10).with_max_connections_per_host(2);
+    send_concurrently(&client);
+    assert_eq!(stats(&client), (0, 3, 1, 2));
+    send_concurrently(&client);
+    assert_eq!(stats(&client), (2, 4, 2, 2));
+    assert_eq!(connections.load(Ordering::SeqCst), 4);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_client_replaces_dead_connections_on_checkout() {
+    let (addr, connections) = spawn_server(KEEP_ALIVE, 1);
+    let url = format!("http://{}/", addr);
+
+    let client = Client::new(10);
+    client.send_async(bitreq::get(&url)).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    client.send_async(bitreq::get(&url)).await.unwrap();
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+    assert_eq!(stats(&client), (0, 2, 1, 1));
+
+    let (addr, connections) = spawn_server(KEEP_ALIVE, usize::MAX);
+    let url = format!("http://{}/", addr);
+    let client = Client::new(10);
+    for _ in 0..3 {
+        client.send_async(bitreq::get(&url)).await.unwrap();
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+    assert_eq!(stats(&client), (2, 1, 0, 1));
+}
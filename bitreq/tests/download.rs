@@ -0,0 +1,157 @@
+#![cfg(feature = "download")]
+
+extern crate bitreq;
+
+mod setup;
+
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bitreq::{download_to_file, Download, Error};
+
+use self::setup::{header, read_head, spawn_raw_server};
+
+const LENGTH: usize = 200_000;
+/// The SHA256 digest of [`body`].
+const SHA256: &str = "e24bc62381f1224fbbb74688663f8f9743b9680b193edd666835e97b06e730eb";
+
+fn body() -> Vec<u8> { (0..LENGTH).map(|i| (i % 251) as u8).collect() }
+
+fn sha256() -> [u8; 32] {
+    let mut digest = [0; 32];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&SHA256[2 * i..2 * i + 2], 16).unwrap();
+    }
+    digest
+}
+
+/// Spawns a server sending [`body`], which drops the first connection halfway through the body.
+/// Unless `ranges` is false, it answers requests for the rest of the body with `206 Partial
+/// Content`. Returns the server's address and the `Range` header of each request.
+fn spawn_server(ranges: bool) -> (SocketAddr, Arc<Mutex<Vec<Option<String>>>>) {
+    let requested_ranges = Arc::new(Mutex::new(Vec::new()));
+    let requests = Arc::clone(&requested_ranges);
+    let body = body();
+    let addr = spawn_raw_server(move |i, mut sock| {
+        let request = read_head(&sock).unwrap_or_default();
+        let range = header(&request, "Range");
+        requests.lock().unwrap().push(range.map(String::from));
+
+        let start = range
+            .and_then(|range| range.strip_prefix("bytes=")?.strip_suffix('-')?.parse().ok())
+            .filter(|_| ranges && header(&request, "If-Range") == Some("\"v1\""));
+        let (head, sent) = match start {
+            Some(start) => (
+                format!(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    start,
+                    LENGTH - 1,
+                    LENGTH,
+                    LENGTH - start
+                ),
+                &body[start..],
+            ),
+            None => (
+                format!(
+                    "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\
+                     Connection: close\r\n\r\n",
+                    LENGTH
+                ),
+                &body[..],
+            ),
+        };
+        let sent = if i == 0 { &sent[..LENGTH / 2] } else { sent };
+        let _ = sock.write_all(head.as_bytes());
+        let _ = sock.write_all(sent);
+    });
+    (addr, requested_ranges)
+}
+
+fn destination(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bitreq-download-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+#[test]
+fn download_resumes_interrupted_transfers() {
+    let (addr, ranges) = spawn_server(true);
+    let path = destination("resumed.bin");
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let reported = Arc::clone(&progress);
+
+    let length = Download::new(bitreq::get(format!("http://{}/file", addr)), &path)
+        .with_sha256(sha256())
+        .with_progress(move |done, total| reported.lock().unwrap().push((done, total)))
+        .send()
+        .unwrap();
+    assert_eq!(length, LENGTH as u64);
+    assert_eq!(std::fs::read(&path).unwrap(), body());
+    assert_eq!(*ranges.lock().unwrap(), [None, Some(format!("bytes={}-", LENGTH / 2))]);
+
+    let progress = progress.lock().unwrap();
+    assert!(progress.windows(2).all(|w| w[0].0 < w[1].0));
+    assert_eq!(progress.last(), Some(&(LENGTH as u64, Some(LENGTH as u64))));
+}
+
+#[test]
+fn download_restarts_without_range_support() {
+    let (addr, ranges) = spawn_server(false);
+    let path = destination("restarted.bin");
+    let request = bitreq::get(format!("http://{}/file", addr));
+    let length = download_to_file(request, &path, Some(sha256()), None).unwrap();
+    assert_eq!(length, LENGTH as u64);
+    assert_eq!(std::fs::read(&path).unwrap(), body());
+    assert_eq!(ranges.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn download_checks_digest() {
+    let (addr, _) = spawn_server(true);
+    let path = destination("mismatch.bin");
+    let result = Download::new(bitreq::get(format!("http://{}/file", addr)), &path)
+        .with_sha256([0; 32])
+        .send();
+    assert!(matches!(result, Err(Error::DigestMismatch)));
+    assert!(!path.exists());
+    assert!(!path.with_file_name("mismatch.bin.part").exists());
+
+    let (addr, _) = spawn_server(true);
+    let result = Download::new(bitreq::get(format!("http://{}/file", addr)), &path)
+        .with_max_resumes(0)
+        .send();
+    assert!(matches!(result, Err(Error::IoError(_))));
+    assert!(!path.exists());
+}
+
+#[test]
+fn download_does_not_resume_after_timeout() {
+    // Every connection stalls halfway through the body.
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = Arc::clone(&connections);
+    let addr = spawn_raw_server(move |_, mut sock| {
+        accepted.fetch_add(1, Ordering::SeqCst);
+        thread::spawn(move || {
+            let _ = read_head(&sock);
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", LENGTH);
+            let _ = sock.write_all(head.as_bytes());
+            let _ = sock.write_all(&body()[..LENGTH / 2]);
+            thread::sleep(Duration::from_secs(2));
+        });
+    });
+    let path = destination("timeout.bin");
+    let start = Instant::now();
+    let request =
+        bitreq::get(format!("http://{}/file", addr)).with_timeout(Duration::from_millis(300));
+    let result = Download::new(request, &path).send();
+    assert!(matches!(result, Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::TimedOut));
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+    assert!(!path.with_file_name("timeout.bin.part").exists());
+}
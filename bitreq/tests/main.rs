@@ -4,6 +4,7 @@ extern crate bitreq;
 mod setup;
 
 use std::io;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use self::setup::*;
@@ -33,6 +34,63 @@ async fn test_json_using_serde() {
     assert_eq!(actual_json, original_json);
 }
 
+#[tokio::test]
+async fn test_compressed_body() {
+    // Without the compression feature we don't send `Accept-Encoding`, so the server doesn't
+    // compress the body.
+    setup();
+    for path in ["/gzip", "/deflate"] {
+        let response = make_request(bitreq::get(url(path))).await;
+        assert_eq!(response.as_str().unwrap(), COMPRESSIBLE_BODY);
+        assert!(!response.headers.contains_key("content-encoding"));
+        let length = response.headers["content-length"].parse::<usize>().unwrap();
+        assert_eq!(length, COMPRESSIBLE_BODY.len());
+
+        // The lazy response only knows the decoded length once the body has been read.
+        let mut lazy_response = bitreq::get(url(path)).send_lazy().unwrap();
+        io::copy(&mut lazy_response, &mut io::sink()).unwrap();
+        assert_eq!(lazy_response.headers["content-length"], length.to_string());
+    }
+}
+
+#[tokio::test]
+#[cfg(feature = "compression")]
+async fn test_compressed_body_limit() {
+    use std::io::Read;
+
+    setup();
+    let request = bitreq::get(url("/gzip_bomb")).with_max_body_size(1024 * 1024);
+    let res = request.clone().send();
+    assert!(matches!(res, Err(bitreq::Error::BodyOverflow)));
+    let mut lazy_response = request.clone().send_lazy().unwrap();
+    let res = lazy_response.read_to_end(&mut Vec::new());
+    assert!(res.unwrap_err().to_string().contains("max_body_size"));
+    #[cfg(feature = "async")]
+    {
+        let res = request.send_async().await;
+        assert!(matches!(res, Err(bitreq::Error::BodyOverflow)));
+    }
+
+    let request = bitreq::get(url("/gzip_bomb")).with_max_body_size(4 * 1024 * 1024);
+    assert_eq!(make_request(request).await.as_bytes().len(), 4 * 1024 * 1024);
+
+    // Explicitly asking for an uncompressed body disables decompression.
+    let request = bitreq::get(url("/gzip")).with_header("Accept-Encoding", "identity");
+    assert_eq!(get_body(request).await, COMPRESSIBLE_BODY);
+}
+
+#[tokio::test]
+#[cfg(feature = "compression")]
+async fn test_head_keeps_encoding_headers() {
+    // A HEAD response has no body to decode, so it describes the encoded one as sent.
+    setup();
+    let response = make_request(bitreq::head(url("/deflate"))).await;
+    assert!(response.as_bytes().is_empty());
+    assert_eq!(response.headers["content-encoding"], "deflate");
+    let length = response.headers["content-length"].parse::<usize>().unwrap();
+    assert_eq!(length, setup::compress("deflate", COMPRESSIBLE_BODY.as_bytes()).len());
+}
+
 #[tokio::test]
 async fn test_timeout_too_low() {
     setup();
@@ -111,6 +169,32 @@ async fn test_redirect_with_overridden_fragment() {
     assert_eq!(res.url.as_str(), url("/a#baz"));
 }
 
+#[tokio::test]
+async fn test_permanent_redirect_post() {
+    setup();
+    // A 308 redirect keeps the method and the body.
+    let response = make_request(bitreq::post(url("/redirect308")).with_body("Q")).await;
+    assert_eq!(response.as_str().unwrap(), "Q");
+    assert_eq!(response.url, url("/echo"));
+    assert_eq!(response.redirects, [url("/redirect308")]);
+}
+
+#[tokio::test]
+async fn test_redirect_history() {
+    setup();
+    let response = make_request(bitreq::get(url("/redirect")).with_body("Q")).await;
+    assert_eq!(response.redirects, [url("/redirect")]);
+    assert!(make_request(bitreq::get(url("/a"))).await.redirects.is_empty());
+
+    let request =
+        bitreq::get(url("/redirect")).with_redirect_policy(bitreq::RedirectPolicy::none());
+    let response = make_request(request).await;
+    assert_eq!(response.status_code, 301);
+    assert_eq!(response.headers["location"], url("/a"));
+    assert_eq!(response.url, url("/redirect"));
+    assert!(response.redirects.is_empty());
+}
+
 #[tokio::test]
 async fn test_infinite_redirect() {
     setup();
@@ -271,3 +355,251 @@ async fn test_future_drop_doesnt_hang() {
         "Request should complete quickly, and certainly not have to wait for its timeout to try again"
     );
 }
+
+#[tokio::test]
+async fn test_body_reader() {
+    setup();
+    let body = "S".repeat(100_000);
+    let reader = io::Cursor::new(body.clone());
+    let response = bitreq::post(url("/echo")).with_body_reader(reader, Some(100_000)).send();
+    assert_eq!(response.unwrap().as_str().unwrap(), body);
+
+    // Without a length, the body is sent chunked.
+    let reader = io::Cursor::new(body.clone());
+    let response = bitreq::post(url("/echo")).with_body_reader(reader, None).send_lazy();
+    let mut received = String::new();
+    io::Read::read_to_string(&mut response.unwrap(), &mut received).unwrap();
+    assert_eq!(received, body);
+
+    let client = bitreq::Client::new(1);
+    let reader = io::Cursor::new(body.clone());
+    let request = bitreq::post(url("/echo")).with_body_reader(reader, None);
+    assert_eq!(client.send(request).unwrap().as_str().unwrap(), body);
+
+    // A reader shorter than the given length is an error.
+    let request = bitreq::post(url("/echo")).with_body_reader(io::Cursor::new("abc"), Some(4));
+    assert!(
+        matches!(request.send(), Err(bitreq::Error::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof)
+    );
+}
+
+#[tokio::test]
+async fn test_body_reader_redirect() {
+    setup();
+    // A 303 redirect turns the request into a GET without a body.
+    let request = bitreq::post(url("/redirect")).with_body_reader(io::Cursor::new("Q"), None);
+    assert_eq!(request.send().unwrap().as_str().unwrap(), "j: ");
+
+    // A 301 redirect would have to send the body again.
+    let request = bitreq::get(url("/redirect")).with_body_reader(io::Cursor::new("Q"), Some(1));
+    assert!(matches!(request.send(), Err(bitreq::Error::BodyStreamConsumed)));
+}
+
+#[tokio::test]
+#[cfg(feature = "async")]
+async fn test_body_async_reader() {
+    setup();
+    let body = "A".repeat(100_000);
+    let reader = io::Cursor::new(body.clone());
+    let request = bitreq::post(url("/echo")).with_body_async_reader(reader, Some(100_000));
+    assert_eq!(request.send_async().await.unwrap().as_str().unwrap(), body);
+
+    let reader = io::Cursor::new(body.clone());
+    let request = bitreq::post(url("/echo")).with_body_async_reader(reader, None);
+    assert_eq!(request.send_async().await.unwrap().as_str().unwrap(), body);
+
+    // Blocking readers work too.
+    let reader = io::Cursor::new(body.clone());
+    let request = bitreq::post(url("/echo")).with_body_reader(reader, None);
+    assert_eq!(request.send_async().await.unwrap().as_str().unwrap(), body);
+
+    // Streamed bodies can be pipelined with other requests on the same connection.
+    let client = bitreq::Client::new(1);
+    let reader = io::Cursor::new(body.clone());
+    let streamed = bitreq::post(url("/echo")).with_body_async_reader(reader, None);
+    let (streamed, other) = tokio::join!(
+        client.send_async(streamed.with_pipelining()),
+        client.send_async(bitreq::post(url("/echo")).with_body("B").with_pipelining()),
+    );
+    assert_eq!(streamed.unwrap().as_str().unwrap(), body);
+    assert_eq!(other.unwrap().as_str().unwrap(), "B");
+
+    // Async readers can't be read from blocking requests.
+    let request = bitreq::post(url("/echo")).with_body_async_reader(io::Cursor::new("A"), None);
+    assert!(matches!(request.send(), Err(bitreq::Error::IoError(_))));
+}
+
+#[tokio::test]
+#[cfg(feature = "async")]
+async fn test_streaming_response() {
+    use tokio::io::AsyncReadExt;
+
+    setup();
+    let body = "S".repeat(100_000);
+    let request = bitreq::post(url("/echo")).with_body(body.clone());
+    let mut response = request.send_streaming_async().await.unwrap();
+    assert_eq!(response.status_code, 200);
+    let mut received = Vec::new();
+    while let Some(chunk) = response.chunk().await.unwrap() {
+        assert!(!chunk.is_empty());
+        received.extend(chunk);
+    }
+    assert_eq!(received, body.as_bytes());
+    assert!(response.chunk().await.unwrap().is_none());
+
+    // The gzip body is sent chunked, and compressed if the compression feature is enabled.
+    let mut response = bitreq::get(url("/gzip")).send_streaming_async().await.unwrap();
+    assert!(!response.headers.contains_key("content-length"));
+    let mut received = String::new();
+    response.read_to_string(&mut received).await.unwrap();
+    assert_eq!(received, COMPRESSIBLE_BODY);
+    assert!(!response.headers.contains_key("transfer-encoding"));
+    let length = response.headers["content-length"].parse::<usize>().unwrap();
+    assert_eq!(length, COMPRESSIBLE_BODY.len());
+
+    let request = bitreq::post(url("/redirect")).with_body("Q");
+    let mut response = request.send_streaming_async().await.unwrap();
+    assert_eq!(response.url, url("/a"));
+    let mut received = String::new();
+    response.read_to_string(&mut received).await.unwrap();
+    assert_eq!(received, "j: Q");
+
+    let request = bitreq::head(url("/b"));
+    let mut response = request.send_streaming_async().await.unwrap();
+    assert_eq!(response.status_code, 418);
+    assert!(response.chunk().await.unwrap().is_none());
+}
+
+#[tokio::test]
+#[cfg(feature = "async")]
+async fn test_streaming_response_limit() {
+    use tokio::io::AsyncReadExt;
+
+    setup();
+    let request = bitreq::post(url("/echo")).with_body("S".repeat(1000)).with_max_body_size(999);
+    let mut response = request.send_streaming_async().await.unwrap();
+    assert!(matches!(response.chunk().await, Err(bitreq::Error::BodyOverflow)));
+
+    let request = bitreq::get(url("/gzip")).with_max_body_size(COMPRESSIBLE_BODY.len() - 1);
+    let mut response = request.send_streaming_async().await.unwrap();
+    let res = response.read_to_end(&mut Vec::new()).await;
+    assert!(res.unwrap_err().to_string().contains("max_body_size"));
+
+    let request = bitreq::get(url("/gzip")).with_max_body_size(COMPRESSIBLE_BODY.len());
+    let mut response = request.send_streaming_async().await.unwrap();
+    let mut received = Vec::new();
+    response.read_to_end(&mut received).await.unwrap();
+    assert_eq!(received, COMPRESSIBLE_BODY.as_bytes());
+}
+
+#[tokio::test]
+async fn test_form() {
+    setup();
+    let request = bitreq::post(url("/echo")).with_form(&[("a b", "1&2"), ("c", "ü")]);
+    assert_eq!(get_body(request).await, "a%20b=1%262&c=%C3%BC");
+
+    let form = bitreq::Multipart::new()
+        .with_text("label", "snapshot")
+        .with_part("file", bitreq::Part::bytes("data").with_file_name("a.bin"));
+    let boundary = form.boundary().to_string();
+    let request = bitreq::post(url("/echo")).with_multipart(form);
+    let expected = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"label\"\r\n\r\nsnapshot\r\n\
+        --{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.bin\"\r\n\
+        Content-Type: application/octet-stream\r\n\r\ndata\r\n--{boundary}--\r\n"
+    );
+    assert_eq!(get_body(request).await, expected);
+}
+
+#[tokio::test]
+async fn test_cookie_jar() {
+    setup();
+    // Cookies set by a redirect are sent to where it leads.
+    let jar = bitreq::CookieJar::new();
+    let request = bitreq::get(url("/set_cookies")).with_cookie_jar(jar.clone());
+    assert_eq!(get_body(request).await, "theme=dark; session=abc");
+    assert_eq!(jar.cookies().len(), 2);
+
+    // They're merged into cookies set explicitly.
+    let request =
+        bitreq::get(url("/cookies")).with_header("Cookie", "a=b").with_cookie_jar(jar.clone());
+    assert_eq!(get_body(request).await, "a=b; theme=dark; session=abc");
+
+    let client = bitreq::Client::new(1).with_cookie_jar(jar.clone());
+    jar.clear();
+    client.send(bitreq::get(url("/set_cookies"))).unwrap();
+    assert_eq!(jar.cookies().len(), 2);
+    let response = client.send(bitreq::get(url("/cookies"))).unwrap();
+    assert_eq!(response.as_str().unwrap(), "theme=dark; session=abc");
+
+    assert_eq!(get_body(bitreq::get(url("/cookies"))).await, "");
+
+    #[cfg(feature = "async")]
+    {
+        let jar = bitreq::CookieJar::new();
+        let request = bitreq::get(url("/set_cookies")).with_cookie_jar(jar.clone());
+        let mut response = request.send_streaming_async().await.unwrap();
+        assert_eq!(response.chunk().await.unwrap().unwrap(), b"theme=dark; session=abc");
+        let client = bitreq::Client::new(1).with_cookie_jar(jar.clone());
+        let response = client.send_async(bitreq::get(url("/cookies"))).await.unwrap();
+        assert_eq!(response.as_str().unwrap(), "theme=dark; session=abc");
+    }
+}
+
+/// Records the order its hooks run in, and turns error statuses into errors.
+struct Recorder(&'static str, Arc<Mutex<Vec<String>>>);
+
+impl bitreq::Middleware for Recorder {
+    fn on_request(&self, request: bitreq::Request) -> Result<bitreq::Request, bitreq::Error> {
+        self.1.lock().unwrap().push(format!("{} request {}", self.0, request.url()));
+        Ok(request)
+    }
+
+    fn on_response(
+        &self,
+        _request: &bitreq::Request,
+        result: Result<bitreq::Response, bitreq::Error>,
+    ) -> Result<bitreq::Response, bitreq::Error> {
+        self.1.lock().unwrap().push(format!("{} response", self.0));
+        match result {
+            Ok(response) if response.status_code >= 400 =>
+                Err(bitreq::Error::Other("error status")),
+            result => result,
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_client_middleware() {
+    setup();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let client = bitreq::Client::new(1)
+        .with_middleware(Recorder("outer", Arc::clone(&log)))
+        .with_middleware(bitreq::BaseUrl::new(url("")))
+        .with_middleware(bitreq::DefaultHeaders::new().with_header("Ping", "Default"))
+        .with_middleware(Recorder("inner", Arc::clone(&log)));
+
+    let response = client.send(bitreq::get("/header_pong")).unwrap();
+    assert_eq!(response.as_str().unwrap(), "Default");
+    assert_eq!(
+        *log.lock().unwrap(),
+        [
+            "outer request /header_pong".to_string(),
+            format!("inner request {}", url("/header_pong")),
+            "inner response".to_string(),
+            "outer response".to_string(),
+        ]
+    );
+
+    let request = bitreq::get("/header_pong").with_header("Ping", "Explicit");
+    assert_eq!(client.send(request).unwrap().as_str().unwrap(), "Explicit");
+    assert!(matches!(client.send(bitreq::get("/missing")), Err(bitreq::Error::Other(_))));
+
+    #[cfg(feature = "async")]
+    {
+        let response = client.send_async(bitreq::get("/header_pong")).await.unwrap();
+        assert_eq!(response.as_str().unwrap(), "Default");
+        let result = client.send_async(bitreq::get("/missing")).await;
+        assert!(matches!(result, Err(bitreq::Error::Other(_))));
+    }
+}
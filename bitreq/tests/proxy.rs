@@ -0,0 +1,319 @@
+#![cfg(feature = "proxy")]
+
+extern crate bitreq;
+
+mod setup;
+
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use bitreq::{Client, Error, Proxy, ProxyConfig};
+
+use self::setup::{read_head, spawn_raw_server};
+
+/// Spawns an HTTP server answering every request with "ok" and closing the connection.
+fn spawn_http_server() -> SocketAddr {
+    spawn_http_server_with(
+        "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok".into(),
+    )
+}
+
+/// Spawns an HTTP server answering every request with `response` and closing the connection.
+fn spawn_http_server_with(response: String) -> SocketAddr {
+    spawn_raw_server(move |_, mut sock| {
+        let _ = read_head(&sock);
+        let _ = sock.write_all(response.as_bytes());
+    })
+}
+
+/// Spawns an HTTP proxy which tunnels every `CONNECT` request to `upstream`, no matter the
+/// target. Returns its address and the targets it was asked to connect to.
+fn spawn_connect_proxy(upstream: SocketAddr) -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+    let targets = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&targets);
+    let addr = spawn_raw_server(move |_, mut sock| {
+        let Ok(head) = read_head(&sock) else { return };
+        let target = head.strip_prefix("CONNECT ").and_then(|rest| rest.split(' ').next());
+        seen.lock().unwrap().push(target.unwrap_or_default().to_owned());
+        let _ = sock.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n");
+        thread::spawn(move || tunnel(sock, upstream));
+    });
+    (addr, targets)
+}
+
+/// The address a SOCKS5 client asked our proxy to connect to.
+#[derive(Debug, PartialEq)]
+enum Target {
+    Ip(Vec<u8>, u16),
+    Domain(String, u16),
+}
+
+/// Spawns a SOCKS5 proxy which tunnels every connection to `upstream`, no matter the target,
+/// requiring `creds` if given. Returns its address and the targets it was asked to connect to.
+fn spawn_socks5_proxy(
+    upstream: SocketAddr,
+    creds: Option<(&'static str, &'static str)>,
+) -> (SocketAddr, Arc<Mutex<Vec<Target>>>) {
+    let targets = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&targets);
+    let addr = spawn_raw_server(move |_, sock| {
+        let seen = Arc::clone(&seen);
+        thread::spawn(move || {
+            if let Ok(Some(target)) = socks5_accept(&sock, creds) {
+                seen.lock().unwrap().push(target);
+                let _ = tunnel(sock, upstream);
+            }
+        });
+    });
+    (addr, targets)
+}
+
+fn read_vec(sock: &mut &TcpStream, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0; len];
+    sock.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Performs the server side of the SOCKS5 handshake, returning the requested target if the client
+/// authenticated successfully.
+fn socks5_accept(mut sock: &TcpStream, creds: Option<(&str, &str)>) -> io::Result<Option<Target>> {
+    let greeting = read_vec(&mut sock, 2)?;
+    assert_eq!(greeting[0], 5);
+    let methods = read_vec(&mut sock, greeting[1].into())?;
+
+    if let Some((user, password)) = creds {
+        if !methods.contains(&2) {
+            sock.write_all(&[5, 0xff])?;
+            return Ok(None);
+        }
+        sock.write_all(&[5, 2])?;
+        let header = read_vec(&mut sock, 2)?;
+        assert_eq!(header[0], 1);
+        let got_user = read_vec(&mut sock, header[1].into())?;
+        let password_len = read_vec(&mut sock, 1)?[0];
+        let got_password = read_vec(&mut sock, password_len.into())?;
+        if got_user != user.as_bytes() || got_password != password.as_bytes() {
+            sock.write_all(&[1, 1])?;
+            return Ok(None);
+        }
+        sock.write_all(&[1, 0])?;
+    } else {
+        assert!(methods.contains(&0));
+        sock.write_all(&[5, 0])?;
+    }
+
+    let request = read_vec(&mut sock, 4)?;
+    assert_eq!(request[..3], [5, 1, 0]);
+    let target = match request[3] {
+        1 => Target::Ip(read_vec(&mut sock, 4)?, 0),
+        4 => Target::Ip(read_vec(&mut sock, 16)?, 0),
+        3 => {
+            let len = read_vec(&mut sock, 1)?[0];
+            Target::Domain(String::from_utf8(read_vec(&mut sock, len.into())?).unwrap(), 0)
+        }
+        _ => panic!("unexpected address type"),
+    };
+    let port = read_vec(&mut sock, 2)?;
+    let port = u16::from_be_bytes([port[0], port[1]]);
+    let target = match target {
+        Target::Ip(ip, _) => Target::Ip(ip, port),
+        Target::Domain(domain, _) => Target::Domain(domain, port),
+    };
+
+    // Reply with a domain name as the bound address, which clients have to skip over.
+    sock.write_all(&[5, 0, 0, 3, 5])?;
+    sock.write_all(b"proxy\x04\x38")?;
+    Ok(Some(target))
+}
+
+fn tunnel(mut sock: TcpStream, upstream: SocketAddr) -> io::Result<()> {
+    let mut upstream = TcpStream::connect(upstream)?;
+    let mut upstream_write = upstream.try_clone()?;
+    let mut client_read = sock.try_clone()?;
+    thread::spawn(move || io::copy(&mut client_read, &mut upstream_write));
+    io::copy(&mut upstream, &mut sock)?;
+    Ok(())
+}
+
+#[test]
+fn socks5_remote_dns() {
+    let upstream = spawn_http_server();
+    let (proxy_addr, targets) = spawn_socks5_proxy(upstream, None);
+    let proxy = Proxy::new_socks5(format!("socks5h://{}", proxy_addr)).unwrap();
+
+    let response =
+        bitreq::get("http://exampleonionaddress.onion:8333/").with_proxy(proxy).send().unwrap();
+    assert_eq!(response.as_str().unwrap(), "ok");
+
+    let targets = targets.lock().unwrap();
+    assert_eq!(*targets, [Target::Domain("exampleonionaddress.onion".to_string(), 8333)]);
+}
+
+#[test]
+fn socks5_local_dns() {
+    let upstream = spawn_http_server();
+    let (proxy_addr, targets) = spawn_socks5_proxy(upstream, None);
+    let proxy = Proxy::new_socks5(format!("socks5://{}", proxy_addr)).unwrap();
+
+    let url = format!("http://localhost:{}/", upstream.port());
+    let response = bitreq::get(url).with_proxy(proxy).send().unwrap();
+    assert_eq!(response.as_str().unwrap(), "ok");
+
+    let targets = targets.lock().unwrap();
+    assert_eq!(targets.len(), 1);
+    match &targets[0] {
+        Target::Ip(ip, port) => {
+            let localhost = [&Ipv4Addr::LOCALHOST.octets()[..], &Ipv6Addr::LOCALHOST.octets()];
+            assert!(localhost.contains(&&ip[..]), "{:?}", ip);
+            assert_eq!(*port, upstream.port());
+        }
+        target => panic!("expected an IP address, got {:?}", target),
+    }
+}
+
+#[test]
+fn socks5_authentication() {
+    let upstream = spawn_http_server();
+    let (proxy_addr, targets) = spawn_socks5_proxy(upstream, Some(("user", "pass")));
+
+    let proxy = Proxy::new_socks5(format!("user:pass@{}", proxy_addr)).unwrap();
+    let response = bitreq::get("http://example.com/").with_proxy(proxy).send().unwrap();
+    assert_eq!(response.as_str().unwrap(), "ok");
+
+    let proxy = Proxy::new_socks5(format!("user:wrong@{}", proxy_addr)).unwrap();
+    let res = bitreq::get("http://example.com/").with_proxy(proxy).send();
+    assert!(matches!(res, Err(Error::InvalidProxyCreds)), "{:?}", res);
+
+    let proxy = Proxy::new_socks5(proxy_addr.to_string()).unwrap();
+    let res = bitreq::get("http://example.com/").with_proxy(proxy).send();
+    assert!(matches!(res, Err(Error::InvalidProxyCreds)), "{:?}", res);
+
+    assert_eq!(targets.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn socks5_connect_failure() {
+    // A proxy that refuses every connection with "connection refused".
+    let proxy_addr = spawn_raw_server(|_, sock| {
+        let mut sock = &sock;
+        let _ = read_vec(&mut sock, 3);
+        let _ = sock.write_all(&[5, 0]);
+        let _ = read_vec(&mut sock, 4 + 1 + 11 + 2);
+        let _ = sock.write_all(&[5, 5, 0, 1, 0, 0, 0, 0, 0, 0]);
+    });
+
+    let proxy = Proxy::new_socks5(proxy_addr.to_string()).unwrap();
+    let res = bitreq::get("http://example.com/").with_proxy(proxy).send();
+    assert!(matches!(res, Err(Error::Socks5ConnectFailed(5))), "{:?}", res);
+}
+
+#[test]
+fn socks5_with_client() {
+    let upstream = spawn_http_server();
+    let (proxy_addr, targets) = spawn_socks5_proxy(upstream, None);
+    let proxy = Proxy::new_socks5(proxy_addr.to_string()).unwrap();
+
+    let client = bitreq::Client::new(10);
+    for _ in 0..2 {
+        let request = bitreq::get("http://example.onion/").with_proxy(proxy.clone());
+        let response = client.send(request).unwrap();
+        assert_eq!(response.as_str().unwrap(), "ok");
+    }
+    // The server closes every connection, so each request goes through the proxy again.
+    assert_eq!(targets.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+#[cfg(feature = "async")]
+async fn socks5_async() {
+    let upstream = spawn_http_server();
+    let (proxy_addr, targets) = spawn_socks5_proxy(upstream, Some(("user", "pass")));
+    let proxy = Proxy::new_socks5(format!("user:pass@{}", proxy_addr)).unwrap();
+
+    let response =
+        bitreq::get("http://example.onion/").with_proxy(proxy.clone()).send_async().await.unwrap();
+    assert_eq!(response.as_str().unwrap(), "ok");
+
+    let client = bitreq::Client::new(10);
+    let request = bitreq::get("http://example.onion/").with_proxy(proxy);
+    let response = client.send_async(request).await.unwrap();
+    assert_eq!(response.as_str().unwrap(), "ok");
+
+    assert_eq!(targets.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn proxy_config_picks_proxy_per_url() {
+    let upstream = spawn_http_server();
+    let (proxy_addr, targets) = spawn_connect_proxy(upstream);
+    let config = ProxyConfig::new()
+        .with_http_proxy(Proxy::new_http(proxy_addr.to_string()).unwrap())
+        .with_no_proxy("localhost, .internal");
+    let client = Client::new(10).with_proxy_config(config);
+
+    let response = client.send(bitreq::get("http://example.com/")).unwrap();
+    assert_eq!(response.as_str().unwrap(), "ok");
+    assert_eq!(*targets.lock().unwrap(), ["example.com:80"]);
+
+    // Excluded hosts are connected to directly.
+    let url = format!("http://localhost:{}/", upstream.port());
+    let response = client.send(bitreq::get(url)).unwrap();
+    assert_eq!(response.as_str().unwrap(), "ok");
+    let res = client.send(bitreq::get("http://node.internal:1/"));
+    assert!(res.is_err());
+    assert_eq!(targets.lock().unwrap().len(), 1);
+
+    // An explicit proxy takes precedence.
+    let (other_proxy_addr, other_targets) = spawn_connect_proxy(upstream);
+    let other_proxy = Proxy::new_http(other_proxy_addr.to_string()).unwrap();
+    let response = client.send(bitreq::get("http://example.com/").with_proxy(other_proxy)).unwrap();
+    assert_eq!(response.as_str().unwrap(), "ok");
+    assert_eq!(targets.lock().unwrap().len(), 1);
+    assert_eq!(*other_targets.lock().unwrap(), ["example.com:80"]);
+}
+
+#[test]
+fn proxy_config_picks_proxy_per_redirect() {
+    let destination = spawn_http_server();
+    let location = format!("http://localhost:{}/", destination.port());
+    let redirect = spawn_http_server_with(format!(
+        "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        location
+    ));
+    let (proxy_addr, targets) = spawn_connect_proxy(redirect);
+    let config = ProxyConfig::new()
+        .with_http_proxy(Proxy::new_http(proxy_addr.to_string()).unwrap())
+        .with_no_proxy("localhost");
+    let client = Client::new(10).with_proxy_config(config.clone());
+
+    // The first hop goes through the proxy, the redirect to localhost doesn't.
+    let response = client.send(bitreq::get("http://example.com/")).unwrap();
+    assert_eq!(response.as_str().unwrap(), "ok");
+    assert_eq!(response.url, location);
+    assert_eq!(*targets.lock().unwrap(), ["example.com:80"]);
+
+    // The same applies to a configuration set on the request.
+    let response = bitreq::get("http://example.com/").with_proxy_config(config).send().unwrap();
+    assert_eq!(response.url, location);
+    assert_eq!(targets.lock().unwrap().len(), 2);
+}
+
+#[tokio::test]
+#[cfg(feature = "async")]
+async fn proxy_config_async() {
+    let upstream = spawn_http_server();
+    let (proxy_addr, targets) = spawn_connect_proxy(upstream);
+    let config = ProxyConfig::new()
+        .with_http_proxy(Proxy::new_http(proxy_addr.to_string()).unwrap())
+        .with_no_proxy("127.0.0.0/8");
+    let client = Client::new(10).with_proxy_config(config);
+
+    let response = client.send_async(bitreq::get("http://example.com/")).await.unwrap();
+    assert_eq!(response.as_str().unwrap(), "ok");
+    let url = format!("http://127.0.0.1:{}/", upstream.port());
+    let response = client.send_async(bitreq::get(url)).await.unwrap();
+    assert_eq!(response.as_str().unwrap(), "ok");
+    assert_eq!(*targets.lock().unwrap(), ["example.com:80"]);
+}
@@ -0,0 +1,120 @@
+#![cfg(feature = "std")]
+
+extern crate bitreq;
+
+mod setup;
+
+use std::io::Write;
+use std::net::SocketAddr;
+
+use bitreq::{Client, RedirectPolicy, Request};
+
+use self::setup::{read_head, spawn_raw_server};
+
+/// Spawns a server which redirects `/same` to `/echo`, and `/cross` to `/echo` on `other`, if
+/// set. Any other path is answered with the head of the request as the body.
+fn spawn_server(other: Option<SocketAddr>) -> SocketAddr {
+    spawn_raw_server(move |_, mut sock| {
+        let head = read_head(&sock).unwrap_or_default();
+        let path = head.split(' ').nth(1).unwrap_or_default();
+        let response = match (path, other) {
+            ("/same", _) => redirect("/echo"),
+            ("/cross", Some(other)) => redirect(&format!("http://{other}/echo")),
+            _ => format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{head}",
+                head.len()
+            ),
+        };
+        let _ = sock.write_all(response.as_bytes());
+    })
+}
+
+fn redirect(location: &str) -> String {
+    format!(
+        "HTTP/1.1 302 Found\r\nLocation: {location}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+    )
+}
+
+fn with_credentials(request: Request) -> Request {
+    request
+        .with_header("Authorization", "Basic c2VjcmV0")
+        .with_header("Cookie", "session=abc")
+        .with_header("X-Other", "kept")
+}
+
+#[test]
+fn keeps_credentials_within_origin() {
+    let addr = spawn_server(None);
+    let response = with_credentials(bitreq::get(format!("http://{addr}/same"))).send().unwrap();
+    let head = response.as_str().unwrap();
+    assert!(head.contains("Authorization: Basic c2VjcmV0\r\n"));
+    assert!(head.contains("Cookie: session=abc\r\n"));
+    assert_eq!(response.url, format!("http://{addr}/echo"));
+    assert_eq!(response.redirects, [format!("http://{addr}/same")]);
+}
+
+#[test]
+fn strips_credentials_on_cross_origin_redirect() {
+    let other = spawn_server(None);
+    let addr = spawn_server(Some(other));
+    let request = with_credentials(bitreq::get(format!("http://{addr}/cross")));
+
+    let check = |response: bitreq::Response| {
+        let head = response.as_str().unwrap();
+        assert!(!head.contains("Authorization"));
+        assert!(!head.contains("Cookie"));
+        assert!(head.contains("X-Other: kept\r\n"));
+        assert_eq!(response.url, format!("http://{other}/echo"));
+        assert_eq!(response.redirects, [format!("http://{addr}/cross")]);
+    };
+    check(request.clone().send().unwrap());
+    check(Client::new(1).send(request).unwrap());
+}
+
+#[test]
+fn applies_redirect_policy() {
+    let other = spawn_server(None);
+    let addr = spawn_server(Some(other));
+
+    let policy = RedirectPolicy::new().with_same_origin_only(true);
+    let request = bitreq::get(format!("http://{addr}/cross")).with_redirect_policy(policy.clone());
+    let response = request.send().unwrap();
+    assert_eq!(response.status_code, 302);
+    assert_eq!(response.headers["location"], format!("http://{other}/echo"));
+    assert!(response.redirects.is_empty());
+    let response =
+        bitreq::get(format!("http://{addr}/same")).with_redirect_policy(policy).send().unwrap();
+    assert_eq!(response.status_code, 200);
+
+    let policy = RedirectPolicy::new().with_filter(move |attempt| {
+        assert_eq!(attempt.status_code(), 302);
+        assert_eq!(attempt.previous().path(), "/same");
+        assert!(attempt.history().is_empty());
+        attempt.is_same_origin() && attempt.next().path() != "/echo"
+    });
+    let request = bitreq::get(format!("http://{addr}/same")).with_redirect_policy(policy);
+    assert_eq!(request.send_lazy().unwrap().status_code, 302);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_strips_credentials_on_cross_origin_redirect() {
+    let other = spawn_server(None);
+    let addr = spawn_server(Some(other));
+    let request = with_credentials(bitreq::get(format!("http://{addr}/cross")));
+
+    let response = request.clone().send_async().await.unwrap();
+    assert!(!response.as_str().unwrap().contains("Authorization"));
+    assert_eq!(response.redirects, [format!("http://{addr}/cross")]);
+
+    let response = Client::new(1).send_async(request.clone()).await.unwrap();
+    assert!(!response.as_str().unwrap().contains("Authorization"));
+
+    let mut response = request.send_streaming_async().await.unwrap();
+    assert_eq!(response.redirects, [format!("http://{addr}/cross")]);
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.unwrap() {
+        body.extend(chunk);
+    }
+    assert!(!String::from_utf8(body).unwrap().contains("Authorization"));
+}
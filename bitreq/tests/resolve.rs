@@ -0,0 +1,96 @@
+#![cfg(feature = "std")]
+
+extern crate bitreq;
+
+mod setup;
+
+use std::io::{self, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use bitreq::{Client, Resolver, StaticResolver};
+
+use self::setup::{header, read_head, spawn_raw_server};
+
+/// Spawns a server on 127.0.0.1 answering each request with its `Host` header. Returns the
+/// server's port.
+fn spawn_server() -> u16 {
+    let addr = spawn_raw_server(|_, mut sock| {
+        let head = read_head(&sock).unwrap_or_default();
+        let host = header(&head, "Host").unwrap_or_default();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            host.len(),
+            host
+        );
+        let _ = sock.write_all(response.as_bytes());
+    });
+    addr.port()
+}
+
+/// Resolves every host to an IPv6 address which doesn't answer, then to 127.0.0.1.
+struct BlackholeFirst;
+
+impl Resolver for BlackholeFirst {
+    fn resolve(&self, _host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        // An address of the discard-only prefix (RFC 6666), so connecting to it either fails or
+        // hangs until it times out.
+        let blackhole = Ipv6Addr::new(0x100, 0, 0, 0, 0, 0, 0, 1);
+        Ok(vec![(blackhole, port).into(), (Ipv4Addr::LOCALHOST, port).into()])
+    }
+}
+
+fn pinned() -> StaticResolver {
+    StaticResolver::new().with_host("node.bitreq.test", [Ipv4Addr::LOCALHOST.into()])
+}
+
+#[test]
+fn pins_host_names() {
+    let port = spawn_server();
+    let url = format!("http://node.bitreq.test:{}/", port);
+    let response = bitreq::get(&url).with_resolver(pinned()).send().unwrap();
+    assert_eq!(response.as_str().unwrap(), format!("node.bitreq.test:{}", port));
+
+    let response = Client::new(1).with_resolver(pinned()).send(bitreq::get(&url)).unwrap();
+    assert_eq!(response.as_str().unwrap(), format!("node.bitreq.test:{}", port));
+
+    let resolver = StaticResolver::new().with_host("node.bitreq.test", []);
+    let result = bitreq::get(&url).with_resolver(resolver).send();
+    assert!(matches!(result, Err(bitreq::Error::AddressNotFound)));
+}
+
+#[test]
+fn dials_addresses_in_parallel() {
+    let port = spawn_server();
+    let start = Instant::now();
+    let response = bitreq::get(format!("http://localhost:{}/", port))
+        .with_resolver(BlackholeFirst)
+        .with_timeout(Duration::from_secs(10))
+        .send()
+        .unwrap();
+    assert_eq!(response.status_code, 200);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_resolves_and_dials_in_parallel() {
+    let port = spawn_server();
+    let url = format!("http://node.bitreq.test:{}/", port);
+    let response = bitreq::get(&url).with_resolver(pinned()).send_async().await.unwrap();
+    assert_eq!(response.as_str().unwrap(), format!("node.bitreq.test:{}", port));
+
+    let client = Client::new(1).with_resolver(pinned());
+    let response = client.send_async(bitreq::get(&url)).await.unwrap();
+    assert_eq!(response.as_str().unwrap(), format!("node.bitreq.test:{}", port));
+
+    let start = Instant::now();
+    let response = bitreq::get(&url)
+        .with_resolver(BlackholeFirst)
+        .with_timeout(Duration::from_secs(10))
+        .send_async()
+        .await
+        .unwrap();
+    assert_eq!(response.status_code, 200);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
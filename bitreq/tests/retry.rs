@@ -0,0 +1,143 @@
+#![cfg(feature = "std")]
+
+extern crate bitreq;
+
+mod setup;
+
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bitreq::{Client, RetryPolicy};
+
+use self::setup::{read_head, spawn_raw_server};
+
+/// How the test server answers a connection.
+#[derive(Clone, Copy)]
+enum Reply {
+    Status(u16),
+    /// Closes the connection without answering.
+    Close,
+}
+
+/// Spawns a server giving the `replies` in order, then answering "200 OK" to every further
+/// connection. Returns the server's address and the number of connections accepted so far.
+fn spawn_server(replies: &[Reply]) -> (SocketAddr, Arc<AtomicUsize>) {
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let replies = replies.to_vec();
+    let counter = Arc::clone(&accepted);
+    let addr = spawn_raw_server(move |n, mut sock| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        let _ = read_head(&sock);
+        match replies.get(n).copied().unwrap_or(Reply::Status(200)) {
+            Reply::Status(code) => {
+                let response = format!(
+                    "HTTP/1.1 {code} Status\r\nContent-Length: 3\r\nConnection: close\r\n\r\n{code}"
+                );
+                let _ = sock.write_all(response.as_bytes());
+            }
+            Reply::Close => drop(sock),
+        }
+    });
+    (addr, accepted)
+}
+
+fn fast_retry() -> RetryPolicy {
+    RetryPolicy::new().with_backoff(Duration::from_millis(1), Duration::from_millis(10))
+}
+
+#[test]
+fn retries_retryable_status() {
+    let (addr, accepted) = spawn_server(&[Reply::Status(503), Reply::Status(502)]);
+    let response = bitreq::get(format!("http://{addr}")).with_retry(fast_retry()).send().unwrap();
+    assert_eq!(response.status_code, 200);
+    assert_eq!(accepted.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn returns_last_response_when_out_of_attempts() {
+    let replies = [Reply::Status(503); 3];
+    let (addr, accepted) = spawn_server(&replies);
+    let retry = fast_retry().with_max_attempts(2);
+    let response = bitreq::get(format!("http://{addr}")).with_retry(retry).send().unwrap();
+    assert_eq!(response.status_code, 503);
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn retries_closed_connection() {
+    let (addr, accepted) = spawn_server(&[Reply::Close]);
+    let response = bitreq::get(format!("http://{addr}")).with_retry(fast_retry()).send_lazy();
+    assert_eq!(response.unwrap().status_code, 200);
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn does_not_retry_other_statuses_or_without_policy() {
+    let (addr, accepted) = spawn_server(&[Reply::Status(500)]);
+    let response = bitreq::get(format!("http://{addr}")).with_retry(fast_retry()).send().unwrap();
+    assert_eq!(response.status_code, 500);
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+    let (addr, accepted) = spawn_server(&[Reply::Status(503)]);
+    let response = bitreq::get(format!("http://{addr}")).send().unwrap();
+    assert_eq!(response.status_code, 503);
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn retries_non_idempotent_only_if_enabled() {
+    let (addr, accepted) = spawn_server(&[Reply::Status(503)]);
+    let response = bitreq::post(format!("http://{addr}")).with_retry(fast_retry()).send().unwrap();
+    assert_eq!(response.status_code, 503);
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+    let (addr, accepted) = spawn_server(&[Reply::Status(503)]);
+    let retry = fast_retry().with_retry_non_idempotent(true);
+    let response = bitreq::post(format!("http://{addr}")).with_retry(retry).send().unwrap();
+    assert_eq!(response.status_code, 200);
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn respects_timeout() {
+    let (addr, accepted) = spawn_server(&[Reply::Status(503)]);
+    let retry = RetryPolicy::new().with_backoff(Duration::from_secs(10), Duration::from_secs(10));
+    let start = Instant::now();
+    let response = bitreq::get(format!("http://{addr}"))
+        .with_timeout(Duration::from_secs(2))
+        .with_retry(retry)
+        .send()
+        .unwrap();
+    assert_eq!(response.status_code, 503);
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn client_retries() {
+    let (addr, accepted) = spawn_server(&[Reply::Close, Reply::Status(504)]);
+    let client = Client::new(1);
+    let response = client.send(bitreq::get(format!("http://{addr}")).with_retry(fast_retry()));
+    assert_eq!(response.unwrap().status_code, 200);
+    assert_eq!(accepted.load(Ordering::SeqCst), 3);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_retries() {
+    let (addr, accepted) = spawn_server(&[Reply::Close, Reply::Status(503)]);
+    let response =
+        bitreq::get(format!("http://{addr}")).with_retry(fast_retry()).send_async().await;
+    assert_eq!(response.unwrap().status_code, 200);
+    assert_eq!(accepted.load(Ordering::SeqCst), 3);
+
+    let (addr, accepted) = spawn_server(&[Reply::Status(503)]);
+    let client = Client::new(1);
+    let request = bitreq::get(format!("http://{addr}")).with_retry(fast_retry());
+    let response = client.send_async(request).await;
+    assert_eq!(response.unwrap().status_code, 200);
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+}
@@ -1,17 +1,43 @@
 #![cfg(feature = "std")]
+// Each test file uses a different part of this module.
+#![allow(dead_code)]
 
 extern crate bitreq;
 extern crate tiny_http;
-use std::io::Read;
+use std::io::{self, Read};
+use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::str::FromStr;
 use std::sync::{Arc, Once};
 use std::thread;
 use std::time::Duration;
 
+use bitreq::RequestExt;
+
 use self::tiny_http::{Header, Method, Response, Server, StatusCode};
 
 static INIT: Once = Once::new();
 
+/// The body served by `/gzip` and `/deflate`, compressed if the client accepts it.
+pub const COMPRESSIBLE_BODY: &str = "Lorem ipsum dolor sit amet, lorem ipsum dolor sit amet. \
+    Lorem ipsum dolor sit amet, lorem ipsum dolor sit amet.";
+
+pub fn compress(encoding: &str, data: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+
+    if encoding == "gzip" {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    } else {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+}
+
 pub fn setup() {
     INIT.call_once(|| {
         let server = Arc::new(Server::http("localhost:35562").unwrap());
@@ -128,6 +154,12 @@ pub fn setup() {
                         respond!(response);
                     }
 
+                    Method::Post if url == "/redirect308" => {
+                        let response = Response::empty(308)
+                            .with_header(Header::from_str("Location: /echo").unwrap());
+                        respond!(response);
+                    }
+
                     Method::Get if url == "/infiniteredirect" => {
                         let response = Response::empty(301).with_header(
                             Header::from_bytes(
@@ -154,6 +186,51 @@ pub fn setup() {
                         respond!(response);
                     }
 
+                    Method::Get if url == "/set_cookies" => {
+                        let response = Response::empty(302)
+                            .with_header(Header::from_str("Location: /cookies").unwrap())
+                            .with_header(
+                                Header::from_str("Set-Cookie: session=abc; Path=/").unwrap(),
+                            )
+                            .with_header(
+                                Header::from_str("Set-Cookie: theme=dark; Path=/cookies; HttpOnly")
+                                    .unwrap(),
+                            );
+                        respond!(response);
+                    }
+                    Method::Get if url == "/cookies" => {
+                        let cookies = headers
+                            .iter()
+                            .filter(|header| header.field.equiv("Cookie"))
+                            .map(|header| header.value.as_str())
+                            .collect::<Vec<_>>();
+                        respond!(Response::from_string(cookies.join("\n")));
+                    }
+
+                    Method::Get | Method::Head if url == "/gzip" || url == "/deflate" => {
+                        let encoding = &url[1..];
+                        let accepted = headers.iter().any(|header| {
+                            header.field.equiv("Accept-Encoding")
+                                && header.value.as_str().contains(encoding)
+                        });
+                        let (body, headers) = if accepted {
+                            let header = format!("Content-Encoding: {}", encoding);
+                            let headers = vec![Header::from_str(&header).unwrap()];
+                            (compress(encoding, COMPRESSIBLE_BODY.as_bytes()), headers)
+                        } else {
+                            (COMPRESSIBLE_BODY.as_bytes().to_vec(), vec![])
+                        };
+                        // Send the gzip body chunked, and the deflate one with a Content-Length.
+                        let length = if encoding == "gzip" { None } else { Some(body.len()) };
+                        let body = std::io::Cursor::new(body);
+                        respond!(Response::new(StatusCode(200), headers, body, length, None));
+                    }
+                    Method::Get if url == "/gzip_bomb" => {
+                        let body = compress("gzip", &vec![0; 4 * 1024 * 1024]);
+                        let header = Header::from_str("Content-Encoding: gzip").unwrap();
+                        respond!(Response::from_data(body).with_header(header));
+                    }
+
                     Method::Post if url == "/echo" => {
                         respond!(Response::from_string(content));
                     }
@@ -201,6 +278,7 @@ pub fn setup() {
 
 pub fn url(req: &str) -> String { format!("http://localhost:35562{}", req) }
 
+static SYNC_CLIENT: std::sync::OnceLock<bitreq::Client> = std::sync::OnceLock::new();
 #[cfg(feature = "async")]
 static CLIENT: std::sync::OnceLock<bitreq::Client> = std::sync::OnceLock::new();
 #[cfg(feature = "async")]
@@ -216,6 +294,7 @@ pub async fn maybe_make_request(
         (Ok(resp), Ok(mut lazy_resp)) => {
             assert_eq!(lazy_resp.status_code, resp.status_code);
             assert_eq!(lazy_resp.reason_phrase, resp.reason_phrase);
+            assert_eq!(lazy_resp.redirects, resp.redirects);
             let mut lazy_bytes = Vec::new();
             lazy_resp.read_to_end(&mut lazy_bytes).unwrap();
             assert_eq!(lazy_bytes, resp.as_bytes());
@@ -224,6 +303,20 @@ pub async fn maybe_make_request(
         (res, lazy_res) => panic!("{res:?} != {}", lazy_res.is_err()),
     }
 
+    let sync_client = SYNC_CLIENT.get_or_init(|| bitreq::Client::new(100));
+    let sync_client_response = request.clone().send_with_client(sync_client);
+    match (&response, sync_client_response) {
+        (Ok(resp), Ok(client_resp)) => {
+            assert_eq!(client_resp.status_code, resp.status_code);
+            assert_eq!(client_resp.reason_phrase, resp.reason_phrase);
+            assert_eq!(client_resp.url, resp.url);
+            assert_eq!(client_resp.redirects, resp.redirects);
+            assert_eq!(client_resp.as_bytes(), resp.as_bytes());
+        }
+        (Err(e), Err(client_e)) => assert_eq!(format!("{e:?}"), format!("{client_e:?}")),
+        (res, client_res) => panic!("{res:?} != {client_res:?}"),
+    }
+
     #[cfg(feature = "async")]
     {
         if let Ok(resp) = &response {
@@ -264,14 +357,17 @@ pub async fn maybe_make_request(
             (Ok(resp), Ok(async_resp), Ok(mut lazy_resp), Ok(client_resp)) => {
                 assert_eq!(async_resp.status_code, resp.status_code);
                 assert_eq!(async_resp.reason_phrase, resp.reason_phrase);
+                assert_eq!(async_resp.redirects, resp.redirects);
                 assert_eq!(async_resp.as_bytes(), resp.as_bytes());
 
                 assert_eq!(client_resp.status_code, resp.status_code);
                 assert_eq!(client_resp.reason_phrase, resp.reason_phrase);
+                assert_eq!(client_resp.redirects, resp.redirects);
                 assert_eq!(client_resp.as_bytes(), resp.as_bytes());
 
                 assert_eq!(lazy_resp.status_code, resp.status_code);
                 assert_eq!(lazy_resp.reason_phrase, resp.reason_phrase);
+                assert_eq!(lazy_resp.redirects, resp.redirects);
                 let mut lazy_bytes = Vec::new();
                 lazy_resp.read_to_end(&mut lazy_bytes).unwrap();
                 assert_eq!(lazy_bytes, resp.as_bytes());
@@ -302,3 +398,38 @@ pub async fn get_status_code(request: bitreq::Request) -> i32 {
     let response = make_request(request).await;
     response.status_code
 }
+
+/// Spawns a server on a random local port which calls `handle` with the index and the socket of
+/// each connection it accepts, one after the other. Returns the server's address.
+pub fn spawn_raw_server<F>(mut handle: F) -> SocketAddr
+where
+    F: FnMut(usize, TcpStream) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for (i, sock) in listener.incoming().flatten().enumerate() {
+            handle(i, sock);
+        }
+    });
+    addr
+}
+
+/// Reads an HTTP request or response head, up to and including the empty line ending it.
+pub fn read_head(mut sock: &TcpStream) -> io::Result<String> {
+    let mut buf = Vec::new();
+    let mut byte = [0; 1];
+    while !buf.ends_with(b"\r\n\r\n") {
+        sock.read_exact(&mut byte)?;
+        buf.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Returns the value of the header called `name` in `head`, if any.
+pub fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (field, value) = line.split_once(':')?;
+        field.eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
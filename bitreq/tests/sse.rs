@@ -0,0 +1,94 @@
+#![cfg(feature = "std")]
+
+extern crate bitreq;
+
+mod setup;
+
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bitreq::{Error, Event, EventSource};
+
+use self::setup::{header, read_head, spawn_raw_server};
+
+/// The responses of the server, one per connection: a chunked stream ending in the middle of an
+/// event, a stream ended by closing the connection, and a request to stop reconnecting.
+const RESPONSES: [&str; 3] = [
+    "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream; charset=utf-8\r\n\
+     Transfer-Encoding: chunked\r\n\r\n\
+     13\r\n: hello\nretry: 50\n\n\r\n11\r\nid: 1\ndata: one\n\n\r\n\
+     d\r\ndata: partial\r\n0\r\n\r\n",
+    "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n\
+     event: block\nid: 2\ndata: two\ndata: lines\n\n",
+    "HTTP/1.1 204 No Content\r\n\r\n",
+];
+
+/// Spawns a server answering the `n`th connection with `responses[n]`, and closing any further
+/// ones. Returns the server's address and the `Last-Event-ID` header of each request.
+fn spawn_server(
+    responses: &'static [&'static str],
+) -> (SocketAddr, Arc<Mutex<Vec<Option<String>>>>) {
+    let last_event_ids = Arc::new(Mutex::new(Vec::new()));
+    let ids = Arc::clone(&last_event_ids);
+    let addr = spawn_raw_server(move |n, mut sock| {
+        let Some(response) = responses.get(n) else { return };
+        let head = read_head(&sock).unwrap_or_default();
+        ids.lock().unwrap().push(header(&head, "Last-Event-ID").map(String::from));
+        let _ = sock.write_all(response.as_bytes());
+    });
+    (addr, last_event_ids)
+}
+
+fn expected_events() -> Vec<(String, String, Option<String>)> {
+    vec![
+        ("message".to_string(), "one".to_string(), Some("1".to_string())),
+        ("block".to_string(), "two\nlines".to_string(), Some("2".to_string())),
+    ]
+}
+
+fn fields(event: Event) -> (String, String, Option<String>) { (event.event, event.data, event.id) }
+
+#[test]
+fn event_source_reconnects_and_resumes() {
+    let (addr, last_event_ids) = spawn_server(&RESPONSES);
+    let start = Instant::now();
+    let events = EventSource::new(bitreq::get(format!("http://{}/events", addr)))
+        .with_last_event_id("0")
+        .map(|event| event.map(fields))
+        .collect::<Result<Vec<_>, Error>>()
+        .unwrap();
+    assert_eq!(events, expected_events());
+    assert_eq!(
+        *last_event_ids.lock().unwrap(),
+        [Some("0".into()), Some("1".into()), Some("2".into())]
+    );
+    // The server asked to wait 50 milliseconds before each reconnection.
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(100) && elapsed < Duration::from_secs(3));
+}
+
+#[test]
+fn event_source_rejects_other_responses() {
+    static RESPONSES: [&str; 1] = ["HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"];
+    let (addr, _) = spawn_server(&RESPONSES);
+    let mut events = EventSource::new(bitreq::get(format!("http://{}/events", addr)));
+    assert!(matches!(events.next(), Some(Err(Error::NotAnEventStream(404)))));
+    assert!(events.next().is_none());
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_event_source_reconnects_and_resumes() {
+    let (addr, last_event_ids) = spawn_server(&RESPONSES);
+    let mut events = bitreq::AsyncEventSource::new(bitreq::get(format!("http://{}/events", addr)));
+    let mut received = Vec::new();
+    while let Some(event) = events.next_event().await {
+        received.push(fields(event.unwrap()));
+    }
+    assert_eq!(received, expected_events());
+    assert_eq!(events.last_event_id(), Some("2"));
+    assert_eq!(events.retry_delay(), Duration::from_millis(50));
+    assert_eq!(*last_event_ids.lock().unwrap(), [None, Some("1".into()), Some("2".into())]);
+}
@@ -0,0 +1,123 @@
+#![cfg(feature = "std")]
+
+extern crate bitreq;
+
+mod setup;
+
+use std::io::{self, Read, Write};
+use std::net::{Ipv6Addr, SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bitreq::{Client, Error, Resolver};
+
+use self::setup::{read_head, spawn_raw_server};
+
+const BODY: &[u8] = b"0123456789";
+
+/// Spawns a server answering each request with [`BODY`], sending the head right away and then
+/// one byte of the body every `delay`. Returns the server's address.
+fn spawn_server(delay: Duration) -> SocketAddr {
+    spawn_raw_server(move |_, sock| {
+        thread::spawn(move || serve(sock, delay));
+    })
+}
+
+fn serve(mut sock: TcpStream, delay: Duration) {
+    if read_head(&sock).is_err() {
+        return;
+    }
+    let head =
+        format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", BODY.len());
+    let _ = sock.write_all(head.as_bytes());
+    for byte in BODY {
+        thread::sleep(delay);
+        if sock.write_all(&[*byte]).is_err() {
+            return;
+        }
+    }
+}
+
+/// Resolves every host to an address of the discard-only prefix (RFC 6666), which doesn't
+/// answer.
+struct Blackhole;
+
+impl Resolver for Blackhole {
+    fn resolve(&self, _host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok(vec![(Ipv6Addr::new(0x100, 0, 0, 0, 0, 0, 0, 1), port).into()])
+    }
+}
+
+/// Checks that connecting to a [`Blackhole`] address gave up quickly, either at the connect
+/// timeout or right away if the address isn't routable at all.
+fn assert_connect_timeout<T: std::fmt::Debug>(result: Result<T, Error>, start: Instant) {
+    match result {
+        Err(Error::ConnectTimeout) => {}
+        Err(Error::IoError(e)) if e.kind() != io::ErrorKind::TimedOut => {}
+        res => panic!("unexpected result: {:?}", res),
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+fn slow_request(addr: SocketAddr) -> bitreq::Request {
+    bitreq::get(format!("http://{}/", addr))
+        .with_read_idle_timeout(Duration::from_millis(200))
+        .with_timeout(Duration::from_secs(10))
+}
+
+#[test]
+fn connect_timeout() {
+    let request = bitreq::get("http://node.bitreq.test/")
+        .with_resolver(Blackhole)
+        .with_connect_timeout(Duration::from_millis(300))
+        .with_timeout(Duration::from_secs(10));
+    let start = Instant::now();
+    assert_connect_timeout(request.clone().send(), start);
+    let start = Instant::now();
+    assert_connect_timeout(Client::new(1).send(request), start);
+}
+
+#[test]
+fn read_idle_timeout() {
+    let slow = spawn_server(Duration::from_secs(2));
+    let start = Instant::now();
+    assert!(matches!(slow_request(slow).send(), Err(Error::ReadIdleTimeout)));
+    assert!(matches!(Client::new(1).send(slow_request(slow)), Err(Error::ReadIdleTimeout)));
+    assert!(start.elapsed() < Duration::from_secs(3));
+
+    // The head arrives in time, so only reading the body times out.
+    let mut response = slow_request(slow).send_lazy().unwrap();
+    let err = response.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(matches!(Error::from(err), Error::ReadIdleTimeout));
+
+    // A response which takes longer than the read idle timeout in total is fine, as long as the
+    // server keeps sending.
+    let trickling = spawn_server(Duration::from_millis(50));
+    let response = slow_request(trickling).send().unwrap();
+    assert_eq!(response.as_bytes(), BODY);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_timeouts() {
+    let request = bitreq::get("http://node.bitreq.test/")
+        .with_resolver(Blackhole)
+        .with_connect_timeout(Duration::from_millis(300))
+        .with_timeout(Duration::from_secs(10));
+    let start = Instant::now();
+    assert_connect_timeout(request.send_async().await, start);
+
+    let slow = spawn_server(Duration::from_secs(2));
+    let start = Instant::now();
+    assert!(matches!(slow_request(slow).send_async().await, Err(Error::ReadIdleTimeout)));
+    let client = Client::new(1);
+    assert!(matches!(client.send_async(slow_request(slow)).await, Err(Error::ReadIdleTimeout)));
+    let mut response = slow_request(slow).send_streaming_async().await.unwrap();
+    assert!(matches!(response.chunk().await, Err(Error::ReadIdleTimeout)));
+    assert!(start.elapsed() < Duration::from_secs(3));
+
+    let trickling = spawn_server(Duration::from_millis(50));
+    let response = slow_request(trickling).send_async().await.unwrap();
+    assert_eq!(response.as_bytes(), BODY);
+}
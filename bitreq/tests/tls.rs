@@ -0,0 +1,160 @@
+#![cfg(any(
+    feature = "https-rustls",
+    feature = "https-rustls-probe",
+    feature = "https-native-tls"
+))]
+
+extern crate bitreq;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::thread;
+
+use bitreq::{Client, TlsConfig};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+
+const CA_PEM: &[u8] = include_bytes!("tls/ca.pem");
+const SERVER_PEM: &[u8] = include_bytes!("tls/server.pem");
+const SERVER_KEY: &[u8] = include_bytes!("tls/server.key");
+const CLIENT_PEM: &[u8] = include_bytes!("tls/client.pem");
+const CLIENT_KEY: &[u8] = include_bytes!("tls/client.key");
+
+/// SHA-256 of the DER-encoded `SubjectPublicKeyInfo` of `tls/server.pem`.
+const SERVER_SPKI_SHA256: [u8; 32] = [
+    0xfa, 0x54, 0xe9, 0xc6, 0xd1, 0xa5, 0x31, 0x57, 0x08, 0x14, 0xf2, 0xbb, 0x03, 0x9a, 0x3b, 0x25,
+    0x21, 0xfd, 0x6c, 0x38, 0x97, 0xe1, 0xc2, 0xd1, 0x48, 0x1e, 0x39, 0x18, 0x78, 0xe2, 0xd7, 0xe0,
+];
+
+/// Spawns an HTTPS server with a certificate issued by `tls/ca.pem`, answering every request with
+/// "ok" and closing the connection. If `require_client_cert` is set, clients must present a
+/// certificate issued by the same CA.
+fn spawn_https_server(require_client_cert: bool) -> SocketAddr {
+    let certs = CertificateDer::pem_slice_iter(SERVER_PEM).collect::<Result<Vec<_>, _>>().unwrap();
+    let key = PrivateKeyDer::from_pem_slice(SERVER_KEY).unwrap();
+    let builder = ServerConfig::builder();
+    let builder = if require_client_cert {
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from_pem_slice(CA_PEM).unwrap()).unwrap();
+        builder.with_client_cert_verifier(
+            WebPkiClientVerifier::builder(Arc::new(roots)).build().unwrap(),
+        )
+    } else {
+        builder.with_no_client_auth()
+    };
+    let config = Arc::new(builder.with_single_cert(certs, key).unwrap());
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for sock in listener.incoming().flatten() {
+            let conn = ServerConnection::new(Arc::clone(&config)).unwrap();
+            let mut stream = StreamOwned::new(conn, sock);
+            let mut buf = Vec::new();
+            let mut byte = [0; 1];
+            while !buf.ends_with(b"\r\n\r\n") {
+                if stream.read_exact(&mut byte).is_err() {
+                    break;
+                }
+                buf.push(byte[0]);
+            }
+            if buf.ends_with(b"\r\n\r\n") {
+                let response =
+                    "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+                let _ = stream.write_all(response.as_bytes());
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+            }
+        }
+    });
+    addr
+}
+
+fn trusting_ca() -> TlsConfig { TlsConfig::new().with_root_certificates_pem(CA_PEM) }
+
+#[test]
+fn untrusted_server_is_rejected() {
+    let addr = spawn_https_server(false);
+    assert!(bitreq::get(format!("https://{addr}")).send().is_err());
+    let tls = TlsConfig::new().with_built_in_roots(false);
+    assert!(bitreq::get(format!("https://{addr}")).with_tls_config(tls).send().is_err());
+}
+
+#[test]
+fn custom_root_certificate() {
+    let addr = spawn_https_server(false);
+    let response = bitreq::get(format!("https://{addr}")).with_tls_config(trusting_ca()).send();
+    assert_eq!(response.unwrap().as_str().unwrap(), "ok");
+
+    let tls = trusting_ca().with_built_in_roots(false);
+    let response = bitreq::get(format!("https://{addr}")).with_tls_config(tls).send();
+    assert_eq!(response.unwrap().as_str().unwrap(), "ok");
+}
+
+#[test]
+fn client_certificate() {
+    let addr = spawn_https_server(true);
+    assert!(bitreq::get(format!("https://{addr}")).with_tls_config(trusting_ca()).send().is_err());
+
+    let tls = trusting_ca().with_client_identity_pem(CLIENT_PEM, CLIENT_KEY);
+    let response = bitreq::get(format!("https://{addr}")).with_tls_config(tls).send();
+    assert_eq!(response.unwrap().as_str().unwrap(), "ok");
+}
+
+#[test]
+fn pinned_public_key() {
+    let addr = spawn_https_server(false);
+    let tls = trusting_ca().with_pinned_spki_sha256(SERVER_SPKI_SHA256);
+    let response = bitreq::get(format!("https://{addr}")).with_tls_config(tls).send();
+    assert_eq!(response.unwrap().as_str().unwrap(), "ok");
+
+    let tls = trusting_ca().with_pinned_spki_sha256([0; 32]);
+    assert!(bitreq::get(format!("https://{addr}")).with_tls_config(tls).send().is_err());
+
+    // Pins don't replace the usual certificate checks.
+    let tls = TlsConfig::new().with_pinned_spki_sha256(SERVER_SPKI_SHA256);
+    assert!(bitreq::get(format!("https://{addr}")).with_tls_config(tls).send().is_err());
+}
+
+#[test]
+fn client_tls_config() {
+    let addr = spawn_https_server(true);
+    let tls = trusting_ca().with_client_identity_pem(CLIENT_PEM, CLIENT_KEY);
+    let client = Client::new(1).with_tls_config(tls);
+    let response = client.send(bitreq::get(format!("https://{addr}")));
+    assert_eq!(response.unwrap().as_str().unwrap(), "ok");
+
+    // A request's own settings take precedence.
+    let request = bitreq::get(format!("https://{addr}")).with_tls_config(trusting_ca());
+    assert!(client.send(request).is_err());
+}
+
+#[cfg(any(
+    feature = "async-https-rustls",
+    feature = "async-https-rustls-probe",
+    feature = "async-https-native-tls"
+))]
+#[tokio::test(flavor = "multi_thread")]
+async fn async_tls_config() {
+    let addr = spawn_https_server(true);
+    let request = bitreq::get(format!("https://{addr}")).with_tls_config(trusting_ca());
+    assert!(request.send_async().await.is_err());
+
+    let tls = trusting_ca()
+        .with_client_identity_pem(CLIENT_PEM, CLIENT_KEY)
+        .with_pinned_spki_sha256(SERVER_SPKI_SHA256);
+    let response = bitreq::get(format!("https://{addr}")).with_tls_config(tls).send_async().await;
+    assert_eq!(response.unwrap().as_str().unwrap(), "ok");
+
+    let tls = trusting_ca()
+        .with_client_identity_pem(CLIENT_PEM, CLIENT_KEY)
+        .with_pinned_spki_sha256([0; 32]);
+    assert!(bitreq::get(format!("https://{addr}"))
+        .with_tls_config(tls)
+        .send_async()
+        .await
+        .is_err());
+}
@@ -0,0 +1,119 @@
+#![cfg(all(unix, feature = "std"))]
+
+extern crate bitreq;
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use bitreq::Client;
+
+/// Spawns a server on a new Unix socket which keeps connections alive and answers each request
+/// with its method, path and `Host` header. Returns the socket's path and the number of
+/// connections accepted so far.
+fn spawn_server(name: &str) -> (PathBuf, Arc<AtomicUsize>) {
+    let path = std::env::temp_dir().join(format!("bitreq-{}-{}.sock", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&accepted);
+    thread::spawn(move || {
+        for sock in listener.incoming().flatten() {
+            counter.fetch_add(1, Ordering::SeqCst);
+            thread::spawn(move || serve(sock));
+        }
+    });
+    (path, accepted)
+}
+
+fn serve(sock: UnixStream) {
+    let mut reader = BufReader::new(&sock);
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        let mut host = String::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Host: ") {
+                host = value.trim().to_string();
+            }
+        }
+        let mut parts = request_line.split(' ');
+        let body = format!("{} {} {}", parts.next().unwrap(), parts.next().unwrap(), host);
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: keep-alive\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        if (&sock).write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+#[test]
+fn sends_over_unix_socket() {
+    let (path, _) = spawn_server("send");
+    let request = bitreq::get("http://localhost/v1/info?all=1").with_unix_socket(&path);
+    let response = request.clone().send().unwrap();
+    assert_eq!(response.as_str().unwrap(), "GET /v1/info?all=1 localhost");
+
+    let mut body = String::new();
+    let mut lazy = request.send_lazy().unwrap();
+    std::io::Read::read_to_string(&mut lazy, &mut body).unwrap();
+    assert_eq!(body, "GET /v1/info?all=1 localhost");
+
+    let request = bitreq::get("https://localhost/").with_unix_socket(&path);
+    assert!(matches!(request.send(), Err(bitreq::Error::IoError(_))));
+}
+
+#[test]
+fn client_reuses_unix_connections() {
+    let (path, accepted) = spawn_server("client");
+    let client = Client::new(1);
+    for _ in 0..3 {
+        let request = bitreq::post("http://docker/ping").with_unix_socket(&path);
+        assert_eq!(client.send(request).unwrap().as_str().unwrap(), "POST /ping docker");
+    }
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn async_sends_over_unix_socket() {
+    let (path, accepted) = spawn_server("async");
+    let request = bitreq::get("http://localhost/a").with_unix_socket(&path);
+    let response = request.clone().send_async().await.unwrap();
+    assert_eq!(response.as_str().unwrap(), "GET /a localhost");
+
+    let mut response = request.clone().send_streaming_async().await.unwrap();
+    assert_eq!(response.chunk().await.unwrap().unwrap(), b"GET /a localhost");
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+
+    // Prime the pool, so that the pipelined requests all share the connection.
+    let client = Client::new(1);
+    client.send_async(request).await.unwrap();
+    let handles = (0..5)
+        .map(|i| {
+            let client = client.clone();
+            let request = bitreq::get(format!("http://localhost/{}", i))
+                .with_unix_socket(&path)
+                .with_pipelining();
+            tokio::spawn(async move { client.send_async(request).await })
+        })
+        .collect::<Vec<_>>();
+    for (i, handle) in handles.into_iter().enumerate() {
+        let response = handle.await.unwrap().unwrap();
+        assert_eq!(response.as_str().unwrap(), format!("GET /{} localhost", i));
+    }
+    assert_eq!(accepted.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,156 @@
+#![cfg(all(feature = "websocket", feature = "async"))]
+
+extern crate bitreq;
+
+mod setup;
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::engine::Engine;
+use bitcoin_hashes::{sha1, Hash};
+use bitreq::{AsyncWebSocket, CloseFrame, Error, Message, WebSocket};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use self::setup::{header, read_head, spawn_raw_server};
+
+/// What the server received: the payload of each pong and the status code of each close frame.
+type Received = Arc<Mutex<Vec<String>>>;
+
+/// Spawns a WebSocket echo server, serving each connection on a runtime of its own, which pings
+/// the client before echoing each message, and splits echoed text messages into two fragments.
+fn spawn_echo_server() -> (SocketAddr, Received) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&received);
+    let addr = spawn_raw_server(move |_, sock| {
+        let log = Arc::clone(&log);
+        std::thread::spawn(move || {
+            let runtime =
+                tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            let _ = runtime.block_on(echo(sock, log));
+        });
+    });
+    (addr, received)
+}
+
+async fn echo(sock: std::net::TcpStream, log: Received) -> std::io::Result<()> {
+    let head = read_head(&sock)?;
+    sock.set_nonblocking(true)?;
+    let mut sock = TcpStream::from_std(sock)?;
+    let key = header(&head, "Sec-WebSocket-Key").unwrap();
+    let accept =
+        sha1::Hash::hash(format!("{}258EAFA5-E914-47DA-95CA-C5AB0DC85B11", key).as_bytes());
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        STANDARD.encode(accept.to_byte_array())
+    );
+    sock.write_all(response.as_bytes()).await?;
+
+    loop {
+        let (opcode, payload) = read_frame(&mut sock).await?;
+        match opcode {
+            0x1 => {
+                sock.write_all(&frame(0x89, b"ping")).await?;
+                let (first, rest) = payload.split_at(payload.len() / 2);
+                // A text frame without the FIN bit, then a final continuation frame.
+                sock.write_all(&frame(0x01, first)).await?;
+                sock.write_all(&frame(0x80, rest)).await?;
+            }
+            0x2 => sock.write_all(&frame(0x82, &payload)).await?,
+            0xA => log.lock().unwrap().push(format!("pong {}", String::from_utf8_lossy(&payload))),
+            0x8 => {
+                let code = u16::from_be_bytes([payload[0], payload[1]]);
+                log.lock().unwrap().push(format!("close {}", code));
+                sock.write_all(&frame(0x88, &payload[..2])).await?;
+                return Ok(());
+            }
+            _ => panic!("unexpected opcode {}", opcode),
+        }
+    }
+}
+
+/// Reads a masked frame from the client, returning its opcode and unmasked payload.
+async fn read_frame(sock: &mut TcpStream) -> std::io::Result<(u8, Vec<u8>)> {
+    let head = [sock.read_u8().await?, sock.read_u8().await?];
+    assert_eq!(head[0] & 0x80, 0x80, "the client doesn't fragment messages");
+    assert_eq!(head[1] & 0x80, 0x80, "the client masks frames");
+    let length = match head[1] & 0x7F {
+        126 => u64::from(sock.read_u16().await?),
+        127 => sock.read_u64().await?,
+        length => u64::from(length),
+    };
+    let mut mask = [0; 4];
+    sock.read_exact(&mut mask).await?;
+    let mut payload = vec![0; length as usize];
+    sock.read_exact(&mut payload).await?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((head[0] & 0x0F, payload))
+}
+
+/// Encodes an unmasked frame, `first_byte` holding the FIN bit and the opcode.
+fn frame(first_byte: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![first_byte];
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn big_message() -> Vec<u8> { (0..70_000).map(|i| (i % 251) as u8).collect() }
+
+#[test]
+fn websocket_echoes_messages() {
+    let (addr, received) = spawn_echo_server();
+    let mut socket = WebSocket::connect(bitreq::get(format!("ws://{}/echo", addr))).unwrap();
+
+    socket.send(Message::Text("hello, world".to_string())).unwrap();
+    assert_eq!(socket.read().unwrap(), Message::Ping(b"ping".to_vec()));
+    assert_eq!(socket.read().unwrap(), Message::Text("hello, world".to_string()));
+    socket.send(Message::Binary(big_message())).unwrap();
+    assert_eq!(socket.read().unwrap(), Message::Binary(big_message()));
+
+    let frame = CloseFrame { code: 4000, reason: "done".to_string() };
+    socket.close(Some(frame)).unwrap();
+    assert_eq!(*received.lock().unwrap(), ["pong ping", "close 4000"]);
+}
+
+#[test]
+fn websocket_handshake_fails_without_upgrade() {
+    let addr = spawn_raw_server(|_, mut sock| {
+        use std::io::Write;
+        let _ = read_head(&sock);
+        let _ = sock.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+    });
+    let result = WebSocket::connect(bitreq::get(format!("ws://{}/echo", addr)));
+    assert!(matches!(result, Err(Error::WebSocketHandshakeFailed(404))));
+}
+
+#[tokio::test]
+async fn async_websocket_echoes_messages() {
+    let (addr, received) = spawn_echo_server();
+    let request = bitreq::get(format!("ws://{}/echo", addr));
+    let mut socket = AsyncWebSocket::connect(request).await.unwrap();
+
+    socket.send(Message::Text("hello".to_string())).await.unwrap();
+    assert_eq!(socket.read().await.unwrap(), Message::Ping(b"ping".to_vec()));
+    assert_eq!(socket.read().await.unwrap(), Message::Text("hello".to_string()));
+    socket.send(Message::Binary(big_message())).await.unwrap();
+    assert_eq!(socket.read().await.unwrap(), Message::Binary(big_message()));
+
+    socket.close(None).await.unwrap();
+    assert_eq!(*received.lock().unwrap(), ["pong ping", "close 1000"]);
+}
@@ -125,6 +125,11 @@ impl Client {
         }
     }
 
+    /// Creates a client sending requests over `transport`, e.g. a mock transport in tests.
+    pub fn with_transport<T: jsonrpc::client_async::Transport>(transport: T) -> Self {
+        Self { inner: jsonrpc::client_async::Client::with_transport(transport) }
+    }
+
     /// Call an RPC `method` with given `args` list.
     pub async fn call<T: for<'a> serde::de::Deserialize<'a>>(
         &self,
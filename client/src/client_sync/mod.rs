@@ -111,6 +111,11 @@ macro_rules! define_jsonrpc_bitreq_client {
                 Ok(Self { inner })
             }
 
+            /// Creates a client sending requests over `transport`, e.g. a mock transport in tests.
+            pub fn with_transport<T: jsonrpc::client::Transport>(transport: T) -> Self {
+                Self { inner: jsonrpc::client::Client::with_transport(transport) }
+            }
+
             /// Call an RPC `method` with given `args` list.
             pub fn call<T: for<'a> serde::de::Deserialize<'a>>(
                 &self,
@@ -157,3 +162,32 @@ macro_rules! impl_client_check_expected_server_version {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use jsonrpc::error::RpcError;
+    use jsonrpc::mock::MockTransport;
+    use serde_json::json;
+
+    use super::v17::Client;
+    use super::Error;
+
+    #[test]
+    fn client_with_mock_transport() {
+        let genesis = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        let mock = MockTransport::new();
+        mock.respond("getblockcount", 800_000).respond("getblockhash", json!(genesis));
+        mock.error(
+            "getblock",
+            RpcError { code: -5, message: "Block not found".into(), data: None },
+        );
+        let client = Client::with_transport(mock.clone());
+
+        assert_eq!(client.get_block_count().unwrap().0, 800_000);
+        let hash = client.get_block_hash(0).unwrap().block_hash().unwrap();
+        assert_eq!(hash.to_string(), genesis);
+        let result = client.get_block_verbose_zero(hash);
+        assert!(matches!(result, Err(Error::JsonRpc(jsonrpc::Error::Rpc(ref e))) if e.code == -5));
+        assert_eq!(mock.calls("getblockhash"), [json!([0])]);
+    }
+}
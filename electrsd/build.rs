@@ -8,7 +8,7 @@ mod download {
 #[cfg(feature = "download")]
 mod download {
     use std::fs::File;
-    use std::io::{BufRead, BufReader, Cursor};
+    use std::io::{BufRead, BufReader};
     use std::os::unix::fs::PermissionsExt;
     use std::path::Path;
     use std::str::FromStr;
@@ -51,13 +51,17 @@ mod download {
                 std::env::var("ELECTRSD_DOWNLOAD_ENDPOINT").unwrap_or(GITHUB_URL.to_string());
             let url = format!("{}/{}", download_endpoint, download_filename);
 
-            let downloaded_bytes = bitreq::get(url.clone()).send()?.into_bytes();
+            // Streams to disk, resuming if the connection drops, and checks the hash before the
+            // archive appears under its name. Goes through the proxy set in the usual environment
+            // variables, e.g. `HTTPS_PROXY`, if any.
+            std::fs::create_dir_all(&electrs_exe_home)?;
+            let archive_path = electrs_exe_home.join(&download_filename);
+            let request = bitreq::get(url).with_proxy_config(bitreq::Proxy::from_env());
+            bitreq::Download::new(request, &archive_path)
+                .with_sha256(expected_hash.to_byte_array())
+                .send()?;
 
-            let downloaded_hash = sha256::Hash::hash(&downloaded_bytes);
-            assert_eq!(expected_hash, downloaded_hash, "expected hash of {} is not matching", url);
-            let cursor = Cursor::new(downloaded_bytes);
-
-            let mut archive = zip::ZipArchive::new(cursor)?;
+            let mut archive = zip::ZipArchive::new(File::open(&archive_path)?)?;
             let mut file = archive.by_index(0)?;
             let parent = destination_filename.parent().unwrap();
             std::fs::create_dir_all(parent)?;
@@ -67,6 +71,9 @@ mod download {
                 &destination_filename,
                 std::fs::Permissions::from_mode(0o755),
             )?;
+            drop(file);
+            drop(archive);
+            std::fs::remove_file(&archive_path)?;
         }
         Ok(())
     }
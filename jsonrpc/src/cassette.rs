@@ -0,0 +1,473 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements transports recording the requests sent over another transport and
+//! their responses to a JSON "cassette" file, and replaying them later without a server.
+//!
+//! This allows testing code that talks to bitcoind deterministically and offline: run the test
+//! once against a real server with a [`RecordingTransport`], then replace it with a
+//! [`ReplayTransport`] reading the cassette.
+//!
+//! Both transports implement [`client::Transport`] and, with the `client_async` feature,
+//! [`client_async::Transport`]. The requests of a batch are recorded, and replayed, one by one.
+//! Notifications aren't answered, so they aren't recorded either and are ignored when replaying.
+//!
+//! ```no_run
+//! use jsonrpc::cassette::{MatchMode, RecordingTransport, ReplayTransport};
+//! use jsonrpc::simple_http::SimpleHttpTransport;
+//! use jsonrpc::Client;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let transport = SimpleHttpTransport::builder().url("localhost:18443")?.build();
+//! let client = Client::with_transport(RecordingTransport::new(transport, "uptime.json"));
+//! let recorded: u64 = client.call("uptime", None)?;
+//!
+//! let client = Client::with_transport(ReplayTransport::load("uptime.json", MatchMode::Exact)?);
+//! let replayed: u64 = client.call("uptime", None)?;
+//! assert_eq!(recorded, replayed);
+//! # Ok(()) }
+//! ```
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::sync::Mutex;
+use std::{error, fmt, io, path};
+
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+#[cfg(feature = "client_async")]
+use crate::client_async::{self, BoxFuture};
+use crate::error::RpcError;
+use crate::{client, Request, Response};
+
+/// A request and its response, as stored in a cassette.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Interaction {
+    method: String,
+    /// The parameters of the request, `null` if it had none.
+    params: Value,
+    result: Option<Box<RawValue>>,
+    error: Option<RpcError>,
+}
+
+impl Interaction {
+    fn new(request: &Request, response: &Response) -> Result<Interaction, Error> {
+        Ok(Interaction {
+            method: request.method.to_owned(),
+            params: params(request)?,
+            result: response.result.clone(),
+            error: response.error.clone(),
+        })
+    }
+
+    /// Returns the recorded response, answering the request with `id`.
+    fn response(&self, id: Value) -> Response {
+        Response {
+            result: self.result.clone(),
+            error: self.error.clone(),
+            id,
+            jsonrpc: Some(String::from("2.0")),
+        }
+    }
+}
+
+/// The contents of a cassette file.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Cassette {
+    interactions: Vec<Interaction>,
+}
+
+fn params(request: &Request) -> Result<Value, Error> {
+    match request.params {
+        Some(params) => Ok(serde_json::from_str(params.get())?),
+        None => Ok(Value::Null),
+    }
+}
+
+/// Transport recording the requests sent over another transport and their responses to a
+/// cassette file.
+///
+/// The cassette is written again after each response, so it's complete even if the test using
+/// the transport fails. Requests failing at the transport level aren't recorded.
+#[derive(Debug)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    path: path::PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl<T> RecordingTransport<T> {
+    /// Creates a new [`RecordingTransport`] sending requests over `inner`, and recording them to
+    /// the cassette at `path`. An existing cassette is overwritten.
+    pub fn new<P: AsRef<path::Path>>(inner: T, path: P) -> RecordingTransport<T> {
+        RecordingTransport {
+            inner,
+            path: path.as_ref().to_path_buf(),
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    /// Records the responses to `requests`, matching them by ID.
+    fn record(&self, requests: &[Request], responses: &[Response]) -> Result<(), Error> {
+        // No part of this codebase should panic, so unwrapping a mutex lock is fine
+        let mut cassette = self.cassette.lock().expect("poisoned mutex");
+        for request in requests {
+            if let Some(response) = responses.iter().find(|r| Some(&r.id) == request.id.as_ref()) {
+                cassette.interactions.push(Interaction::new(request, response)?);
+            }
+        }
+        let mut file = BufWriter::new(File::create(&self.path)?);
+        serde_json::to_writer_pretty(&mut file, &*cassette)?;
+        Ok(file.flush()?)
+    }
+
+    fn fmt_cassette(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "recording to {}", self.path.display())
+    }
+}
+
+impl<T: client::Transport> client::Transport for RecordingTransport<T> {
+    fn send_request(&self, req: Request) -> Result<Response, crate::Error> {
+        let response = self.inner.send_request(req.clone())?;
+        self.record(&[req], std::slice::from_ref(&response))?;
+        Ok(response)
+    }
+
+    fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+        let responses = self.inner.send_batch(reqs)?;
+        self.record(reqs, &responses)?;
+        Ok(responses)
+    }
+
+    fn send_notification(&self, req: Request) -> Result<(), crate::Error> {
+        self.inner.send_notification(req)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { self.fmt_cassette(f) }
+}
+
+#[cfg(feature = "client_async")]
+impl<T: client_async::Transport> client_async::Transport for RecordingTransport<T> {
+    fn send_request<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<Response, crate::Error>> {
+        Box::pin(async move {
+            let response = self.inner.send_request(req.clone()).await?;
+            self.record(&[req], std::slice::from_ref(&response))?;
+            Ok(response)
+        })
+    }
+
+    fn send_batch<'a>(
+        &'a self,
+        reqs: &'a [Request<'a>],
+    ) -> BoxFuture<'a, Result<Vec<Response>, crate::Error>> {
+        Box::pin(async move {
+            let responses = self.inner.send_batch(reqs).await?;
+            self.record(reqs, &responses)?;
+            Ok(responses)
+        })
+    }
+
+    fn send_notification<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<(), crate::Error>> {
+        self.inner.send_notification(req)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { self.fmt_cassette(f) }
+}
+
+/// How a [`ReplayTransport`] finds the recording of a request.
+///
+/// Each recording is replayed at most once, so a request sent twice needs to have been
+/// recorded twice.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchMode {
+    /// Replays the first unused recording of a request with the same method and parameters.
+    #[default]
+    Exact,
+    /// Replays the first unused recording of a request with the same method, whatever its
+    /// parameters.
+    Method,
+    /// Replays the recordings in order, each of which must be of a request with the same method
+    /// and parameters.
+    Ordered,
+}
+
+/// Transport answering requests with the responses recorded in a cassette file by a
+/// [`RecordingTransport`], without contacting any server.
+///
+/// A request without a matching recording fails with [`Error::NoRecording`].
+#[derive(Debug)]
+pub struct ReplayTransport {
+    path: path::PathBuf,
+    mode: MatchMode,
+    interactions: Vec<Interaction>,
+    /// Whether each interaction was replayed already.
+    used: Mutex<Vec<bool>>,
+}
+
+impl ReplayTransport {
+    /// Creates a new [`ReplayTransport`] replaying the cassette at `path`.
+    pub fn load<P: AsRef<path::Path>>(path: P, mode: MatchMode) -> Result<ReplayTransport, Error> {
+        let path = path.as_ref().to_path_buf();
+        let cassette: Cassette = serde_json::from_reader(BufReader::new(File::open(&path)?))?;
+        let used = Mutex::new(vec![false; cassette.interactions.len()]);
+        Ok(ReplayTransport { path, mode, interactions: cassette.interactions, used })
+    }
+
+    /// Returns the number of recordings which haven't been replayed yet.
+    pub fn remaining(&self) -> usize {
+        // No part of this codebase should panic, so unwrapping a mutex lock is fine
+        self.used.lock().expect("poisoned mutex").iter().filter(|used| !**used).count()
+    }
+
+    fn replay(&self, request: &Request) -> Result<Response, Error> {
+        let params = params(request)?;
+        let matches = |i: &usize| {
+            let interaction = &self.interactions[*i];
+            interaction.method == request.method
+                && (self.mode == MatchMode::Method || interaction.params == params)
+        };
+
+        // No part of this codebase should panic, so unwrapping a mutex lock is fine
+        let mut used = self.used.lock().expect("poisoned mutex");
+        let mut unused = (0..used.len()).filter(|i| !used[*i]);
+        let index = match self.mode {
+            MatchMode::Ordered => unused.next().filter(matches),
+            MatchMode::Exact | MatchMode::Method => unused.find(matches),
+        };
+        let index = index
+            .ok_or_else(|| Error::NoRecording { method: request.method.to_owned(), params })?;
+        used[index] = true;
+        Ok(self.interactions[index].response(request.id.clone().unwrap_or_default()))
+    }
+
+    fn replay_batch(&self, requests: &[Request]) -> Result<Vec<Response>, Error> {
+        requests.iter().filter(|request| request.id.is_some()).map(|r| self.replay(r)).collect()
+    }
+
+    fn fmt_cassette(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "replaying {}", self.path.display())
+    }
+}
+
+impl client::Transport for ReplayTransport {
+    fn send_request(&self, req: Request) -> Result<Response, crate::Error> {
+        Ok(self.replay(&req)?)
+    }
+
+    fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+        Ok(self.replay_batch(reqs)?)
+    }
+
+    fn send_notification(&self, _: Request) -> Result<(), crate::Error> { Ok(()) }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { self.fmt_cassette(f) }
+}
+
+#[cfg(feature = "client_async")]
+impl client_async::Transport for ReplayTransport {
+    fn send_request<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<Response, crate::Error>> {
+        let response = self.replay(&req).map_err(crate::Error::from);
+        Box::pin(async move { response })
+    }
+
+    fn send_batch<'a>(
+        &'a self,
+        reqs: &'a [Request<'a>],
+    ) -> BoxFuture<'a, Result<Vec<Response>, crate::Error>> {
+        let responses = self.replay_batch(reqs).map_err(crate::Error::from);
+        Box::pin(async move { responses })
+    }
+
+    fn send_notification<'a>(&'a self, _: Request<'a>) -> BoxFuture<'a, Result<(), crate::Error>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { self.fmt_cassette(f) }
+}
+
+/// Error that can occur while recording or replaying a cassette.
+#[derive(Debug)]
+pub enum Error {
+    /// An error occurred reading or writing the cassette file.
+    Io(io::Error),
+    /// JSON error.
+    Json(serde_json::Error),
+    /// The cassette has no unused recording matching a request.
+    NoRecording {
+        /// The method of the request.
+        method: String,
+        /// The parameters of the request, `null` if it had none.
+        params: Value,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        use Error::*;
+
+        match *self {
+            Io(ref e) => write!(f, "cassette file error: {}", e),
+            Json(ref e) => write!(f, "JSON error: {}", e),
+            NoRecording { ref method, ref params } =>
+                write!(f, "no recording for request {} with params {}", method, params),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        use self::Error::*;
+
+        match *self {
+            Io(ref e) => Some(e),
+            Json(ref e) => Some(e),
+            NoRecording { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self { Error::Io(e) }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self { Error::Json(e) }
+}
+
+impl From<Error> for crate::Error {
+    fn from(e: Error) -> crate::Error {
+        match e {
+            Error::Json(e) => crate::Error::Json(e),
+            e => crate::Error::Transport(Box::new(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::error::{standard_error, StandardError};
+    use crate::{arg, Client};
+
+    /// Answers `echo` with its parameters and other methods with an error, counting requests.
+    #[derive(Default)]
+    struct EchoTransport(Mutex<usize>);
+
+    impl EchoTransport {
+        fn answer(&self, req: &Request) -> Response {
+            *self.0.lock().unwrap() += 1;
+            let result = match req.method {
+                "echo" => Ok(serde_json::from_str(req.params.map_or("null", |p| p.get())).unwrap()),
+                _ => Err(standard_error(StandardError::MethodNotFound, None)),
+            };
+            crate::error::result_to_response(result, req.id.clone().unwrap_or_default())
+        }
+    }
+
+    impl client::Transport for EchoTransport {
+        fn send_request(&self, req: Request) -> Result<Response, crate::Error> {
+            Ok(self.answer(&req))
+        }
+
+        fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+            Ok(reqs.iter().filter(|req| req.id.is_some()).map(|req| self.answer(req)).collect())
+        }
+
+        fn fmt_target(&self, _: &mut fmt::Formatter) -> fmt::Result { Ok(()) }
+    }
+
+    fn cassette_path(name: &str) -> path::PathBuf {
+        env::temp_dir().join(format!("jsonrpc_cassette_{}_{}.json", name, process::id()))
+    }
+
+    /// Records `echo [1]`, `echo [2]` and a failed `nope` request to a cassette, along with a
+    /// notification which isn't recorded.
+    fn record(path: &path::Path) {
+        let client =
+            Client::with_transport(RecordingTransport::new(EchoTransport::default(), path));
+        let (one, two) = (arg([1]), arg([2]));
+        assert_eq!(client.call::<Vec<u8>>("echo", Some(&one)).unwrap(), [1]);
+        let requests = [
+            client.build_request("echo", Some(&two)),
+            client.build_notification("echo", Some(&one)),
+            client.build_request("nope", None),
+        ];
+        let responses = client.send_batch(&requests).unwrap();
+        assert!(responses[1].is_none());
+        assert_eq!(responses[2].as_ref().unwrap().error.as_ref().unwrap().code, -32601);
+    }
+
+    fn replay(path: &path::Path, mode: MatchMode) -> Client {
+        Client::with_transport(ReplayTransport::load(path, mode).unwrap())
+    }
+
+    fn assert_no_recording(result: Result<Vec<u8>, crate::Error>) {
+        let Err(crate::Error::Transport(e)) = result else { panic!("replayed {:?}", result) };
+        assert!(matches!(*e.downcast::<Error>().unwrap(), Error::NoRecording { .. }));
+    }
+
+    #[test]
+    fn replays_exact_requests() {
+        let path = cassette_path("exact");
+        record(&path);
+        assert_eq!(ReplayTransport::load(&path, MatchMode::Exact).unwrap().remaining(), 3);
+
+        let client = replay(&path, MatchMode::Exact);
+        let (one, two) = (arg([1]), arg([2]));
+        assert_eq!(client.call::<Vec<u8>>("echo", Some(&two)).unwrap(), [2]);
+        assert_eq!(client.call::<Vec<u8>>("echo", Some(&one)).unwrap(), [1]);
+        assert!(client.call::<()>("nope", None).is_err());
+        // Each recording is replayed once.
+        assert_no_recording(client.call("echo", Some(&one)));
+
+        let client = replay(&path, MatchMode::Exact);
+        assert_no_recording(client.call("echo", Some(&arg([3]))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replays_by_method_or_order() {
+        let path = cassette_path("modes");
+        record(&path);
+
+        let client = replay(&path, MatchMode::Method);
+        assert_eq!(client.call::<Vec<u8>>("echo", Some(&arg([3]))).unwrap(), [1]);
+        assert_eq!(client.call::<Vec<u8>>("echo", None).unwrap(), [2]);
+        assert_no_recording(client.call("echo", None));
+
+        let client = replay(&path, MatchMode::Ordered);
+        assert_no_recording(client.call("echo", Some(&arg([2]))));
+        let client = replay(&path, MatchMode::Ordered);
+        let requests = [arg([1]), arg([2])];
+        let requests: Vec<_> =
+            requests.iter().map(|params| client.build_request("echo", Some(params))).collect();
+        let responses = client.send_batch(&requests).unwrap();
+        assert_eq!(responses[1].as_ref().unwrap().result::<Vec<u8>>().unwrap(), [2]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "client_async")]
+    #[test]
+    fn replays_async_requests() {
+        let path = cassette_path("async");
+        record(&path);
+
+        let transport = ReplayTransport::load(&path, MatchMode::Ordered).unwrap();
+        let client = crate::client_async::Client::with_transport(transport);
+        let result = futures::executor::block_on(client.call::<Vec<u8>>("echo", Some(&arg([1]))));
+        assert_eq!(result.unwrap(), [1]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -23,6 +23,13 @@ pub trait Transport: Send + Sync + 'static {
     fn send_request(&self, _: Request) -> Result<Response, Error>;
     /// Sends a batch of RPC requests over the transport.
     fn send_batch(&self, _: &[Request]) -> Result<Vec<Response>, Error>;
+    /// Sends an RPC notification, i.e. a request without an ID, over the transport.
+    ///
+    /// The default implementation sends it like a request and ignores the response, so
+    /// transports talking to servers which don't answer notifications should override it.
+    fn send_notification(&self, req: Request) -> Result<(), Error> {
+        self.send_request(req).map(|_| ())
+    }
     /// Formats the target of this transport. I.e. the URL/socket/...
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result;
 }
@@ -48,7 +55,16 @@ impl Client {
     /// [`crate::arg`] or [`crate::try_arg`].
     pub fn build_request<'a>(&self, method: &'a str, params: Option<&'a RawValue>) -> Request<'a> {
         let nonce = self.nonce.fetch_add(1, atomic::Ordering::Relaxed);
-        Request { method, params, id: serde_json::Value::from(nonce), jsonrpc: Some("2.0") }
+        Request { method, params, id: Some(serde_json::Value::from(nonce)), jsonrpc: Some("2.0") }
+    }
+
+    /// Builds a notification, i.e. a request without an ID, which the server doesn't answer.
+    pub fn build_notification<'a>(
+        &self,
+        method: &'a str,
+        params: Option<&'a RawValue>,
+    ) -> Request<'a> {
+        Request { method, params, id: None, jsonrpc: Some("2.0") }
     }
 
     /// Sends a request to a client.
@@ -56,31 +72,49 @@ impl Client {
         self.transport.send_request(request)
     }
 
+    /// Sends a notification to the client.
+    ///
+    /// To construct the arguments, one can use one of the shorthand methods
+    /// [`crate::arg`] or [`crate::try_arg`].
+    pub fn send_notification(&self, method: &str, args: Option<&RawValue>) -> Result<(), Error> {
+        self.transport.send_notification(self.build_notification(method, args))
+    }
+
     /// Sends a batch of requests to the client.
     ///
     /// Note that the requests need to have valid IDs, so it is advised to create the requests
-    /// with [`Client::build_request`].
+    /// with [`Client::build_request`]. The batch may also hold notifications, created with
+    /// [`Client::build_notification`]. A batch of only notifications is sent one notification
+    /// at a time, since servers don't answer such a batch at all.
     ///
     /// # Returns
     ///
     /// The return vector holds the response for the request at the corresponding index. If no
-    /// response was provided, it's [`None`].
+    /// response was provided, it's [`None`], which is always the case for notifications.
     pub fn send_batch(&self, requests: &[Request]) -> Result<Vec<Option<Response>>, Error> {
         if requests.is_empty() {
             return Err(Error::EmptyBatch);
         }
 
+        let calls = requests.iter().filter(|r| r.id.is_some()).count();
+        if calls == 0 {
+            for request in requests {
+                self.transport.send_notification(request.clone())?;
+            }
+            return Ok(vec![None; requests.len()]);
+        }
+
         // If the request body is invalid JSON, the response is a single response object.
         // We ignore this case since we are confident we are producing valid JSON.
         let responses = self.transport.send_batch(requests)?;
-        if responses.len() > requests.len() {
+        if responses.len() > calls {
             return Err(Error::WrongBatchResponseSize);
         }
 
         //TODO(stevenroose) check if the server preserved order to avoid doing the mapping
 
         // First index responses by ID and catch duplicate IDs.
-        let mut by_id = HashMap::with_capacity(requests.len());
+        let mut by_id = HashMap::with_capacity(calls);
         for resp in responses.into_iter() {
             let id = HashableValue(Cow::Owned(resp.id.clone()));
             if let Some(dup) = by_id.insert(id, resp) {
@@ -88,8 +122,10 @@ impl Client {
             }
         }
         // Match responses to the requests.
-        let results =
-            requests.iter().map(|r| by_id.remove(&HashableValue(Cow::Borrowed(&r.id)))).collect();
+        let results = requests
+            .iter()
+            .map(|r| r.id.as_ref().and_then(|id| by_id.remove(&HashableValue(Cow::Borrowed(id)))))
+            .collect();
 
         // Since we're also just producing the first duplicate ID, we can also just produce the
         // first incorrect ID in case there are multiple.
@@ -116,12 +152,24 @@ impl Client {
         if response.jsonrpc.is_some() && response.jsonrpc != Some(From::from("2.0")) {
             return Err(Error::VersionMismatch);
         }
-        if response.id != id {
+        if Some(&response.id) != id.as_ref() {
             return Err(Error::NonceMismatch);
         }
 
         response.result()
     }
+
+    /// Makes a request with named parameters and deserializes the response.
+    ///
+    /// The parameters are sent as a JSON object, which Bitcoin Core accepts since v0.14.
+    pub fn call_named<R: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        method: &str,
+        args: &serde_json::Map<String, Value>,
+    ) -> Result<R, Error> {
+        let args = crate::try_arg(args)?;
+        self.call(method, Some(&args))
+    }
 }
 
 impl fmt::Debug for Client {
@@ -204,6 +252,33 @@ mod tests {
         fn fmt_target(&self, _: &mut fmt::Formatter) -> fmt::Result { Ok(()) }
     }
 
+    /// Answers calls with their parameters and records the methods of notifications.
+    #[derive(Default)]
+    struct EchoTransport {
+        notifications: sync::Arc<sync::Mutex<Vec<String>>>,
+    }
+
+    impl Transport for EchoTransport {
+        fn send_request(&self, req: Request) -> Result<Response, Error> { Ok(echo(&req)) }
+        fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, Error> {
+            Ok(reqs.iter().filter(|req| req.id.is_some()).map(echo).collect())
+        }
+        fn send_notification(&self, req: Request) -> Result<(), Error> {
+            self.notifications.lock().unwrap().push(req.method.to_owned());
+            Ok(())
+        }
+        fn fmt_target(&self, _: &mut fmt::Formatter) -> fmt::Result { Ok(()) }
+    }
+
+    fn echo(req: &Request) -> Response {
+        Response {
+            result: req.params.map(ToOwned::to_owned),
+            error: None,
+            id: req.id.clone().unwrap(),
+            jsonrpc: Some(String::from("2.0")),
+        }
+    }
+
     #[test]
     fn sanity() {
         let client = Client::with_transport(DummyTransport);
@@ -215,6 +290,39 @@ mod tests {
         assert!(req1.id != req2.id);
     }
 
+    #[test]
+    fn named_params() {
+        let client = Client::with_transport(EchoTransport::default());
+        let params = serde_json::json!({"blockhash": "00", "verbosity": 0});
+        let result: Value = client.call_named("getblock", params.as_object().unwrap()).unwrap();
+        assert_eq!(result, params);
+    }
+
+    #[test]
+    fn notifications() {
+        let transport = EchoTransport::default();
+        let notifications = sync::Arc::clone(&transport.notifications);
+        let client = Client::with_transport(transport);
+        let notification = client.build_notification("ping", None);
+        assert_eq!(notification.id, None);
+        client.send_notification("ping", None).unwrap();
+
+        let (one, three) = (crate::arg(1), crate::arg(3));
+        let requests = [
+            client.build_request("echo", Some(&one)),
+            notification,
+            client.build_request("echo", Some(&three)),
+        ];
+        let responses = client.send_batch(&requests).unwrap();
+        assert_eq!(responses[0].as_ref().unwrap().result::<u64>().unwrap(), 1);
+        assert!(responses[1].is_none());
+        assert_eq!(responses[2].as_ref().unwrap().result::<u64>().unwrap(), 3);
+
+        let requests = [client.build_notification("a", None), client.build_notification("b", None)];
+        assert_eq!(client.send_batch(&requests).unwrap().len(), 2);
+        assert_eq!(*notifications.lock().unwrap(), ["ping", "a", "b"]);
+    }
+
     #[test]
     fn hash_value() {
         let val = HashableValue(Cow::Owned(Value::from_str("null").unwrap()));
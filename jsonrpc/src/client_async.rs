@@ -33,6 +33,13 @@ pub trait Transport: Send + Sync + 'static {
         &'a self,
         reqs: &'a [Request<'a>],
     ) -> BoxFuture<'a, Result<Vec<Response>, Error>>;
+    /// Sends an RPC notification, i.e. a request without an ID, over the transport.
+    ///
+    /// The default implementation sends it like a request and ignores the response, so
+    /// transports talking to servers which don't answer notifications should override it.
+    fn send_notification<'a>(&'a self, req: Request<'a>) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move { self.send_request(req).await.map(|_| ()) })
+    }
     /// Formats the target of this transport. I.e. the URL/socket/...
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result;
 }
@@ -60,11 +67,20 @@ impl Client {
         Request {
             method,
             params,
-            id: serde_json::Value::from(nonce),
+            id: Some(serde_json::Value::from(nonce)),
             jsonrpc: Some(JSONRPC_VERSION),
         }
     }
 
+    /// Builds a notification, i.e. a request without an ID, which the server doesn't answer.
+    pub fn build_notification<'a>(
+        &self,
+        method: &'a str,
+        params: Option<&'a RawValue>,
+    ) -> Request<'a> {
+        Request { method, params, id: None, jsonrpc: Some(JSONRPC_VERSION) }
+    }
+
     /// Sends a request to a client.
     pub fn send_request<'a>(
         &'a self,
@@ -73,15 +89,29 @@ impl Client {
         self.transport.send_request(request)
     }
 
+    /// Sends a notification to the client.
+    ///
+    /// To construct the arguments, one can use one of the shorthand methods
+    /// [`crate::arg`] or [`crate::try_arg`].
+    pub async fn send_notification(
+        &self,
+        method: &str,
+        args: Option<&RawValue>,
+    ) -> Result<(), Error> {
+        self.transport.send_notification(self.build_notification(method, args)).await
+    }
+
     /// Sends a batch of requests to the client.
     ///
     /// Note that the requests need to have valid IDs, so it is advised to create the requests
-    /// with [`Client::build_request`].
+    /// with [`Client::build_request`]. The batch may also hold notifications, created with
+    /// [`Client::build_notification`]. A batch of only notifications is sent one notification
+    /// at a time, since servers don't answer such a batch at all.
     ///
     /// # Returns
     ///
     /// The return vector holds the response for the request at the corresponding index. If no
-    /// response was provided, it's [`None`].
+    /// response was provided, it's [`None`], which is always the case for notifications.
     pub async fn send_batch(
         &self,
         requests: &[Request<'_>],
@@ -90,17 +120,25 @@ impl Client {
             return Err(Error::EmptyBatch);
         }
 
+        let calls = requests.iter().filter(|r| r.id.is_some()).count();
+        if calls == 0 {
+            for request in requests {
+                self.transport.send_notification(request.clone()).await?;
+            }
+            return Ok(vec![None; requests.len()]);
+        }
+
         // If the request body is invalid JSON, the response is a single response object.
         // We ignore this case since we are confident we are producing valid JSON.
         let responses = self.transport.send_batch(requests).await?;
-        if responses.len() > requests.len() {
+        if responses.len() > calls {
             return Err(Error::WrongBatchResponseSize);
         }
 
         //TODO(stevenroose) check if the server preserved order to avoid doing the mapping
 
         // First index responses by ID and catch duplicate IDs.
-        let mut by_id = HashMap::with_capacity(requests.len());
+        let mut by_id = HashMap::with_capacity(calls);
         for resp in responses.into_iter() {
             let id = HashableValue(Cow::Owned(resp.id.clone()));
             if let Some(dup) = by_id.insert(id, resp) {
@@ -108,8 +146,10 @@ impl Client {
             }
         }
         // Match responses to the requests.
-        let results =
-            requests.iter().map(|r| by_id.remove(&HashableValue(Cow::Borrowed(&r.id)))).collect();
+        let results = requests
+            .iter()
+            .map(|r| r.id.as_ref().and_then(|id| by_id.remove(&HashableValue(Cow::Borrowed(id)))))
+            .collect();
 
         // Since we're also just producing the first duplicate ID, we can also just produce the
         // first incorrect ID in case there are multiple.
@@ -136,12 +176,24 @@ impl Client {
         if response.jsonrpc.is_some() && response.jsonrpc.as_deref() != Some(JSONRPC_VERSION) {
             return Err(Error::VersionMismatch);
         }
-        if response.id != id {
+        if Some(&response.id) != id.as_ref() {
             return Err(Error::NonceMismatch);
         }
 
         response.result()
     }
+
+    /// Makes a request with named parameters and deserializes the response.
+    ///
+    /// The parameters are sent as a JSON object, which Bitcoin Core accepts since v0.14.
+    pub async fn call_named<R: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        method: &str,
+        args: &serde_json::Map<String, Value>,
+    ) -> Result<R, Error> {
+        let args = crate::try_arg(args)?;
+        self.call(method, Some(&args)).await
+    }
 }
 
 impl fmt::Debug for Client {
@@ -215,6 +267,7 @@ mod tests {
     use std::str::FromStr;
     use std::sync;
 
+    use futures::executor::block_on;
     use futures::future::{err, ok};
 
     use super::*;
@@ -235,6 +288,41 @@ mod tests {
         fn fmt_target(&self, _: &mut fmt::Formatter) -> fmt::Result { Ok(()) }
     }
 
+    /// Answers calls with their parameters and records the methods of notifications.
+    #[derive(Default)]
+    struct EchoTransport {
+        notifications: sync::Arc<sync::Mutex<Vec<String>>>,
+    }
+
+    impl Transport for EchoTransport {
+        fn send_request<'a>(&'a self, req: Request<'a>) -> BoxFuture<'a, Result<Response, Error>> {
+            Box::pin(ok(echo(&req)))
+        }
+
+        fn send_batch<'a>(
+            &'a self,
+            reqs: &'a [Request<'a>],
+        ) -> BoxFuture<'a, Result<Vec<Response>, Error>> {
+            Box::pin(ok(reqs.iter().filter(|req| req.id.is_some()).map(echo).collect()))
+        }
+
+        fn send_notification<'a>(&'a self, req: Request<'a>) -> BoxFuture<'a, Result<(), Error>> {
+            self.notifications.lock().unwrap().push(req.method.to_owned());
+            Box::pin(ok(()))
+        }
+
+        fn fmt_target(&self, _: &mut fmt::Formatter) -> fmt::Result { Ok(()) }
+    }
+
+    fn echo(req: &Request) -> Response {
+        Response {
+            result: req.params.map(ToOwned::to_owned),
+            error: None,
+            id: req.id.clone().unwrap(),
+            jsonrpc: Some(String::from(JSONRPC_VERSION)),
+        }
+    }
+
     #[test]
     fn sanity() {
         let client = Client::with_transport(DummyTransport);
@@ -246,6 +334,38 @@ mod tests {
         assert!(req1.id != req2.id);
     }
 
+    #[test]
+    fn named_params() {
+        let client = Client::with_transport(EchoTransport::default());
+        let params = serde_json::json!({"blockhash": "00", "verbosity": 0});
+        let result: Value =
+            block_on(client.call_named("getblock", params.as_object().unwrap())).unwrap();
+        assert_eq!(result, params);
+    }
+
+    #[test]
+    fn notifications() {
+        let transport = EchoTransport::default();
+        let notifications = sync::Arc::clone(&transport.notifications);
+        let client = Client::with_transport(transport);
+        block_on(client.send_notification("ping", None)).unwrap();
+
+        let (one, three) = (crate::arg(1), crate::arg(3));
+        let requests = [
+            client.build_request("echo", Some(&one)),
+            client.build_notification("ping", None),
+            client.build_request("echo", Some(&three)),
+        ];
+        let responses = block_on(client.send_batch(&requests)).unwrap();
+        assert_eq!(responses[0].as_ref().unwrap().result::<u64>().unwrap(), 1);
+        assert!(responses[1].is_none());
+        assert_eq!(responses[2].as_ref().unwrap().result::<u64>().unwrap(), 3);
+
+        let requests = [client.build_notification("a", None), client.build_notification("b", None)];
+        assert_eq!(block_on(client.send_batch(&requests)).unwrap().len(), 2);
+        assert_eq!(*notifications.lock().unwrap(), ["ping", "a", "b"]);
+    }
+
     #[test]
     fn hash_value() {
         let val = HashableValue(Cow::Owned(Value::from_str("null").unwrap()));
@@ -0,0 +1,678 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements a transport spreading requests over several endpoints, e.g. replicas
+//! of a bitcoind node, see [`FailoverTransport`].
+
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::{error, fmt};
+
+#[cfg(feature = "client_async")]
+use crate::client_async::{self, BoxFuture};
+use crate::{client, Request, Response};
+
+/// Read-only methods, which are sent to another endpoint after a transport error by default.
+/// Other methods may have side effects which the first endpoint already carried out, such as
+/// mining a block or sending a transaction, so they aren't unless allowed explicitly.
+const RETRYABLE_METHODS: &[&str] = &[
+    "analyzepsbt",
+    "decodepsbt",
+    "decoderawtransaction",
+    "decodescript",
+    "deriveaddresses",
+    "estimatesmartfee",
+    "getaddressinfo",
+    "getbalance",
+    "getbalances",
+    "getbestblockhash",
+    "getblock",
+    "getblockchaininfo",
+    "getblockcount",
+    "getblockfilter",
+    "getblockhash",
+    "getblockheader",
+    "getblockstats",
+    "getchaintips",
+    "getchaintxstats",
+    "getconnectioncount",
+    "getdeploymentinfo",
+    "getdescriptorinfo",
+    "getdifficulty",
+    "getindexinfo",
+    "getmempoolancestors",
+    "getmempooldescendants",
+    "getmempoolentry",
+    "getmempoolinfo",
+    "getmininginfo",
+    "getnettotals",
+    "getnetworkhashps",
+    "getnetworkinfo",
+    "getpeerinfo",
+    "getrawmempool",
+    "getrawtransaction",
+    "gettransaction",
+    "gettxout",
+    "gettxoutproof",
+    "gettxspendingprevout",
+    "getwalletinfo",
+    "listtransactions",
+    "listunspent",
+    "listwallets",
+    "testmempoolaccept",
+    "uptime",
+    "validateaddress",
+    "verifymessage",
+    "verifytxoutproof",
+];
+
+const DEFAULT_HEALTH_CHECK: &str = "getblockcount";
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How a [`FailoverTransport`] chooses the endpoint a request is sent to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Policy {
+    /// Sends requests to the first healthy endpoint, the others being backups.
+    #[default]
+    Failover,
+    /// Sends requests to each healthy endpoint in turn.
+    RoundRobin,
+    /// Sends requests to the healthy endpoint with the fewest requests in flight.
+    LeastOutstanding,
+}
+
+/// Transport sending requests over one of several inner transports, according to a [`Policy`].
+///
+/// An endpoint failing with a transport error is taken out of rotation, and the request is
+/// sent to the next endpoint if its method is known to be read-only, such as `getblockcount`,
+/// or allowed with [`Builder::retry_method`]. Once its backoff, which doubles with each consecutive
+/// failure, elapsed, the endpoint has to answer a health check request before being used again.
+/// While all endpoints are in backoff, the one whose backoff ends first is checked and used
+/// regardless.
+#[derive(Debug)]
+pub struct FailoverTransport<T> {
+    endpoints: Vec<Endpoint<T>>,
+    policy: Policy,
+    /// The endpoint a [`Policy::RoundRobin`] transport starts with for the next request.
+    next: AtomicUsize,
+    /// The methods which may be sent to another endpoint after a transport error.
+    retryable: HashSet<String>,
+    health_check: String,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+#[derive(Debug)]
+struct Endpoint<T> {
+    transport: T,
+    outstanding: AtomicUsize,
+    health: Mutex<Health>,
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    /// The number of consecutive failures of the endpoint, zero if it's healthy.
+    failures: u32,
+    /// When the endpoint may be checked again, if it's unhealthy.
+    retry_at: Option<Instant>,
+}
+
+/// An endpoint a request may be sent to.
+struct Candidate {
+    index: usize,
+    /// Whether the endpoint has to pass a health check first.
+    check: bool,
+}
+
+/// Decrements the number of requests in flight to an endpoint when dropped, even if the future
+/// sending the request is.
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl<'a> InFlight<'a> {
+    fn new(outstanding: &'a AtomicUsize) -> InFlight<'a> {
+        outstanding.fetch_add(1, Ordering::SeqCst);
+        InFlight(outstanding)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) { self.0.fetch_sub(1, Ordering::SeqCst); }
+}
+
+impl<T> FailoverTransport<T> {
+    /// Returns a builder for [`FailoverTransport`].
+    pub fn builder() -> Builder<T> { Builder::new() }
+
+    /// Returns the endpoints to try for a request, in order.
+    fn candidates(&self) -> Vec<Candidate> {
+        let now = Instant::now();
+        // The endpoint whose backoff ends first, with when it does.
+        let mut soonest: Option<(Instant, usize)> = None;
+        let mut candidates: Vec<Candidate> = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .filter_map(|(index, endpoint)| {
+                // No part of this codebase should panic, so unwrapping a mutex lock is fine
+                match endpoint.health.lock().expect("poisoned mutex").retry_at {
+                    None => Some(Candidate { index, check: false }),
+                    Some(retry_at) if retry_at <= now => Some(Candidate { index, check: true }),
+                    Some(retry_at) => {
+                        if soonest.map_or(true, |(at, _)| retry_at < at) {
+                            soonest = Some((retry_at, index));
+                        }
+                        None
+                    }
+                }
+            })
+            .collect();
+        // Rather than failing without trying while all endpoints are in backoff, the one which
+        // would be back first is checked early.
+        if candidates.is_empty() {
+            candidates.extend(soonest.map(|(_, index)| Candidate { index, check: true }));
+        }
+        match self.policy {
+            Policy::Failover => {}
+            Policy::RoundRobin if !candidates.is_empty() => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+                candidates.rotate_left(start);
+            }
+            Policy::RoundRobin => {}
+            Policy::LeastOutstanding => candidates.sort_by_key(|candidate| {
+                self.endpoints[candidate.index].outstanding.load(Ordering::SeqCst)
+            }),
+        }
+        candidates
+    }
+
+    /// Whether requests for all `methods` may be sent to another endpoint after a failure.
+    fn retryable<'a>(&self, mut methods: impl Iterator<Item = &'a str>) -> bool {
+        methods.all(|method| self.retryable.contains(method))
+    }
+
+    fn health_check_request(&self) -> Request<'_> {
+        Request {
+            method: &self.health_check,
+            params: None,
+            id: Some(serde_json::Value::from(0)),
+            jsonrpc: Some("2.0"),
+        }
+    }
+
+    fn record_success(&self, index: usize) {
+        // No part of this codebase should panic, so unwrapping a mutex lock is fine
+        *self.endpoints[index].health.lock().expect("poisoned mutex") = Health::default();
+    }
+
+    fn record_failure(&self, index: usize) {
+        // No part of this codebase should panic, so unwrapping a mutex lock is fine
+        let mut health = self.endpoints[index].health.lock().expect("poisoned mutex");
+        let backoff = self.initial_backoff.saturating_mul(1 << health.failures.min(16));
+        health.failures = health.failures.saturating_add(1);
+        health.retry_at = Some(Instant::now() + backoff.min(self.max_backoff));
+    }
+
+    /// Records the outcome of a request sent to an endpoint, continuing with the error if the
+    /// request should be sent to the next endpoint.
+    fn outcome<R>(
+        &self,
+        index: usize,
+        result: Result<R, crate::Error>,
+        retryable: bool,
+    ) -> ControlFlow<Result<R, crate::Error>, crate::Error> {
+        match result {
+            Err(e @ crate::Error::Transport(_)) => {
+                self.record_failure(index);
+                if retryable {
+                    ControlFlow::Continue(e)
+                } else {
+                    ControlFlow::Break(Err(e))
+                }
+            }
+            result => {
+                self.record_success(index);
+                ControlFlow::Break(result)
+            }
+        }
+    }
+
+    fn fmt_endpoints(
+        &self,
+        f: &mut fmt::Formatter,
+        fmt_target: impl Fn(&T, &mut fmt::Formatter) -> fmt::Result,
+    ) -> fmt::Result {
+        write!(f, "failover(")?;
+        for (i, endpoint) in self.endpoints.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            fmt_target(&endpoint.transport, f)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl<T: client::Transport> FailoverTransport<T> {
+    fn send<R>(
+        &self,
+        retryable: bool,
+        send: impl Fn(&T) -> Result<R, crate::Error>,
+    ) -> Result<R, crate::Error> {
+        let mut last_error = None;
+        for Candidate { index, check } in self.candidates() {
+            let endpoint = &self.endpoints[index];
+            let _in_flight = InFlight::new(&endpoint.outstanding);
+            if check {
+                if let Err(e) = endpoint.transport.send_request(self.health_check_request()) {
+                    self.record_failure(index);
+                    last_error = Some(e);
+                    continue;
+                }
+            }
+            match self.outcome(index, send(&endpoint.transport), retryable) {
+                ControlFlow::Break(result) => return result,
+                ControlFlow::Continue(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::NoHealthyEndpoint.into()))
+    }
+}
+
+impl<T: client::Transport> client::Transport for FailoverTransport<T> {
+    fn send_request(&self, req: Request) -> Result<Response, crate::Error> {
+        let retryable = self.retryable(std::iter::once(req.method));
+        self.send(retryable, |transport| transport.send_request(req.clone()))
+    }
+
+    fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+        let retryable = self.retryable(reqs.iter().map(|req| req.method));
+        self.send(retryable, |transport| transport.send_batch(reqs))
+    }
+
+    fn send_notification(&self, req: Request) -> Result<(), crate::Error> {
+        let retryable = self.retryable(std::iter::once(req.method));
+        self.send(retryable, |transport| transport.send_notification(req.clone()))
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_endpoints(f, |transport, f| transport.fmt_target(f))
+    }
+}
+
+#[cfg(feature = "client_async")]
+impl<T: client_async::Transport> FailoverTransport<T> {
+    async fn send_async<'a, R>(
+        &'a self,
+        retryable: bool,
+        send: impl Fn(&'a T) -> BoxFuture<'a, Result<R, crate::Error>>,
+    ) -> Result<R, crate::Error> {
+        let mut last_error = None;
+        for Candidate { index, check } in self.candidates() {
+            let endpoint = &self.endpoints[index];
+            let _in_flight = InFlight::new(&endpoint.outstanding);
+            if check {
+                let request = self.health_check_request();
+                if let Err(e) = endpoint.transport.send_request(request).await {
+                    self.record_failure(index);
+                    last_error = Some(e);
+                    continue;
+                }
+            }
+            match self.outcome(index, send(&endpoint.transport).await, retryable) {
+                ControlFlow::Break(result) => return result,
+                ControlFlow::Continue(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| Error::NoHealthyEndpoint.into()))
+    }
+}
+
+#[cfg(feature = "client_async")]
+impl<T: client_async::Transport> client_async::Transport for FailoverTransport<T> {
+    fn send_request<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<Response, crate::Error>> {
+        let retryable = self.retryable(std::iter::once(req.method));
+        Box::pin(self.send_async(retryable, move |transport| transport.send_request(req.clone())))
+    }
+
+    fn send_batch<'a>(
+        &'a self,
+        reqs: &'a [Request<'a>],
+    ) -> BoxFuture<'a, Result<Vec<Response>, crate::Error>> {
+        let retryable = self.retryable(reqs.iter().map(|req| req.method));
+        Box::pin(self.send_async(retryable, move |transport| transport.send_batch(reqs)))
+    }
+
+    fn send_notification<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<(), crate::Error>> {
+        let retryable = self.retryable(std::iter::once(req.method));
+        Box::pin(
+            self.send_async(retryable, move |transport| transport.send_notification(req.clone())),
+        )
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_endpoints(f, |transport, f| transport.fmt_target(f))
+    }
+}
+
+/// Builder for [`FailoverTransport`].
+#[derive(Debug)]
+pub struct Builder<T> {
+    tp: FailoverTransport<T>,
+}
+
+impl<T> Builder<T> {
+    /// Constructs a new [`Builder`] without endpoints, using [`Policy::Failover`].
+    pub fn new() -> Builder<T> {
+        Builder {
+            tp: FailoverTransport {
+                endpoints: Vec::new(),
+                policy: Policy::default(),
+                next: AtomicUsize::new(0),
+                retryable: RETRYABLE_METHODS.iter().map(|m| m.to_string()).collect(),
+                health_check: DEFAULT_HEALTH_CHECK.to_owned(),
+                initial_backoff: DEFAULT_INITIAL_BACKOFF,
+                max_backoff: DEFAULT_MAX_BACKOFF,
+            },
+        }
+    }
+
+    /// Adds an endpoint, after the ones added before.
+    pub fn endpoint(mut self, transport: T) -> Self {
+        self.tp.endpoints.push(Endpoint {
+            transport,
+            outstanding: AtomicUsize::new(0),
+            health: Mutex::new(Health::default()),
+        });
+        self
+    }
+
+    /// Sets the policy choosing the endpoint a request is sent to.
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.tp.policy = policy;
+        self
+    }
+
+    /// Sets how long a failed endpoint is out of rotation after its first failure, and at most
+    /// after consecutive ones. Defaults to 1 second and 1 minute.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.tp.initial_backoff = initial;
+        self.tp.max_backoff = max;
+        self
+    }
+
+    /// Sets the method, called without parameters, checking an endpoint is healthy again before
+    /// sending requests to it. Defaults to `getblockcount`.
+    pub fn health_check(mut self, method: &str) -> Self {
+        self.tp.health_check = method.to_owned();
+        self
+    }
+
+    /// Allows requests for `method` to be sent to another endpoint after a transport error,
+    /// e.g. for a read-only method missing from the defaults, or for `sendrawtransaction`,
+    /// which fails harmlessly if the transaction was already sent.
+    pub fn retry_method(mut self, method: &str) -> Self {
+        self.tp.retryable.insert(method.to_owned());
+        self
+    }
+
+    /// Prevents requests for `method` from being sent to another endpoint after a transport
+    /// error, as it has side effects.
+    pub fn no_retry_method(mut self, method: &str) -> Self {
+        self.tp.retryable.remove(method);
+        self
+    }
+
+    /// Builds the final [`FailoverTransport`].
+    pub fn build(self) -> FailoverTransport<T> { self.tp }
+}
+
+impl<T> Default for Builder<T> {
+    fn default() -> Self { Builder::new() }
+}
+
+/// Error that can occur while using the failover transport.
+#[derive(Debug)]
+pub enum Error {
+    /// The transport has no endpoint to send the request to.
+    NoHealthyEndpoint,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Error::NoHealthyEndpoint => f.write_str("no healthy endpoint to send the request to"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<Error> for crate::Error {
+    fn from(e: Error) -> crate::Error { crate::Error::Transport(Box::new(e)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::{io, thread};
+
+    use super::*;
+    use crate::Client;
+
+    /// Answers requests with its name unless it's down, logging the requests it receives.
+    #[derive(Clone)]
+    struct Node {
+        name: &'static str,
+        up: Arc<AtomicBool>,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Node {
+        fn new(name: &'static str, log: &Arc<Mutex<Vec<String>>>) -> Node {
+            Node { name, up: Arc::new(AtomicBool::new(true)), log: Arc::clone(log) }
+        }
+
+        fn set_up(&self, up: bool) { self.up.store(up, Ordering::SeqCst) }
+
+        fn answer(&self, req: &Request) -> Result<Response, crate::Error> {
+            self.log.lock().unwrap().push(format!("{} {}", self.name, req.method));
+            if !self.up.load(Ordering::SeqCst) {
+                let e = io::Error::new(io::ErrorKind::ConnectionRefused, self.name);
+                return Err(crate::Error::Transport(Box::new(e)));
+            }
+            Ok(crate::error::result_to_response(
+                Ok(self.name.into()),
+                req.id.clone().unwrap_or_default(),
+            ))
+        }
+    }
+
+    impl client::Transport for Node {
+        fn send_request(&self, req: Request) -> Result<Response, crate::Error> { self.answer(&req) }
+
+        fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+            reqs.iter().map(|req| self.answer(req)).collect()
+        }
+
+        fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str(self.name) }
+    }
+
+    #[cfg(feature = "client_async")]
+    impl client_async::Transport for Node {
+        fn send_request<'a>(
+            &'a self,
+            req: Request<'a>,
+        ) -> BoxFuture<'a, Result<Response, crate::Error>> {
+            let response = self.answer(&req);
+            Box::pin(async move { response })
+        }
+
+        fn send_batch<'a>(
+            &'a self,
+            reqs: &'a [Request<'a>],
+        ) -> BoxFuture<'a, Result<Vec<Response>, crate::Error>> {
+            let responses = reqs.iter().map(|req| self.answer(req)).collect();
+            Box::pin(async move { responses })
+        }
+
+        fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str(self.name) }
+    }
+
+    fn nodes() -> (Node, Node, Arc<Mutex<Vec<String>>>) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        (Node::new("a", &log), Node::new("b", &log), log)
+    }
+
+    fn drain(log: &Mutex<Vec<String>>) -> Vec<String> { log.lock().unwrap().drain(..).collect() }
+
+    #[test]
+    fn fails_over_to_backup() {
+        let (a, b, log) = nodes();
+        let backoff = Duration::from_millis(50);
+        let transport = FailoverTransport::builder()
+            .endpoint(a.clone())
+            .endpoint(b)
+            .backoff(backoff, backoff)
+            .health_check("uptime")
+            .build();
+        let client = Client::with_transport(transport);
+        assert_eq!(format!("{:?}", client), "jsonrpc::Client(failover(a, b))");
+
+        a.set_up(false);
+        assert_eq!(client.call::<String>("getblockcount", None).unwrap(), "b");
+        assert_eq!(client.call::<String>("getblockcount", None).unwrap(), "b");
+        assert_eq!(drain(&log), ["a getblockcount", "b getblockcount", "b getblockcount"]);
+
+        // The primary is back in rotation once it passed a health check after its backoff.
+        a.set_up(true);
+        thread::sleep(backoff);
+        assert_eq!(client.call::<String>("getblockcount", None).unwrap(), "a");
+        assert_eq!(drain(&log), ["a uptime", "a getblockcount"]);
+    }
+
+    #[test]
+    fn fails_without_healthy_endpoint() {
+        let (a, b, log) = nodes();
+        let transport =
+            FailoverTransport::builder().endpoint(a.clone()).endpoint(b.clone()).build();
+        let client = Client::with_transport(transport);
+
+        a.set_up(false);
+        b.set_up(false);
+        // The error of the last endpoint tried is returned.
+        let Err(crate::Error::Transport(e)) = client.call::<String>("getblockcount", None) else {
+            panic!("endpoints are down")
+        };
+        assert_eq!(e.downcast::<io::Error>().unwrap().to_string(), "b");
+        assert_eq!(drain(&log), ["a getblockcount", "b getblockcount"]);
+
+        // While both are in backoff, the endpoint which failed first is still checked.
+        let Err(crate::Error::Transport(e)) = client.call::<String>("getblockcount", None) else {
+            panic!("endpoints are down")
+        };
+        assert_eq!(e.downcast::<io::Error>().unwrap().to_string(), "a");
+        assert_eq!(drain(&log), ["a getblockcount"]);
+
+        let client = Client::with_transport(FailoverTransport::<Node>::builder().build());
+        let Err(crate::Error::Transport(e)) = client.call::<String>("getblockcount", None) else {
+            panic!("there are no endpoints")
+        };
+        assert!(matches!(*e.downcast::<Error>().unwrap(), Error::NoHealthyEndpoint));
+    }
+
+    #[test]
+    fn tries_endpoint_leaving_backoff_first() {
+        let (a, b, log) = nodes();
+        let transport = FailoverTransport::builder()
+            .endpoint(a.clone())
+            .endpoint(b.clone())
+            .health_check("uptime")
+            .build();
+        let client = Client::with_transport(transport);
+
+        a.set_up(false);
+        assert_eq!(client.call::<String>("getblockcount", None).unwrap(), "b");
+        b.set_up(false);
+        assert!(client.call::<String>("getblockcount", None).is_err());
+        assert_eq!(drain(&log), ["a getblockcount", "b getblockcount", "b getblockcount"]);
+
+        // Both are in backoff, but the primary's ends first, so it's used once it passes a
+        // health check.
+        a.set_up(true);
+        assert_eq!(client.call::<String>("getblockcount", None).unwrap(), "a");
+        assert_eq!(drain(&log), ["a uptime", "a getblockcount"]);
+        assert_eq!(client.call::<String>("getblockcount", None).unwrap(), "a");
+        assert_eq!(drain(&log), ["a getblockcount"]);
+    }
+
+    #[test]
+    fn only_retries_read_only_methods() {
+        let (a, b, log) = nodes();
+        a.set_up(false);
+        // Methods which aren't known to be read-only aren't retried either.
+        for method in ["sendrawtransaction", "generatetoaddress", "somefuturemethod"] {
+            let transport =
+                FailoverTransport::builder().endpoint(a.clone()).endpoint(b.clone()).build();
+            let client = Client::with_transport(transport);
+            assert!(client.call::<String>(method, None).is_err());
+            assert_eq!(drain(&log), [format!("a {}", method)]);
+        }
+
+        let transport = FailoverTransport::builder()
+            .endpoint(a)
+            .endpoint(b)
+            .retry_method("sendrawtransaction")
+            .no_retry_method("getblockcount")
+            .build();
+        let client = Client::with_transport(transport);
+        assert_eq!(client.call::<String>("sendrawtransaction", None).unwrap(), "b");
+        assert_eq!(drain(&log), ["a sendrawtransaction", "b sendrawtransaction"]);
+    }
+
+    #[test]
+    fn balances_requests() {
+        let (a, b, log) = nodes();
+        let transport = FailoverTransport::builder()
+            .endpoint(a.clone())
+            .endpoint(b.clone())
+            .policy(Policy::RoundRobin)
+            .build();
+        let client = Client::with_transport(transport);
+        let names: Vec<String> =
+            (0..4).map(|_| client.call("getblockcount", None).unwrap()).collect();
+        assert_eq!(names, ["a", "b", "a", "b"]);
+        drain(&log);
+
+        let transport = FailoverTransport::builder()
+            .endpoint(a)
+            .endpoint(b)
+            .policy(Policy::LeastOutstanding)
+            .build();
+        transport.endpoints[0].outstanding.store(1, Ordering::SeqCst);
+        let client = Client::with_transport(transport);
+        assert_eq!(client.call::<String>("getblockcount", None).unwrap(), "b");
+    }
+
+    #[cfg(feature = "client_async")]
+    #[test]
+    fn fails_over_async() {
+        let (a, b, log) = nodes();
+        let transport = FailoverTransport::builder().endpoint(a.clone()).endpoint(b).build();
+        let client = crate::client_async::Client::with_transport(transport);
+
+        a.set_up(false);
+        let result = futures::executor::block_on(client.call::<String>("getblockcount", None));
+        assert_eq!(result.unwrap(), "b");
+        assert_eq!(drain(&log), ["a getblockcount", "b getblockcount"]);
+    }
+}
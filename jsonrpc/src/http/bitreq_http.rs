@@ -32,6 +32,9 @@ pub struct BitreqHttpTransport {
     timeout: Duration,
     /// The value of the `Authorization` HTTP header, i.e., a base64 encoding of 'user:password'.
     basic_auth: Option<String>,
+    /// The proxy to connect to the RPC server through, e.g. Tor's SOCKS5 proxy.
+    #[cfg(feature = "proxy")]
+    proxy: Option<bitreq::Proxy>,
 }
 
 impl Default for BitreqHttpTransport {
@@ -40,6 +43,8 @@ impl Default for BitreqHttpTransport {
             url: format!("{}:{}", DEFAULT_URL, DEFAULT_PORT),
             timeout: DEFAULT_TIMEOUT,
             basic_auth: None,
+            #[cfg(feature = "proxy")]
+            proxy: None,
         }
     }
 }
@@ -55,20 +60,27 @@ impl BitreqHttpTransport {
     where
         R: for<'a> serde::de::Deserialize<'a>,
     {
-        let req = match &self.basic_auth {
-            Some(auth) => bitreq::Request::new(bitreq::Method::Post, &self.url)
-                .with_timeout(self.timeout)
-                .with_header("Authorization", auth)
-                .with_json(&req)?,
-            None => bitreq::Request::new(bitreq::Method::Post, &self.url)
-                .with_timeout(self.timeout)
-                .with_json(&req)?,
-        };
+        let mut http_req =
+            bitreq::Request::new(bitreq::Method::Post, &self.url).with_timeout(self.timeout);
+        if let Some(auth) = &self.basic_auth {
+            http_req = http_req.with_header("Authorization", auth);
+        }
+        #[cfg(feature = "proxy")]
+        if let Some(proxy) = &self.proxy {
+            http_req = http_req.with_proxy(proxy.clone());
+        }
+        let req = http_req.with_json(&req)?;
 
         // Send the request and parse the response. If the response is an error that does not
         // contain valid JSON in its body (for instance if the bitcoind HTTP server work queue
         // depth is exceeded), return the raw HTTP error so users can match against it.
         let resp = req.send()?;
+        // A 204 response has no body, which is how notifications are answered.
+        if resp.status_code == 204 {
+            if let Ok(none) = serde_json::from_value(serde_json::Value::Null) {
+                return Ok(none);
+            }
+        }
         match resp.json() {
             Ok(json) => Ok(json),
             Err(bitreq_err) =>
@@ -93,6 +105,12 @@ impl Transport for BitreqHttpTransport {
         Ok(self.request(reqs)?)
     }
 
+    fn send_notification(&self, req: Request) -> Result<(), crate::Error> {
+        // Servers predating notifications support answer them like requests.
+        self.request::<serde::de::IgnoredAny>(req)?;
+        Ok(())
+    }
+
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.url) }
 }
 
@@ -151,6 +169,14 @@ impl Builder {
         self
     }
 
+    /// Connects to the RPC server through `proxy`, for example a SOCKS5 proxy created with
+    /// [`bitreq::Proxy::new_socks5`] to reach a node listening on an onion address.
+    #[cfg(feature = "proxy")]
+    pub fn proxy(mut self, proxy: bitreq::Proxy) -> Self {
+        self.tp.proxy = Some(proxy);
+        self
+    }
+
     /// Builds the final [`BitreqHttpTransport`].
     pub fn build(self) -> BitreqHttpTransport { self.tp }
 }
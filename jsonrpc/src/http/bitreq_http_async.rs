@@ -25,6 +25,11 @@ pub struct BitreqHttpTransport {
     timeout: Duration,
     /// The value of the `Authorization` HTTP header, i.e., a base64 encoding of 'user:password'.
     basic_auth: Option<String>,
+    /// The proxy to connect to the RPC server through, e.g. Tor's SOCKS5 proxy.
+    #[cfg(feature = "proxy")]
+    proxy: Option<bitreq::Proxy>,
+    /// The client requests are sent with, if any middlewares were added.
+    client: Option<bitreq::Client>,
 }
 
 impl Default for BitreqHttpTransport {
@@ -33,6 +38,9 @@ impl Default for BitreqHttpTransport {
             url: format!("{}:{}", DEFAULT_URL, DEFAULT_PORT),
             timeout: DEFAULT_TIMEOUT,
             basic_auth: None,
+            #[cfg(feature = "proxy")]
+            proxy: None,
+            client: None,
         }
     }
 }
@@ -55,20 +63,30 @@ impl BitreqHttpTransport {
     where
         R: for<'a> serde::de::Deserialize<'a>,
     {
-        let req = match &self.basic_auth {
-            Some(auth) => bitreq::Request::new(bitreq::Method::Post, &self.url)
-                .with_timeout(self.timeout)
-                .with_header("Authorization", auth)
-                .with_json(&req)?,
-            None => bitreq::Request::new(bitreq::Method::Post, &self.url)
-                .with_timeout(self.timeout)
-                .with_json(&req)?,
-        };
+        let mut http_req =
+            bitreq::Request::new(bitreq::Method::Post, &self.url).with_timeout(self.timeout);
+        if let Some(auth) = &self.basic_auth {
+            http_req = http_req.with_header("Authorization", auth);
+        }
+        #[cfg(feature = "proxy")]
+        if let Some(proxy) = &self.proxy {
+            http_req = http_req.with_proxy(proxy.clone());
+        }
+        let req = http_req.with_json(&req)?;
 
         // Send the request and parse the response. If the response is an error that does not
         // contain valid JSON in its body (for instance if the bitcoind HTTP server work queue
         // depth is exceeded), return the raw HTTP error so users can match against it.
-        let resp = req.send_async().await?;
+        let resp = match &self.client {
+            Some(client) => client.send_async(req).await?,
+            None => req.send_async().await?,
+        };
+        // A 204 response has no body, which is how notifications are answered.
+        if resp.status_code == 204 {
+            if let Ok(none) = serde_json::from_value(serde_json::Value::Null) {
+                return Ok(none);
+            }
+        }
         match resp.json() {
             Ok(json) => Ok(json),
             Err(bitreq_err) =>
@@ -99,6 +117,17 @@ impl Transport for BitreqHttpTransport {
         Box::pin(self.request(reqs))
     }
 
+    fn send_notification<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<(), crate::Error>> {
+        // Servers predating notifications support answer them like requests.
+        Box::pin(async move {
+            self.request::<serde::de::IgnoredAny>(req).await?;
+            Ok(())
+        })
+    }
+
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.url) }
 }
 
@@ -158,6 +187,25 @@ impl Builder {
         self
     }
 
+    /// Connects to the RPC server through `proxy`, for example a SOCKS5 proxy created with
+    /// [`bitreq::Proxy::new_socks5`] to reach a node listening on an onion address.
+    #[cfg(feature = "proxy")]
+    pub fn proxy(mut self, proxy: bitreq::Proxy) -> Self {
+        self.tp.proxy = Some(proxy);
+        self
+    }
+
+    /// Runs `middleware` around every HTTP request the transport sends, after the middlewares
+    /// added before, see [`bitreq::Middleware`].
+    ///
+    /// Once a middleware is added, requests are sent through a [`bitreq::Client`], which keeps
+    /// the connection to the server alive between requests.
+    pub fn middleware<M: bitreq::Middleware + 'static>(mut self, middleware: M) -> Self {
+        let client = self.tp.client.take().unwrap_or_else(|| bitreq::Client::new(1));
+        self.tp.client = Some(client.with_middleware(middleware));
+        self
+    }
+
     /// Builds the final [`BitreqHttpTransport`].
     pub fn build(self) -> BitreqHttpTransport { self.tp }
 }
@@ -249,6 +297,7 @@ mod tests {
             .url("http://localhost:22")
             .unwrap()
             .basic_auth("user".to_string(), None)
+            .middleware(bitreq::DefaultHeaders::new().with_header("User-Agent", "jsonrpc"))
             .build();
         let _ = Client::with_transport(tp);
     }
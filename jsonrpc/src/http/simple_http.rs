@@ -242,6 +242,11 @@ impl SimpleHttpTransport {
             // There is no body in a 401 response, so don't try to read it
             return Err(Error::HttpErrorCode(response_code));
         }
+        if response_code == 204 {
+            // Neither is there one in a 204 response, which is how notifications are answered
+            return serde_json::from_value(serde_json::Value::Null)
+                .map_err(|_| Error::HttpErrorCode(response_code));
+        }
 
         // Read up to `content_length` bytes. Note that if there is no content-length
         // header, we will assume an effectively infinite content length, i.e. we will
@@ -353,6 +358,12 @@ impl Transport for SimpleHttpTransport {
         Ok(self.request(reqs)?)
     }
 
+    fn send_notification(&self, req: Request) -> Result<(), crate::Error> {
+        // Servers predating notifications support answer them like requests.
+        self.request::<serde::de::IgnoredAny>(req)?;
+        Ok(())
+    }
+
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "http://{}:{}{}", self.addr.ip(), self.addr.port(), self.path)
     }
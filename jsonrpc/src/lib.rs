@@ -20,11 +20,19 @@ pub extern crate base64;
 #[cfg(feature = "bitreq")]
 pub extern crate bitreq;
 
+#[cfg(feature = "cassette")]
+pub mod cassette;
 pub mod client;
 #[cfg(feature = "client_async")]
 pub mod client_async;
 pub mod error;
+#[cfg(feature = "failover")]
+pub mod failover;
 pub mod http;
+#[cfg(feature = "mock")]
+pub mod mock;
+#[cfg(feature = "server")]
+pub mod server;
 
 #[cfg(feature = "bitreq_http")]
 pub use http::bitreq_http;
@@ -73,8 +81,10 @@ pub struct Request<'a> {
     pub method: &'a str,
     /// Parameters to the RPC call.
     pub params: Option<&'a RawValue>,
-    /// Identifier for this request, which should appear in the response.
-    pub id: serde_json::Value,
+    /// Identifier for this request, which should appear in the response, or [`None`] for a
+    /// notification, which isn't answered.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<serde_json::Value>,
     /// jsonrpc field, MUST be "2.0".
     pub jsonrpc: Option<&'a str>,
 }
@@ -224,7 +234,7 @@ mod tests {
         let request = Request {
             method: "list",
             params: raw_value.as_deref(),
-            id: serde_json::json!(2),
+            id: Some(serde_json::json!(2)),
             jsonrpc: Some("2.0"),
         };
         assert_eq!(
@@ -241,7 +251,7 @@ mod tests {
         let request = Request {
             method: "object",
             params: raw_value.as_deref(),
-            id: serde_json::json!(2),
+            id: Some(serde_json::json!(2)),
             jsonrpc: Some("2.0"),
         };
         assert_eq!(
@@ -249,4 +259,13 @@ mod tests {
             r#"{"method":"object","params":{"height":0},"id":2,"jsonrpc":"2.0"}"#
         );
     }
+
+    #[test]
+    fn test_request_notification() {
+        let request = Request { method: "ping", params: None, id: None, jsonrpc: Some("2.0") };
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"method":"ping","params":null,"jsonrpc":"2.0"}"#
+        );
+    }
 }
@@ -0,0 +1,368 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements an in-memory transport answering requests with responses scripted by
+//! a test, see [`MockTransport`].
+
+use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "client_async")]
+use std::future::Future;
+#[cfg(feature = "client_async")]
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+#[cfg(feature = "client_async")]
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+#[cfg(feature = "client_async")]
+use std::time::Instant;
+use std::{fmt, thread};
+
+use serde::Serialize;
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+#[cfg(feature = "client_async")]
+use crate::client_async::{self, BoxFuture};
+use crate::error::{standard_error, RpcError, StandardError};
+use crate::{client, Request, Response};
+
+/// Transport answering requests with responses registered per method, and recording the
+/// parameters of the requests it receives.
+///
+/// The responses registered for a method are returned in order, the last one for all remaining
+/// requests. Requests for methods without responses are answered with a
+/// [`StandardError::MethodNotFound`] error.
+///
+/// Clones of a `MockTransport` share their state, so a test can keep one to check the requests
+/// received by a client built over another. It implements [`client::Transport`] and, with the
+/// `client_async` feature, [`client_async::Transport`].
+///
+/// ```
+/// use jsonrpc::mock::MockTransport;
+/// use jsonrpc::Client;
+/// use serde_json::json;
+///
+/// let genesis = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+/// let mock = MockTransport::new();
+/// mock.respond("getblockhash", genesis).expect_calls("getblockhash", 1);
+///
+/// let client = Client::with_transport(mock.clone());
+/// let params = jsonrpc::arg([0]);
+/// let hash: String = client.call("getblockhash", Some(&params)).unwrap();
+///
+/// assert_eq!(hash, genesis);
+/// assert_eq!(mock.calls("getblockhash"), [json!([0])]);
+/// mock.verify();
+/// ```
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    methods: Arc<Mutex<HashMap<String, MockMethod>>>,
+}
+
+/// What a [`MockTransport`] knows about a method.
+#[derive(Default)]
+struct MockMethod {
+    responses: VecDeque<Result<Box<RawValue>, RpcError>>,
+    delay: Option<Duration>,
+    expected_calls: Option<usize>,
+    /// The parameters of each request received, `null` for requests without any.
+    calls: Vec<Value>,
+}
+
+impl MockTransport {
+    /// Creates a new [`MockTransport`] without any responses.
+    pub fn new() -> MockTransport { MockTransport::default() }
+
+    /// Registers `result` as the next response for `method`.
+    ///
+    /// `result` is usually a [`serde_json::Value`], or a value of the type the method returns.
+    pub fn respond<T: Serialize>(&self, method: &str, result: T) -> &Self {
+        self.update(method, |m| m.responses.push_back(Ok(crate::arg(result))));
+        self
+    }
+
+    /// Registers `error` as the next response for `method`.
+    ///
+    /// Use [`standard_error`] for the standard errors, or create an [`RpcError`] with the code
+    /// of the error for others.
+    pub fn error(&self, method: &str, error: RpcError) -> &Self {
+        self.update(method, |m| m.responses.push_back(Err(error)));
+        self
+    }
+
+    /// Delays the responses for `method` by `delay`.
+    pub fn delay(&self, method: &str, delay: Duration) -> &Self {
+        self.update(method, |m| m.delay = Some(delay));
+        self
+    }
+
+    /// Expects `method` to be called `times` times, see [`MockTransport::verify`].
+    pub fn expect_calls(&self, method: &str, times: usize) -> &Self {
+        self.update(method, |m| m.expected_calls = Some(times));
+        self
+    }
+
+    /// Returns the parameters of each request received for `method`, `null` for requests
+    /// without any.
+    pub fn calls(&self, method: &str) -> Vec<Value> {
+        self.lock().get(method).map(|m| m.calls.clone()).unwrap_or_default()
+    }
+
+    /// Checks that each method was called as many times as expected.
+    ///
+    /// # Panics
+    ///
+    /// If a method wasn't called as many times as expected with
+    /// [`MockTransport::expect_calls`].
+    pub fn verify(&self) {
+        let mut unmet: Vec<String> = self
+            .lock()
+            .iter()
+            .filter_map(|(name, method)| {
+                let expected = method.expected_calls?;
+                let calls = method.calls.len();
+                (calls != expected)
+                    .then(|| format!("{} called {} times, expected {}", name, calls, expected))
+            })
+            .collect();
+        unmet.sort();
+        assert!(unmet.is_empty(), "unmet mock expectations: {}", unmet.join(", "));
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, MockMethod>> {
+        // No part of this codebase should panic, so unwrapping a mutex lock is fine
+        self.methods.lock().expect("poisoned mutex")
+    }
+
+    fn update(&self, method: &str, f: impl FnOnce(&mut MockMethod)) {
+        f(self.lock().entry(method.to_owned()).or_default())
+    }
+
+    /// Records `request`, returning its response and how long to delay it.
+    fn answer(&self, request: &Request) -> Result<(Response, Option<Duration>), crate::Error> {
+        let params = match request.params {
+            Some(params) => serde_json::from_str(params.get())?,
+            None => Value::Null,
+        };
+        let mut methods = self.lock();
+        let method = methods.entry(request.method.to_owned()).or_default();
+        method.calls.push(params);
+        let result = if method.responses.len() > 1 {
+            method.responses.pop_front()
+        } else {
+            method.responses.front().cloned()
+        };
+        let result =
+            result.unwrap_or_else(|| Err(standard_error(StandardError::MethodNotFound, None)));
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        let id = request.id.clone().unwrap_or_default();
+        let response = Response { result, error, id, jsonrpc: Some(String::from("2.0")) };
+        Ok((response, method.delay))
+    }
+
+    /// Answers `requests`, returning the responses and the longest delay among their methods.
+    fn answer_batch(
+        &self,
+        requests: &[Request],
+    ) -> Result<(Vec<Response>, Option<Duration>), crate::Error> {
+        let mut responses = Vec::with_capacity(requests.len());
+        let mut delay = None;
+        for request in requests {
+            let (response, method_delay) = self.answer(request)?;
+            // Notifications are recorded, but not answered.
+            if request.id.is_some() {
+                responses.push(response);
+            }
+            delay = delay.max(method_delay);
+        }
+        Ok((responses, delay))
+    }
+}
+
+impl fmt::Debug for MockTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let methods = self.lock();
+        let mut names: Vec<&String> = methods.keys().collect();
+        names.sort();
+        f.debug_struct("MockTransport").field("methods", &names).finish()
+    }
+}
+
+impl client::Transport for MockTransport {
+    fn send_request(&self, req: Request) -> Result<Response, crate::Error> {
+        let (response, delay) = self.answer(&req)?;
+        if let Some(delay) = delay {
+            thread::sleep(delay);
+        }
+        Ok(response)
+    }
+
+    fn send_batch(&self, reqs: &[Request]) -> Result<Vec<Response>, crate::Error> {
+        let (responses, delay) = self.answer_batch(reqs)?;
+        if let Some(delay) = delay {
+            thread::sleep(delay);
+        }
+        Ok(responses)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "mock") }
+}
+
+#[cfg(feature = "client_async")]
+impl client_async::Transport for MockTransport {
+    fn send_request<'a>(
+        &'a self,
+        req: Request<'a>,
+    ) -> BoxFuture<'a, Result<Response, crate::Error>> {
+        let answer = self.answer(&req);
+        Box::pin(async move {
+            let (response, delay) = answer?;
+            if let Some(delay) = delay {
+                Delay::new(delay).await;
+            }
+            Ok(response)
+        })
+    }
+
+    fn send_batch<'a>(
+        &'a self,
+        reqs: &'a [Request<'a>],
+    ) -> BoxFuture<'a, Result<Vec<Response>, crate::Error>> {
+        let answer = self.answer_batch(reqs);
+        Box::pin(async move {
+            let (responses, delay) = answer?;
+            if let Some(delay) = delay {
+                Delay::new(delay).await;
+            }
+            Ok(responses)
+        })
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "mock") }
+}
+
+/// A future completing after a delay, without depending on an async runtime: a thread wakes
+/// the task once the delay elapsed.
+#[cfg(feature = "client_async")]
+struct Delay {
+    deadline: Instant,
+    /// The waker of the task to wake, shared with the thread once it's started.
+    waker: Option<Arc<Mutex<Waker>>>,
+}
+
+#[cfg(feature = "client_async")]
+impl Delay {
+    fn new(delay: Duration) -> Delay { Delay { deadline: Instant::now() + delay, waker: None } }
+}
+
+#[cfg(feature = "client_async")]
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+        match self.waker {
+            // No part of this codebase should panic, so unwrapping a mutex lock is fine
+            Some(ref waker) => waker.lock().expect("poisoned mutex").clone_from(cx.waker()),
+            None => {
+                let waker = Arc::new(Mutex::new(cx.waker().clone()));
+                let (thread_waker, deadline) = (Arc::clone(&waker), self.deadline);
+                thread::spawn(move || {
+                    thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    thread_waker.lock().expect("poisoned mutex").wake_by_ref();
+                });
+                self.waker = Some(waker);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{arg, Client};
+
+    #[test]
+    fn answers_scripted_responses() {
+        let mock = MockTransport::new();
+        mock.respond("getblockcount", 1).respond("getblockcount", 2);
+        mock.error(
+            "getblock",
+            RpcError { code: -5, message: "Block not found".into(), data: None },
+        );
+        let client = Client::with_transport(mock.clone());
+
+        let counts: Vec<u64> =
+            (0..3).map(|_| client.call("getblockcount", None).unwrap()).collect();
+        assert_eq!(counts, [1, 2, 2]);
+
+        let params = arg(["00ff"]);
+        let result = client.call::<Value>("getblock", Some(&params));
+        assert!(matches!(result, Err(crate::Error::Rpc(ref e)) if e.code == -5));
+        let result = client.call::<Value>("getbestblockhash", None);
+        assert!(matches!(result, Err(crate::Error::Rpc(ref e)) if e.code == -32601));
+
+        assert_eq!(mock.calls("getblock"), [json!(["00ff"])]);
+        assert_eq!(mock.calls("getblockcount"), vec![Value::Null; 3]);
+        assert!(mock.calls("getblockhash").is_empty());
+    }
+
+    #[test]
+    fn answers_batches() {
+        let mock = MockTransport::new();
+        mock.respond("echo", "hello");
+        let client = Client::with_transport(mock.clone());
+
+        let requests = [client.build_request("echo", None), client.build_request("nope", None)];
+        let responses = client.send_batch(&requests).unwrap();
+        assert_eq!(responses[0].as_ref().unwrap().result::<String>().unwrap(), "hello");
+        assert_eq!(responses[1].as_ref().unwrap().error.as_ref().unwrap().code, -32601);
+    }
+
+    #[test]
+    fn verifies_expected_calls() {
+        let mock = MockTransport::new();
+        mock.respond("ping", ()).expect_calls("ping", 2).expect_calls("stop", 0);
+        let client = Client::with_transport(mock.clone());
+
+        client.call::<()>("ping", None).unwrap();
+        let result = std::panic::catch_unwind(|| mock.verify());
+        assert!(result.is_err());
+        client.call::<()>("ping", None).unwrap();
+        mock.verify();
+    }
+
+    #[test]
+    fn delays_responses() {
+        let mock = MockTransport::new();
+        mock.respond("ping", ()).delay("ping", Duration::from_millis(50));
+        let client = Client::with_transport(mock);
+
+        let start = Instant::now();
+        client.call::<()>("ping", None).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[cfg(feature = "client_async")]
+    #[test]
+    fn answers_async_requests() {
+        let mock = MockTransport::new();
+        mock.respond("ping", "pong").delay("ping", Duration::from_millis(50));
+        let client = crate::client_async::Client::with_transport(mock.clone());
+
+        let start = Instant::now();
+        let result = futures::executor::block_on(client.call::<String>("ping", None));
+        assert_eq!(result.unwrap(), "pong");
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(mock.calls("ping"), [Value::Null]);
+    }
+}
@@ -0,0 +1,283 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements a minimal HTTP/1.1 server answering the requests of the
+//! [`SimpleHttpTransport`](crate::simple_http::SimpleHttpTransport) and other clients of the
+//! bitcoind RPC server.
+//!
+//! Only `POST` requests with a `Content-Length` header are supported, and connections are kept
+//! alive unless the client asks otherwise. Each connection is served on its own thread.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{io, thread};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use crate::server::Router;
+
+/// Absolute maximum content length allowed for a request body.
+const MAX_BODY_SIZE: usize = 32 * 1024 * 1024;
+/// Maximum total size of the request line and header fields of a request.
+const MAX_HEAD_SIZE: u64 = 16 * 1024;
+/// Maximum number of header fields in a request.
+const MAX_HEADERS: usize = 100;
+
+/// Simple synchronous HTTP server.
+#[derive(Debug)]
+pub struct HttpServer {
+    listener: TcpListener,
+    router: Arc<Router>,
+    timeout: Option<Duration>,
+    /// The `user:pass` credentials requests must authenticate with, if any.
+    credentials: Option<Arc<str>>,
+}
+
+impl HttpServer {
+    /// Creates a new `HttpServer` listening on `addr`, without timeouts or authentication.
+    pub fn bind<A: ToSocketAddrs>(addr: A, router: Router) -> io::Result<HttpServer> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(HttpServer { listener, router: Arc::new(router), timeout: None, credentials: None })
+    }
+
+    /// Sets the read and write timeout to use for connections.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Requires requests to authenticate with HTTP basic authentication, answering others with
+    /// `401 Unauthorized`.
+    pub fn auth<S: AsRef<str>>(mut self, user: S, pass: Option<S>) -> Self {
+        let mut credentials = user.as_ref().to_owned();
+        credentials.push(':');
+        if let Some(ref pass) = pass {
+            credentials.push_str(pass.as_ref());
+        }
+        self.credentials = Some(credentials.into());
+        self
+    }
+
+    /// Requires requests to authenticate with a cookie string ('user:pass').
+    pub fn cookie_auth<S: AsRef<str>>(mut self, cookie: S) -> Self {
+        self.credentials = Some(cookie.as_ref().into());
+        self
+    }
+
+    /// Returns the internet socket address the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> { self.listener.local_addr() }
+
+    /// Serves connections until accepting one fails.
+    pub fn serve(&self) -> io::Result<()> {
+        for sock in self.listener.incoming() {
+            let sock = sock?;
+            sock.set_read_timeout(self.timeout)?;
+            sock.set_write_timeout(self.timeout)?;
+            let router = Arc::clone(&self.router);
+            let credentials = self.credentials.clone();
+            thread::spawn(move || serve_connection(&router, credentials.as_deref(), sock));
+        }
+        Ok(())
+    }
+}
+
+/// Answers the requests of a connection until it's closed.
+fn serve_connection(router: &Router, credentials: Option<&str>, sock: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(&sock);
+    let mut writer = &sock;
+    loop {
+        let mut head = (&mut reader).take(MAX_HEAD_SIZE);
+        let mut request_line = String::new();
+        if head.read_line(&mut request_line)? == 0 {
+            return Ok(());
+        }
+        if !request_line.ends_with('\n') {
+            return write_status(writer, "431 Request Header Fields Too Large");
+        }
+        let mut parts = request_line.split_whitespace();
+        let (method, version) = (parts.next(), parts.nth(1));
+
+        // Parse request header fields
+        let mut content_length = None;
+        let mut authorized = credentials.is_none();
+        let mut keep_alive = version == Some("HTTP/1.1");
+        for count in 1.. {
+            let mut header = String::new();
+            head.read_line(&mut header)?;
+            // Nothing is read once the connection is closed, or once the size limit is reached.
+            if header == "\r\n" || (header.is_empty() && head.limit() > 0) {
+                break;
+            }
+            if !header.ends_with('\n') || count > MAX_HEADERS {
+                return write_status(writer, "431 Request Header Fields Too Large");
+            }
+            let Some((name, value)) = header.split_once(':') else { continue };
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse::<usize>().ok();
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorized |= is_authorized(value, credentials);
+            } else if name.eq_ignore_ascii_case("connection") {
+                keep_alive = !value.eq_ignore_ascii_case("close");
+            }
+        }
+
+        if method.is_none() || version.is_none() {
+            return write_status(writer, "400 Bad Request");
+        }
+        // The body of a rejected request can't be skipped reliably, so the connection is closed.
+        if !authorized {
+            return write_status(writer, "401 Unauthorized");
+        }
+        if method != Some("POST") {
+            return write_status(writer, "405 Method Not Allowed");
+        }
+        let length = match content_length {
+            None => return write_status(writer, "411 Length Required"),
+            Some(length) if length > MAX_BODY_SIZE =>
+                return write_status(writer, "413 Payload Too Large"),
+            Some(length) => length,
+        };
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+
+        match router.handle(&body) {
+            Some(body) => write!(
+                writer,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )?,
+            None => writer.write_all(b"HTTP/1.1 204 No Content\r\n\r\n")?,
+        }
+        writer.flush()?;
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+/// Checks the value of an `Authorization` header against the expected `user:pass` credentials.
+fn is_authorized(header: &str, credentials: Option<&str>) -> bool {
+    let Some(encoded) = header.strip_prefix("Basic ") else { return false };
+    match (BASE64.decode(encoded.trim()), credentials) {
+        (Ok(decoded), Some(credentials)) => decoded == credentials.as_bytes(),
+        _ => false,
+    }
+}
+
+/// Writes an empty response with `status` before closing the connection.
+fn write_status(mut writer: &TcpStream, status: &str) -> io::Result<()> {
+    write!(writer, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    fn spawn_server() -> SocketAddr {
+        let router = Router::new().method("add", |(a, b): (u64, u64)| Ok(a + b));
+        let server = HttpServer::bind("127.0.0.1:0", router).unwrap().auth("user", Some("pass"));
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.serve());
+        addr
+    }
+
+    /// A connection to the server sending raw HTTP requests, so the tests don't depend on the
+    /// features of the HTTP transports (e.g. `proxy`).
+    struct Connection(BufReader<TcpStream>);
+
+    impl Connection {
+        fn open(addr: SocketAddr) -> Connection {
+            Connection(BufReader::new(TcpStream::connect(addr).unwrap()))
+        }
+
+        /// Sends `request` as is, returning the status code and body of the response.
+        fn send(&mut self, request: &str) -> (u16, String) {
+            self.0.get_mut().write_all(request.as_bytes()).unwrap();
+            let mut status_line = String::new();
+            self.0.read_line(&mut status_line).unwrap();
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                self.0.read_line(&mut header).unwrap();
+                if header == "\r\n" {
+                    break;
+                }
+                let (name, value) = header.split_once(':').unwrap();
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            self.0.read_exact(&mut body).unwrap();
+            let status = status_line.split_whitespace().nth(1).unwrap().parse().unwrap();
+            (status, String::from_utf8(body).unwrap())
+        }
+
+        /// Posts `body`, authenticating with `credentials` ('user:pass').
+        fn post(&mut self, credentials: &str, body: &str) -> (u16, String) {
+            self.send(&format!(
+                "POST / HTTP/1.1\r\nAuthorization: Basic {}\r\nContent-Length: {}\r\n\r\n{}",
+                BASE64.encode(credentials),
+                body.len(),
+                body
+            ))
+        }
+
+        /// Posts `body` with valid credentials and parses the JSON response.
+        fn call(&mut self, body: &str) -> Value {
+            let (status, body) = self.post("user:pass", body);
+            assert_eq!(status, 200);
+            serde_json::from_str(&body).unwrap()
+        }
+    }
+
+    #[test]
+    fn serves_requests() {
+        let mut conn = Connection::open(spawn_server());
+        let request = r#"{"jsonrpc":"2.0","method":"add","params":[2,3],"id":1}"#;
+        // The connection is kept alive between requests.
+        for _ in 0..2 {
+            assert_eq!(conn.call(request)["result"], 5);
+        }
+
+        let response = conn.call(r#"{"jsonrpc":"2.0","method":"nope","id":2}"#);
+        assert_eq!(response["error"]["code"], -32601);
+
+        // Notifications are answered with a 204, which doesn't break the connection either.
+        let notification = r#"{"jsonrpc":"2.0","method":"add","params":[2,3]}"#;
+        assert_eq!(conn.post("user:pass", notification), (204, String::new()));
+        let batch = format!("[{},{}]", notification, request);
+        let responses = conn.call(&batch);
+        assert_eq!(responses.as_array().unwrap().len(), 1);
+        assert_eq!(responses[0]["result"], 5);
+    }
+
+    #[test]
+    fn requires_authentication() {
+        let addr = spawn_server();
+        let request = r#"{"jsonrpc":"2.0","method":"add","params":[2,3],"id":1}"#;
+        assert_eq!(Connection::open(addr).post("user:wrong", request).0, 401);
+        let unauthenticated = "POST / HTTP/1.1\r\nContent-Length: 0\r\n\r\n";
+        assert_eq!(Connection::open(addr).send(unauthenticated).0, 401);
+    }
+
+    #[test]
+    fn limits_request_head() {
+        let addr = spawn_server();
+        // The requests stop where the server stops reading, so it doesn't close the connection
+        // with unread data, which would reset it.
+        let long_line = format!("POST /{}", "a".repeat(MAX_HEAD_SIZE as usize - 6));
+        assert_eq!(Connection::open(addr).send(&long_line).0, 431);
+
+        let many_headers =
+            format!("POST / HTTP/1.1\r\n{}", "X-Header: x\r\n".repeat(MAX_HEADERS + 1));
+        assert_eq!(Connection::open(addr).send(&many_headers).0, 431);
+    }
+}
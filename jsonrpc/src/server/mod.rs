@@ -0,0 +1,269 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! # Server support
+//!
+//! Support for serving JSONRPC requests, e.g. to write fake services for tests.
+//!
+//! A [`Router`] maps method names to typed handlers and answers request bodies, including
+//! batches and notifications. It can be served over HTTP, a raw TCP socket or a Unix Domain
+//! Socket, mirroring the transports of this crate.
+//!
+//! ```
+//! use jsonrpc::server::Router;
+//!
+//! let router = Router::new()
+//!     .method("getblockcount", |()| Ok(800_000))
+//!     .method("echo", |(message,): (String,)| Ok(message));
+//!
+//! let request = br#"{"jsonrpc":"2.0","method":"getblockcount","id":1}"#;
+//! let response = router.handle(request).unwrap();
+//! assert_eq!(response, r#"{"result":800000,"error":null,"id":1,"jsonrpc":"2.0"}"#);
+//! ```
+
+#[cfg(feature = "simple_http")]
+pub mod http;
+#[cfg(feature = "simple_tcp")]
+pub mod tcp;
+#[cfg(all(feature = "simple_uds", not(windows)))]
+pub mod uds;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufReader, Read, Write};
+
+use serde::de::{Deserialize, DeserializeOwned, Deserializer};
+use serde::Serialize;
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+use crate::error::{standard_error, RpcError, StandardError};
+use crate::Response;
+
+/// A method registered on a [`Router`].
+type Method = Box<dyn Fn(Option<&RawValue>) -> Result<Box<RawValue>, RpcError> + Send + Sync>;
+
+/// Routes JSONRPC requests to the handlers of their methods.
+///
+/// Requests for unknown methods are answered with a [`StandardError::MethodNotFound`] error,
+/// and requests whose parameters don't match their handler with a
+/// [`StandardError::InvalidParams`] error. Notifications, i.e. requests without an `id`, are
+/// handled but not answered. The `jsonrpc` field of requests isn't checked, as Bitcoin Core
+/// doesn't check it either.
+#[derive(Default)]
+pub struct Router {
+    methods: HashMap<String, Method>,
+}
+
+impl Router {
+    /// Creates a new router without any methods.
+    pub fn new() -> Router { Router::default() }
+
+    /// Registers `handler` for the method `name`, replacing any handler registered for it
+    /// before.
+    ///
+    /// The parameters of a request are deserialized into `P`, e.g. a tuple for positional
+    /// parameters or a struct for named ones. Methods without parameters can take `()`, which
+    /// accepts missing parameters as well as an empty array or object.
+    pub fn method<P, R, F>(mut self, name: &str, handler: F) -> Router
+    where
+        P: DeserializeOwned,
+        R: Serialize,
+        F: Fn(P) -> Result<R, RpcError> + Send + Sync + 'static,
+    {
+        let method = move |params: Option<&RawValue>| {
+            let result = handler(parse_params(params)?)?;
+            serde_json::value::to_raw_value(&result).map_err(|e| internal_error(&e))
+        };
+        self.methods.insert(name.to_owned(), Box::new(method));
+        self
+    }
+
+    /// Handles the body of a request, which may be a single request or a batch.
+    ///
+    /// Returns the body of the response, or [`None`] if the body only held notifications.
+    pub fn handle(&self, body: &[u8]) -> Option<String> {
+        let body = match serde_json::from_slice::<Box<RawValue>>(body) {
+            Ok(body) => body,
+            Err(e) => return Some(serialize(&error_response(StandardError::ParseError, &e))),
+        };
+        if !body.get().trim_start().starts_with('[') {
+            return self.handle_request(&body).map(|response| serialize(&response));
+        }
+
+        let batch: Vec<Box<RawValue>> = match serde_json::from_str(body.get()) {
+            Ok(batch) => batch,
+            Err(e) => return Some(serialize(&error_response(StandardError::InvalidRequest, &e))),
+        };
+        if batch.is_empty() {
+            let error = "batches can't be empty";
+            return Some(serialize(&error_response(StandardError::InvalidRequest, &error)));
+        }
+        let responses: Vec<Response> =
+            batch.iter().filter_map(|request| self.handle_request(request)).collect();
+        if responses.is_empty() {
+            None
+        } else {
+            Some(serialize(&responses))
+        }
+    }
+
+    /// Handles a single request of a body, returning its response unless it's a notification.
+    fn handle_request(&self, request: &RawValue) -> Option<Response> {
+        let request: IncomingRequest = match serde_json::from_str(request.get()) {
+            Ok(request) => request,
+            Err(e) => return Some(error_response(StandardError::InvalidRequest, &e)),
+        };
+        let result = match self.methods.get(&request.method) {
+            Some(method) => method(request.params.as_deref()),
+            None => Err(standard_error(StandardError::MethodNotFound, None)),
+        };
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Some(Response { result, error, id: request.id?, jsonrpc: Some(String::from("2.0")) })
+    }
+
+    /// Reads requests from a stream of JSON values, such as the one of a raw socket, and writes
+    /// their responses back until the stream is closed or an invalid value is read.
+    ///
+    /// This is how the raw TCP and UDS servers answer their connections.
+    pub fn serve_stream<R: Read, W: Write>(&self, reader: R, mut writer: W) -> io::Result<()> {
+        let values = serde_json::Deserializer::from_reader(BufReader::new(reader));
+        for value in values.into_iter::<Box<RawValue>>() {
+            let (body, done) = match value {
+                Ok(value) => (self.handle(value.get().as_bytes()), false),
+                Err(e) if e.is_io() => return Err(e.into()),
+                Err(e) if e.is_eof() => return Ok(()),
+                Err(e) => (Some(serialize(&error_response(StandardError::ParseError, &e))), true),
+            };
+            if let Some(body) = body {
+                writer.write_all(body.as_bytes())?;
+                writer.flush()?;
+            }
+            if done {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut methods: Vec<&String> = self.methods.keys().collect();
+        methods.sort();
+        f.debug_struct("Router").field("methods", &methods).finish()
+    }
+}
+
+/// A request received by a [`Router`].
+#[derive(serde::Deserialize)]
+struct IncomingRequest {
+    method: String,
+    params: Option<Box<RawValue>>,
+    /// The ID of the request, or [`None`] for a notification. An explicit `null` ID doesn't make
+    /// a notification.
+    #[serde(default, deserialize_with = "deserialize_id")]
+    id: Option<Value>,
+}
+
+fn deserialize_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+/// Deserializes the parameters of a request for a handler taking `P`.
+fn parse_params<P: DeserializeOwned>(params: Option<&RawValue>) -> Result<P, RpcError> {
+    const EMPTY: [&str; 3] = ["null", "[]", "{}"];
+
+    let params = params.map_or("null", RawValue::get);
+    match serde_json::from_str(params) {
+        Ok(params) => Ok(params),
+        Err(e) if EMPTY.contains(&params.trim()) =>
+            EMPTY.iter().find_map(|empty| serde_json::from_str(empty).ok()).ok_or(e),
+        Err(e) => Err(e),
+    }
+    .map_err(|e| standard_error(StandardError::InvalidParams, Some(crate::arg(e.to_string()))))
+}
+
+fn internal_error(error: &dyn fmt::Display) -> RpcError {
+    standard_error(StandardError::InternalError, Some(crate::arg(error.to_string())))
+}
+
+/// Creates the response to a request which couldn't be read, with the error as data.
+fn error_response(code: StandardError, error: &dyn fmt::Display) -> Response {
+    let error = standard_error(code, Some(crate::arg(error.to_string())));
+    Response { result: None, error: Some(error), id: Value::Null, jsonrpc: Some("2.0".into()) }
+}
+
+fn serialize<T: Serialize + ?Sized>(response: &T) -> String {
+    serde_json::to_string(response).expect("responses always serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn router() -> Router {
+        Router::new()
+            .method("getblockcount", |()| Ok(800_000))
+            .method("add", |(a, b): (u64, u64)| Ok(a + b))
+            .method("fail", |()| -> Result<(), _> { Err(internal_error(&"failed")) })
+    }
+
+    fn handle(router: &Router, request: &str) -> Option<Value> {
+        router.handle(request.as_bytes()).map(|body| serde_json::from_str(&body).unwrap())
+    }
+
+    fn error_code(response: &Value) -> i64 { response["error"]["code"].as_i64().unwrap() }
+
+    #[test]
+    fn routes_requests() {
+        let router = router();
+        let response = handle(&router, r#"{"method":"add","params":[2,3],"id":"a"}"#).unwrap();
+        assert_eq!(response, json!({"result": 5, "error": null, "id": "a", "jsonrpc": "2.0"}));
+
+        for params in ["", r#","params":null"#, r#","params":[]"#, r#","params":{}"#] {
+            let request = format!(r#"{{"method":"getblockcount"{},"id":1}}"#, params);
+            assert_eq!(handle(&router, &request).unwrap()["result"], 800_000);
+        }
+        let response = handle(&router, r#"{"method":"getblockcount","id":null}"#).unwrap();
+        assert_eq!(response["id"], Value::Null);
+    }
+
+    #[test]
+    fn returns_standard_errors() {
+        let router = router();
+        assert_eq!(error_code(&handle(&router, "{").unwrap()), -32700);
+        assert_eq!(error_code(&handle(&router, r#"{"id":1}"#).unwrap()), -32600);
+        assert_eq!(error_code(&handle(&router, "[]").unwrap()), -32600);
+        assert_eq!(error_code(&handle(&router, r#"{"method":"nope","id":1}"#).unwrap()), -32601);
+        let request = r#"{"method":"add","params":["2",3],"id":1}"#;
+        assert_eq!(error_code(&handle(&router, request).unwrap()), -32602);
+        let response = handle(&router, r#"{"method":"fail","id":1}"#).unwrap();
+        assert_eq!(error_code(&response), -32603);
+        assert_eq!(response["error"]["data"], "failed");
+    }
+
+    #[test]
+    fn handles_batches_and_notifications() {
+        let router = router();
+        assert_eq!(handle(&router, r#"{"method":"getblockcount"}"#), None);
+        assert_eq!(handle(&router, r#"[{"method":"nope"},{"method":"add"}]"#), None);
+
+        let batch = r#"[
+            {"method": "add", "params": [1, 2], "id": 1},
+            {"method": "getblockcount"},
+            {"method": "nope", "id": 2},
+            42
+        ]"#;
+        let responses = handle(&router, batch).unwrap();
+        let responses = responses.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["result"], 3);
+        assert_eq!((error_code(&responses[1]), &responses[1]["id"]), (-32601, &json!(2)));
+        assert_eq!((error_code(&responses[2]), &responses[2]["id"]), (-32600, &Value::Null));
+    }
+}
@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements a server over a raw [`std::net::TcpListener`], answering the requests
+//! of the [`TcpTransport`](crate::simple_tcp::TcpTransport).
+//!
+//! Requests are read as a stream of JSON values, each answered with a JSON value unless it only
+//! holds notifications. Each connection is served on its own thread.
+
+use std::sync::Arc;
+use std::{io, net, thread, time};
+
+use crate::server::Router;
+
+/// Simple synchronous TCP server.
+#[derive(Debug)]
+pub struct TcpServer {
+    listener: net::TcpListener,
+    router: Arc<Router>,
+    timeout: Option<time::Duration>,
+}
+
+impl TcpServer {
+    /// Creates a new `TcpServer` listening on `addr`, without timeouts.
+    pub fn bind<A: net::ToSocketAddrs>(addr: A, router: Router) -> io::Result<TcpServer> {
+        let listener = net::TcpListener::bind(addr)?;
+        Ok(TcpServer { listener, router: Arc::new(router), timeout: None })
+    }
+
+    /// Sets the read and write timeout to use for connections.
+    pub fn timeout(mut self, timeout: Option<time::Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the internet socket address the server is listening on.
+    pub fn local_addr(&self) -> io::Result<net::SocketAddr> { self.listener.local_addr() }
+
+    /// Serves connections until accepting one fails.
+    pub fn serve(&self) -> io::Result<()> {
+        for sock in self.listener.incoming() {
+            let sock = sock?;
+            sock.set_read_timeout(self.timeout)?;
+            sock.set_write_timeout(self.timeout)?;
+            let router = Arc::clone(&self.router);
+            thread::spawn(move || router.serve_stream(&sock, &sock));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simple_tcp::TcpTransport;
+    use crate::{arg, Client};
+
+    #[test]
+    fn serves_tcp_transport() {
+        let router = Router::new().method("add", |(a, b): (u64, u64)| Ok(a + b));
+        let server = TcpServer::bind("127.0.0.1:0", router).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.serve());
+
+        let mut transport = TcpTransport::new(addr);
+        transport.timeout = Some(time::Duration::from_secs(5));
+        let client = Client::with_transport(transport);
+        let params = arg([2, 3]);
+        assert_eq!(client.call::<u64>("add", Some(&params)).unwrap(), 5);
+
+        let requests =
+            [client.build_request("add", Some(&params)), client.build_request("nope", None)];
+        let responses = client.send_batch(&requests).unwrap();
+        assert_eq!(responses[0].as_ref().unwrap().result::<u64>().unwrap(), 5);
+        assert_eq!(responses[1].as_ref().unwrap().error.as_ref().unwrap().code, -32601);
+
+        client.send_notification("add", Some(&params)).unwrap();
+        assert_eq!(client.call::<u64>("add", Some(&params)).unwrap(), 5);
+    }
+}
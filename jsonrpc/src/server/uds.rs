@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! This module implements a server over a raw [`std::os::unix::net::UnixListener`], answering
+//! the requests of the [`UdsTransport`](crate::simple_uds::UdsTransport).
+//!
+//! Requests are read as a stream of JSON values, each answered with a JSON value unless it only
+//! holds notifications. Each connection is served on its own thread.
+
+use std::os::unix::net::UnixListener;
+use std::sync::Arc;
+use std::{io, path, thread, time};
+
+use crate::server::Router;
+
+/// Simple synchronous UDS server.
+#[derive(Debug)]
+pub struct UdsServer {
+    listener: UnixListener,
+    router: Arc<Router>,
+    timeout: Option<time::Duration>,
+}
+
+impl UdsServer {
+    /// Creates a new [`UdsServer`] listening on the socket at `sockpath`, without timeouts.
+    pub fn bind<P: AsRef<path::Path>>(sockpath: P, router: Router) -> io::Result<UdsServer> {
+        let listener = UnixListener::bind(sockpath)?;
+        Ok(UdsServer { listener, router: Arc::new(router), timeout: None })
+    }
+
+    /// Sets the read and write timeout to use for connections.
+    pub fn timeout(mut self, timeout: Option<time::Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Serves connections until accepting one fails.
+    pub fn serve(&self) -> io::Result<()> {
+        for sock in self.listener.incoming() {
+            let sock = sock?;
+            sock.set_read_timeout(self.timeout)?;
+            sock.set_write_timeout(self.timeout)?;
+            let router = Arc::clone(&self.router);
+            thread::spawn(move || router.serve_stream(&sock, &sock));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+    use crate::simple_uds::UdsTransport;
+    use crate::Client;
+
+    #[test]
+    fn serves_uds_transport() {
+        let socket_path: path::PathBuf =
+            format!("uds_server_scratch_{}.socket", process::id()).into();
+        // Any leftover?
+        fs::remove_file(&socket_path).unwrap_or(());
+
+        let router = Router::new().method("getinfo", |()| Ok("info"));
+        let server = UdsServer::bind(&socket_path, router).unwrap();
+        thread::spawn(move || server.serve());
+
+        let mut transport = UdsTransport::new(&socket_path);
+        transport.timeout = Some(time::Duration::from_secs(5));
+        let client = Client::with_transport(transport);
+        assert_eq!(client.call::<String>("getinfo", None).unwrap(), "info");
+
+        // Clean up
+        fs::remove_file(&socket_path).unwrap();
+    }
+}
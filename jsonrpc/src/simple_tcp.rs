@@ -25,10 +25,7 @@ impl TcpTransport {
     where
         R: for<'a> serde::de::Deserialize<'a>,
     {
-        let mut sock = net::TcpStream::connect(self.addr)?;
-        sock.set_read_timeout(self.timeout)?;
-        sock.set_write_timeout(self.timeout)?;
-
+        let mut sock = self.connect()?;
         serde_json::to_writer(&mut sock, &req)?;
 
         // NOTE: we don't check the id there, so it *must* be synchronous
@@ -38,6 +35,20 @@ impl TcpTransport {
             .ok_or(Error::Timeout)??;
         Ok(resp)
     }
+
+    /// Sends a notification, without waiting for a response since none is sent.
+    fn notify(&self, req: impl serde::Serialize) -> Result<(), Error> {
+        let mut sock = self.connect()?;
+        serde_json::to_writer(&mut sock, &req)?;
+        Ok(())
+    }
+
+    fn connect(&self) -> Result<net::TcpStream, Error> {
+        let sock = net::TcpStream::connect(self.addr)?;
+        sock.set_read_timeout(self.timeout)?;
+        sock.set_write_timeout(self.timeout)?;
+        Ok(sock)
+    }
 }
 
 impl Transport for TcpTransport {
@@ -49,6 +60,8 @@ impl Transport for TcpTransport {
         Ok(self.request(reqs)?)
     }
 
+    fn send_notification(&self, req: Request) -> Result<(), crate::Error> { Ok(self.notify(req)?) }
+
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.addr) }
 }
 
@@ -122,7 +135,7 @@ mod tests {
         let dummy_req = Request {
             method: "arandommethod",
             params: None,
-            id: serde_json::Value::Number(4242242.into()),
+            id: Some(serde_json::Value::Number(4242242.into())),
             jsonrpc: Some("2.0"),
         };
         let dummy_req_ser = serde_json::to_vec(&dummy_req).unwrap();
@@ -27,10 +27,7 @@ impl UdsTransport {
     where
         R: for<'a> serde::de::Deserialize<'a>,
     {
-        let mut sock = UnixStream::connect(&self.sockpath)?;
-        sock.set_read_timeout(self.timeout)?;
-        sock.set_write_timeout(self.timeout)?;
-
+        let mut sock = self.connect()?;
         serde_json::to_writer(&mut sock, &req)?;
 
         // NOTE: we don't check the id there, so it *must* be synchronous
@@ -40,6 +37,20 @@ impl UdsTransport {
             .ok_or(Error::Timeout)??;
         Ok(resp)
     }
+
+    /// Sends a notification, without waiting for a response since none is sent.
+    fn notify(&self, req: impl serde::Serialize) -> Result<(), Error> {
+        let mut sock = self.connect()?;
+        serde_json::to_writer(&mut sock, &req)?;
+        Ok(())
+    }
+
+    fn connect(&self) -> Result<UnixStream, Error> {
+        let sock = UnixStream::connect(&self.sockpath)?;
+        sock.set_read_timeout(self.timeout)?;
+        sock.set_write_timeout(self.timeout)?;
+        Ok(sock)
+    }
 }
 
 impl Transport for UdsTransport {
@@ -51,6 +62,10 @@ impl Transport for UdsTransport {
         Ok(self.request(reqs)?)
     }
 
+    fn send_notification(&self, req: Request) -> Result<(), crate::error::Error> {
+        Ok(self.notify(req)?)
+    }
+
     fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.sockpath.to_string_lossy())
     }
@@ -128,7 +143,7 @@ mod tests {
         let dummy_req = Request {
             method: "getinfo",
             params: None,
-            id: serde_json::Value::Number(111.into()),
+            id: Some(serde_json::Value::Number(111.into())),
             jsonrpc: Some("2.0"),
         };
         let dummy_req_ser = serde_json::to_vec(&dummy_req).unwrap();